@@ -0,0 +1,408 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    io::Read,
+    net::{ToSocketAddrs, UdpSocket},
+    time::{Duration, Instant},
+};
+
+use flate2::read::ZlibDecoder;
+use glam::{IVec3, Vec3};
+use world::Block;
+
+use crate::connection::{Connection, NetError, PEER_ID_INEXISTENT};
+use crate::packet::*;
+use crate::srp::{self, SrpClient};
+
+/// Block serialization version requested from servers, the one
+/// [`Block::parse_network_data`] reads.
+pub(crate) const SER_FMT_VER: u8 = 29;
+
+/// Network protocol versions spoken. Everything read by the client has the
+/// same layout in all of them.
+pub(crate) const PROTOCOL_VERSION_MIN: u16 = 37;
+pub(crate) const PROTOCOL_VERSION_MAX: u16 = 46;
+
+/// Content ids the engine reserves for itself. Node definitions leave them
+/// out.
+pub(crate) const CONTENT_UNKNOWN: u16 = 125;
+pub(crate) const CONTENT_AIR: u16 = 126;
+pub(crate) const CONTENT_IGNORE: u16 = 127;
+
+/// Size of a node in the units positions are sent in.
+pub(crate) const BS: f32 = 10.0;
+
+/// Largest decompressed node definitions accepted.
+const MAX_NODEDEF_SIZE: u64 = 64 << 20;
+
+/// How often `TOSERVER_INIT` is sent until the server answers.
+const INIT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How long connecting may take until the node definitions have arrived.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Blocks requested around the player, in blocks.
+const WANTED_RANGE: u8 = 4;
+
+/// A connection to a Minetest server as a player, which receives the map
+/// blocks the server sends around the player's position.
+///
+/// Only the packets needed to log in and to receive blocks are understood.
+/// The player only moves when the server moves it or when
+/// [`Client::set_position`] is called, and everything else the server sends
+/// is ignored.
+pub struct Client {
+    connection: Connection,
+    node_names: HashMap<u16, String>,
+    position: Vec3,
+
+    /// Blocks that arrived while connecting.
+    pending: VecDeque<(IVec3, Block)>,
+}
+
+impl Client {
+    /// Connects to a server and logs in, registering the player with
+    /// `password` if the server doesn't know it yet. Returns once the
+    /// node definitions have been received and the server has placed the
+    /// player.
+    pub fn connect(
+        address: impl ToSocketAddrs,
+        name: &str,
+        password: &str,
+    ) -> Result<Self, NetError> {
+        let socket = UdpSocket::bind(("0.0.0.0", 0))?;
+        socket.connect(address)?;
+
+        let mut client = Self {
+            connection: Connection::new(socket, PEER_ID_INEXISTENT),
+            node_names: HashMap::new(),
+            position: Vec3::ZERO,
+            pending: VecDeque::new(),
+        };
+
+        client.log_in(name, password)?;
+
+        client.send(1, PacketWriter::new(TOSERVER_INIT2).string(""), true)?;
+
+        // The node definitions are sent right after, along with items and
+        // media that aren't needed here.
+        client.wait_for(CONNECT_TIMEOUT, |client, command, r| {
+            if command == TOCLIENT_NODEDEF {
+                client.node_names = read_node_definitions(r)?;
+                return Ok(true);
+            }
+            Ok(false)
+        })?;
+
+        let version = env!("CARGO_PKG_VERSION");
+        let mut parts = version.split('.').map(|part| part.parse().unwrap_or(0));
+        client.send(
+            1,
+            PacketWriter::new(TOSERVER_CLIENT_READY)
+                .u8(parts.next().unwrap_or(0))
+                .u8(parts.next().unwrap_or(0))
+                .u8(parts.next().unwrap_or(0))
+                .u8(0)
+                .string(format!("{}-{version}", env!("CARGO_PKG_NAME")))
+                .u16(0),
+            true,
+        )?;
+
+        // The server places the player once the client is ready. Blocks are
+        // sent on another channel and may arrive first.
+        client.wait_for(CONNECT_TIMEOUT, |client, command, r| match command {
+            TOCLIENT_MOVE_PLAYER => {
+                let pos = r.v3f()? / BS;
+                client.set_position(pos)?;
+                Ok(true)
+            }
+            TOCLIENT_BLOCKDATA => {
+                let block = client.read_block(r)?;
+                client.pending.push_back(block);
+                Ok(false)
+            }
+            _ => Ok(false),
+        })?;
+
+        Ok(client)
+    }
+
+    /// The player's position in nodes, as last set by the server or by
+    /// [`Client::set_position`].
+    pub fn position(&self) -> Vec3 {
+        self.position
+    }
+
+    /// Moves the player, so that the server sends the blocks around `pos`.
+    pub fn set_position(&mut self, pos: Vec3) -> Result<(), NetError> {
+        self.position = pos;
+
+        self.send(
+            0,
+            PacketWriter::new(TOSERVER_PLAYERPOS)
+                .v3s32_fixed(pos * BS)
+                .v3s32_fixed(Vec3::ZERO)
+                .i32(0)
+                .i32(0)
+                .u32(0)
+                .u8(80)
+                .u8(WANTED_RANGE),
+            false,
+        )
+    }
+
+    /// The names of the server's content ids.
+    pub fn node_names(&self) -> &HashMap<u16, String> {
+        &self.node_names
+    }
+
+    /// Waits up to `timeout` for the next block from the server. Node ids in
+    /// the block are the server's content ids, named after its node
+    /// definitions.
+    pub fn next_block(&mut self, timeout: Duration) -> Result<Option<(IVec3, Block)>, NetError> {
+        if let Some(block) = self.pending.pop_front() {
+            return Ok(Some(block));
+        }
+
+        let deadline = Instant::now() + timeout;
+
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            let Some(packet) = self.connection.receive(remaining)? else {
+                return Ok(None);
+            };
+
+            let mut r = PacketReader::new(&packet);
+            match r.u16()? {
+                TOCLIENT_BLOCKDATA => return self.read_block(&mut r).map(Some),
+                TOCLIENT_MOVE_PLAYER => {
+                    let pos = r.v3f()? / BS;
+                    self.set_position(pos)?;
+                }
+                TOCLIENT_NODEDEF => self.node_names = read_node_definitions(&mut r)?,
+                TOCLIENT_ACCESS_DENIED => return Err(read_access_denied(&mut r)),
+                _ => {}
+            }
+        }
+    }
+
+    fn log_in(&mut self, name: &str, password: &str) -> Result<(), NetError> {
+        let deadline = Instant::now() + CONNECT_TIMEOUT;
+        let mut last_init: Option<Instant> = None;
+
+        // An empty reliable packet opens the connection.
+        self.connection.send(0, &[], true)?;
+
+        // The server answers TOSERVER_INIT once it has assigned a peer id,
+        // and it may be lost like any unreliable packet.
+        let auth_mechanisms = loop {
+            if Instant::now() >= deadline {
+                return Err(NetError::Timeout);
+            }
+
+            if self.connection.peer_id() != PEER_ID_INEXISTENT
+                && last_init.is_none_or(|sent| sent.elapsed() >= INIT_INTERVAL)
+            {
+                self.send(
+                    1,
+                    PacketWriter::new(TOSERVER_INIT)
+                        .u8(SER_FMT_VER)
+                        .u16(0)
+                        .u16(PROTOCOL_VERSION_MIN)
+                        .u16(PROTOCOL_VERSION_MAX)
+                        .string(name),
+                    false,
+                )?;
+                last_init = Some(Instant::now());
+            }
+
+            let Some(packet) = self.connection.receive(Duration::from_millis(100))? else {
+                continue;
+            };
+
+            let mut r = PacketReader::new(&packet);
+            match r.u16()? {
+                TOCLIENT_HELLO => {
+                    let ser_ver = r.u8()?;
+                    let _compression = r.u16()?;
+                    let protocol_version = r.u16()?;
+                    let auth_mechanisms = r.u32()?;
+
+                    if ser_ver != SER_FMT_VER {
+                        return Err(NetError::Unsupported(format!(
+                            "block serialization version {ser_ver}"
+                        )));
+                    }
+
+                    if !(PROTOCOL_VERSION_MIN..=PROTOCOL_VERSION_MAX).contains(&protocol_version) {
+                        return Err(NetError::Unsupported(format!(
+                            "protocol version {protocol_version}"
+                        )));
+                    }
+
+                    break auth_mechanisms;
+                }
+                TOCLIENT_ACCESS_DENIED => return Err(read_access_denied(&mut r)),
+                _ => {}
+            }
+        };
+
+        let remaining = deadline.saturating_duration_since(Instant::now());
+
+        if auth_mechanisms & AUTH_MECHANISM_SRP != 0 {
+            let srp = SrpClient::new(name, password)?;
+            self.send(
+                1,
+                PacketWriter::new(TOSERVER_SRP_BYTES_A)
+                    .string(srp.public_key())
+                    .u8(1),
+                true,
+            )?;
+
+            self.wait_for(remaining, |client, command, r| {
+                if command != TOCLIENT_SRP_BYTES_S_B {
+                    return Ok(false);
+                }
+
+                let salt = r.string()?;
+                let big_b = r.string()?;
+                let m = srp.process_challenge(salt, big_b)?;
+                client.send(1, PacketWriter::new(TOSERVER_SRP_BYTES_M).string(m), true)?;
+
+                Ok(true)
+            })?;
+        } else if auth_mechanisms & AUTH_MECHANISM_FIRST_SRP != 0 {
+            let (salt, verifier) = srp::create_verifier(name, password)?;
+            self.send(
+                1,
+                PacketWriter::new(TOSERVER_FIRST_SRP)
+                    .string(salt)
+                    .string(verifier)
+                    .u8(password.is_empty() as u8),
+                true,
+            )?;
+        } else {
+            return Err(NetError::Unsupported(
+                "the server only offers legacy password authentication".to_string(),
+            ));
+        }
+
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        // The position sent in TOCLIENT_AUTH_ACCEPT is always zero. The
+        // actual one follows in TOCLIENT_MOVE_PLAYER.
+        self.wait_for(remaining, |_, command, _| {
+            Ok(command == TOCLIENT_AUTH_ACCEPT)
+        })
+    }
+
+    /// Reads `TOCLIENT_BLOCKDATA` and acknowledges the block.
+    fn read_block(&mut self, r: &mut PacketReader) -> Result<(IVec3, Block), NetError> {
+        let pos = r.v3s16()?;
+        let block = Block::parse_network_data(r.rest(), &self.node_names)
+            .map_err(|err| NetError::InvalidPacket(err.at(pos).to_string()))?;
+
+        // The server only sends a few blocks until they are acknowledged.
+        self.send(
+            2,
+            PacketWriter::new(TOSERVER_GOTBLOCKS).u8(1).v3s16(pos),
+            true,
+        )?;
+
+        Ok((pos, block))
+    }
+
+    /// Handles packets with `handle` until it returns true, failing if that
+    /// takes longer than `timeout` or the server denies access.
+    fn wait_for(
+        &mut self,
+        timeout: Duration,
+        mut handle: impl FnMut(&mut Self, u16, &mut PacketReader) -> Result<bool, NetError>,
+    ) -> Result<(), NetError> {
+        let deadline = Instant::now() + timeout;
+
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            let Some(packet) = self.connection.receive(remaining)? else {
+                return Err(NetError::Timeout);
+            };
+
+            let mut r = PacketReader::new(&packet);
+            let command = r.u16()?;
+
+            if command == TOCLIENT_ACCESS_DENIED {
+                return Err(read_access_denied(&mut r));
+            }
+
+            if handle(self, command, &mut r)? {
+                return Ok(());
+            }
+        }
+    }
+
+    fn send(&mut self, channel: u8, packet: PacketWriter, reliable: bool) -> Result<(), NetError> {
+        self.connection.send(channel, &packet.finish(), reliable)
+    }
+}
+
+/// Reads the names of the content ids in `TOCLIENT_NODEDEF`. The rest of
+/// the definitions is skipped.
+fn read_node_definitions(r: &mut PacketReader) -> Result<HashMap<u16, String>, NetError> {
+    let compressed = r.long_string()?;
+
+    let mut data = Vec::new();
+    ZlibDecoder::new(compressed)
+        .take(MAX_NODEDEF_SIZE)
+        .read_to_end(&mut data)
+        .map_err(|err| NetError::InvalidPacket(format!("node definitions: {err}")))?;
+
+    let mut r = PacketReader::new(&data);
+    let _version = r.u8()?;
+    let count = r.u16()?;
+
+    let mut r = PacketReader::new(r.long_string()?);
+
+    let mut names = HashMap::from([
+        (CONTENT_UNKNOWN, "unknown".to_string()),
+        (CONTENT_AIR, "air".to_string()),
+        (CONTENT_IGNORE, "ignore".to_string()),
+    ]);
+
+    for _ in 0..count {
+        let id = r.u16()?;
+        let mut def = PacketReader::new(r.string()?);
+        let _version = def.u8()?;
+        names.insert(id, def.text()?);
+    }
+
+    Ok(names)
+}
+
+fn read_access_denied(r: &mut PacketReader) -> NetError {
+    let reason = match r.u8() {
+        Ok(reason) => reason,
+        Err(err) => return err,
+    };
+
+    let message = match reason {
+        ACCESS_DENIED_WRONG_PASSWORD => "invalid password".to_string(),
+        1 => "the server received unexpected data".to_string(),
+        2 => "the server is running in singleplayer mode".to_string(),
+        3 => "the server doesn't support this client's version".to_string(),
+        4 => "the player name contains disallowed characters".to_string(),
+        5 => "the player name isn't allowed".to_string(),
+        6 => "the server has too many players".to_string(),
+        7 => "empty passwords aren't allowed".to_string(),
+        8 => "a player with the same name is already connected".to_string(),
+        9 => "the server failed".to_string(),
+        ACCESS_DENIED_CUSTOM_STRING | ACCESS_DENIED_SHUTDOWN | ACCESS_DENIED_CRASH => {
+            match r.text() {
+                Ok(message) if !message.is_empty() => message,
+                _ if reason == ACCESS_DENIED_SHUTDOWN => "the server shut down".to_string(),
+                _ if reason == ACCESS_DENIED_CRASH => "the server crashed".to_string(),
+                _ => "no reason given".to_string(),
+            }
+        }
+        _ => format!("reason {reason}"),
+    };
+
+    NetError::AccessDenied(message)
+}
@@ -0,0 +1,459 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    io::{self, ErrorKind},
+    net::UdpSocket,
+    time::{Duration, Instant},
+};
+
+#[derive(thiserror::Error, Debug)]
+pub enum NetError {
+    #[error("{0}")]
+    Io(#[from] io::Error),
+
+    #[error("timed out waiting for the server")]
+    Timeout,
+
+    #[error("the connection was closed")]
+    Disconnected,
+
+    #[error("access denied: {0}")]
+    AccessDenied(String),
+
+    #[error("authentication failed: {0}")]
+    Auth(String),
+
+    #[error("unsupported server: {0}")]
+    Unsupported(String),
+
+    #[error("invalid packet: {0}")]
+    InvalidPacket(String),
+}
+
+pub(crate) const PROTOCOL_ID: u32 = 0x4f457403;
+
+/// The peer id of clients that haven't been assigned one yet.
+pub(crate) const PEER_ID_INEXISTENT: u16 = 0;
+#[cfg(feature = "test-util")]
+pub(crate) const PEER_ID_SERVER: u16 = 1;
+
+const CHANNEL_COUNT: usize = 3;
+
+/// Protocol id, sender peer id and channel.
+const BASE_HEADER_SIZE: usize = 7;
+
+/// Largest datagram sent. Longer packets are split.
+const MAX_PACKET_SIZE: usize = 512;
+
+const SEQNUM_INITIAL: u16 = 65500;
+
+const PACKET_TYPE_CONTROL: u8 = 0;
+const PACKET_TYPE_ORIGINAL: u8 = 1;
+const PACKET_TYPE_SPLIT: u8 = 2;
+const PACKET_TYPE_RELIABLE: u8 = 3;
+
+const CONTROL_TYPE_ACK: u8 = 0;
+const CONTROL_TYPE_SET_PEER_ID: u8 = 1;
+const CONTROL_TYPE_PING: u8 = 2;
+const CONTROL_TYPE_DISCO: u8 = 3;
+
+/// How long reliable packets wait for an ack before they're sent again.
+const RESEND_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// How long the connection may be idle before a ping is sent.
+const PING_INTERVAL: Duration = Duration::from_secs(5);
+
+/// How long the peer may stay silent before the connection is given up.
+const PEER_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Longest the socket is waited on, so resends and pings aren't delayed.
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// The reliable UDP layer Minetest runs its packets over.
+///
+/// Packets are sent on one of three channels, each with its own sequence
+/// numbers. Reliable packets are acked by the receiver and sent again until
+/// they are; they are delivered in order, while unreliable ones may be lost.
+/// Packets that don't fit into a datagram are split and reassembled.
+pub(crate) struct Connection {
+    socket: UdpSocket,
+
+    /// The id sent with every datagram, assigned by the server.
+    peer_id: u16,
+    channels: [Channel; CHANNEL_COUNT],
+    received: VecDeque<Vec<u8>>,
+    disconnected: bool,
+    last_received: Instant,
+    last_sent: Instant,
+}
+
+struct Channel {
+    next_outgoing: u16,
+    next_incoming: u16,
+    next_split: u16,
+
+    /// Reliable packets waiting for an ack, by sequence number.
+    unacked: HashMap<u16, Unacked>,
+
+    /// Reliable packets that arrived ahead of the next expected one.
+    early: HashMap<u16, Vec<u8>>,
+
+    /// Chunks of split packets, by split sequence number.
+    splits: HashMap<u16, Vec<Option<Vec<u8>>>>,
+}
+
+struct Unacked {
+    body: Vec<u8>,
+    sent: Instant,
+}
+
+impl Channel {
+    fn new() -> Self {
+        Self {
+            next_outgoing: SEQNUM_INITIAL,
+            next_incoming: SEQNUM_INITIAL,
+            next_split: SEQNUM_INITIAL,
+            unacked: HashMap::new(),
+            early: HashMap::new(),
+            splits: HashMap::new(),
+        }
+    }
+}
+
+impl Connection {
+    /// Runs the protocol over a socket that's connected to the peer.
+    pub fn new(socket: UdpSocket, peer_id: u16) -> Self {
+        let now = Instant::now();
+
+        Self {
+            socket,
+            peer_id,
+            channels: [Channel::new(), Channel::new(), Channel::new()],
+            received: VecDeque::new(),
+            disconnected: false,
+            last_received: now,
+            last_sent: now,
+        }
+    }
+
+    pub fn peer_id(&self) -> u16 {
+        self.peer_id
+    }
+
+    /// Sends a packet, splitting it if needed.
+    pub fn send(&mut self, channel: u8, data: &[u8], reliable: bool) -> Result<(), NetError> {
+        // Room left for the packet after the headers.
+        let reliable_header = if reliable { 3 } else { 0 };
+        let max_original = MAX_PACKET_SIZE - BASE_HEADER_SIZE - reliable_header - 1;
+
+        if data.len() <= max_original {
+            let mut body = vec![PACKET_TYPE_ORIGINAL];
+            body.extend_from_slice(data);
+            return self.send_body(channel, body, reliable);
+        }
+
+        let max_chunk = MAX_PACKET_SIZE - BASE_HEADER_SIZE - reliable_header - 7;
+        let chunks = data.chunks(max_chunk);
+        let count = chunks.len();
+
+        if count > u16::MAX as usize {
+            return Err(NetError::InvalidPacket(format!(
+                "packet of {} bytes is too large to send",
+                data.len()
+            )));
+        }
+
+        let ch = &mut self.channels[channel as usize];
+        let seq = ch.next_split;
+        ch.next_split = seq.wrapping_add(1);
+
+        for (num, chunk) in chunks.enumerate() {
+            let mut body = vec![PACKET_TYPE_SPLIT];
+            body.extend_from_slice(&seq.to_be_bytes());
+            body.extend_from_slice(&(count as u16).to_be_bytes());
+            body.extend_from_slice(&(num as u16).to_be_bytes());
+            body.extend_from_slice(chunk);
+            self.send_body(channel, body, reliable)?;
+        }
+
+        Ok(())
+    }
+
+    /// Tells the client which peer id to use from now on.
+    #[cfg(feature = "test-util")]
+    pub fn send_set_peer_id(&mut self, peer_id: u16) -> Result<(), NetError> {
+        let mut body = vec![PACKET_TYPE_CONTROL, CONTROL_TYPE_SET_PEER_ID];
+        body.extend_from_slice(&peer_id.to_be_bytes());
+        self.send_body(0, body, true)
+    }
+
+    /// Tells the peer that the connection is closed, without waiting for it
+    /// to be received.
+    pub fn disconnect(&mut self) {
+        let _ = self.send_body(0, vec![PACKET_TYPE_CONTROL, CONTROL_TYPE_DISCO], false);
+        self.disconnected = true;
+    }
+
+    /// Waits up to `timeout` for the next packet. Resends unacked packets and
+    /// keeps the connection alive while waiting.
+    pub fn receive(&mut self, timeout: Duration) -> Result<Option<Vec<u8>>, NetError> {
+        let deadline = Instant::now() + timeout;
+        let mut buf = [0; 0x10000];
+
+        loop {
+            if let Some(packet) = self.received.pop_front() {
+                return Ok(Some(packet));
+            }
+
+            if self.disconnected {
+                return Err(NetError::Disconnected);
+            }
+
+            if self.last_received.elapsed() > PEER_TIMEOUT {
+                return Err(NetError::Timeout);
+            }
+
+            self.resend()?;
+
+            let now = Instant::now();
+            if now >= deadline {
+                return Ok(None);
+            }
+
+            let wait = (deadline - now).clamp(Duration::from_millis(1), POLL_INTERVAL);
+            self.socket.set_read_timeout(Some(wait))?;
+
+            match self.socket.recv(&mut buf) {
+                Ok(len) => self.handle_datagram(&buf[..len])?,
+                Err(err) if matches!(err.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {}
+                // A previous datagram couldn't be delivered, which the peer
+                // timeout takes care of.
+                Err(err) if err.kind() == ErrorKind::ConnectionRefused => {}
+                Err(err) => return Err(err.into()),
+            }
+        }
+    }
+
+    fn send_body(&mut self, channel: u8, body: Vec<u8>, reliable: bool) -> Result<(), NetError> {
+        let body = if reliable {
+            let ch = &mut self.channels[channel as usize];
+            let seq = ch.next_outgoing;
+            ch.next_outgoing = seq.wrapping_add(1);
+
+            let mut reliable_body = vec![PACKET_TYPE_RELIABLE];
+            reliable_body.extend_from_slice(&seq.to_be_bytes());
+            reliable_body.extend_from_slice(&body);
+
+            ch.unacked.insert(
+                seq,
+                Unacked {
+                    body: reliable_body.clone(),
+                    sent: Instant::now(),
+                },
+            );
+
+            reliable_body
+        } else {
+            body
+        };
+
+        self.send_datagram(channel, &body)
+    }
+
+    fn send_datagram(&mut self, channel: u8, body: &[u8]) -> Result<(), NetError> {
+        let mut datagram = Vec::with_capacity(BASE_HEADER_SIZE + body.len());
+        datagram.extend_from_slice(&PROTOCOL_ID.to_be_bytes());
+        datagram.extend_from_slice(&self.peer_id.to_be_bytes());
+        datagram.push(channel);
+        datagram.extend_from_slice(body);
+
+        match self.socket.send(&datagram) {
+            Ok(_) => {}
+            // Like lost datagrams, these are covered by resends and the peer
+            // timeout.
+            Err(err) if err.kind() == ErrorKind::ConnectionRefused => {}
+            Err(err) => return Err(err.into()),
+        }
+
+        self.last_sent = Instant::now();
+        Ok(())
+    }
+
+    fn resend(&mut self) -> Result<(), NetError> {
+        let now = Instant::now();
+        let mut due = Vec::new();
+
+        for (channel, ch) in self.channels.iter_mut().enumerate() {
+            for unacked in ch.unacked.values_mut() {
+                if now.duration_since(unacked.sent) >= RESEND_TIMEOUT {
+                    unacked.sent = now;
+                    due.push((channel as u8, unacked.body.clone()));
+                }
+            }
+        }
+
+        for (channel, body) in due {
+            self.send_datagram(channel, &body)?;
+        }
+
+        if self.last_sent.elapsed() >= PING_INTERVAL {
+            self.send_body(0, vec![PACKET_TYPE_CONTROL, CONTROL_TYPE_PING], true)?;
+        }
+
+        Ok(())
+    }
+
+    fn handle_datagram(&mut self, datagram: &[u8]) -> Result<(), NetError> {
+        if datagram.len() < BASE_HEADER_SIZE + 1
+            || datagram[..4] != PROTOCOL_ID.to_be_bytes()
+            || datagram[6] as usize >= CHANNEL_COUNT
+        {
+            // Not ours.
+            return Ok(());
+        }
+
+        self.last_received = Instant::now();
+
+        let channel = datagram[6];
+        self.handle_body(channel, &datagram[BASE_HEADER_SIZE..], false)
+    }
+
+    fn handle_body(&mut self, channel: u8, body: &[u8], in_reliable: bool) -> Result<(), NetError> {
+        let Some((&packet_type, rest)) = body.split_first() else {
+            return Ok(());
+        };
+
+        match packet_type {
+            PACKET_TYPE_CONTROL => self.handle_control(channel, rest)?,
+            PACKET_TYPE_ORIGINAL => {
+                // Empty packets only open connections.
+                if !rest.is_empty() {
+                    self.received.push_back(rest.to_vec());
+                }
+            }
+            PACKET_TYPE_SPLIT => self.handle_split(channel, rest)?,
+            PACKET_TYPE_RELIABLE if !in_reliable => self.handle_reliable(channel, rest)?,
+            _ => {
+                return Err(NetError::InvalidPacket(format!(
+                    "unknown packet type {packet_type}"
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn handle_control(&mut self, channel: u8, data: &[u8]) -> Result<(), NetError> {
+        let Some((&control_type, rest)) = data.split_first() else {
+            return Err(NetError::InvalidPacket("empty control packet".to_string()));
+        };
+
+        match control_type {
+            CONTROL_TYPE_ACK => {
+                let seq = read_u16(rest)?;
+                self.channels[channel as usize].unacked.remove(&seq);
+            }
+            CONTROL_TYPE_SET_PEER_ID => self.peer_id = read_u16(rest)?,
+            CONTROL_TYPE_PING => {}
+            CONTROL_TYPE_DISCO => self.disconnected = true,
+            _ => {
+                return Err(NetError::InvalidPacket(format!(
+                    "unknown control type {control_type}"
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn handle_reliable(&mut self, channel: u8, data: &[u8]) -> Result<(), NetError> {
+        let seq = read_u16(data)?;
+        let inner = &data[2..];
+
+        // Acks get lost too, so packets that were already handled are acked
+        // again.
+        let mut ack = vec![PACKET_TYPE_CONTROL, CONTROL_TYPE_ACK];
+        ack.extend_from_slice(&seq.to_be_bytes());
+        self.send_datagram(channel, &ack)?;
+
+        let ch = &mut self.channels[channel as usize];
+        let ahead = seq.wrapping_sub(ch.next_incoming);
+
+        if ahead >= 0x8000 {
+            // Already handled.
+            return Ok(());
+        }
+
+        if ahead > 0 {
+            ch.early.insert(seq, inner.to_vec());
+            return Ok(());
+        }
+
+        ch.next_incoming = seq.wrapping_add(1);
+        self.handle_body(channel, inner, true)?;
+
+        loop {
+            let ch = &mut self.channels[channel as usize];
+            let Some(inner) = ch.early.remove(&ch.next_incoming) else {
+                break;
+            };
+
+            ch.next_incoming = ch.next_incoming.wrapping_add(1);
+            self.handle_body(channel, &inner, true)?;
+        }
+
+        Ok(())
+    }
+
+    fn handle_split(&mut self, channel: u8, data: &[u8]) -> Result<(), NetError> {
+        if data.len() < 6 {
+            return Err(NetError::InvalidPacket(
+                "truncated split packet".to_string(),
+            ));
+        }
+
+        let seq = read_u16(data)?;
+        let count = read_u16(&data[2..])? as usize;
+        let num = read_u16(&data[4..])? as usize;
+        let chunk = &data[6..];
+
+        if num >= count {
+            return Err(NetError::InvalidPacket(format!(
+                "split chunk {num} of {count}"
+            )));
+        }
+
+        let splits = &mut self.channels[channel as usize].splits;
+        let chunks = splits.entry(seq).or_insert_with(|| vec![None; count]);
+
+        if chunks.len() != count {
+            return Err(NetError::InvalidPacket(format!(
+                "split packet {seq} changed its chunk count"
+            )));
+        }
+
+        chunks[num] = Some(chunk.to_vec());
+
+        if chunks.iter().all(Option::is_some) {
+            let chunks = splits.remove(&seq).unwrap();
+            self.received
+                .push_back(chunks.into_iter().flatten().flatten().collect());
+        }
+
+        Ok(())
+    }
+}
+
+fn read_u16(data: &[u8]) -> Result<u16, NetError> {
+    match data {
+        [a, b, ..] => Ok(u16::from_be_bytes([*a, *b])),
+        _ => Err(NetError::InvalidPacket("truncated packet".to_string())),
+    }
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        if !self.disconnected {
+            self.disconnect();
+        }
+    }
+}
@@ -0,0 +1,382 @@
+use std::{
+    collections::{HashMap, HashSet},
+    io::Write,
+    net::{SocketAddr, ToSocketAddrs, UdpSocket},
+    time::{Duration, Instant},
+};
+
+use flate2::{Compression, write::ZlibEncoder};
+use glam::{IVec3, Vec3};
+use world::Block;
+
+use crate::client::{
+    BS, CONTENT_AIR, CONTENT_IGNORE, CONTENT_UNKNOWN, PROTOCOL_VERSION_MAX, PROTOCOL_VERSION_MIN,
+    SER_FMT_VER,
+};
+use crate::connection::{Connection, NetError, PEER_ID_SERVER};
+use crate::packet::*;
+use crate::srp::SrpServer;
+
+/// Peer id given to the client.
+const CLIENT_PEER_ID: u16 = 2;
+
+/// Version of the node features in `TOCLIENT_NODEDEF`.
+const CONTENTFEATURES_VERSION: u8 = 13;
+
+/// What a [`FakeServer`] saw of a session.
+#[derive(Debug, Default)]
+pub struct FakeServerReport {
+    /// The name the client logged in with.
+    pub name: String,
+
+    /// Whether the player was registered by the session.
+    pub registered: bool,
+
+    /// Blocks acknowledged by the client.
+    pub acknowledged: Vec<IVec3>,
+
+    /// Positions sent by the client, in nodes.
+    pub positions: Vec<Vec3>,
+}
+
+/// A stand-in for a Minetest server that plays a fixed script, for testing
+/// clients without running the engine.
+///
+/// It serves a single client: the player logs in with SRP, or registers if
+/// no password was set, and receives node definitions naming the nodes in
+/// the server's blocks. Once the client is ready the player is moved to the
+/// spawn and all blocks are sent, and the session ends when the client has
+/// acknowledged them or disconnects.
+///
+/// Blocks are sent without node metadata, and nothing beyond what
+/// [`Client`](crate::Client) needs is sent.
+pub struct FakeServer {
+    socket: UdpSocket,
+    spawn: Vec3,
+    accounts: HashMap<String, Account>,
+    blocks: Vec<(IVec3, Block)>,
+    timeout: Duration,
+}
+
+struct Account {
+    salt: Vec<u8>,
+    verifier: Vec<u8>,
+}
+
+impl FakeServer {
+    pub fn bind(address: impl ToSocketAddrs) -> Result<Self, NetError> {
+        Ok(Self {
+            socket: UdpSocket::bind(address)?,
+            spawn: Vec3::ZERO,
+            accounts: HashMap::new(),
+            blocks: Vec::new(),
+            timeout: Duration::from_secs(10),
+        })
+    }
+
+    pub fn local_addr(&self) -> Result<SocketAddr, NetError> {
+        Ok(self.socket.local_addr()?)
+    }
+
+    /// Sets where players appear when logging in.
+    pub fn set_spawn(&mut self, pos: Vec3) {
+        self.spawn = pos;
+    }
+
+    /// Registers a player, who then has to log in with `password`. Players
+    /// that aren't registered are on their first login.
+    pub fn add_player(&mut self, name: &str, password: &str) -> Result<(), NetError> {
+        let (salt, verifier) = crate::srp::create_verifier(name, password)?;
+        self.accounts
+            .insert(name.to_lowercase(), Account { salt, verifier });
+        Ok(())
+    }
+
+    /// Adds a block to send to the client.
+    pub fn add_block(&mut self, pos: IVec3, block: Block) {
+        self.blocks.push((pos, block));
+    }
+
+    /// How long the client may stay silent before the session fails.
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+
+    /// Waits for a client and plays the script with it. Fails if the client
+    /// sends unexpected packets, is denied access or stays silent for too
+    /// long.
+    pub fn serve(mut self) -> Result<FakeServerReport, NetError> {
+        self.socket.set_read_timeout(Some(self.timeout))?;
+        let (_, client_addr) =
+            self.socket
+                .peek_from(&mut [0; 16])
+                .map_err(|err| match err.kind() {
+                    std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut => {
+                        NetError::Timeout
+                    }
+                    _ => err.into(),
+                })?;
+        self.socket.connect(client_addr)?;
+
+        let (node_ids, node_names) = self.content_ids();
+
+        let mut connection = Connection::new(self.socket.try_clone()?, PEER_ID_SERVER);
+        connection.send_set_peer_id(CLIENT_PEER_ID)?;
+
+        let mut report = FakeServerReport::default();
+        let mut srp: Option<(SrpServer, Vec<u8>)> = None;
+        let mut unacknowledged: HashSet<IVec3> = HashSet::new();
+        let mut ready = false;
+        let mut last_packet = Instant::now();
+
+        loop {
+            if ready && unacknowledged.is_empty() {
+                return Ok(report);
+            }
+
+            if last_packet.elapsed() > self.timeout {
+                return Err(NetError::Timeout);
+            }
+
+            let packet = match connection.receive(Duration::from_millis(100)) {
+                Ok(Some(packet)) => packet,
+                Ok(None) => continue,
+                Err(NetError::Disconnected) => return Ok(report),
+                Err(err) => return Err(err),
+            };
+            last_packet = Instant::now();
+
+            let mut r = PacketReader::new(&packet);
+            let command = r.u16()?;
+
+            match command {
+                TOSERVER_INIT => {
+                    let ser_ver = r.u8()?;
+                    let _compression = r.u16()?;
+                    let min_protocol = r.u16()?;
+                    let max_protocol = r.u16()?;
+                    report.name = r.text()?;
+
+                    if ser_ver < SER_FMT_VER
+                        || max_protocol < PROTOCOL_VERSION_MIN
+                        || min_protocol > PROTOCOL_VERSION_MAX
+                    {
+                        return Err(deny(&mut connection, 3, "unsupported client version"));
+                    }
+
+                    let auth_mechanisms = if self.account(&report.name).is_some() {
+                        AUTH_MECHANISM_SRP
+                    } else {
+                        AUTH_MECHANISM_FIRST_SRP
+                    };
+
+                    let hello = PacketWriter::new(TOCLIENT_HELLO)
+                        .u8(SER_FMT_VER)
+                        .u16(0)
+                        .u16(max_protocol.min(PROTOCOL_VERSION_MAX))
+                        .u32(auth_mechanisms)
+                        .string(&report.name);
+                    connection.send(0, &hello.finish(), true)?;
+                }
+                TOSERVER_FIRST_SRP => {
+                    if self.account(&report.name).is_some() {
+                        return Err(deny(&mut connection, 1, "the player is already registered"));
+                    }
+
+                    let salt = r.string()?.to_vec();
+                    let verifier = r.string()?.to_vec();
+                    self.accounts
+                        .insert(report.name.to_lowercase(), Account { salt, verifier });
+                    report.registered = true;
+
+                    self.send_auth_accept(&mut connection)?;
+                }
+                TOSERVER_SRP_BYTES_A => {
+                    let Some(account) = self.account(&report.name) else {
+                        return Err(deny(&mut connection, 1, "the player isn't registered"));
+                    };
+
+                    let big_a = r.string()?.to_vec();
+                    let server = SrpServer::new(&account.verifier)?;
+                    let challenge = PacketWriter::new(TOCLIENT_SRP_BYTES_S_B)
+                        .string(&account.salt)
+                        .string(server.public_key());
+                    connection.send(0, &challenge.finish(), true)?;
+
+                    srp = Some((server, big_a));
+                }
+                TOSERVER_SRP_BYTES_M => {
+                    let (Some((server, big_a)), Some(account)) =
+                        (srp.take(), self.account(&report.name))
+                    else {
+                        return Err(deny(&mut connection, 1, "unexpected SRP proof"));
+                    };
+
+                    let m = r.string()?;
+                    if !server.verify(&report.name, &account.salt, &big_a, m) {
+                        connection.send(
+                            0,
+                            &PacketWriter::new(TOCLIENT_ACCESS_DENIED)
+                                .u8(ACCESS_DENIED_WRONG_PASSWORD)
+                                .finish(),
+                            true,
+                        )?;
+                        return Err(NetError::Auth(
+                            "the client sent a wrong password".to_string(),
+                        ));
+                    }
+
+                    self.send_auth_accept(&mut connection)?;
+                }
+                TOSERVER_INIT2 => {
+                    let nodedef = write_node_definitions(&node_names)?;
+                    connection.send(0, &nodedef, true)?;
+                }
+                TOSERVER_CLIENT_READY => {
+                    let move_player = PacketWriter::new(TOCLIENT_MOVE_PLAYER)
+                        .v3f(self.spawn * BS)
+                        .f32(0.0)
+                        .f32(0.0);
+                    connection.send(0, &move_player.finish(), true)?;
+
+                    for (pos, block) in &self.blocks {
+                        let packet = PacketWriter::new(TOCLIENT_BLOCKDATA)
+                            .v3s16(*pos)
+                            .raw(&serialize_network_block(block, &node_ids)?);
+                        connection.send(2, &packet.finish(), true)?;
+                        unacknowledged.insert(*pos);
+                    }
+                    ready = true;
+                }
+                TOSERVER_GOTBLOCKS => {
+                    let count = r.u8()?;
+                    for _ in 0..count {
+                        let pos = r.v3s16()?;
+                        if unacknowledged.remove(&pos) {
+                            report.acknowledged.push(pos);
+                        }
+                    }
+                }
+                TOSERVER_PLAYERPOS => {
+                    let pos = IVec3::new(r.i32()?, r.i32()?, r.i32()?);
+                    report.positions.push(pos.as_vec3() / 1000.0);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn account(&self, name: &str) -> Option<&Account> {
+        self.accounts.get(&name.to_lowercase())
+    }
+
+    /// Accepts the login. Like the engine, the position is left at zero
+    /// until the client is ready.
+    fn send_auth_accept(&self, connection: &mut Connection) -> Result<(), NetError> {
+        let accept = PacketWriter::new(TOCLIENT_AUTH_ACCEPT)
+            .v3f(Vec3::ZERO)
+            .u64(0)
+            .f32(0.09)
+            .u32(0);
+        connection.send(0, &accept.finish(), true)
+    }
+
+    /// Assigns content ids to the nodes in the blocks, the way the engine
+    /// does: the reserved ids are skipped and everything else is numbered
+    /// from zero.
+    fn content_ids(&self) -> (HashMap<String, u16>, Vec<(u16, String)>) {
+        let mut ids = HashMap::from([
+            ("unknown".to_string(), CONTENT_UNKNOWN),
+            ("air".to_string(), CONTENT_AIR),
+            ("ignore".to_string(), CONTENT_IGNORE),
+        ]);
+        let mut names = Vec::new();
+        let mut next_id = 0;
+
+        for (_, block) in &self.blocks {
+            for (_, name) in block.mappings() {
+                if ids.contains_key(name) {
+                    continue;
+                }
+
+                if next_id == CONTENT_UNKNOWN {
+                    next_id = CONTENT_IGNORE + 1;
+                }
+
+                ids.insert(name.to_string(), next_id);
+                names.push((next_id, name.to_string()));
+                next_id += 1;
+            }
+        }
+
+        (ids, names)
+    }
+}
+
+fn deny(connection: &mut Connection, reason: u8, message: &str) -> NetError {
+    let packet = PacketWriter::new(TOCLIENT_ACCESS_DENIED)
+        .u8(reason)
+        .finish();
+    if let Err(err) = connection.send(0, &packet, true) {
+        return err;
+    }
+    NetError::InvalidPacket(message.to_string())
+}
+
+fn write_node_definitions(names: &[(u16, String)]) -> Result<Vec<u8>, NetError> {
+    let mut entries = PacketWriter::empty();
+    for (id, name) in names {
+        let def = PacketWriter::empty()
+            .u8(CONTENTFEATURES_VERSION)
+            .string(name)
+            .finish();
+        entries = entries.u16(*id).string(def);
+    }
+
+    let data = PacketWriter::empty()
+        .u8(1)
+        .u16(names.len() as u16)
+        .long_string(entries.finish())
+        .finish();
+
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(&data)?;
+
+    Ok(PacketWriter::new(TOCLIENT_NODEDEF)
+        .long_string(encoder.finish()?)
+        .finish())
+}
+
+/// Serializes a block the way servers send it, with content ids instead of
+/// the block's own mappings.
+fn serialize_network_block(block: &Block, ids: &HashMap<String, u16>) -> Result<Vec<u8>, NetError> {
+    let mut ids_data = Vec::with_capacity(4096 * 2);
+    let mut param1_data = Vec::with_capacity(4096);
+    let mut param2_data = Vec::with_capacity(4096);
+
+    for (_, node) in block.nodes() {
+        let name = block.get_name_by_id(node.id).unwrap_or("unknown");
+        let id = ids.get(name).copied().unwrap_or(CONTENT_UNKNOWN);
+        ids_data.extend_from_slice(&id.to_be_bytes());
+        param1_data.push(node.param1);
+        param2_data.push(node.param2);
+    }
+
+    let mut raw = vec![block.flags()];
+    raw.extend_from_slice(&block.lighting_complete().to_be_bytes());
+    raw.extend_from_slice(&[2, 2]);
+    raw.extend(ids_data);
+    raw.extend(param1_data);
+    raw.extend(param2_data);
+
+    // No node metadata.
+    raw.push(0);
+
+    let mut data = vec![SER_FMT_VER];
+    zstd::stream::copy_encode(raw.as_slice(), &mut data, 0)?;
+
+    // Followed by the network specific version.
+    data.push(2);
+
+    Ok(data)
+}
@@ -0,0 +1,13 @@
+mod client;
+mod connection;
+#[cfg(feature = "test-util")]
+mod fake_server;
+mod packet;
+mod srp;
+
+pub use self::client::*;
+pub use self::connection::*;
+#[cfg(feature = "test-util")]
+pub use self::fake_server::*;
+#[cfg(feature = "test-util")]
+pub use self::srp::{SrpClient, SrpServer, create_verifier, verifier};
@@ -0,0 +1,219 @@
+use glam::{IVec3, Vec3};
+
+use crate::NetError;
+
+pub(crate) const TOSERVER_INIT: u16 = 0x02;
+pub(crate) const TOSERVER_INIT2: u16 = 0x11;
+pub(crate) const TOSERVER_PLAYERPOS: u16 = 0x23;
+pub(crate) const TOSERVER_GOTBLOCKS: u16 = 0x24;
+pub(crate) const TOSERVER_CLIENT_READY: u16 = 0x43;
+pub(crate) const TOSERVER_FIRST_SRP: u16 = 0x50;
+pub(crate) const TOSERVER_SRP_BYTES_A: u16 = 0x51;
+pub(crate) const TOSERVER_SRP_BYTES_M: u16 = 0x52;
+
+pub(crate) const TOCLIENT_HELLO: u16 = 0x02;
+pub(crate) const TOCLIENT_AUTH_ACCEPT: u16 = 0x03;
+pub(crate) const TOCLIENT_ACCESS_DENIED: u16 = 0x0a;
+pub(crate) const TOCLIENT_BLOCKDATA: u16 = 0x20;
+pub(crate) const TOCLIENT_MOVE_PLAYER: u16 = 0x34;
+pub(crate) const TOCLIENT_NODEDEF: u16 = 0x3a;
+pub(crate) const TOCLIENT_SRP_BYTES_S_B: u16 = 0x60;
+
+/// Auth mechanisms offered in `TOCLIENT_HELLO`.
+pub(crate) const AUTH_MECHANISM_SRP: u32 = 1 << 1;
+pub(crate) const AUTH_MECHANISM_FIRST_SRP: u32 = 1 << 2;
+
+/// Reasons sent in `TOCLIENT_ACCESS_DENIED`.
+pub(crate) const ACCESS_DENIED_WRONG_PASSWORD: u8 = 0;
+pub(crate) const ACCESS_DENIED_CUSTOM_STRING: u8 = 10;
+pub(crate) const ACCESS_DENIED_SHUTDOWN: u8 = 11;
+pub(crate) const ACCESS_DENIED_CRASH: u8 = 12;
+
+/// Positions, speeds and angles are sent in hundredths.
+const FIXED_POINT_SCALE: f32 = 100.0;
+
+/// Builds a packet, starting with its command.
+pub(crate) struct PacketWriter {
+    data: Vec<u8>,
+}
+
+impl PacketWriter {
+    pub fn new(command: u16) -> Self {
+        Self {
+            data: command.to_be_bytes().to_vec(),
+        }
+    }
+
+    /// Builds data that's embedded in a packet, without a command.
+    #[cfg(feature = "test-util")]
+    pub fn empty() -> Self {
+        Self { data: Vec::new() }
+    }
+
+    pub fn u8(mut self, value: u8) -> Self {
+        self.data.push(value);
+        self
+    }
+
+    pub fn u16(mut self, value: u16) -> Self {
+        self.data.extend_from_slice(&value.to_be_bytes());
+        self
+    }
+
+    pub fn u32(mut self, value: u32) -> Self {
+        self.data.extend_from_slice(&value.to_be_bytes());
+        self
+    }
+
+    pub fn i32(mut self, value: i32) -> Self {
+        self.data.extend_from_slice(&value.to_be_bytes());
+        self
+    }
+
+    #[cfg(feature = "test-util")]
+    pub fn u64(mut self, value: u64) -> Self {
+        self.data.extend_from_slice(&value.to_be_bytes());
+        self
+    }
+
+    #[cfg(feature = "test-util")]
+    pub fn f32(mut self, value: f32) -> Self {
+        self.data.extend_from_slice(&value.to_be_bytes());
+        self
+    }
+
+    #[cfg(feature = "test-util")]
+    pub fn v3f(self, value: Vec3) -> Self {
+        self.f32(value.x).f32(value.y).f32(value.z)
+    }
+
+    pub fn v3s16(mut self, value: IVec3) -> Self {
+        for c in value.to_array() {
+            self.data.extend_from_slice(&(c as i16).to_be_bytes());
+        }
+        self
+    }
+
+    /// A vector in hundredths.
+    pub fn v3s32_fixed(self, value: Vec3) -> Self {
+        let value = (value * FIXED_POINT_SCALE).round().as_ivec3();
+        self.i32(value.x).i32(value.y).i32(value.z)
+    }
+
+    /// Bytes prefixed with their u16 length.
+    pub fn string(self, value: impl AsRef<[u8]>) -> Self {
+        let value = value.as_ref();
+        self.u16(value.len() as u16).raw(value)
+    }
+
+    /// Bytes prefixed with their u32 length.
+    #[cfg(feature = "test-util")]
+    pub fn long_string(self, value: impl AsRef<[u8]>) -> Self {
+        let value = value.as_ref();
+        self.u32(value.len() as u32).raw(value)
+    }
+
+    pub fn raw(mut self, value: &[u8]) -> Self {
+        self.data.extend_from_slice(value);
+        self
+    }
+
+    pub fn finish(self) -> Vec<u8> {
+        self.data
+    }
+}
+
+/// Reads the fields of a received packet.
+pub(crate) struct PacketReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> PacketReader<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Self { data, pos: 0 }
+    }
+
+    pub fn remaining(&self) -> usize {
+        self.data.len() - self.pos
+    }
+
+    pub fn bytes(&mut self, len: usize) -> Result<&'a [u8], NetError> {
+        if self.remaining() < len {
+            return Err(NetError::InvalidPacket(format!(
+                "expected {len} more bytes at offset {}",
+                self.pos
+            )));
+        }
+
+        let bytes = &self.data[self.pos..self.pos + len];
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], NetError> {
+        Ok(self.bytes(N)?.try_into().unwrap())
+    }
+
+    pub fn u8(&mut self) -> Result<u8, NetError> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    pub fn u16(&mut self) -> Result<u16, NetError> {
+        Ok(u16::from_be_bytes(self.array()?))
+    }
+
+    pub fn i16(&mut self) -> Result<i16, NetError> {
+        Ok(i16::from_be_bytes(self.array()?))
+    }
+
+    pub fn u32(&mut self) -> Result<u32, NetError> {
+        Ok(u32::from_be_bytes(self.array()?))
+    }
+
+    #[cfg(feature = "test-util")]
+    pub fn i32(&mut self) -> Result<i32, NetError> {
+        Ok(i32::from_be_bytes(self.array()?))
+    }
+
+    pub fn f32(&mut self) -> Result<f32, NetError> {
+        Ok(f32::from_be_bytes(self.array()?))
+    }
+
+    pub fn v3f(&mut self) -> Result<Vec3, NetError> {
+        Ok(Vec3::new(self.f32()?, self.f32()?, self.f32()?))
+    }
+
+    pub fn v3s16(&mut self) -> Result<IVec3, NetError> {
+        Ok(IVec3::new(
+            self.i16()? as i32,
+            self.i16()? as i32,
+            self.i16()? as i32,
+        ))
+    }
+
+    /// Bytes prefixed with their u16 length.
+    pub fn string(&mut self) -> Result<&'a [u8], NetError> {
+        let len = self.u16()? as usize;
+        self.bytes(len)
+    }
+
+    /// Bytes prefixed with their u32 length.
+    pub fn long_string(&mut self) -> Result<&'a [u8], NetError> {
+        let len = self.u32()? as usize;
+        self.bytes(len)
+    }
+
+    /// A u16 prefixed string that has to be UTF-8.
+    pub fn text(&mut self) -> Result<String, NetError> {
+        let bytes = self.string()?;
+        String::from_utf8(bytes.to_vec())
+            .map_err(|_| NetError::InvalidPacket("string isn't valid UTF-8".to_string()))
+    }
+
+    pub fn rest(&mut self) -> &'a [u8] {
+        let rest = &self.data[self.pos..];
+        self.pos = self.data.len();
+        rest
+    }
+}
@@ -0,0 +1,216 @@
+//! SRP-6a as Minetest uses it for logging in: SHA-256 with the 2048-bit group
+//! from RFC 5054.
+//!
+//! Numbers are sent as unpadded big-endian bytes. Only `k` and `u` hash
+//! their inputs padded to the length of N.
+
+use std::sync::LazyLock;
+
+use num_bigint::BigUint;
+use sha2::{Digest, Sha256};
+
+use crate::NetError;
+
+static N: LazyLock<BigUint> = LazyLock::new(|| {
+    let hex = "\
+        AC6BDB41324A9A9BF166DE5E1389582FAF72B6651987EE07FC3192943DB56050\
+        A37329CBB4A099ED8193E0757767A13DD52312AB4B03310DCD7F48A9DA04FD50\
+        E8083969EDB767B0CF6095179A163AB3661A05FBD5FAAAE82918A9962F0B93B8\
+        55F97993EC975EEAA80D740ADBF4FF747359D041D5C33EA71D281E446B14773B\
+        CA97B43A23FB801676BD207A436C6481F1D2B9078717461A5B9D32E688F87748\
+        544523B524B0D57D5EA77A2775D2ECFA032CFBDBF52FB3786160279004E57AE6\
+        AF874E7303CE53299CCC041C7BC308D82A5698F3A8D0C38271AE35F8E9DBFBB6\
+        94B5C803D89F7AE435DE236D525F54759B65E372FCD68EF20FA7111F9E4AFF73";
+    BigUint::parse_bytes(hex.as_bytes(), 16).unwrap()
+});
+
+const G: u32 = 2;
+
+/// Bytes of N, which padded numbers are extended to.
+const N_LEN: usize = 256;
+
+const SALT_LEN: usize = 16;
+
+/// Bytes of the random secrets `a` and `b`.
+const SECRET_LEN: usize = 32;
+
+/// Returns a new random salt and the verifier the server stores for the
+/// password, as sent in `TOSERVER_FIRST_SRP`.
+pub fn create_verifier(name: &str, password: &str) -> Result<(Vec<u8>, Vec<u8>), NetError> {
+    let salt = random_bytes(SALT_LEN)?;
+    let verifier = verifier(name, password, &salt);
+    Ok((salt, verifier))
+}
+
+/// Returns the verifier for the password with a given salt.
+pub fn verifier(name: &str, password: &str, salt: &[u8]) -> Vec<u8> {
+    let x = calculate_x(salt, name, password);
+    g().modpow(&x, &N).to_bytes_be()
+}
+
+/// The client side of a login.
+pub struct SrpClient {
+    name: String,
+    password: String,
+    a: BigUint,
+    big_a: BigUint,
+}
+
+impl SrpClient {
+    pub fn new(name: &str, password: &str) -> Result<Self, NetError> {
+        Ok(Self::with_secret(
+            name,
+            password,
+            &random_bytes(SECRET_LEN)?,
+        ))
+    }
+
+    /// Uses the given secret `a` instead of a random one.
+    pub fn with_secret(name: &str, password: &str, a: &[u8]) -> Self {
+        let a = BigUint::from_bytes_be(a);
+        let big_a = g().modpow(&a, &N);
+
+        Self {
+            name: name.to_string(),
+            password: password.to_string(),
+            a,
+            big_a,
+        }
+    }
+
+    /// The public value `A` sent in `TOSERVER_SRP_BYTES_A`.
+    pub fn public_key(&self) -> Vec<u8> {
+        self.big_a.to_bytes_be()
+    }
+
+    /// Answers the server's salt and public value `B` with the proof `M` sent
+    /// in `TOSERVER_SRP_BYTES_M`.
+    pub fn process_challenge(&self, salt: &[u8], big_b: &[u8]) -> Result<Vec<u8>, NetError> {
+        let big_b = BigUint::from_bytes_be(big_b);
+        if &big_b % &*N == BigUint::ZERO {
+            return Err(NetError::Auth(
+                "the server sent an invalid public key".to_string(),
+            ));
+        }
+
+        let u = calculate_u(&self.big_a, &big_b);
+        if u == BigUint::ZERO {
+            return Err(NetError::Auth(
+                "the server sent an invalid public key".to_string(),
+            ));
+        }
+
+        let x = calculate_x(salt, &self.name, &self.password);
+        let kv = calculate_k() * g().modpow(&x, &N) % &*N;
+
+        // B - kv, kept positive.
+        let base = (&big_b % &*N + &*N - kv) % &*N;
+        let s = base.modpow(&(&self.a + u * x), &N);
+
+        Ok(calculate_m(&self.name, salt, &self.big_a, &big_b, &s))
+    }
+}
+
+/// The server side of a login, for a player whose salt and verifier are
+/// known.
+#[cfg(feature = "test-util")]
+pub struct SrpServer {
+    verifier: BigUint,
+    b: BigUint,
+    big_b: BigUint,
+}
+
+#[cfg(feature = "test-util")]
+impl SrpServer {
+    pub fn new(verifier: &[u8]) -> Result<Self, NetError> {
+        Ok(Self::with_secret(verifier, &random_bytes(SECRET_LEN)?))
+    }
+
+    /// Uses the given secret `b` instead of a random one.
+    pub fn with_secret(verifier: &[u8], b: &[u8]) -> Self {
+        let verifier = BigUint::from_bytes_be(verifier);
+        let b = BigUint::from_bytes_be(b);
+        let big_b = (calculate_k() * &verifier + g().modpow(&b, &N)) % &*N;
+
+        Self { verifier, b, big_b }
+    }
+
+    /// The public value `B` sent in `TOCLIENT_SRP_BYTES_S_B`.
+    pub fn public_key(&self) -> Vec<u8> {
+        self.big_b.to_bytes_be()
+    }
+
+    /// Whether the client's proof `M` shows that it knows the password.
+    pub fn verify(&self, name: &str, salt: &[u8], big_a: &[u8], m: &[u8]) -> bool {
+        let big_a = BigUint::from_bytes_be(big_a);
+        if &big_a % &*N == BigUint::ZERO {
+            return false;
+        }
+
+        let u = calculate_u(&big_a, &self.big_b);
+        let s = (&big_a * self.verifier.modpow(&u, &N)).modpow(&self.b, &N);
+
+        calculate_m(name, salt, &big_a, &self.big_b, &s) == m
+    }
+}
+
+fn g() -> BigUint {
+    BigUint::from(G)
+}
+
+/// H(N | pad(g))
+fn calculate_k() -> BigUint {
+    BigUint::from_bytes_be(&hash(&[&N.to_bytes_be(), &pad(&g())]))
+}
+
+/// H(pad(A) | pad(B))
+fn calculate_u(big_a: &BigUint, big_b: &BigUint) -> BigUint {
+    BigUint::from_bytes_be(&hash(&[&pad(big_a), &pad(big_b)]))
+}
+
+/// H(s | H(lowercase(I) ":" P)). Player names are case insensitive, so the
+/// verifier uses the lowercase name.
+fn calculate_x(salt: &[u8], name: &str, password: &str) -> BigUint {
+    let name = name.to_lowercase();
+    let inner = hash(&[name.as_bytes(), b":", password.as_bytes()]);
+    BigUint::from_bytes_be(&hash(&[salt, &inner]))
+}
+
+/// H(H(N) xor H(g) | H(I) | s | A | B | K) with K = H(S)
+fn calculate_m(name: &str, salt: &[u8], big_a: &BigUint, big_b: &BigUint, s: &BigUint) -> Vec<u8> {
+    let hash_n = hash(&[&N.to_bytes_be()]);
+    let hash_g = hash(&[&g().to_bytes_be()]);
+    let hash_xor: Vec<u8> = hash_n.iter().zip(&hash_g).map(|(n, g)| n ^ g).collect();
+
+    let k = hash(&[&s.to_bytes_be()]);
+
+    hash(&[
+        &hash_xor,
+        &hash(&[name.as_bytes()]),
+        salt,
+        &big_a.to_bytes_be(),
+        &big_b.to_bytes_be(),
+        &k,
+    ])
+}
+
+fn hash(parts: &[&[u8]]) -> Vec<u8> {
+    let mut hasher = Sha256::new();
+    for part in parts {
+        hasher.update(part);
+    }
+    hasher.finalize().to_vec()
+}
+
+fn pad(n: &BigUint) -> Vec<u8> {
+    let bytes = n.to_bytes_be();
+    let mut padded = vec![0; N_LEN.saturating_sub(bytes.len())];
+    padded.extend_from_slice(&bytes);
+    padded
+}
+
+fn random_bytes(len: usize) -> Result<Vec<u8>, NetError> {
+    let mut bytes = vec![0; len];
+    getrandom::fill(&mut bytes).map_err(std::io::Error::other)?;
+    Ok(bytes)
+}
@@ -0,0 +1,155 @@
+use std::{thread, time::Duration};
+
+use glam::{IVec3, Vec3, ivec3, vec3};
+use net::{Client, FakeServer, NetError};
+use world::{Block, Node};
+
+fn test_block(top: &str) -> Block {
+    let mut block = Block::new();
+    let stone = block.get_or_insert_id("default:stone");
+    let top = block.get_or_insert_id(top);
+
+    for pos in (0..16).flat_map(|z| (0..16).flat_map(move |x| (0..16).map(move |y| ivec3(x, y, z))))
+    {
+        let id = match pos.y {
+            0..8 => stone,
+            8 => top,
+            _ => continue,
+        };
+        let node = Node {
+            id,
+            param1: 0,
+            param2: pos.x as u8,
+        };
+        block.set_node(pos, node).unwrap();
+    }
+
+    block.set_flags(Block::FLAG_GENERATED);
+    block.set_lighting_complete(Block::LIGHTING_COMPLETE);
+    block
+}
+
+fn start_server(
+    setup: impl FnOnce(&mut FakeServer),
+) -> (
+    String,
+    thread::JoinHandle<Result<net::FakeServerReport, NetError>>,
+) {
+    let mut server = FakeServer::bind("127.0.0.1:0").unwrap();
+    server.set_timeout(Duration::from_secs(5));
+    setup(&mut server);
+
+    let address = server.local_addr().unwrap().to_string();
+    (address, thread::spawn(move || server.serve()))
+}
+
+fn node_names(block: &Block) -> Vec<(IVec3, &str, u8)> {
+    block
+        .nodes()
+        .map(|(pos, node)| (pos, block.get_name_by_id(node.id).unwrap(), node.param2))
+        .collect()
+}
+
+#[test]
+fn receives_blocks() {
+    let blocks = [
+        (ivec3(0, 0, 0), test_block("default:dirt_with_grass")),
+        (ivec3(-1, 2, 3), test_block("default:sand")),
+    ];
+
+    let server_blocks = blocks.clone();
+    let (address, server) = start_server(move |server| {
+        server.set_spawn(vec3(8.0, 9.5, 8.0));
+        server.add_player("Tester", "secret").unwrap();
+        for (pos, block) in server_blocks {
+            server.add_block(pos, block);
+        }
+    });
+
+    let mut client = Client::connect(address, "tester", "secret").unwrap();
+    assert_eq!(client.position(), vec3(8.0, 9.5, 8.0));
+    assert_eq!(
+        client.node_names().get(&126).map(String::as_str),
+        Some("air")
+    );
+
+    let mut received = Vec::new();
+    while received.len() < blocks.len() {
+        let block = client.next_block(Duration::from_secs(5)).unwrap();
+        received.push(block.expect("the server sends every block"));
+    }
+
+    for ((pos, block), (expected_pos, expected)) in received.iter().zip(&blocks) {
+        assert_eq!(pos, expected_pos);
+        assert_eq!(node_names(block), node_names(expected));
+        assert_eq!(block.flags(), expected.flags());
+        assert_eq!(block.lighting_complete(), expected.lighting_complete());
+    }
+
+    let report = server.join().unwrap().unwrap();
+    assert_eq!(report.name, "tester");
+    assert!(!report.registered);
+    assert_eq!(report.acknowledged, vec![ivec3(0, 0, 0), ivec3(-1, 2, 3)]);
+    // Only the position the server moved the player to is reported back.
+    assert_eq!(report.positions, vec![vec3(8.0, 9.5, 8.0)]);
+}
+
+#[test]
+fn registers_on_first_login() {
+    let (address, server) = start_server(|_| {});
+
+    let client = Client::connect(address, "newcomer", "password").unwrap();
+    drop(client);
+
+    let report = server.join().unwrap().unwrap();
+    assert_eq!(report.name, "newcomer");
+    assert!(report.registered);
+    assert!(report.acknowledged.is_empty());
+}
+
+#[test]
+fn rejects_wrong_password() {
+    let (address, server) = start_server(|server| {
+        server.add_player("tester", "secret").unwrap();
+    });
+
+    let result = Client::connect(address, "tester", "guess");
+    assert!(
+        matches!(result, Err(NetError::AccessDenied(_))),
+        "{:?}",
+        result.err()
+    );
+
+    assert!(matches!(server.join().unwrap(), Err(NetError::Auth(_))));
+}
+
+#[test]
+fn reassembles_large_packets() {
+    // Random params compress badly, so the block has to be split.
+    let mut block = test_block("default:dirt");
+    let mut state = 1u32;
+    for (pos, mut node) in test_block("default:dirt").nodes() {
+        state = state.wrapping_mul(1103515245).wrapping_add(12345);
+        node.param1 = (state >> 16) as u8;
+        block.set_node(pos, node).unwrap();
+    }
+
+    let server_block = block.clone();
+    let (address, server) = start_server(move |server| {
+        server.add_block(IVec3::ZERO, server_block);
+    });
+
+    let mut client = Client::connect(address, "tester", "").unwrap();
+    let (_, received) = client.next_block(Duration::from_secs(5)).unwrap().unwrap();
+
+    let params = |block: &Block| {
+        block
+            .nodes()
+            .map(|(_, node)| node.param1)
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(params(&received), params(&block));
+    assert_eq!(client.position(), Vec3::ZERO);
+
+    server.join().unwrap().unwrap();
+}
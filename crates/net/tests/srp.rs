@@ -0,0 +1,61 @@
+use net::{SrpClient, SrpServer, verifier};
+
+fn hex(s: &str) -> Vec<u8> {
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap())
+        .collect()
+}
+
+// Computed with a standalone script following the engine's csrp with
+// SRP_SHA256 and SRP_NG_2048: k and u hash padded numbers, M hashes the
+// name as given while x uses the lowercase name.
+const NAME: &str = "Alice";
+const PASSWORD: &str = "password123";
+const SALT: &str = "beb25379d1a8581eb5a727673a2441ee";
+const M: &str = "81439489a68cf6fcb084a846f4527d6056166d1c531ecfe1fdea6dab8fadd435";
+
+fn secret(first: u8) -> Vec<u8> {
+    (first..first + 32).collect()
+}
+
+#[test]
+fn client_proof_matches_known_vector() {
+    let salt = hex(SALT);
+    let verifier = verifier(NAME, PASSWORD, &salt);
+
+    let client = SrpClient::with_secret(NAME, PASSWORD, &secret(1));
+    let server = SrpServer::with_secret(&verifier, &secret(101));
+
+    let m = client
+        .process_challenge(&salt, &server.public_key())
+        .unwrap();
+    assert_eq!(m, hex(M));
+
+    assert!(server.verify(NAME, &salt, &client.public_key(), &m));
+}
+
+#[test]
+fn verifier_ignores_name_case() {
+    let salt = hex(SALT);
+    assert_eq!(
+        verifier("ALICE", PASSWORD, &salt),
+        verifier(NAME, PASSWORD, &salt)
+    );
+    assert_ne!(
+        verifier(NAME, "Password123", &salt),
+        verifier(NAME, PASSWORD, &salt)
+    );
+}
+
+#[test]
+fn server_rejects_wrong_password() {
+    let salt = hex(SALT);
+    let server = SrpServer::with_secret(&verifier(NAME, PASSWORD, &salt), &secret(101));
+
+    let client = SrpClient::with_secret(NAME, "guess", &secret(1));
+    let m = client
+        .process_challenge(&salt, &server.public_key())
+        .unwrap();
+    assert!(!server.verify(NAME, &salt, &client.public_key(), &m));
+}
@@ -1,18 +1,24 @@
 use asset::{Mesh, Vertex};
 use glam::{IVec3, Vec2, Vec3, ivec3, vec3};
-use world::Block;
+use world::{Block, NodeDef, NodeDefRegistry};
 
-pub fn make_mesh(block: &Block) -> Mesh {
+pub fn make_mesh(block: &Block, node_defs: &NodeDefRegistry) -> Mesh {
     let mut mesh = Mesh::new();
 
+    let node_def = |pos: IVec3| -> &NodeDef {
+        let name = block
+            .get_node(pos)
+            .and_then(|node| block.get_name_by_id(node.id))
+            .unwrap_or("unknown");
+        node_defs.get_or_unknown(name)
+    };
+
     for z in 0..16 {
         for y in 0..16 {
             for x in 0..16 {
                 let pos = ivec3(x, y, z);
 
-                let name = block.get_name_by_id(block.get_node(pos).id).unwrap();
-
-                if name == "air" {
+                if !node_def(pos).is_visible() {
                     continue;
                 }
 
@@ -27,7 +33,7 @@ pub fn make_mesh(block: &Block) -> Mesh {
                         return false;
                     }
 
-                    block.get_name_by_id(block.get_node(pos).id).unwrap() != "air"
+                    node_def(pos).is_opaque()
                 };
 
                 let sides = [
@@ -0,0 +1,123 @@
+//! Compares the memory use and node access speed of blocks as stored by
+//! [`world::Block`] against a flat array of 4 bytes per node, which is how
+//! blocks used to be held in memory.
+//!
+//! ```sh
+//! cargo run --release -p world --example block_memory -- path/to/world
+//! ```
+
+use std::{collections::HashMap, error::Error, hint::black_box, mem::size_of, time::Instant};
+
+use glam::ivec3;
+use world::{Block, World};
+
+const VOLUME: usize = 16 * 16 * 16;
+
+/// The previous in-memory layout: ids, param1 and param2 in one array, and
+/// names in a hash map.
+struct FlatBlock {
+    node_data: Vec<u8>,
+    mappings: HashMap<u16, String>,
+}
+
+impl FlatBlock {
+    fn new(block: &Block) -> Self {
+        let mut node_data = vec![0; VOLUME * 4];
+
+        for (i, (_, node)) in block.nodes().enumerate() {
+            node_data[2 * i..2 * i + 2].copy_from_slice(&node.id.to_be_bytes());
+            node_data[VOLUME * 2 + i] = node.param1;
+            node_data[VOLUME * 3 + i] = node.param2;
+        }
+
+        let mappings = block
+            .mappings()
+            .map(|(id, name)| (id, name.to_string()))
+            .collect();
+
+        Self {
+            node_data,
+            mappings,
+        }
+    }
+
+    fn memory_usage(&self) -> usize {
+        // Header fields, the node array, a hash map per section and the
+        // static object list, as in the old `Block` struct.
+        let header = 16 + size_of::<Vec<u8>>() * 2 + size_of::<HashMap<u16, String>>() * 3;
+
+        let mappings: usize = self
+            .mappings
+            .values()
+            .map(|name| size_of::<(u16, String)>() + 1 + name.capacity())
+            .sum();
+
+        header + self.node_data.capacity() + mappings
+    }
+
+    fn id_at(&self, i: usize) -> u16 {
+        u16::from_be_bytes([self.node_data[2 * i], self.node_data[2 * i + 1]])
+    }
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let Some(path) = std::env::args().nth(1) else {
+        eprintln!("world path required");
+        std::process::exit(1);
+    };
+
+    let world = World::open(path)?;
+
+    let mut blocks = Vec::new();
+    for pos in world.map.list_blocks()? {
+        blocks.push(Block::parse_data_lazy(&world.map.get_block_data(pos)?)?);
+    }
+
+    let flat_blocks: Vec<FlatBlock> = blocks.iter().map(FlatBlock::new).collect();
+
+    let uniform = blocks.iter().filter(|b| b.uniform_node().is_some()).count();
+    let compact: usize = blocks.iter().map(Block::memory_usage).sum();
+    let flat: usize = flat_blocks.iter().map(FlatBlock::memory_usage).sum();
+
+    println!("{} blocks, {uniform} uniform", blocks.len());
+    println!(
+        "memory: {} KiB compact, {} KiB flat ({:.1}%)",
+        compact / 1024,
+        flat / 1024,
+        compact as f64 / flat as f64 * 100.0
+    );
+
+    let start = Instant::now();
+    let mut sum = 0u64;
+    for block in &blocks {
+        for z in 0..16 {
+            for y in 0..16 {
+                for x in 0..16 {
+                    sum += block
+                        .get_node(ivec3(x, y, z))
+                        .map_or(0, |node| node.id as u64);
+                }
+            }
+        }
+    }
+    black_box(sum);
+    let compact_time = start.elapsed();
+
+    let start = Instant::now();
+    let mut sum = 0u64;
+    for block in &flat_blocks {
+        for z in 0..16 {
+            for y in 0..16 {
+                for x in 0..16 {
+                    sum += block.id_at(z * 16 * 16 + y * 16 + x) as u64;
+                }
+            }
+        }
+    }
+    black_box(sum);
+    let flat_time = start.elapsed();
+
+    println!("reading all nodes: {compact_time:?} compact, {flat_time:?} flat");
+
+    Ok(())
+}
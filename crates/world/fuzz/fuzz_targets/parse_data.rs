@@ -0,0 +1,38 @@
+//! Feeds arbitrary data to the block parsers. Blocks that parse must also
+//! serialize and parse again to the same nodes.
+//!
+//! ```sh
+//! cargo +nightly fuzz run parse_data
+//! ```
+//!
+//! Real blocks from a world make a good starting corpus, since random data
+//! rarely gets past zstd.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use world::Block;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = Block::parse_timestamp(data);
+    let _ = Block::parse_nodes(data);
+
+    if let Ok(block) = Block::parse_data_lazy(data) {
+        let _ = block.metadata().count();
+        let _ = block.serialize_data();
+    }
+
+    let Ok(block) = Block::parse_data(data) else {
+        return;
+    };
+
+    let Ok(serialized) = block.serialize_data() else {
+        return;
+    };
+
+    let reparsed = Block::parse_data(&serialized).expect("serialized block doesn't parse");
+
+    for ((pos, node), (_, reparsed_node)) in block.nodes().zip(reparsed.nodes()) {
+        assert_eq!(node, reparsed_node, "node at {pos} differs");
+    }
+});
@@ -0,0 +1,173 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    io::{Cursor, Read, Write},
+};
+
+use crate::map::read_section;
+use crate::serialize::*;
+use crate::{Block, Map, MapError, Section};
+
+const BATCH_SIZE: usize = 1024;
+
+#[derive(Clone, Debug, Default)]
+pub struct RenameReport {
+    pub blocks: usize,
+
+    /// Blocks in which at least one name was replaced.
+    pub renamed_blocks: usize,
+
+    /// Blocks in an older format, which are left as they are.
+    pub skipped: usize,
+
+    /// Number of blocks each alias was applied to, by old name.
+    pub aliases: BTreeMap<String, usize>,
+}
+
+/// Reads the aliases registered with `minetest.register_alias` and
+/// `minetest.register_alias_force` (or their `core.` forms) in Lua source
+/// code, such as a mod's `init.lua` or `aliases.lua`.
+///
+/// Only calls with literal names are found. Later registrations of the same
+/// name replace earlier ones.
+pub fn parse_register_aliases(source: &str) -> BTreeMap<String, String> {
+    let calls = crate::lua::find_string_calls(
+        source.as_bytes(),
+        &["register_alias", "register_alias_force"],
+    );
+
+    calls
+        .into_iter()
+        .filter_map(|args| match <[_; 2]>::try_from(args) {
+            Ok([old, new]) => Some((String::from_utf8(old).ok()?, String::from_utf8(new).ok()?)),
+            Err(_) => None,
+        })
+        .collect()
+}
+
+/// Replaces node names in every block of `map` according to `aliases`, which
+/// maps old names to new ones.
+///
+/// Like the engine, aliases are applied once, so an alias pointing to another
+/// alias isn't followed. Only the name mappings of each block are rewritten;
+/// nodes, metadata and everything else are kept as they are. With `dry_run`,
+/// nothing is written and only the report is produced.
+pub fn rename_nodes(
+    map: &Map,
+    aliases: &BTreeMap<String, String>,
+    dry_run: bool,
+) -> Result<RenameReport, MapError> {
+    let mut report = RenameReport::default();
+
+    let positions = map.list_blocks()?;
+    report.blocks = positions.len();
+
+    for batch in positions.chunks(BATCH_SIZE) {
+        if !dry_run {
+            map.begin_save()?;
+        }
+
+        for &pos in batch {
+            let data = map.get_block_data(pos)?;
+
+            let Some(renamed) = rename_in_data(&data, aliases).map_err(|err| err.at(pos))? else {
+                report.skipped += 1;
+                continue;
+            };
+
+            if renamed.used.is_empty() {
+                continue;
+            }
+
+            report.renamed_blocks += 1;
+
+            for name in renamed.used {
+                *report.aliases.entry(name.to_string()).or_default() += 1;
+            }
+
+            if !dry_run {
+                map.set_block_data(pos, &renamed.data)?;
+            }
+        }
+
+        if !dry_run {
+            map.end_save()?;
+        }
+    }
+
+    Ok(report)
+}
+
+struct RenamedData<'a> {
+    data: Vec<u8>,
+
+    /// Old names of the aliases that were applied. If empty, `data` is empty
+    /// too.
+    used: BTreeSet<&'a str>,
+}
+
+/// Rewrites the name mappings of serialized block data, or returns `None` for
+/// older block versions.
+fn rename_in_data<'a>(
+    data: &[u8],
+    aliases: &'a BTreeMap<String, String>,
+) -> Result<Option<RenamedData<'a>>, MapError> {
+    if data.first() != Some(&Block::SERIALIZATION_VERSION) {
+        return Ok(None);
+    }
+
+    let payload = Block::decompress(data)?;
+    let mut cur = Cursor::new(payload.as_slice());
+
+    // Flags, lighting_complete, timestamp and the mapping version.
+    let mut header = [0; 8];
+    let (count, mappings, used) = read_section(&mut cur, Section::Mappings, |cur| {
+        cur.read_exact(&mut header)?;
+
+        let count = read_u16(cur)?;
+        let mut mappings = Vec::with_capacity(count as usize);
+        let mut used = BTreeSet::new();
+
+        for _ in 0..count {
+            let id = read_u16(cur)?;
+            let name = read_string(cur)?;
+
+            match aliases.get_key_value(&name) {
+                Some((old, new)) => {
+                    used.insert(old.as_str());
+                    mappings.push((id, new.clone()));
+                }
+                None => mappings.push((id, name)),
+            }
+        }
+
+        Ok((count, mappings, used))
+    })?;
+
+    if used.is_empty() {
+        return Ok(Some(RenamedData {
+            data: Vec::new(),
+            used,
+        }));
+    }
+
+    let timestamp = u32::from_be_bytes(header[3..7].try_into().unwrap());
+    header[3..7].copy_from_slice(&Block::touched_timestamp(timestamp).to_be_bytes());
+
+    let mut renamed = Vec::with_capacity(payload.len());
+    renamed.write_all(&header)?;
+    write_u16(&mut renamed, count)?;
+
+    for (id, name) in &mappings {
+        write_u16(&mut renamed, *id)?;
+        write_string(&mut renamed, name)?;
+    }
+
+    // The nodes and everything after them are copied unchanged.
+    let rest = cur.position() as usize;
+    renamed.write_all(&payload[rest..])?;
+
+    let mut data = vec![Block::SERIALIZATION_VERSION];
+    zstd::stream::copy_encode(renamed.as_slice(), &mut data, 0)?;
+
+    Ok(Some(RenamedData { data, used }))
+}
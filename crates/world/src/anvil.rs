@@ -0,0 +1,653 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    io::{self, Read},
+    path::Path,
+};
+
+use flate2::read::{GzDecoder, ZlibDecoder};
+use glam::{IVec3, ivec3};
+
+use crate::{
+    Block, Map, MapError, Node, NodeArea, ParseErrorKind, nbt::Nbt, schematic::modify_blocks,
+};
+
+/// Table of Minetest Game nodes for common Minecraft blocks.
+const MINETEST_GAME_MAPPING: &str = include_str!("anvil_mapping.txt");
+
+const SECTOR_SIZE: usize = 4096;
+const REGION_CHUNKS: i32 = 32;
+
+const COMPRESSION_GZIP: u8 = 1;
+const COMPRESSION_ZLIB: u8 = 2;
+const COMPRESSION_NONE: u8 = 3;
+
+/// Set in the compression type of chunks stored in a separate `.mcc` file.
+const COMPRESSION_EXTERNAL: u8 = 0x80;
+
+/// First data version with block palettes, from Minecraft 1.13.
+const DATA_VERSION_PALETTES: i64 = 1451;
+
+/// First data version whose block state entries don't span two longs, from
+/// Minecraft 1.16.
+const DATA_VERSION_PADDED_STATES: i64 = 2529;
+
+/// Errors importing Minecraft worlds.
+#[derive(thiserror::Error, Debug)]
+pub enum AnvilError {
+    #[error("block mapping line {line}: {message}")]
+    Mapping { line: usize, message: String },
+
+    #[error("chunk {x},{z}: {kind}")]
+    Chunk {
+        x: i32,
+        z: i32,
+        kind: ParseErrorKind,
+    },
+
+    #[error(transparent)]
+    Map(#[from] MapError),
+
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+}
+
+#[derive(Clone, Debug)]
+struct MappingEntry {
+    properties: Vec<(String, String)>,
+    node: String,
+    param2: u8,
+}
+
+/// Translates Minecraft block states into Minetest nodes.
+///
+/// The table is text with one block state per line, followed by the node
+/// it becomes and optionally its `param2`:
+///
+/// ```text
+/// # Comments start with a hash.
+/// minecraft:stone                 default:stone
+/// minecraft:oak_log[axis=x]       default:tree    12
+/// ```
+///
+/// Properties in brackets must all match the block state. When several lines
+/// match, the one with the most properties wins, then the earliest one.
+/// Names without a namespace are in the `minecraft` namespace.
+///
+/// Exports read the table the other way round, turning each node into the
+/// first state that becomes it.
+#[derive(Clone, Debug, Default)]
+pub struct BlockMapping {
+    entries: HashMap<String, Vec<MappingEntry>>,
+
+    /// Block states by node name, with their `param2`, in the order of the
+    /// table.
+    states: HashMap<String, Vec<(u8, String)>>,
+}
+
+impl BlockMapping {
+    pub fn parse(text: &str) -> Result<Self, AnvilError> {
+        let mut mapping = Self::default();
+
+        for (index, line) in text.lines().enumerate() {
+            let error = |message: &str| AnvilError::Mapping {
+                line: index + 1,
+                message: message.to_string(),
+            };
+
+            let line = line.split('#').next().unwrap_or_default();
+            let mut fields = line.split_whitespace();
+            let Some(state) = fields.next() else {
+                continue;
+            };
+
+            let (name, properties) =
+                parse_state(state).ok_or_else(|| error("invalid block state"))?;
+            let node = fields
+                .next()
+                .ok_or_else(|| error("missing node name"))?
+                .to_string();
+            let param2 = match fields.next() {
+                Some(param2) => param2.parse().map_err(|_| error("invalid param2"))?,
+                None => 0,
+            };
+            if fields.next().is_some() {
+                return Err(error("unexpected text after param2"));
+            }
+
+            mapping
+                .states
+                .entry(node.clone())
+                .or_default()
+                .push((param2, format_state(&name, &properties)));
+            mapping.entries.entry(name).or_default().push(MappingEntry {
+                properties,
+                node,
+                param2,
+            });
+        }
+
+        Ok(mapping)
+    }
+
+    /// The built-in table for Minetest Game, which covers the most common
+    /// blocks of generated terrain.
+    pub fn minetest_game() -> Self {
+        Self::parse(MINETEST_GAME_MAPPING).expect("built-in block mapping is valid")
+    }
+
+    /// The text of the built-in table, as a starting point for custom ones.
+    pub fn minetest_game_text() -> &'static str {
+        MINETEST_GAME_MAPPING
+    }
+
+    /// Finds the node name and `param2` for the block state `name` with
+    /// `properties`.
+    pub fn lookup(&self, name: &str, properties: &[(String, String)]) -> Option<(&str, u8)> {
+        let mut best: Option<&MappingEntry> = None;
+
+        for entry in self.entries.get(name)? {
+            let matches = entry
+                .properties
+                .iter()
+                .all(|property| properties.contains(property));
+
+            if matches && best.is_none_or(|best| entry.properties.len() > best.properties.len()) {
+                best = Some(entry);
+            }
+        }
+
+        best.map(|entry| (entry.node.as_str(), entry.param2))
+    }
+
+    /// Finds the block state for the node `name` with `param2`. Nodes with
+    /// a `param2` the table doesn't mention get the state of their first
+    /// line.
+    pub fn reverse_lookup(&self, name: &str, param2: u8) -> Option<&str> {
+        let states = self.states.get(name)?;
+        let (_, state) = states
+            .iter()
+            .find(|(state_param2, _)| *state_param2 == param2)
+            .or_else(|| states.first())?;
+        Some(state)
+    }
+
+    /// Whether any line of the table is about blocks named `name`.
+    pub fn contains(&self, name: &str) -> bool {
+        self.entries.contains_key(name)
+    }
+}
+
+/// Splits `name[key=value,...]` into the namespaced name and its properties.
+pub(crate) fn parse_state(state: &str) -> Option<(String, Vec<(String, String)>)> {
+    let (name, properties) = match state.split_once('[') {
+        Some((name, rest)) => (name, rest.strip_suffix(']')?),
+        None => (state, ""),
+    };
+
+    if name.is_empty() {
+        return None;
+    }
+
+    let name = if name.contains(':') {
+        name.to_string()
+    } else {
+        format!("minecraft:{name}")
+    };
+
+    let properties = properties
+        .split(',')
+        .filter(|property| !property.is_empty())
+        .map(|property| {
+            let (key, value) = property.split_once('=')?;
+            Some((key.to_string(), value.to_string()))
+        })
+        .collect::<Option<_>>()?;
+
+    Some((name, properties))
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct AnvilImportOptions {
+    /// Area to import, in Minecraft coordinates.
+    pub area: NodeArea,
+
+    /// Added to the Minetest position of every node.
+    pub offset: IVec3,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct AnvilImportProgress {
+    pub done: usize,
+    pub total: usize,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct AnvilImportReport {
+    /// Chunks that were imported.
+    pub chunks: usize,
+
+    /// Chunks in the area that don't exist or aren't fully generated.
+    pub skipped_chunks: usize,
+
+    /// Nodes written to the map.
+    pub nodes: usize,
+
+    /// Number of nodes of each block state the mapping has no node for.
+    /// States of blocks the mapping doesn't mention at all are counted by
+    /// name only, others with their properties.
+    pub unmapped: BTreeMap<String, usize>,
+}
+
+/// Imports `options.area` from the region files in `region_dir`, the
+/// `region` directory of a Minecraft world saved by version 1.13 or later.
+///
+/// Minecraft's z axis points south while Minetest's points north, so a node
+/// at `x, y, z` ends up at `x, y, -z - 1` plus the offset, keeping the
+/// compass directions of the world. Nodes replace whatever was in the map
+/// along with its metadata. Block states the mapping has no node for leave
+/// the map as it is and are counted in the report. Block entities such as
+/// chest contents aren't imported.
+pub fn import_anvil(
+    map: &Map,
+    region_dir: &Path,
+    mapping: &BlockMapping,
+    options: &AnvilImportOptions,
+    mut on_progress: impl FnMut(AnvilImportProgress),
+) -> Result<AnvilImportReport, AnvilError> {
+    let mut report = AnvilImportReport::default();
+
+    let area = options.area;
+    let min_chunk = area.min.div_euclid(IVec3::splat(16));
+    let max_chunk = area.max.div_euclid(IVec3::splat(16));
+    let min_region = min_chunk.div_euclid(IVec3::splat(REGION_CHUNKS));
+    let max_region = max_chunk.div_euclid(IVec3::splat(REGION_CHUNKS));
+
+    let total = ((max_chunk.x - min_chunk.x + 1) * (max_chunk.z - min_chunk.z + 1)) as usize;
+    let mut done = 0;
+
+    for region_z in min_region.z..=max_region.z {
+        for region_x in min_region.x..=max_region.x {
+            let path = region_dir.join(format!("r.{region_x}.{region_z}.mca"));
+            let region = match fs::read(&path) {
+                Ok(region) => region,
+                Err(err) if err.kind() == io::ErrorKind::NotFound => Vec::new(),
+                Err(err) => return Err(err.into()),
+            };
+
+            let first = ivec3(region_x, 0, region_z) * REGION_CHUNKS;
+            let chunks_min = min_chunk.max(first);
+            let chunks_max = max_chunk.min(first + REGION_CHUNKS - 1);
+
+            for chunk_z in chunks_min.z..=chunks_max.z {
+                for chunk_x in chunks_min.x..=chunks_max.x {
+                    let error = |kind| AnvilError::Chunk {
+                        x: chunk_x,
+                        z: chunk_z,
+                        kind,
+                    };
+
+                    let chunk = read_chunk(&region, region_dir, chunk_x, chunk_z).map_err(error)?;
+                    let sections = match chunk {
+                        Some(chunk) => read_sections(&chunk).map_err(error)?,
+                        None => None,
+                    };
+
+                    match sections {
+                        Some(sections) => {
+                            report.nodes += import_chunk(
+                                map,
+                                mapping,
+                                options,
+                                chunk_x,
+                                chunk_z,
+                                &sections,
+                                &mut report.unmapped,
+                            )?;
+                            report.chunks += 1;
+                        }
+                        None => report.skipped_chunks += 1,
+                    }
+
+                    done += 1;
+                    on_progress(AnvilImportProgress { done, total });
+                }
+            }
+        }
+    }
+
+    Ok(report)
+}
+
+/// Name and `param2` of a node from the mapping.
+pub(crate) type MappedNode<'a> = (&'a str, u8);
+
+/// A 16×16×16 section of a chunk: indices into the palette in y, z, x
+/// order.
+struct Section {
+    y: i32,
+    palette: Vec<(String, Vec<(String, String)>)>,
+    states: Vec<u16>,
+}
+
+/// Reads and decompresses the chunk from its region file. Returns `None` if
+/// the chunk doesn't exist.
+fn read_chunk(
+    region: &[u8],
+    region_dir: &Path,
+    chunk_x: i32,
+    chunk_z: i32,
+) -> Result<Option<Nbt>, ParseErrorKind> {
+    if region.is_empty() {
+        return Ok(None);
+    }
+
+    let index =
+        (chunk_x.rem_euclid(REGION_CHUNKS) + chunk_z.rem_euclid(REGION_CHUNKS) * 32) as usize;
+    let location = region
+        .get(index * 4..index * 4 + 4)
+        .ok_or(ParseErrorKind::UnexpectedEnd)?;
+    let sector = u32::from_be_bytes([0, location[0], location[1], location[2]]) as usize;
+    if sector == 0 {
+        return Ok(None);
+    }
+
+    let start = sector * SECTOR_SIZE;
+    let header = region
+        .get(start..start + 5)
+        .ok_or(ParseErrorKind::UnexpectedEnd)?;
+    let length = u32::from_be_bytes(header[..4].try_into().unwrap()) as usize;
+    let compression = header[4];
+
+    let external;
+    let data = if compression & COMPRESSION_EXTERNAL != 0 {
+        let path = region_dir.join(format!("c.{chunk_x}.{chunk_z}.mcc"));
+        external = fs::read(path).map_err(|err| ParseErrorKind::Invalid(err.to_string()))?;
+        &external[..]
+    } else {
+        region
+            .get(start + 5..start + 4 + length.max(1))
+            .ok_or(ParseErrorKind::UnexpectedEnd)?
+    };
+
+    let mut decompressed = Vec::new();
+    let limit = Block::MAX_PAYLOAD_SIZE as u64 + 1;
+    let result = match compression & !COMPRESSION_EXTERNAL {
+        COMPRESSION_GZIP => GzDecoder::new(data)
+            .take(limit)
+            .read_to_end(&mut decompressed),
+        COMPRESSION_ZLIB => ZlibDecoder::new(data)
+            .take(limit)
+            .read_to_end(&mut decompressed),
+        COMPRESSION_NONE => {
+            decompressed.extend_from_slice(data);
+            Ok(data.len())
+        }
+        other => {
+            return Err(ParseErrorKind::Invalid(format!(
+                "unsupported compression {other}"
+            )));
+        }
+    };
+    result.map_err(ParseErrorKind::Decompress)?;
+
+    if decompressed.len() > Block::MAX_PAYLOAD_SIZE {
+        return Err(ParseErrorKind::TooLarge);
+    }
+
+    Nbt::read(&decompressed).map(Some)
+}
+
+/// Reads the block sections of a chunk, in the layout of Minecraft 1.18 and
+/// later or the older one with a `Level` compound. Returns `None` for chunks
+/// that aren't fully generated.
+fn read_sections(chunk: &Nbt) -> Result<Option<Vec<Section>>, ParseErrorKind> {
+    let data_version = chunk.get("DataVersion").and_then(Nbt::as_i64).unwrap_or(0);
+    if data_version < DATA_VERSION_PALETTES {
+        return Err(invalid("chunk from before Minecraft 1.13"));
+    }
+
+    let level = chunk.get("Level").unwrap_or(chunk);
+
+    let status = level.get("Status").and_then(Nbt::as_str);
+    let complete = matches!(
+        status,
+        None | Some("full" | "minecraft:full" | "postprocessed" | "fullchunk")
+    );
+    if !complete {
+        return Ok(None);
+    }
+
+    let sections = level
+        .get("sections")
+        .or_else(|| level.get("Sections"))
+        .and_then(Nbt::as_list)
+        .unwrap_or_default();
+
+    let mut result = Vec::new();
+    for section in sections {
+        let y = section
+            .get("Y")
+            .and_then(Nbt::as_i64)
+            .ok_or_else(|| invalid("section without `Y`"))? as i32;
+
+        // Sections of the newer layout keep their blocks in a compound,
+        // older ones have the palette and states next to `Y`.
+        let (palette, states) = match section.get("block_states") {
+            Some(block_states) => (block_states.get("palette"), block_states.get("data")),
+            None => (section.get("Palette"), section.get("BlockStates")),
+        };
+
+        // Sections of only lighting data don't have any blocks.
+        let Some(palette) = palette else {
+            continue;
+        };
+
+        let palette = palette
+            .as_list()
+            .ok_or_else(|| invalid("invalid block palette"))?
+            .iter()
+            .map(read_block_state)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let states = match states {
+            Some(states) => states
+                .as_long_array()
+                .ok_or_else(|| invalid("invalid block states"))?,
+            None => &[],
+        };
+
+        let states = unpack_states(states, palette.len(), data_version)?;
+        result.push(Section { y, palette, states });
+    }
+
+    Ok(Some(result))
+}
+
+fn read_block_state(state: &Nbt) -> Result<(String, Vec<(String, String)>), ParseErrorKind> {
+    let name = state
+        .get("Name")
+        .and_then(Nbt::as_str)
+        .ok_or_else(|| invalid("block state without a name"))?;
+
+    let mut properties = Vec::new();
+    if let Some(entries) = state.get("Properties").and_then(Nbt::as_compound) {
+        for (key, value) in entries {
+            let value = value
+                .as_str()
+                .ok_or_else(|| invalid(&format!("invalid block state property `{key}`")))?;
+            properties.push((key.clone(), value.to_string()));
+        }
+    }
+    properties.sort();
+
+    Ok((name.to_string(), properties))
+}
+
+/// Unpacks the palette indices of the 4096 blocks of a section. Palettes of
+/// a single state may come without any data.
+fn unpack_states(
+    longs: &[i64],
+    palette_len: usize,
+    data_version: i64,
+) -> Result<Vec<u16>, ParseErrorKind> {
+    if palette_len == 0 {
+        return Err(invalid("empty block palette"));
+    }
+    if palette_len == 1 && longs.is_empty() {
+        return Ok(vec![0; 4096]);
+    }
+
+    let bits = (usize::BITS - (palette_len - 1).leading_zeros()).max(4) as usize;
+    let mask = (1u64 << bits) - 1;
+    let padded = data_version >= DATA_VERSION_PADDED_STATES;
+
+    let needed = if padded {
+        4096usize.div_ceil(64 / bits)
+    } else {
+        (4096 * bits).div_ceil(64)
+    };
+    if longs.len() < needed {
+        return Err(invalid("block states too short for the palette"));
+    }
+
+    let mut states = Vec::with_capacity(4096);
+    for index in 0..4096 {
+        let state = if padded {
+            let per_long = 64 / bits;
+            let long = longs[index / per_long] as u64;
+            (long >> (index % per_long * bits)) & mask
+        } else {
+            let bit = index * bits;
+            let low = longs[bit / 64] as u64 >> (bit % 64);
+            let high = match (bit % 64 + bits > 64, longs.get(bit / 64 + 1)) {
+                (true, Some(&next)) => (next as u64) << (64 - bit % 64),
+                _ => 0,
+            };
+            (low | high) & mask
+        };
+
+        if state as usize >= palette_len {
+            return Err(invalid(&format!("block state {state} not in the palette")));
+        }
+        states.push(state as u16);
+    }
+
+    Ok(states)
+}
+
+/// Writes the nodes of one chunk within the area to the map, returning how
+/// many were written.
+fn import_chunk(
+    map: &Map,
+    mapping: &BlockMapping,
+    options: &AnvilImportOptions,
+    chunk_x: i32,
+    chunk_z: i32,
+    sections: &[Section],
+    unmapped: &mut BTreeMap<String, usize>,
+) -> Result<usize, MapError> {
+    let area = options.area;
+
+    // Nodes by Minetest block, as node indices and palette entries.
+    let mut by_block: HashMap<IVec3, Vec<(usize, MappedNode)>> = HashMap::new();
+
+    for section in sections {
+        let nodes: Vec<Option<MappedNode>> = section
+            .palette
+            .iter()
+            .map(|(name, properties)| mapping.lookup(name, properties))
+            .collect();
+
+        for (index, &state) in section.states.iter().enumerate() {
+            let index = index as i32;
+            let pos = ivec3(
+                chunk_x * 16 + (index & 15),
+                section.y * 16 + (index >> 8),
+                chunk_z * 16 + ((index >> 4) & 15),
+            );
+            if pos.cmplt(area.min).any() || pos.cmpgt(area.max).any() {
+                continue;
+            }
+
+            let Some(node) = nodes[state as usize] else {
+                let (name, properties) = &section.palette[state as usize];
+                *unmapped
+                    .entry(unmapped_key(mapping, name, properties))
+                    .or_default() += 1;
+                continue;
+            };
+
+            let pos = ivec3(pos.x, pos.y, -pos.z - 1) + options.offset;
+            let block_pos = pos.div_euclid(IVec3::splat(16));
+            let index = Block::node_index(pos - block_pos * 16).expect("node lies in its block");
+            by_block.entry(block_pos).or_default().push((index, node));
+        }
+    }
+
+    write_mapped_nodes(map, &by_block)
+}
+
+/// Writes nodes, given by their index within their block, replacing the
+/// nodes there along with their metadata and node timers. Returns the number
+/// of nodes written.
+pub(crate) fn write_mapped_nodes(
+    map: &Map,
+    by_block: &HashMap<IVec3, Vec<(usize, MappedNode)>>,
+) -> Result<usize, MapError> {
+    let mut positions: Vec<IVec3> = by_block.keys().copied().collect();
+    positions.sort_by_key(|pos| (pos.z, pos.y, pos.x));
+
+    modify_blocks(map, &positions, |block_pos, block| {
+        let nodes = &by_block[&block_pos];
+
+        for &(index, (name, param2)) in nodes {
+            let id = block.get_or_insert_id(name);
+            block.set_node_at(
+                index,
+                Node {
+                    id,
+                    param1: 0,
+                    param2,
+                },
+            );
+
+            let local_pos = Block::node_pos(index);
+            block.remove_metadata(local_pos);
+            block.remove_node_timer(local_pos);
+        }
+
+        nodes.len()
+    })
+}
+
+/// How [`AnvilImportReport::unmapped`] names a block state.
+pub(crate) fn unmapped_key(
+    mapping: &BlockMapping,
+    name: &str,
+    properties: &[(String, String)],
+) -> String {
+    if mapping.contains(name) {
+        format_state(name, properties)
+    } else {
+        name.to_string()
+    }
+}
+
+/// Writes a block state as `name[key=value,...]`, leaving out the brackets
+/// if there are no properties.
+pub(crate) fn format_state(name: &str, properties: &[(String, String)]) -> String {
+    if properties.is_empty() {
+        return name.to_string();
+    }
+
+    let properties: Vec<String> = properties
+        .iter()
+        .map(|(key, value)| format!("{key}={value}"))
+        .collect();
+    format!("{name}[{}]", properties.join(","))
+}
+
+fn invalid(message: &str) -> ParseErrorKind {
+    ParseErrorKind::Invalid(message.to_string())
+}
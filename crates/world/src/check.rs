@@ -0,0 +1,288 @@
+use std::{
+    collections::{BTreeSet, HashSet},
+    fmt::Display,
+};
+
+use glam::{IVec3, ivec3};
+
+use crate::{Block, Map, MapError, ParseErrorKind};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ProblemKind {
+    /// The backend failed to return the block data.
+    Unreadable,
+
+    /// The block uses a serialization version that can't be decoded.
+    UnsupportedVersion,
+
+    /// The block data can't be decoded.
+    Corrupt,
+
+    /// The block data ends before all nodes were read.
+    Truncated,
+
+    /// Node metadata, static objects or node timers can't be decoded.
+    InvalidMetadata,
+
+    /// Some node ids have no name in the block's mappings.
+    MissingMapping,
+
+    /// The backend holds more than one entry for the position.
+    DuplicatePosition,
+}
+
+impl ProblemKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ProblemKind::Unreadable => "unreadable",
+            ProblemKind::UnsupportedVersion => "unsupported_version",
+            ProblemKind::Corrupt => "corrupt",
+            ProblemKind::Truncated => "truncated",
+            ProblemKind::InvalidMetadata => "invalid_metadata",
+            ProblemKind::MissingMapping => "missing_mapping",
+            ProblemKind::DuplicatePosition => "duplicate_position",
+        }
+    }
+}
+
+impl Display for ProblemKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// What `--repair` does, or would do, about a problem.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum RepairAction {
+    /// Nothing can be done without losing data that may still be recoverable.
+    None,
+
+    /// The block is deleted.
+    DeleteBlock,
+
+    /// Node metadata, static objects and node timers are dropped, nodes are
+    /// kept.
+    DropMetadata,
+
+    /// Ids without a name are mapped to `air`.
+    MapToAir,
+
+    /// All entries are deleted and the first one is written back.
+    Deduplicate,
+}
+
+impl RepairAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RepairAction::None => "none",
+            RepairAction::DeleteBlock => "delete_block",
+            RepairAction::DropMetadata => "drop_metadata",
+            RepairAction::MapToAir => "map_to_air",
+            RepairAction::Deduplicate => "deduplicate",
+        }
+    }
+}
+
+impl Display for RepairAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Problem {
+    pub pos: IVec3,
+    pub kind: ProblemKind,
+    pub message: String,
+    pub repair: RepairAction,
+
+    /// Whether the repair action was carried out.
+    pub repaired: bool,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct CheckOptions {
+    pub repair: bool,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct CheckSummary {
+    pub blocks: usize,
+    pub problems: usize,
+    pub repaired: usize,
+}
+
+/// Walks every block of the map and reports problems to `on_problem` as they
+/// are found.
+///
+/// With `options.repair` set, problems that have a repair action are fixed in
+/// place. Errors are only returned when the block list itself can't be read
+/// or a repair fails.
+pub fn check_map(
+    map: &Map,
+    options: &CheckOptions,
+    mut on_problem: impl FnMut(&Problem),
+) -> Result<CheckSummary, MapError> {
+    let mut summary = CheckSummary::default();
+
+    let positions = map.list_blocks()?;
+
+    let mut seen = HashSet::new();
+    let mut duplicates = BTreeSet::new();
+    for &pos in &positions {
+        if !seen.insert(pos) {
+            duplicates.insert((pos.x, pos.y, pos.z));
+        }
+    }
+
+    // Duplicates are resolved first so that repairs of the remaining problems
+    // apply to the entry that is kept.
+    for &(x, y, z) in &duplicates {
+        let pos = ivec3(x, y, z);
+        let count = positions.iter().filter(|&&p| p == pos).count();
+
+        let mut problem = Problem {
+            pos,
+            kind: ProblemKind::DuplicatePosition,
+            message: format!("{count} entries for the same position"),
+            repair: RepairAction::Deduplicate,
+            repaired: false,
+        };
+
+        if options.repair {
+            let data = map.get_block_data(pos)?;
+            map.delete_block(pos)?;
+            map.set_block_data(pos, &data)?;
+            problem.repaired = true;
+        }
+
+        summary.report(&problem);
+        on_problem(&problem);
+    }
+
+    let mut visited = HashSet::new();
+    for &pos in &positions {
+        if !visited.insert(pos) {
+            continue;
+        }
+
+        summary.blocks += 1;
+
+        if let Some(mut problem) = check_block(map, pos) {
+            if options.repair {
+                problem.repaired = repair_block(map, &problem)?;
+            }
+
+            summary.report(&problem);
+            on_problem(&problem);
+        }
+    }
+
+    Ok(summary)
+}
+
+impl CheckSummary {
+    fn report(&mut self, problem: &Problem) {
+        self.problems += 1;
+
+        if problem.repaired {
+            self.repaired += 1;
+        }
+    }
+}
+
+fn check_block(map: &Map, pos: IVec3) -> Option<Problem> {
+    let problem = |kind, message: String, repair| {
+        Some(Problem {
+            pos,
+            kind,
+            message,
+            repair,
+            repaired: false,
+        })
+    };
+
+    let data = match map.get_block_data(pos) {
+        Ok(data) => data,
+        Err(err) => return problem(ProblemKind::Unreadable, err.to_string(), RepairAction::None),
+    };
+
+    let block = match Block::parse_nodes(&data) {
+        Ok(block) => block,
+        Err(err) => {
+            let (kind, repair) = match err.kind {
+                ParseErrorKind::UnsupportedVersion(_) => {
+                    (ProblemKind::UnsupportedVersion, RepairAction::None)
+                }
+                ParseErrorKind::UnexpectedEnd => {
+                    (ProblemKind::Truncated, RepairAction::DeleteBlock)
+                }
+                _ => (ProblemKind::Corrupt, RepairAction::DeleteBlock),
+            };
+
+            return problem(kind, err.to_string(), repair);
+        }
+    };
+
+    if let Err(err) = Block::parse_data(&data) {
+        return problem(
+            ProblemKind::InvalidMetadata,
+            err.to_string(),
+            RepairAction::DropMetadata,
+        );
+    }
+
+    let unmapped = unmapped_ids(&block);
+    if !unmapped.is_empty() {
+        let ids: Vec<_> = unmapped.iter().map(|id| id.to_string()).collect();
+
+        return problem(
+            ProblemKind::MissingMapping,
+            format!("node ids without a name: {}", ids.join(", ")),
+            RepairAction::MapToAir,
+        );
+    }
+
+    None
+}
+
+fn repair_block(map: &Map, problem: &Problem) -> Result<bool, MapError> {
+    let pos = problem.pos;
+
+    match problem.repair {
+        RepairAction::None | RepairAction::Deduplicate => return Ok(false),
+        RepairAction::DeleteBlock => map.delete_block(pos)?,
+        RepairAction::DropMetadata => {
+            let mut block = Block::parse_nodes(&map.get_block_data(pos)?)?;
+            map_to_air(&mut block);
+            block.touch();
+            map.set_block(pos, &block)?;
+        }
+        RepairAction::MapToAir => {
+            let mut block = map.get_block(pos)?;
+            map_to_air(&mut block);
+            block.touch();
+            map.set_block(pos, &block)?;
+        }
+    }
+
+    Ok(true)
+}
+
+fn unmapped_ids(block: &Block) -> BTreeSet<u16> {
+    let mut ids = BTreeSet::new();
+
+    for (_, node) in block.nodes() {
+        if block.get_name_by_id(node.id).is_none() {
+            ids.insert(node.id);
+        }
+    }
+
+    ids
+}
+
+fn map_to_air(block: &mut Block) {
+    for id in unmapped_ids(block) {
+        block.set_name(id, "air");
+    }
+}
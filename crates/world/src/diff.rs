@@ -0,0 +1,216 @@
+use std::collections::{BTreeMap, HashSet};
+
+use glam::IVec3;
+
+use crate::{Block, Map, MapError, Node};
+
+#[derive(Clone, Debug, Default)]
+pub struct DiffOptions {
+    /// Treat blocks with equal timestamps as unchanged without comparing
+    /// their nodes. The engine sets the timestamp to its game time when it
+    /// saves a block, so edits made within the same second, or by tools that
+    /// leave timestamps alone, are missed. Undefined timestamps are never
+    /// trusted.
+    pub trust_timestamps: bool,
+
+    /// Report changes in `param1`. It mostly holds light, which changes
+    /// whenever anything nearby changes, so it is ignored by default.
+    pub compare_param1: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BlockChangeKind {
+    Added,
+    Removed,
+    Modified,
+}
+
+/// A node as stored in a block, with its id resolved to a name.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NamedNode {
+    pub name: String,
+    pub param1: u8,
+    pub param2: u8,
+}
+
+#[derive(Clone, Debug)]
+pub struct NodeChange {
+    /// Node position in world coordinates.
+    pub pos: IVec3,
+    pub old: NamedNode,
+    pub new: NamedNode,
+}
+
+#[derive(Clone, Debug)]
+pub struct BlockChange {
+    pub pos: IVec3,
+    pub kind: BlockChangeKind,
+
+    /// Changed nodes of modified blocks. Empty for added and removed blocks.
+    pub nodes: Vec<NodeChange>,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct NodeCount {
+    pub added: u64,
+    pub removed: u64,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct DiffSummary {
+    pub added_blocks: usize,
+    pub removed_blocks: usize,
+    pub modified_blocks: usize,
+    pub unchanged_blocks: usize,
+
+    /// Nodes that appeared and disappeared, by node name. Nodes of added and
+    /// removed blocks are counted too.
+    pub nodes: BTreeMap<String, NodeCount>,
+}
+
+/// Compares two maps, typically two snapshots of the same world, and reports
+/// every added, removed or modified block to `on_change` in position order.
+pub fn diff_maps(
+    old: &Map,
+    new: &Map,
+    options: &DiffOptions,
+    mut on_change: impl FnMut(&BlockChange),
+) -> Result<DiffSummary, MapError> {
+    let mut summary = DiffSummary::default();
+
+    let old_positions: HashSet<IVec3> = old.list_blocks()?.into_iter().collect();
+    let new_positions: HashSet<IVec3> = new.list_blocks()?.into_iter().collect();
+
+    let mut positions: Vec<IVec3> = old_positions.union(&new_positions).copied().collect();
+    positions.sort_by_key(|pos| (pos.z, pos.y, pos.x));
+
+    for pos in positions {
+        let change = match (old_positions.contains(&pos), new_positions.contains(&pos)) {
+            (true, true) => diff_block(old, new, pos, options, &mut summary)?,
+            (false, true) => {
+                count_nodes(&new.get_block(pos)?, &mut summary, |count| &mut count.added);
+                summary.added_blocks += 1;
+                Some(BlockChange {
+                    pos,
+                    kind: BlockChangeKind::Added,
+                    nodes: Vec::new(),
+                })
+            }
+            (true, false) => {
+                count_nodes(&old.get_block(pos)?, &mut summary, |count| {
+                    &mut count.removed
+                });
+                summary.removed_blocks += 1;
+                Some(BlockChange {
+                    pos,
+                    kind: BlockChangeKind::Removed,
+                    nodes: Vec::new(),
+                })
+            }
+            (false, false) => None,
+        };
+
+        if let Some(change) = change {
+            on_change(&change);
+        }
+    }
+
+    Ok(summary)
+}
+
+fn diff_block(
+    old: &Map,
+    new: &Map,
+    pos: IVec3,
+    options: &DiffOptions,
+    summary: &mut DiffSummary,
+) -> Result<Option<BlockChange>, MapError> {
+    let old_data = old.get_block_data(pos)?;
+    let new_data = new.get_block_data(pos)?;
+
+    let timestamp = |data: &[u8]| Block::parse_timestamp(data).map_err(|err| err.at(pos));
+
+    let unchanged = old_data == new_data
+        || options.trust_timestamps && {
+            let old_timestamp = timestamp(&old_data)?;
+            old_timestamp != Block::TIMESTAMP_UNDEFINED && old_timestamp == timestamp(&new_data)?
+        };
+
+    if unchanged {
+        summary.unchanged_blocks += 1;
+        return Ok(None);
+    }
+
+    let old_block = Block::parse_data(&old_data).map_err(|err| err.at(pos))?;
+    let new_block = Block::parse_data(&new_data).map_err(|err| err.at(pos))?;
+
+    let mut nodes = Vec::new();
+
+    for ((local, old_node), (_, new_node)) in old_block.nodes().zip(new_block.nodes()) {
+        let old_node = named_node(&old_block, old_node);
+        let new_node = named_node(&new_block, new_node);
+
+        let differs = old_node.name != new_node.name
+            || old_node.param2 != new_node.param2
+            || options.compare_param1 && old_node.param1 != new_node.param1;
+
+        if !differs {
+            continue;
+        }
+
+        if old_node.name != new_node.name {
+            let counts = &mut summary.nodes;
+            counts.entry(old_node.name.clone()).or_default().removed += 1;
+            counts.entry(new_node.name.clone()).or_default().added += 1;
+        }
+
+        nodes.push(NodeChange {
+            pos: pos * 16 + local,
+            old: old_node,
+            new: new_node,
+        });
+    }
+
+    // Blocks that only differ in ignored fields, such as light or metadata,
+    // are not reported.
+    if nodes.is_empty() {
+        summary.unchanged_blocks += 1;
+        return Ok(None);
+    }
+
+    summary.modified_blocks += 1;
+
+    Ok(Some(BlockChange {
+        pos,
+        kind: BlockChangeKind::Modified,
+        nodes,
+    }))
+}
+
+fn named_node(block: &Block, node: Node) -> NamedNode {
+    NamedNode {
+        name: block
+            .get_name_by_id(node.id)
+            .unwrap_or("unknown")
+            .to_string(),
+        param1: node.param1,
+        param2: node.param2,
+    }
+}
+
+fn count_nodes(
+    block: &Block,
+    summary: &mut DiffSummary,
+    field: impl Fn(&mut NodeCount) -> &mut u64,
+) {
+    let mut counts: BTreeMap<u16, u64> = BTreeMap::new();
+
+    for (_, node) in block.nodes() {
+        *counts.entry(node.id).or_default() += 1;
+    }
+
+    for (id, count) in counts {
+        let name = block.get_name_by_id(id).unwrap_or("unknown");
+        *field(summary.nodes.entry(name.to_string()).or_default()) += count;
+    }
+}
@@ -0,0 +1,400 @@
+use std::{collections::BTreeMap, fmt::Display, str::FromStr};
+
+#[derive(thiserror::Error, Debug)]
+pub enum InventoryError {
+    #[error("invalid inventory line: `{0}`")]
+    InvalidLine(String),
+
+    #[error("invalid item string: `{0}`")]
+    InvalidItem(String),
+
+    #[error("unterminated inventory list `{0}`")]
+    UnterminatedList(String),
+}
+
+/// A stack of items as stored in inventories, e.g.
+/// `default:pick_steel 1 3000 "\u0001description\u0002Pick\u0003"`.
+///
+/// An empty name means an empty stack.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ItemStack {
+    pub name: String,
+    pub count: u16,
+
+    /// Tool wear, from 0 (new) to 65535 (broken).
+    pub wear: u16,
+
+    pub metadata: BTreeMap<String, String>,
+}
+
+impl ItemStack {
+    pub fn new(name: &str, count: u16) -> Self {
+        Self {
+            name: name.to_string(),
+            count,
+            ..Default::default()
+        }
+    }
+
+    pub fn empty() -> Self {
+        Self::default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.name.is_empty() || self.count == 0
+    }
+}
+
+impl FromStr for ItemStack {
+    type Err = InventoryError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || InventoryError::InvalidItem(s.to_string());
+
+        let mut rest = s.trim_start();
+
+        let name = read_word(&mut rest).ok_or_else(invalid)?;
+        if name.is_empty() {
+            return Ok(Self::empty());
+        }
+
+        let mut stack = Self::new(&name, 1);
+
+        if let Some(count) = read_word(&mut rest).filter(|w| !w.is_empty()) {
+            stack.count = count.parse().map_err(|_| invalid())?;
+        }
+
+        if let Some(wear) = read_word(&mut rest).filter(|w| !w.is_empty()) {
+            stack.wear = wear.parse().map_err(|_| invalid())?;
+        }
+
+        if let Some(metadata) = read_word(&mut rest).filter(|w| !w.is_empty()) {
+            stack.metadata = parse_item_metadata(&metadata);
+        }
+
+        Ok(stack)
+    }
+}
+
+impl Display for ItemStack {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.is_empty() {
+            return Ok(());
+        }
+
+        // Trailing fields are only written if they differ from their defaults.
+        let parts = if !self.metadata.is_empty() {
+            4
+        } else if self.wear != 0 {
+            3
+        } else if self.count != 1 {
+            2
+        } else {
+            1
+        };
+
+        f.write_str(&quote_if_needed(&self.name))?;
+
+        if parts >= 2 {
+            write!(f, " {}", self.count)?;
+        }
+
+        if parts >= 3 {
+            write!(f, " {}", self.wear)?;
+        }
+
+        if parts >= 4 {
+            write!(
+                f,
+                " {}",
+                quote_if_needed(&serialize_item_metadata(&self.metadata))
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct InventoryList {
+    pub name: String,
+
+    /// Width used when the list is shown as a grid, 0 if unspecified.
+    pub width: u32,
+
+    pub items: Vec<ItemStack>,
+}
+
+impl InventoryList {
+    pub fn new(name: &str, size: usize) -> Self {
+        Self {
+            name: name.to_string(),
+            width: 0,
+            items: vec![ItemStack::empty(); size],
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        self.items.len()
+    }
+}
+
+/// Named item lists, as used by players, node metadata and detached
+/// inventories.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Inventory {
+    pub lists: Vec<InventoryList>,
+}
+
+impl Inventory {
+    pub fn new() -> Self {
+        Self { lists: Vec::new() }
+    }
+
+    /// Parses the text format, with or without the closing `EndInventory`
+    /// line.
+    pub fn parse(text: &str) -> Result<Self, InventoryError> {
+        let mut inventory = Self::new();
+        let mut lines = text.lines();
+
+        while let Some(line) = lines.next() {
+            let line = line.trim();
+            let (keyword, args) = line.split_once(' ').unwrap_or((line, ""));
+
+            match keyword {
+                "" => continue,
+                "EndInventory" | "end" => break,
+                "List" => {
+                    let (name, size) = args
+                        .trim()
+                        .split_once(' ')
+                        .and_then(|(name, size)| Some((name, size.trim().parse().ok()?)))
+                        .ok_or_else(|| InventoryError::InvalidLine(line.to_string()))?;
+
+                    let list = parse_list(name, size, &mut lines)?;
+                    inventory.set_list(list);
+                }
+                _ => return Err(InventoryError::InvalidLine(line.to_string())),
+            }
+        }
+
+        Ok(inventory)
+    }
+
+    /// Serializes all lists followed by the closing `EndInventory` line.
+    pub fn serialize(&self) -> String {
+        let mut text = String::new();
+
+        for list in &self.lists {
+            text.push_str(&format!("List {} {}\n", list.name, list.size()));
+            text.push_str(&format!("Width {}\n", list.width));
+
+            for item in &list.items {
+                if item.is_empty() {
+                    text.push_str("Empty\n");
+                } else {
+                    text.push_str(&format!("Item {item}\n"));
+                }
+            }
+
+            text.push_str("EndInventoryList\n");
+        }
+
+        text.push_str("EndInventory\n");
+        text
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lists.is_empty()
+    }
+
+    pub fn list(&self, name: &str) -> Option<&InventoryList> {
+        self.lists.iter().find(|list| list.name == name)
+    }
+
+    pub fn list_mut(&mut self, name: &str) -> Option<&mut InventoryList> {
+        self.lists.iter_mut().find(|list| list.name == name)
+    }
+
+    /// Adds a list, replacing any existing list with the same name.
+    pub fn set_list(&mut self, list: InventoryList) {
+        match self.list_mut(&list.name) {
+            Some(existing) => *existing = list,
+            None => self.lists.push(list),
+        }
+    }
+
+    pub fn remove_list(&mut self, name: &str) -> Option<InventoryList> {
+        let index = self.lists.iter().position(|list| list.name == name)?;
+        Some(self.lists.remove(index))
+    }
+}
+
+fn parse_list<'a>(
+    name: &str,
+    size: usize,
+    lines: &mut impl Iterator<Item = &'a str>,
+) -> Result<InventoryList, InventoryError> {
+    let mut list = InventoryList {
+        name: name.to_string(),
+        width: 0,
+        items: Vec::with_capacity(size),
+    };
+
+    for line in lines {
+        let line = line.trim();
+        let (keyword, args) = line.split_once(' ').unwrap_or((line, ""));
+
+        match keyword {
+            "" => continue,
+            "EndInventoryList" | "end" => {
+                // Lists are always as long as their declared size.
+                list.items.resize(size, ItemStack::empty());
+                return Ok(list);
+            }
+            "Width" => {
+                list.width = args
+                    .trim()
+                    .parse()
+                    .map_err(|_| InventoryError::InvalidLine(line.to_string()))?;
+            }
+            "Item" => list.items.push(args.parse()?),
+            // `Keep` marks unchanged slots in incremental updates.
+            "Empty" | "Keep" => list.items.push(ItemStack::empty()),
+            _ => return Err(InventoryError::InvalidLine(line.to_string())),
+        }
+    }
+
+    Err(InventoryError::UnterminatedList(name.to_string()))
+}
+
+const METADATA_START: char = '\x01';
+const METADATA_KV_DELIM: char = '\x02';
+const METADATA_PAIR_DELIM: char = '\x03';
+
+fn parse_item_metadata(s: &str) -> BTreeMap<String, String> {
+    let mut metadata = BTreeMap::new();
+
+    let Some(pairs) = s.strip_prefix(METADATA_START) else {
+        // Items from before metadata had fields keep it all in one string.
+        metadata.insert(String::new(), s.to_string());
+        return metadata;
+    };
+
+    for pair in pairs.split(METADATA_PAIR_DELIM) {
+        if let Some((key, value)) = pair.split_once(METADATA_KV_DELIM) {
+            metadata.insert(key.to_string(), value.to_string());
+        }
+    }
+
+    metadata
+}
+
+fn serialize_item_metadata(metadata: &BTreeMap<String, String>) -> String {
+    // Legacy metadata is written back as it was read.
+    if let Some(value) = metadata.get("")
+        && metadata.len() == 1
+        && !value.starts_with(METADATA_START)
+    {
+        return value.clone();
+    }
+
+    let mut s = String::from(METADATA_START);
+
+    for (key, value) in metadata {
+        s.push_str(key);
+        s.push(METADATA_KV_DELIM);
+        s.push_str(value);
+        s.push(METADATA_PAIR_DELIM);
+    }
+
+    s
+}
+
+/// Reads a space-delimited word or a JSON-style quoted string. Returns `None`
+/// on malformed quoting and an empty string at the end of input.
+fn read_word(s: &mut &str) -> Option<String> {
+    let trimmed = s.trim_start();
+
+    if let Some(quoted) = trimmed.strip_prefix('"') {
+        let (word, rest) = unquote(quoted)?;
+        *s = rest;
+        return Some(word);
+    }
+
+    let end = trimmed.find(' ').unwrap_or(trimmed.len());
+    let (word, rest) = trimmed.split_at(end);
+    *s = rest;
+
+    Some(word.to_string())
+}
+
+/// Decodes a quoted string up to its closing quote. `\uXXXX` escapes stand
+/// for single bytes, so multi-byte characters are written as several escapes.
+fn unquote(s: &str) -> Option<(String, &str)> {
+    let mut bytes = Vec::new();
+    let mut chars = s.char_indices();
+
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' => {
+                let word = String::from_utf8(bytes).ok()?;
+                return Some((word, &s[i + 1..]));
+            }
+            '\\' => {
+                let escaped = match chars.next()?.1 {
+                    'b' => b'\x08',
+                    'f' => b'\x0c',
+                    'n' => b'\n',
+                    'r' => b'\r',
+                    't' => b'\t',
+                    'u' => {
+                        let hex: String = (0..4)
+                            .filter_map(|_| chars.next())
+                            .map(|(_, c)| c)
+                            .collect();
+                        u16::from_str_radix(&hex, 16).ok()? as u8
+                    }
+                    c if c.is_ascii() => c as u8,
+                    _ => return None,
+                };
+                bytes.push(escaped);
+            }
+            c => bytes.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes()),
+        }
+    }
+
+    None
+}
+
+fn quote_if_needed(s: &str) -> String {
+    let needs_quotes = s
+        .bytes()
+        .any(|b| b <= 0x1f || b >= 0x7f || b == b' ' || b == b'"');
+
+    if !needs_quotes {
+        return s.to_string();
+    }
+
+    let mut quoted = String::from('"');
+
+    for b in s.bytes() {
+        match b {
+            b'"' => quoted.push_str("\\\""),
+            b'\\' => quoted.push_str("\\\\"),
+            b'\x08' => quoted.push_str("\\b"),
+            b'\x0c' => quoted.push_str("\\f"),
+            b'\n' => quoted.push_str("\\n"),
+            b'\r' => quoted.push_str("\\r"),
+            b'\t' => quoted.push_str("\\t"),
+            32..=126 => quoted.push(b as char),
+            _ => quoted.push_str(&format!("\\u{b:04x}")),
+        }
+    }
+
+    quoted.push('"');
+    quoted
+}
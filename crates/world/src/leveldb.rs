@@ -0,0 +1,209 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use glam::{IVec3, ivec3};
+use leveldb::{
+    compaction::Compaction,
+    database::Database,
+    iterator::{Iterable, LevelDBIterator},
+    kv::KV,
+    options::{Options, ReadOptions, WriteOptions},
+};
+
+use crate::{BackendError, MapBackend, MapError};
+
+/// Map stored in a LevelDB database, usually `map.db` in the world directory.
+pub struct LevelDbBackend {
+    db: Database<BlockKey>,
+    path: PathBuf,
+}
+
+impl LevelDbBackend {
+    pub fn new(path: impl AsRef<Path>) -> Result<Self, MapError> {
+        Self::open(path.as_ref(), false)
+    }
+
+    pub fn create(path: impl AsRef<Path>) -> Result<Self, MapError> {
+        Self::open(path.as_ref(), true)
+    }
+
+    fn open(path: &Path, create_if_missing: bool) -> Result<Self, MapError> {
+        let mut options = Options::new();
+        options.create_if_missing = create_if_missing;
+
+        let db = Database::open(path, options)?;
+
+        Ok(Self {
+            db,
+            path: path.to_path_buf(),
+        })
+    }
+}
+
+impl MapBackend for LevelDbBackend {
+    fn get_block_data(&mut self, pos: IVec3) -> Result<Vec<u8>, MapError> {
+        let data = self.db.get(ReadOptions::new(), BlockKey::from_pos(pos))?;
+        data.ok_or(MapError::BlockNotFound)
+    }
+
+    fn set_block_data(&mut self, pos: IVec3, data: &[u8]) -> Result<(), MapError> {
+        self.db
+            .put(WriteOptions::new(), BlockKey::from_pos(pos), data)?;
+        Ok(())
+    }
+
+    fn delete_block(&mut self, pos: IVec3) -> Result<(), MapError> {
+        self.db
+            .delete(WriteOptions::new(), BlockKey::from_pos(pos))?;
+        Ok(())
+    }
+
+    fn list_blocks(&mut self) -> Result<Vec<IVec3>, MapError> {
+        self.db
+            .keys_iter(ReadOptions::new())
+            .map(|key| key.to_pos())
+            .collect()
+    }
+
+    /// Blocks along the x axis have consecutive keys, so each row of the box
+    /// is read with a single range scan rather than a lookup per block.
+    fn get_blocks_in_box(
+        &mut self,
+        min: IVec3,
+        max: IVec3,
+    ) -> Result<Vec<(IVec3, Vec<u8>)>, MapError> {
+        let mut blocks = Vec::new();
+
+        for z in min.z..=max.z {
+            for y in min.y..=max.y {
+                let first = BlockKey::pack(ivec3(min.x, y, z));
+                let last = BlockKey::pack(ivec3(max.x, y, z));
+
+                for (start, end) in text_ranges(first, last) {
+                    // Keys of this range only, as other ranges return the
+                    // rest.
+                    let packed_range = start.min(end)..=start.max(end);
+
+                    let start = BlockKey(start.to_string().into_bytes());
+                    let end = BlockKey(end.to_string().into_bytes());
+
+                    for (key, data) in self.db.iter(ReadOptions::new()).from(&start) {
+                        if key.0 > end.0 {
+                            break;
+                        }
+
+                        // Longer keys sharing a prefix sort in between.
+                        if key
+                            .to_packed()
+                            .is_some_and(|packed| packed_range.contains(&packed))
+                        {
+                            blocks.push((key.to_pos()?, data));
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(blocks)
+    }
+
+    /// Compacts the whole key range, which drops overwritten and deleted
+    /// entries from the table files.
+    fn vacuum(&mut self) -> Result<(), MapError> {
+        // Keys are decimal numbers, so they all sort between these two.
+        let start = BlockKey(Vec::new());
+        let limit = BlockKey(vec![0xff]);
+        self.db.compact(&start, &limit);
+        Ok(())
+    }
+
+    fn size_on_disk(&mut self) -> Result<Option<u64>, MapError> {
+        let mut size = 0;
+
+        for entry in fs::read_dir(&self.path)? {
+            let metadata = entry?.metadata()?;
+            if metadata.is_file() {
+                size += metadata.len();
+            }
+        }
+
+        Ok(Some(size))
+    }
+}
+
+/// Keys are block positions packed into a single integer and written out as
+/// decimal text.
+struct BlockKey(Vec<u8>);
+
+impl BlockKey {
+    fn pack(pos: IVec3) -> i64 {
+        pos.z as i64 * 0x1000000 + pos.y as i64 * 0x1000 + pos.x as i64
+    }
+
+    fn from_pos(pos: IVec3) -> Self {
+        Self(Self::pack(pos).to_string().into_bytes())
+    }
+
+    fn to_packed(&self) -> Option<i64> {
+        std::str::from_utf8(&self.0).ok()?.parse().ok()
+    }
+
+    fn to_pos(&self) -> Result<IVec3, MapError> {
+        let packed = self.to_packed().ok_or_else(|| {
+            let key = String::from_utf8_lossy(&self.0);
+            BackendError::InvalidKey(key.into_owned())
+        })?;
+
+        fn unpack(value: i64) -> i32 {
+            let value = value.rem_euclid(4096) as i32;
+            if value < 2048 { value } else { value - 4096 }
+        }
+
+        let x = unpack(packed);
+        let packed = (packed - x as i64) / 4096;
+        let y = unpack(packed);
+        let packed = (packed - y as i64) / 4096;
+        let z = unpack(packed);
+
+        Ok(ivec3(x, y, z))
+    }
+}
+
+impl db_key::Key for BlockKey {
+    fn from_u8(key: &[u8]) -> Self {
+        Self(key.to_vec())
+    }
+
+    fn as_slice<T, F: Fn(&[u8]) -> T>(&self, f: F) -> T {
+        f(&self.0)
+    }
+}
+
+/// Splits the key range from `first` to `last` into ranges whose keys all
+/// have the same sign and number of digits. Within such a range, the text of
+/// the keys sorts like the numbers, except that the order is reversed for
+/// negative numbers. Returns the lowest and highest key of each range in text
+/// order.
+fn text_ranges(first: i64, last: i64) -> Vec<(i64, i64)> {
+    let mut ranges = Vec::new();
+    let mut start = first;
+
+    while start <= last {
+        let digits = start.unsigned_abs().checked_ilog10().unwrap_or(0);
+
+        if start < 0 {
+            // Up to -100 for -123, or up to -1 for -5.
+            let end = (-(10i64.pow(digits))).min(last);
+            ranges.push((end, start));
+            start = end + 1;
+        } else {
+            let end = (10i64.pow(digits + 1) - 1).min(last);
+            ranges.push((start, end));
+            start = end + 1;
+        }
+    }
+
+    ranges
+}
@@ -1,17 +1,87 @@
+mod alias;
+mod anvil;
+mod check;
+mod diff;
+mod inventory;
+#[cfg(feature = "leveldb")]
+mod leveldb;
+mod light;
+mod loader;
+mod lua;
 mod map;
+mod mapgen;
 mod meta;
+mod metadata;
+mod migrate;
+mod nbt;
+mod nodedef;
+mod palette;
+mod param2;
+#[cfg(feature = "postgres")]
+mod postgres;
+mod prune;
+mod recompress;
+mod schematic;
+#[cfg(feature = "serde")]
+mod serde_impls;
+mod serialize;
+mod sponge;
 mod sqlite;
+mod vox;
+mod watch;
+mod worldedit;
 
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
+pub use self::alias::*;
+pub use self::anvil::*;
+pub use self::check::*;
+pub use self::diff::*;
+pub use self::inventory::*;
+#[cfg(feature = "leveldb")]
+pub use self::leveldb::*;
+pub use self::light::*;
+pub use self::loader::*;
+pub use self::lua::*;
 pub use self::map::*;
+pub use self::mapgen::*;
 pub use self::meta::*;
+pub use self::metadata::{LuaEntity, MetadataField, NodeMetadata, NodeTimer, StaticObject};
+pub use self::migrate::*;
+pub use self::nodedef::*;
+pub use self::param2::*;
+#[cfg(feature = "postgres")]
+pub use self::postgres::*;
+pub use self::prune::*;
+pub use self::recompress::*;
+pub use self::schematic::*;
+pub use self::sponge::*;
 pub use self::sqlite::*;
+pub use self::vox::*;
+pub use self::watch::*;
+pub use self::worldedit::*;
 
 pub struct World {
     pub name: String,
     pub meta: WorldMeta,
-    pub map: Map,
+
+    /// Shared so that it can be read by a [`MapLoader`] thread.
+    pub map: Arc<Map>,
+}
+
+pub struct WorldOptions {
+    pub gameid: String,
+    pub backend: String,
+}
+
+impl Default for WorldOptions {
+    fn default() -> Self {
+        Self {
+            gameid: "minetest".to_string(),
+            backend: "sqlite3".to_string(),
+        }
+    }
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -27,35 +97,125 @@ pub enum Error {
 
     #[error("invalid path: {0}")]
     InvalidPath(PathBuf),
+
+    #[error("world already exists: {0}")]
+    AlreadyExists(PathBuf),
+
+    #[error("missing setting in world.mt: {0}")]
+    MissingSetting(&'static str),
+
+    #[error("world already uses the {0} backend")]
+    SameBackend(String),
+
+    #[error("the {0} map of the world already holds {1} blocks")]
+    DestinationNotEmpty(String, usize),
+
+    #[error("{0} blocks differ after migration")]
+    VerificationFailed(usize),
+
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
 }
 
 impl World {
     pub fn open(path: impl AsRef<Path>) -> Result<Self, Error> {
         let path = path.as_ref();
-
-        let name = path
-            .components()
-            .next_back()
-            .ok_or(Error::InvalidPath(path.to_path_buf()))?
-            .as_os_str()
-            .to_string_lossy()
-            .to_string();
+        let name = world_name(path)?;
 
         let meta_path = path.join("world.mt");
         let meta = WorldMeta::open(meta_path)?;
-        let backend = meta.get_str("backend").unwrap();
-
-        let map = match backend {
-            "sqlite3" => {
-                let sqlite_path = path.join("map.sqlite");
-                let sqlite = SqliteBackend::new(sqlite_path)?;
-                Map::new(sqlite)
-            }
-            _ => {
-                return Err(Error::UnknownBackend(backend.to_owned()));
-            }
-        };
-
-        Ok(Self { name, meta, map })
+        let backend = meta
+            .get_str("backend")
+            .ok_or(Error::MissingSetting("backend"))?;
+        let map = open_map(path, &meta, backend, false)?;
+
+        Ok(Self {
+            name,
+            meta,
+            map: Arc::new(map),
+        })
+    }
+
+    pub fn create(path: impl AsRef<Path>, options: WorldOptions) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let name = world_name(path)?;
+
+        let meta_path = path.join("world.mt");
+        if meta_path.exists() {
+            return Err(Error::AlreadyExists(path.to_path_buf()));
+        }
+
+        std::fs::create_dir_all(path)?;
+
+        let mut meta = WorldMeta::new();
+        meta.set_str("gameid", &options.gameid);
+        meta.set_str("backend", &options.backend);
+
+        let map = open_map(path, &meta, &options.backend, true)?;
+
+        meta.save(meta_path)?;
+
+        Ok(Self {
+            name,
+            meta,
+            map: Arc::new(map),
+        })
     }
 }
+
+/// Opens the map of the world at `path` stored with `backend`, which doesn't
+/// have to be the backend named in `meta`. With `create` set, missing
+/// databases and tables are created.
+#[cfg_attr(not(feature = "postgres"), allow(unused_variables))]
+pub fn open_map(path: &Path, meta: &WorldMeta, backend: &str, create: bool) -> Result<Map, Error> {
+    let map = match backend {
+        "sqlite3" => {
+            let sqlite_path = path.join("map.sqlite");
+            let sqlite = if create {
+                SqliteBackend::create(sqlite_path)?
+            } else {
+                SqliteBackend::new(sqlite_path)?
+            };
+            Map::new(sqlite)
+        }
+        #[cfg(feature = "leveldb")]
+        "leveldb" => {
+            let leveldb_path = path.join("map.db");
+            let leveldb = if create {
+                LevelDbBackend::create(leveldb_path)?
+            } else {
+                LevelDbBackend::new(leveldb_path)?
+            };
+            Map::new(leveldb)
+        }
+        #[cfg(feature = "postgres")]
+        "postgresql" => {
+            let connection = meta
+                .get_str("pgsql_connection")
+                .ok_or(Error::MissingSetting("pgsql_connection"))?;
+            let postgres = if create {
+                PostgresBackend::create(connection)?
+            } else {
+                PostgresBackend::new(connection)?
+            };
+            Map::new(postgres)
+        }
+        _ => {
+            return Err(Error::UnknownBackend(backend.to_owned()));
+        }
+    };
+
+    Ok(map)
+}
+
+fn world_name(path: &Path) -> Result<String, Error> {
+    let name = path
+        .components()
+        .next_back()
+        .ok_or(Error::InvalidPath(path.to_path_buf()))?
+        .as_os_str()
+        .to_string_lossy()
+        .to_string();
+
+    Ok(name)
+}
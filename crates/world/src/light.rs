@@ -0,0 +1,360 @@
+use std::collections::HashMap;
+
+use glam::{IVec3, ivec3};
+
+use crate::{Block, Map, MapError, Node, NodeDefRegistry, ParamType};
+
+/// Light level of direct sunlight. Only sunlight can reach it.
+pub const LIGHT_SUN: u8 = 15;
+
+/// Brightest light a node can emit.
+pub const LIGHT_MAX: u8 = 14;
+
+/// `param1` of nodes with `paramtype = "light"` holds two light banks: the
+/// light during the day in the low nibble and at night in the high one.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum LightBank {
+    Day,
+    Night,
+}
+
+impl Node {
+    pub fn light(&self, bank: LightBank) -> u8 {
+        match bank {
+            LightBank::Day => self.param1 & 0x0f,
+            LightBank::Night => self.param1 >> 4,
+        }
+    }
+
+    pub fn set_light(&mut self, bank: LightBank, light: u8) {
+        let light = light.min(LIGHT_SUN);
+
+        self.param1 = match bank {
+            LightBank::Day => (self.param1 & 0xf0) | light,
+            LightBank::Night => (self.param1 & 0x0f) | (light << 4),
+        };
+    }
+}
+
+/// Recomputes sunlight and artificial light for all blocks between `min` and
+/// `max` (block positions, inclusive) and marks them as fully lit.
+///
+/// Light coming in from blocks around the region is taken from what is
+/// currently stored in them. Blocks without a block above them receive
+/// sunlight unless they are flagged as underground.
+///
+/// Light leaving the region isn't spread into the blocks around it. Those
+/// that share a face with the region are marked as not fully lit instead,
+/// so that the engine relights them when it loads them.
+pub fn relight(
+    map: &Map,
+    node_defs: &NodeDefRegistry,
+    min: IVec3,
+    max: IVec3,
+) -> Result<(), MapError> {
+    let mut region = LightRegion::load(map, node_defs, min, max)?;
+
+    region.propagate(LightBank::Day);
+    region.propagate(LightBank::Night);
+
+    region.store(map)
+}
+
+#[derive(Clone, Copy, Default)]
+struct LightProps {
+    propagates: bool,
+    sunlight_propagates: bool,
+    source: u8,
+}
+
+struct RegionBlock {
+    block: Block,
+    props: Vec<LightProps>,
+    light: [Vec<u8>; 2],
+}
+
+struct LightRegion {
+    min: IVec3,
+    max: IVec3,
+    blocks: HashMap<IVec3, RegionBlock>,
+    neighbours: HashMap<IVec3, (Block, HashMap<u16, LightProps>)>,
+}
+
+impl LightRegion {
+    fn load(
+        map: &Map,
+        node_defs: &NodeDefRegistry,
+        min: IVec3,
+        max: IVec3,
+    ) -> Result<Self, MapError> {
+        let mut blocks = HashMap::new();
+        let mut neighbours = HashMap::new();
+
+        for (pos, data) in map.get_blocks_in_box(min - 1, max + 1)? {
+            let block = Block::parse_data(&data).map_err(|err| err.at(pos))?;
+            let id_props = light_props(&block, node_defs);
+
+            if pos.cmplt(min).any() || pos.cmpgt(max).any() {
+                neighbours.insert(pos, (block, id_props));
+                continue;
+            }
+
+            let props = (0..Block::VOLUME)
+                .map(|i| id_props[&block.node_at(i).id])
+                .collect();
+
+            let light = [vec![0; Block::VOLUME], vec![0; Block::VOLUME]];
+
+            blocks.insert(
+                pos,
+                RegionBlock {
+                    block,
+                    props,
+                    light,
+                },
+            );
+        }
+
+        Ok(Self {
+            min,
+            max,
+            blocks,
+            neighbours,
+        })
+    }
+
+    fn propagate(&mut self, bank: LightBank) {
+        let mut queue: Vec<Vec<IVec3>> = vec![Vec::new(); LIGHT_SUN as usize + 1];
+
+        for (&block_pos, region_block) in &mut self.blocks {
+            for i in 0..Block::VOLUME {
+                let source = region_block.props[i].source.min(LIGHT_MAX);
+                region_block.light[bank as usize][i] = source;
+
+                if source > 0 {
+                    queue[source as usize].push(block_pos * 16 + Block::node_pos(i));
+                }
+            }
+        }
+
+        if bank == LightBank::Day {
+            self.seed_sunlight(&mut queue);
+        }
+
+        self.seed_borders(bank, &mut queue);
+
+        for level in (2..=LIGHT_SUN).rev() {
+            while let Some(pos) = queue[level as usize].pop() {
+                if self.light(bank, pos) != Some(level) {
+                    continue;
+                }
+
+                for dir in DIRECTIONS {
+                    let neighbour = pos + dir;
+
+                    let Some(props) = self.props(neighbour) else {
+                        continue;
+                    };
+
+                    if props.propagates && self.light(bank, neighbour) < Some(level - 1) {
+                        self.set_light(bank, neighbour, level - 1);
+                        queue[level as usize - 1].push(neighbour);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Blocks are visited from the top down, so that sunlight leaving the
+    /// bottom of a block is known when the block below it is visited.
+    fn seed_sunlight(&mut self, queue: &mut [Vec<IVec3>]) {
+        for bz in self.min.z..=self.max.z {
+            for bx in self.min.x..=self.max.x {
+                for by in (self.min.y..=self.max.y).rev() {
+                    let block_pos = ivec3(bx, by, bz);
+                    if self.blocks.contains_key(&block_pos) {
+                        self.seed_sunlight_in_block(block_pos, queue);
+                    }
+                }
+            }
+        }
+    }
+
+    fn seed_sunlight_in_block(&mut self, block_pos: IVec3, queue: &mut [Vec<IVec3>]) {
+        let above = block_pos + IVec3::Y;
+        let underground = self.blocks[&block_pos].block.flags() & Block::FLAG_UNDERGROUND != 0;
+
+        for z in 0..16 {
+            for x in 0..16 {
+                let column_top = block_pos * 16 + ivec3(x, 15, z);
+
+                let sunlit = if self.blocks.contains_key(&above) {
+                    self.light(LightBank::Day, column_top + IVec3::Y) == Some(LIGHT_SUN)
+                } else if let Some((block, props)) = self.neighbours.get(&above) {
+                    let node = block.node_at(z as usize * 256 + x as usize);
+                    props[&node.id].sunlight_propagates && node.light(LightBank::Day) == LIGHT_SUN
+                } else {
+                    !underground
+                };
+
+                if !sunlit {
+                    continue;
+                }
+
+                for y in (0..16).rev() {
+                    let pos = column_top.with_y(block_pos.y * 16 + y);
+                    if !self
+                        .props(pos)
+                        .is_some_and(|props| props.sunlight_propagates)
+                    {
+                        break;
+                    }
+
+                    self.set_light(LightBank::Day, pos, LIGHT_SUN);
+                    queue[LIGHT_SUN as usize].push(pos);
+                }
+            }
+        }
+    }
+
+    fn seed_borders(&mut self, bank: LightBank, queue: &mut [Vec<IVec3>]) {
+        let mut seeds = Vec::new();
+
+        for (&block_pos, (block, props)) in &self.neighbours {
+            for i in 0..Block::VOLUME {
+                let local = Block::node_pos(i);
+                let node = block.node_at(i);
+
+                if !props[&node.id].propagates {
+                    continue;
+                }
+
+                let light = node.light(bank);
+                if light <= 1 {
+                    continue;
+                }
+
+                for dir in DIRECTIONS {
+                    let pos = block_pos * 16 + local + dir;
+                    if self.props(pos).is_some_and(|props| props.propagates) {
+                        seeds.push((pos, light - 1));
+                    }
+                }
+            }
+        }
+
+        for (pos, light) in seeds {
+            if self.light(bank, pos) < Some(light) {
+                self.set_light(bank, pos, light);
+                queue[light as usize].push(pos);
+            }
+        }
+    }
+
+    fn store(mut self, map: &Map) -> Result<(), MapError> {
+        for (pos, region_block) in &mut self.blocks {
+            let block = &mut region_block.block;
+            let mut day_night_differs = false;
+
+            for i in 0..Block::VOLUME {
+                if !region_block.props[i].propagates {
+                    continue;
+                }
+
+                let day = region_block.light[LightBank::Day as usize][i];
+                let night = region_block.light[LightBank::Night as usize][i];
+                day_night_differs |= day != night;
+
+                let mut node = block.node_at(i);
+                node.set_light(LightBank::Day, day);
+                node.set_light(LightBank::Night, night);
+                block.set_node_at(i, node);
+            }
+
+            let flags = if day_night_differs {
+                block.flags() | Block::FLAG_DAY_NIGHT_DIFFERS
+            } else {
+                block.flags() & !Block::FLAG_DAY_NIGHT_DIFFERS
+            };
+
+            block.set_flags(flags);
+            block.set_lighting_complete(Block::LIGHTING_COMPLETE);
+            block.touch();
+
+            map.set_block(*pos, block)?;
+        }
+
+        for (pos, (block, _)) in &mut self.neighbours {
+            let outside = pos.cmplt(self.min) | pos.cmpgt(self.max);
+            if outside.bitmask().count_ones() != 1 {
+                continue;
+            }
+
+            block.set_lighting_complete(0);
+            block.touch();
+
+            map.set_block(*pos, block)?;
+        }
+
+        Ok(())
+    }
+
+    fn props(&self, pos: IVec3) -> Option<LightProps> {
+        let (block_pos, index) = split_pos(pos);
+        self.blocks.get(&block_pos).map(|b| b.props[index])
+    }
+
+    fn light(&self, bank: LightBank, pos: IVec3) -> Option<u8> {
+        let (block_pos, index) = split_pos(pos);
+        self.blocks
+            .get(&block_pos)
+            .map(|b| b.light[bank as usize][index])
+    }
+
+    fn set_light(&mut self, bank: LightBank, pos: IVec3, light: u8) {
+        let (block_pos, index) = split_pos(pos);
+        if let Some(b) = self.blocks.get_mut(&block_pos) {
+            b.light[bank as usize][index] = light;
+        }
+    }
+}
+
+const DIRECTIONS: [IVec3; 6] = [
+    IVec3::X,
+    IVec3::NEG_X,
+    IVec3::Y,
+    IVec3::NEG_Y,
+    IVec3::Z,
+    IVec3::NEG_Z,
+];
+
+fn light_props(block: &Block, node_defs: &NodeDefRegistry) -> HashMap<u16, LightProps> {
+    let mut props: HashMap<u16, LightProps> = block
+        .mappings()
+        .map(|(id, name)| {
+            let def = node_defs.get_or_unknown(name);
+
+            let props = LightProps {
+                propagates: def.paramtype == ParamType::Light,
+                sunlight_propagates: def.sunlight_propagates,
+                source: def.light_source,
+            };
+
+            (id, props)
+        })
+        .collect();
+
+    // Ids without a name are treated like unknown nodes.
+    for i in 0..Block::VOLUME {
+        props.entry(block.node_at(i).id).or_default();
+    }
+
+    props
+}
+
+fn split_pos(pos: IVec3) -> (IVec3, usize) {
+    let block_pos = pos.div_euclid(IVec3::splat(16));
+    let index = Block::node_index(pos.rem_euclid(IVec3::splat(16))).unwrap();
+
+    (block_pos, index)
+}
@@ -0,0 +1,273 @@
+use std::{
+    collections::HashMap,
+    future::Future,
+    panic::{self, AssertUnwindSafe},
+    pin::Pin,
+    sync::{Arc, Condvar, Mutex, mpsc},
+    task::{Context, Poll, Waker},
+    thread,
+};
+
+use glam::IVec3;
+
+use crate::{Block, Map, MapError};
+
+/// The result of a function running on its own thread.
+///
+/// UI and render loops can check for the result every frame with
+/// [`Task::try_take`], async code can `.await` it, and everything else can
+/// block on [`Task::wait`]. A panic on the thread is resumed wherever the
+/// result is taken.
+pub struct Task<T> {
+    shared: Arc<TaskShared<T>>,
+}
+
+struct TaskShared<T> {
+    state: Mutex<TaskState<T>>,
+    finished: Condvar,
+}
+
+struct TaskState<T> {
+    result: Option<thread::Result<T>>,
+    waker: Option<Waker>,
+}
+
+impl<T: Send + 'static> Task<T> {
+    pub fn spawn(f: impl FnOnce() -> T + Send + 'static) -> Self {
+        let shared = Arc::new(TaskShared {
+            state: Mutex::new(TaskState {
+                result: None,
+                waker: None,
+            }),
+            finished: Condvar::new(),
+        });
+
+        let thread_shared = Arc::clone(&shared);
+        thread::spawn(move || {
+            let result = panic::catch_unwind(AssertUnwindSafe(f));
+
+            let mut state = thread_shared.state.lock().unwrap();
+            state.result = Some(result);
+            if let Some(waker) = state.waker.take() {
+                waker.wake();
+            }
+            thread_shared.finished.notify_all();
+        });
+
+        Self { shared }
+    }
+}
+
+impl<T> Task<T> {
+    /// Whether the result is ready and hasn't been taken yet.
+    pub fn is_finished(&self) -> bool {
+        self.shared.state.lock().unwrap().result.is_some()
+    }
+
+    /// Takes the result if the function has returned. The result can only
+    /// be taken once.
+    pub fn try_take(&mut self) -> Option<T> {
+        let result = self.shared.state.lock().unwrap().result.take()?;
+        Some(resume(result))
+    }
+
+    /// Blocks until the function has returned.
+    pub fn wait(self) -> T {
+        let mut state = self.shared.state.lock().unwrap();
+
+        loop {
+            if let Some(result) = state.result.take() {
+                return resume(result);
+            }
+            state = self.shared.finished.wait(state).unwrap();
+        }
+    }
+}
+
+impl<T> Future for Task<T> {
+    type Output = T;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<T> {
+        let mut state = self.shared.state.lock().unwrap();
+
+        match state.result.take() {
+            Some(result) => Poll::Ready(resume(result)),
+            None => {
+                state.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+fn resume<T>(result: thread::Result<T>) -> T {
+    match result {
+        Ok(value) => value,
+        Err(payload) => panic::resume_unwind(payload),
+    }
+}
+
+/// A block read by a [`MapLoader`].
+pub struct LoadedBlock {
+    pub pos: IVec3,
+
+    /// The parsed block, with node metadata decoded lazily (see
+    /// [`Block::parse_data_lazy`]).
+    pub result: Result<Block, MapError>,
+}
+
+/// Numbers requests in the order they were made.
+type RequestId = u64;
+
+enum Request {
+    Block(RequestId, IVec3),
+    Box(RequestId, IVec3, IVec3),
+}
+
+enum Response {
+    Loaded(RequestId, Box<LoadedBlock>),
+
+    /// Sent after all blocks of a request.
+    Finished,
+}
+
+/// Reads and parses blocks on a dedicated thread, so that UI and render
+/// loops can request blocks and keep drawing while they stream in.
+///
+/// Requests are handled in order. Loaded blocks are collected with
+/// [`MapLoader::loaded`], which never blocks. The thread exits when the
+/// loader is dropped.
+pub struct MapLoader {
+    requests: mpsc::Sender<Request>,
+    responses: mpsc::Receiver<Response>,
+
+    /// Single blocks that were requested but not loaded yet, with their
+    /// requests. Blocks loaded for other requests, such as boxes, leave them
+    /// pending.
+    pending_blocks: HashMap<IVec3, RequestId>,
+
+    /// Requests that weren't finished yet.
+    pending_requests: usize,
+
+    next_request: RequestId,
+}
+
+impl MapLoader {
+    pub fn new(map: Arc<Map>) -> Self {
+        Self::with_notify(map, || {})
+    }
+
+    /// Creates a loader that calls `notify` on the loader thread whenever a
+    /// block was loaded, for example to request a repaint.
+    pub fn with_notify(map: Arc<Map>, notify: impl Fn() + Send + 'static) -> Self {
+        let (requests, request_receiver) = mpsc::channel();
+        let (response_sender, responses) = mpsc::channel();
+
+        thread::spawn(move || {
+            for request in request_receiver {
+                let (id, loaded) = match request {
+                    Request::Block(id, pos) => (id, vec![load_block(&map, pos)]),
+                    Request::Box(id, min, max) => (id, load_box(&map, min, max)),
+                };
+
+                for block in loaded {
+                    if response_sender
+                        .send(Response::Loaded(id, Box::new(block)))
+                        .is_err()
+                    {
+                        return;
+                    }
+                    notify();
+                }
+
+                if response_sender.send(Response::Finished).is_err() {
+                    return;
+                }
+            }
+        });
+
+        Self {
+            requests,
+            responses,
+            pending_blocks: HashMap::new(),
+            pending_requests: 0,
+            next_request: 0,
+        }
+    }
+
+    /// Requests the block at `pos`, unless it's already pending. A missing
+    /// block is reported as [`MapError::BlockNotFound`].
+    pub fn request(&mut self, pos: IVec3) {
+        if self.pending_blocks.contains_key(&pos) {
+            return;
+        }
+
+        let id = self.send(|id| Request::Block(id, pos));
+        self.pending_blocks.insert(pos, id);
+    }
+
+    /// Requests all blocks between `min` and `max` (block positions,
+    /// inclusive), which are read in one go. Missing blocks are left out, and
+    /// if reading fails, the error is reported for `min`.
+    pub fn request_box(&mut self, min: IVec3, max: IVec3) {
+        self.send(|id| Request::Box(id, min, max));
+    }
+
+    /// Blocks loaded since the last call, without waiting for more.
+    pub fn loaded(&mut self) -> impl Iterator<Item = LoadedBlock> + '_ {
+        std::iter::from_fn(|| {
+            loop {
+                match self.responses.try_recv().ok()? {
+                    Response::Loaded(id, block) => {
+                        if self.pending_blocks.get(&block.pos) == Some(&id) {
+                            self.pending_blocks.remove(&block.pos);
+                        }
+                        return Some(*block);
+                    }
+                    Response::Finished => self.pending_requests -= 1,
+                }
+            }
+        })
+    }
+
+    /// Whether all requests were answered and their blocks collected.
+    pub fn is_idle(&self) -> bool {
+        self.pending_requests == 0
+    }
+
+    fn send(&mut self, request: impl FnOnce(RequestId) -> Request) -> RequestId {
+        let id = self.next_request;
+        self.next_request += 1;
+
+        // The thread only exits once the loader is dropped.
+        if self.requests.send(request(id)).is_ok() {
+            self.pending_requests += 1;
+        }
+
+        id
+    }
+}
+
+fn load_block(map: &Map, pos: IVec3) -> LoadedBlock {
+    let result = map
+        .get_block_data(pos)
+        .and_then(|data| Block::parse_data_lazy(&data).map_err(|err| err.at(pos).into()));
+
+    LoadedBlock { pos, result }
+}
+
+fn load_box(map: &Map, min: IVec3, max: IVec3) -> Vec<LoadedBlock> {
+    match map.get_blocks_in_box(min, max) {
+        Ok(blocks) => blocks
+            .into_iter()
+            .map(|(pos, data)| LoadedBlock {
+                pos,
+                result: Block::parse_data_lazy(&data).map_err(|err| err.at(pos).into()),
+            })
+            .collect(),
+        Err(err) => vec![LoadedBlock {
+            pos: min,
+            result: Err(err),
+        }],
+    }
+}
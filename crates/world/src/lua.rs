@@ -0,0 +1,1021 @@
+use std::{
+    collections::{HashMap, HashSet},
+    io::Read,
+};
+
+/// Deepest nesting of tables accepted, to keep recursion off the end of the
+/// stack.
+const MAX_DEPTH: usize = 256;
+
+/// Most values copied out of shared tables. Shared tables are copied
+/// wherever they are referenced, so a small input could otherwise expand to
+/// an enormous value. Other values are limited by the size of the input.
+const MAX_COPIED_VALUES: usize = 1 << 20;
+
+/// Largest decompressed input accepted.
+const MAX_DECOMPRESSED_SIZE: u64 = 64 << 20;
+
+#[derive(thiserror::Error, Debug)]
+pub enum LuaError {
+    #[error("syntax error at byte {pos}: {message}")]
+    Syntax { pos: usize, message: String },
+
+    #[error("reference to undefined value _[{0}]")]
+    UndefinedReference(i64),
+
+    #[error("cyclic tables can't be represented")]
+    CyclicReference,
+
+    #[error("value is nested too deeply or too large")]
+    TooComplex,
+
+    #[error("invalid compressed data: {0}")]
+    Decompress(#[from] std::io::Error),
+}
+
+/// A value produced by `minetest.serialize`.
+///
+/// Lua strings are byte strings, so [`LuaValue::String`] holds bytes; use
+/// [`LuaValue::as_str`] for text.
+#[derive(Clone, Debug, PartialEq)]
+pub enum LuaValue {
+    Nil,
+    Bool(bool),
+    Number(f64),
+    String(Vec<u8>),
+    Table(LuaTable),
+}
+
+/// Table entries in the order they were written. Entries without a key in
+/// the source have the keys 1, 2, 3...
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LuaTable {
+    entries: Vec<(LuaValue, LuaValue)>,
+
+    /// Positions in `entries` by key, so that large tables such as
+    /// WorldEdit schematics can be looked up quickly. Keys without a
+    /// [`TableKey`] are searched for in `entries`.
+    index: HashMap<TableKey, usize>,
+
+    /// Length of the sequence at keys 1, 2, 3..., so that pushing doesn't
+    /// have to count it.
+    sequence_len: usize,
+}
+
+/// A table key that can be hashed.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+enum TableKey {
+    Bool(bool),
+    Number(u64),
+    String(Vec<u8>),
+}
+
+impl TableKey {
+    fn new(value: &LuaValue) -> Option<Self> {
+        match value {
+            LuaValue::Bool(b) => Some(Self::Bool(*b)),
+            // -0.0 and 0.0 are the same key, NaN is never equal to a key.
+            LuaValue::Number(n) if n.is_nan() => None,
+            LuaValue::Number(n) => Some(Self::Number((n + 0.0).to_bits())),
+            LuaValue::String(s) => Some(Self::String(s.clone())),
+            LuaValue::Nil | LuaValue::Table(_) => None,
+        }
+    }
+}
+
+impl LuaValue {
+    /// Decodes the output of `minetest.serialize`, optionally compressed with
+    /// `minetest.compress`. The data is parsed, never executed.
+    pub fn deserialize(data: &[u8]) -> Result<Self, LuaError> {
+        if is_zlib(data) {
+            let mut decompressed = Vec::new();
+            flate2::read::ZlibDecoder::new(data)
+                .take(MAX_DECOMPRESSED_SIZE + 1)
+                .read_to_end(&mut decompressed)?;
+
+            if decompressed.len() as u64 > MAX_DECOMPRESSED_SIZE {
+                return Err(LuaError::TooComplex);
+            }
+
+            return Self::parse(&decompressed);
+        }
+
+        Self::parse(data)
+    }
+
+    fn parse(data: &[u8]) -> Result<Self, LuaError> {
+        let mut parser = Parser {
+            data,
+            pos: 0,
+            depth: 0,
+        };
+        let chunk = parser.chunk()?;
+        chunk.resolve()
+    }
+
+    /// Encodes the value in the format of `minetest.serialize`.
+    pub fn serialize(&self) -> String {
+        let mut out = String::from("return ");
+        write_value(&mut out, self);
+        out
+    }
+
+    pub fn is_nil(&self) -> bool {
+        matches!(self, LuaValue::Nil)
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            LuaValue::Bool(b) => Some(*b),
+            _ => None,
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            LuaValue::Number(n) => Some(*n),
+            _ => None,
+        }
+    }
+
+    pub fn as_i64(&self) -> Option<i64> {
+        self.as_f64()
+            .filter(|n| n.fract() == 0.0 && n.abs() < 2f64.powi(63))
+            .map(|n| n as i64)
+    }
+
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match self {
+            LuaValue::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        self.as_bytes().and_then(|s| std::str::from_utf8(s).ok())
+    }
+
+    pub fn as_table(&self) -> Option<&LuaTable> {
+        match self {
+            LuaValue::Table(t) => Some(t),
+            _ => None,
+        }
+    }
+}
+
+impl From<bool> for LuaValue {
+    fn from(value: bool) -> Self {
+        LuaValue::Bool(value)
+    }
+}
+
+impl From<f64> for LuaValue {
+    fn from(value: f64) -> Self {
+        LuaValue::Number(value)
+    }
+}
+
+impl From<i64> for LuaValue {
+    fn from(value: i64) -> Self {
+        LuaValue::Number(value as f64)
+    }
+}
+
+impl From<&str> for LuaValue {
+    fn from(value: &str) -> Self {
+        LuaValue::String(value.as_bytes().to_vec())
+    }
+}
+
+impl From<String> for LuaValue {
+    fn from(value: String) -> Self {
+        LuaValue::String(value.into_bytes())
+    }
+}
+
+impl From<LuaTable> for LuaValue {
+    fn from(value: LuaTable) -> Self {
+        LuaValue::Table(value)
+    }
+}
+
+impl LuaTable {
+    pub fn new() -> Self {
+        Self {
+            entries: Vec::new(),
+            index: HashMap::new(),
+            sequence_len: 0,
+        }
+    }
+
+    pub fn get(&self, key: impl Into<LuaValue>) -> Option<&LuaValue> {
+        let position = self.position(&key.into())?;
+        Some(&self.entries[position].1)
+    }
+
+    /// Sets `key` to `value`. Setting a key to nil removes it.
+    pub fn insert(&mut self, key: impl Into<LuaValue>, value: impl Into<LuaValue>) {
+        let key = key.into();
+        let value = value.into();
+
+        let existing = self.position(&key);
+
+        match (existing, value) {
+            (Some(i), LuaValue::Nil) => {
+                self.entries.remove(i);
+                self.index = self
+                    .entries
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(i, (key, _))| Some((TableKey::new(key)?, i)))
+                    .collect();
+
+                if let Some(removed) = sequence_index(&key) {
+                    self.sequence_len = self.sequence_len.min(removed - 1);
+                }
+            }
+            (Some(i), value) => self.entries[i].1 = value,
+            (None, LuaValue::Nil) => {}
+            (None, value) => {
+                if let Some(table_key) = TableKey::new(&key) {
+                    self.index.insert(table_key, self.entries.len());
+                }
+                self.entries.push((key, value));
+
+                // The new entry may also join entries after it to the
+                // sequence.
+                while self.get(self.sequence_len as i64 + 1).is_some() {
+                    self.sequence_len += 1;
+                }
+            }
+        }
+    }
+
+    /// Appends `value` after the last element of the sequence.
+    pub fn push(&mut self, value: impl Into<LuaValue>) {
+        self.insert(self.sequence_len as i64 + 1, value);
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&LuaValue, &LuaValue)> {
+        self.entries.iter().map(|(k, v)| (k, v))
+    }
+
+    /// Values at keys 1, 2, 3... up to the first missing one.
+    pub fn sequence(&self) -> impl Iterator<Item = &LuaValue> {
+        (1..=self.sequence_len).map(|i| self.get(i as i64).unwrap())
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    fn position(&self, key: &LuaValue) -> Option<usize> {
+        match TableKey::new(key) {
+            Some(table_key) => self.index.get(&table_key).copied(),
+            None => self.entries.iter().position(|(k, _)| k == key),
+        }
+    }
+}
+
+/// Finds calls to any of `functions` in Lua source code whose arguments are
+/// all string literals, e.g. `minetest.register_alias("old", "new")`, and
+/// returns their arguments. Other calls are skipped. The code isn't run, so
+/// calls in dead branches are found too.
+pub(crate) fn find_string_calls(source: &[u8], functions: &[&str]) -> Vec<Vec<Vec<u8>>> {
+    let mut parser = Parser {
+        data: source,
+        pos: 0,
+        depth: 0,
+    };
+    let mut calls = Vec::new();
+
+    loop {
+        parser.skip_whitespace();
+
+        let Some(&c) = parser.data.get(parser.pos) else {
+            return calls;
+        };
+
+        // Strings are skipped as a whole so that code inside them isn't
+        // mistaken for calls.
+        let skipped = match c {
+            b'"' | b'\'' => parser.quoted_string().map(drop),
+            b'[' if parser.long_bracket_len().is_some() => parser.long_string().map(drop),
+            _ => match parser.peek_word() {
+                Some(word) if functions.contains(&word) => {
+                    parser.pos += word.len();
+                    let start = parser.pos;
+
+                    match parser.string_args() {
+                        Some(args) => calls.push(args),
+                        None => parser.pos = start,
+                    }
+
+                    Ok(())
+                }
+                Some(word) => {
+                    parser.pos += word.len();
+                    Ok(())
+                }
+                None => {
+                    parser.pos += 1;
+                    Ok(())
+                }
+            },
+        };
+
+        if skipped.is_err() {
+            return calls;
+        }
+    }
+}
+
+/// The position of `key` in a sequence, if it is a positive integer.
+fn sequence_index(key: &LuaValue) -> Option<usize> {
+    key.as_f64()
+        .filter(|n| n.fract() == 0.0 && *n >= 1.0 && *n <= usize::MAX as f64)
+        .map(|n| n as usize)
+}
+
+fn is_zlib(data: &[u8]) -> bool {
+    match data {
+        [cmf, flg, ..] => cmf & 0x0f == 8 && (*cmf as u16 * 256 + *flg as u16).is_multiple_of(31),
+        _ => false,
+    }
+}
+
+// Serialized values may refer to shared tables defined up front, e.g.
+// `local _={};_[1]={};return {a=_[1],b=_[1]}`. Expressions keep these
+// references until the whole chunk is read.
+enum Expr {
+    Value(LuaValue),
+    Table(Vec<(Expr, Expr)>),
+    Ref(i64),
+}
+
+struct Chunk {
+    refs: HashMap<i64, Expr>,
+    value: Expr,
+}
+
+impl Chunk {
+    fn resolve(&self) -> Result<LuaValue, LuaError> {
+        self.resolve_expr(&self.value, &mut Resolution::default())
+    }
+
+    fn resolve_expr(&self, expr: &Expr, state: &mut Resolution) -> Result<LuaValue, LuaError> {
+        if !state.refs.is_empty() {
+            state.copied_values += 1;
+        }
+
+        if state.copied_values > MAX_COPIED_VALUES || state.depth > MAX_DEPTH {
+            return Err(LuaError::TooComplex);
+        }
+
+        match expr {
+            Expr::Value(value) => Ok(value.clone()),
+            Expr::Table(fields) => {
+                let mut table = LuaTable::new();
+
+                state.depth += 1;
+                for (key, value) in fields {
+                    let key = self.resolve_expr(key, state)?;
+                    let value = self.resolve_expr(value, state)?;
+                    table.insert(key, value);
+                }
+                state.depth -= 1;
+
+                Ok(LuaValue::Table(table))
+            }
+            Expr::Ref(index) => {
+                if state.refs.contains(index) {
+                    return Err(LuaError::CyclicReference);
+                }
+
+                let expr = self
+                    .refs
+                    .get(index)
+                    .ok_or(LuaError::UndefinedReference(*index))?;
+
+                // References to references recurse as deeply as nested
+                // tables do.
+                state.refs.insert(*index);
+                state.depth += 1;
+                let value = self.resolve_expr(expr, state);
+                state.depth -= 1;
+                state.refs.remove(index);
+
+                value
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+struct Resolution {
+    /// References being resolved, to detect cycles.
+    refs: HashSet<i64>,
+
+    /// Tables and references being resolved.
+    depth: usize,
+
+    /// Values resolved within references.
+    copied_values: usize,
+}
+
+struct Parser<'a> {
+    data: &'a [u8],
+    pos: usize,
+
+    /// Tables being parsed.
+    depth: usize,
+}
+
+impl Parser<'_> {
+    fn chunk(&mut self) -> Result<Chunk, LuaError> {
+        let mut refs = HashMap::new();
+
+        loop {
+            self.skip_whitespace();
+
+            match self.peek_word() {
+                Some("local") => {
+                    self.expect_word("local")?;
+                    self.expect_word("_")?;
+                    self.expect(b'=')?;
+                    self.expect(b'{')?;
+                    self.expect(b'}')?;
+                }
+                Some("_") => {
+                    self.expect_word("_")?;
+                    let index = self.ref_index()?;
+
+                    let mut path = Vec::new();
+                    while self.consume(b'[') {
+                        path.push(self.expr()?);
+                        self.expect(b']')?;
+                    }
+
+                    self.expect(b'=')?;
+                    let value = self.expr()?;
+
+                    if path.is_empty() {
+                        refs.insert(index, value);
+                    } else {
+                        let target = refs
+                            .get_mut(&index)
+                            .ok_or(LuaError::UndefinedReference(index))?;
+                        self.assign(target, path, value)?;
+                    }
+                }
+                Some("return") => {
+                    self.expect_word("return")?;
+                    let value = self.expr()?;
+                    self.consume(b';');
+                    self.skip_whitespace();
+
+                    if self.pos < self.data.len() {
+                        return Err(self.error("unexpected data after return value"));
+                    }
+
+                    return Ok(Chunk { refs, value });
+                }
+                _ if self.pos >= self.data.len() => {
+                    return Err(self.error("missing return statement"));
+                }
+                _ => return Err(self.error("unsupported statement")),
+            }
+
+            self.consume(b';');
+        }
+    }
+
+    fn assign(&self, target: &mut Expr, mut path: Vec<Expr>, value: Expr) -> Result<(), LuaError> {
+        let key = path.remove(0);
+
+        let Expr::Table(fields) = target else {
+            return Err(self.error("assignment to a field of a non-table value"));
+        };
+
+        let existing = fields
+            .iter_mut()
+            .find(|(k, _)| matches!((k, &key), (Expr::Value(a), Expr::Value(b)) if a == b));
+
+        match (existing, path.is_empty()) {
+            (Some((_, field)), true) => *field = value,
+            (Some((_, field)), false) => self.assign(field, path, value)?,
+            (None, true) => fields.push((key, value)),
+            (None, false) => return Err(self.error("assignment to a field of a missing table")),
+        }
+
+        Ok(())
+    }
+
+    fn ref_index(&mut self) -> Result<i64, LuaError> {
+        self.expect(b'[')?;
+        let index = self.number()?;
+        self.expect(b']')?;
+
+        if index.fract() != 0.0 {
+            return Err(self.error("invalid reference index"));
+        }
+
+        Ok(index as i64)
+    }
+
+    fn expr(&mut self) -> Result<Expr, LuaError> {
+        self.skip_whitespace();
+
+        let Some(&c) = self.data.get(self.pos) else {
+            return Err(self.error("expected a value"));
+        };
+
+        match c {
+            b'{' => self.table(),
+            b'"' | b'\'' => Ok(Expr::Value(LuaValue::String(self.quoted_string()?))),
+            b'[' => Ok(Expr::Value(LuaValue::String(self.long_string()?))),
+            b'-' | b'.' | b'0'..=b'9' => Ok(Expr::Value(LuaValue::Number(self.arithmetic()?))),
+            _ => match self.peek_word() {
+                Some("nil") => self.word_value("nil", LuaValue::Nil),
+                Some("true") => self.word_value("true", LuaValue::Bool(true)),
+                Some("false") => self.word_value("false", LuaValue::Bool(false)),
+                Some("math") => Ok(Expr::Value(LuaValue::Number(self.arithmetic()?))),
+                Some("_") => {
+                    self.expect_word("_")?;
+                    Ok(Expr::Ref(self.ref_index()?))
+                }
+                _ => Err(self.error("expected a value")),
+            },
+        }
+    }
+
+    // Parses `("a", "b", ...)`.
+    fn string_args(&mut self) -> Option<Vec<Vec<u8>>> {
+        let mut args = Vec::new();
+
+        if !self.consume(b'(') {
+            return None;
+        }
+
+        if self.consume(b')') {
+            return Some(args);
+        }
+
+        loop {
+            self.skip_whitespace();
+
+            let arg = match self.data.get(self.pos)? {
+                b'"' | b'\'' => self.quoted_string().ok()?,
+                b'[' => self.long_string().ok()?,
+                _ => return None,
+            };
+            args.push(arg);
+
+            if self.consume(b')') {
+                return Some(args);
+            }
+
+            if !self.consume(b',') {
+                return None;
+            }
+        }
+    }
+
+    fn word_value(&mut self, word: &str, value: LuaValue) -> Result<Expr, LuaError> {
+        self.expect_word(word)?;
+        Ok(Expr::Value(value))
+    }
+
+    fn table(&mut self) -> Result<Expr, LuaError> {
+        if self.depth >= MAX_DEPTH {
+            return Err(LuaError::TooComplex);
+        }
+
+        self.depth += 1;
+        let table = self.table_fields();
+        self.depth -= 1;
+
+        table
+    }
+
+    fn table_fields(&mut self) -> Result<Expr, LuaError> {
+        self.expect(b'{')?;
+
+        let mut fields = Vec::new();
+        let mut next_index = 1;
+
+        loop {
+            self.skip_whitespace();
+
+            if self.consume(b'}') {
+                return Ok(Expr::Table(fields));
+            }
+
+            let key = if self.consume_keyed_open() {
+                let key = self.expr()?;
+                self.expect(b']')?;
+                self.expect(b'=')?;
+                Some(key)
+            } else if let Some(name) = self.field_name() {
+                self.expect(b'=')?;
+                Some(Expr::Value(LuaValue::from(name)))
+            } else {
+                None
+            };
+
+            let value = self.expr()?;
+
+            let key = key.unwrap_or_else(|| {
+                let key = Expr::Value(LuaValue::Number(next_index as f64));
+                next_index += 1;
+                key
+            });
+
+            fields.push((key, value));
+
+            self.skip_whitespace();
+            if !self.consume(b',') && !self.consume(b';') {
+                self.expect(b'}')?;
+                return Ok(Expr::Table(fields));
+            }
+        }
+    }
+
+    // `[` starts either a key (`[expr] = value`) or a long string value.
+    fn consume_keyed_open(&mut self) -> bool {
+        self.long_bracket_len().is_none() && self.consume(b'[')
+    }
+
+    // A name followed by `=` (but not `==`) is a field key.
+    fn field_name(&mut self) -> Option<String> {
+        let start = self.pos;
+        let name = self.peek_word()?.to_string();
+
+        if matches!(name.as_str(), "nil" | "true" | "false") {
+            return None;
+        }
+
+        self.pos += name.len();
+        self.skip_whitespace();
+
+        if self.data.get(self.pos) == Some(&b'=') && self.data.get(self.pos + 1) != Some(&b'=') {
+            Some(name)
+        } else {
+            self.pos = start;
+            None
+        }
+    }
+
+    // Numbers, including the forms used for special values: `0/0` (NaN),
+    // `1/0`, `-1/0` and `math.huge`.
+    fn arithmetic(&mut self) -> Result<f64, LuaError> {
+        let value = self.signed_number()?;
+
+        self.skip_whitespace();
+        if self.consume(b'/') {
+            return Ok(value / self.signed_number()?);
+        }
+
+        Ok(value)
+    }
+
+    fn signed_number(&mut self) -> Result<f64, LuaError> {
+        // Counted rather than recursed into, as there may be any number.
+        let mut negative = false;
+        while self.consume(b'-') {
+            negative = !negative;
+        }
+
+        self.skip_whitespace();
+
+        let value = if self.peek_word() == Some("math") {
+            self.expect_word("math")?;
+            self.expect(b'.')?;
+            self.expect_word("huge")?;
+            f64::INFINITY
+        } else {
+            self.number()?
+        };
+
+        Ok(if negative { -value } else { value })
+    }
+
+    fn number(&mut self) -> Result<f64, LuaError> {
+        self.skip_whitespace();
+
+        let start = self.pos;
+        while let Some(&c) = self.data.get(self.pos) {
+            let is_exponent_sign = matches!(c, b'+' | b'-')
+                && self.pos > start
+                && matches!(self.data[self.pos - 1], b'e' | b'E')
+                && !self.data[start..].starts_with(b"0x");
+
+            if c.is_ascii_alphanumeric() || c == b'.' || is_exponent_sign {
+                self.pos += 1;
+            } else {
+                break;
+            }
+        }
+
+        let text = std::str::from_utf8(&self.data[start..self.pos]).unwrap_or_default();
+
+        let value = match text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+            Some(hex) => u64::from_str_radix(hex, 16).ok().map(|n| n as f64),
+            None => text
+                .parse()
+                .ok()
+                .filter(|_| !text.starts_with(['i', 'n', 'I', 'N'])),
+        };
+
+        value.ok_or_else(|| LuaError::Syntax {
+            pos: start,
+            message: format!("invalid number `{text}`"),
+        })
+    }
+
+    fn quoted_string(&mut self) -> Result<Vec<u8>, LuaError> {
+        let quote = self.data[self.pos];
+        self.pos += 1;
+
+        let mut s = Vec::new();
+
+        loop {
+            let Some(&c) = self.data.get(self.pos) else {
+                return Err(self.error("unterminated string"));
+            };
+            self.pos += 1;
+
+            match c {
+                b'\\' => self.escape(&mut s)?,
+                b'\n' => return Err(self.error("unterminated string")),
+                c if c == quote => return Ok(s),
+                c => s.push(c),
+            }
+        }
+    }
+
+    fn escape(&mut self, s: &mut Vec<u8>) -> Result<(), LuaError> {
+        let Some(&c) = self.data.get(self.pos) else {
+            return Err(self.error("unterminated string"));
+        };
+        self.pos += 1;
+
+        match c {
+            b'a' => s.push(0x07),
+            b'b' => s.push(0x08),
+            b'f' => s.push(0x0c),
+            b'n' | b'\n' => s.push(b'\n'),
+            b'r' => s.push(b'\r'),
+            b't' => s.push(b'\t'),
+            b'v' => s.push(0x0b),
+            b'\\' | b'"' | b'\'' => s.push(c),
+            b'x' => {
+                let hex = self.data.get(self.pos..self.pos + 2).unwrap_or_default();
+                let value = std::str::from_utf8(hex)
+                    .ok()
+                    .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                    .ok_or_else(|| self.error("invalid hex escape"))?;
+                self.pos += 2;
+                s.push(value);
+            }
+            b'z' => self.skip_whitespace(),
+            b'0'..=b'9' => {
+                let mut value = (c - b'0') as u32;
+
+                for _ in 0..2 {
+                    match self.data.get(self.pos) {
+                        Some(&d @ b'0'..=b'9') => {
+                            value = value * 10 + (d - b'0') as u32;
+                            self.pos += 1;
+                        }
+                        _ => break,
+                    }
+                }
+
+                let byte = u8::try_from(value).map_err(|_| self.error("escape out of range"))?;
+                s.push(byte);
+            }
+            _ => return Err(self.error("invalid escape sequence")),
+        }
+
+        Ok(())
+    }
+
+    fn long_string(&mut self) -> Result<Vec<u8>, LuaError> {
+        let len = self
+            .long_bracket_len()
+            .ok_or_else(|| self.error("expected a value"))?;
+        self.pos += len;
+
+        let close = [b"]".as_slice(), &b"=".repeat(len - 2), b"]"].concat();
+
+        // A newline right after the opening bracket isn't part of the string.
+        if self.data[self.pos..].starts_with(b"\r\n") {
+            self.pos += 2;
+        } else if self.data.get(self.pos) == Some(&b'\n') {
+            self.pos += 1;
+        }
+
+        let rest = &self.data[self.pos..];
+        let len = rest
+            .windows(close.len())
+            .position(|w| w == close)
+            .ok_or_else(|| self.error("unterminated long string"))?;
+
+        let s = rest[..len].to_vec();
+        self.pos += len + close.len();
+
+        Ok(s)
+    }
+
+    // Length of the `[[`, `[=[`, `[==[`... at the current position.
+    fn long_bracket_len(&self) -> Option<usize> {
+        let rest = self.data.get(self.pos..)?.strip_prefix(b"[")?;
+        let level = rest.iter().take_while(|&&c| c == b'=').count();
+
+        (rest.get(level) == Some(&b'[')).then_some(level + 2)
+    }
+
+    fn skip_whitespace(&mut self) {
+        loop {
+            while self
+                .data
+                .get(self.pos)
+                .is_some_and(|c| c.is_ascii_whitespace())
+            {
+                self.pos += 1;
+            }
+
+            if !self.data[self.pos..].starts_with(b"--") {
+                return;
+            }
+
+            self.pos += 2;
+
+            if self.long_bracket_len().is_some() {
+                if self.long_string().is_err() {
+                    self.pos = self.data.len();
+                }
+            } else {
+                while self.data.get(self.pos).is_some_and(|&c| c != b'\n') {
+                    self.pos += 1;
+                }
+            }
+        }
+    }
+
+    fn peek_word(&self) -> Option<&str> {
+        let rest = self.data.get(self.pos..)?;
+        let len = rest
+            .iter()
+            .take_while(|c| c.is_ascii_alphanumeric() || **c == b'_')
+            .count();
+
+        if len == 0 || rest[0].is_ascii_digit() {
+            return None;
+        }
+
+        std::str::from_utf8(&rest[..len]).ok()
+    }
+
+    fn expect_word(&mut self, word: &str) -> Result<(), LuaError> {
+        self.skip_whitespace();
+
+        if self.peek_word() != Some(word) {
+            return Err(self.error(&format!("expected `{word}`")));
+        }
+
+        self.pos += word.len();
+        Ok(())
+    }
+
+    fn consume(&mut self, c: u8) -> bool {
+        self.skip_whitespace();
+
+        if self.data.get(self.pos) == Some(&c) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, c: u8) -> Result<(), LuaError> {
+        if self.consume(c) {
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected `{}`", c as char)))
+        }
+    }
+
+    fn error(&self, message: &str) -> LuaError {
+        LuaError::Syntax {
+            pos: self.pos,
+            message: message.to_string(),
+        }
+    }
+}
+
+fn write_value(out: &mut String, value: &LuaValue) {
+    match value {
+        LuaValue::Nil => out.push_str("nil"),
+        LuaValue::Bool(b) => out.push_str(if *b { "true" } else { "false" }),
+        LuaValue::Number(n) => write_number(out, *n),
+        LuaValue::String(s) => write_string(out, s),
+        LuaValue::Table(table) => write_table(out, table),
+    }
+}
+
+fn write_number(out: &mut String, n: f64) {
+    if n.is_nan() {
+        out.push_str("0/0");
+    } else if n.is_infinite() {
+        out.push_str(if n > 0.0 { "1/0" } else { "-1/0" });
+    } else if n.fract() == 0.0 && n.abs() < 2f64.powi(53) {
+        out.push_str(&(n as i64).to_string());
+    } else {
+        out.push_str(&format!("{n:?}"));
+    }
+}
+
+fn write_string(out: &mut String, s: &[u8]) {
+    out.push('"');
+
+    for &c in s {
+        match c {
+            b'"' => out.push_str("\\\""),
+            b'\\' => out.push_str("\\\\"),
+            b'\n' => out.push_str("\\n"),
+            b'\r' => out.push_str("\\r"),
+            0x20..=0x7e => out.push(c as char),
+            // Always three digits so a following digit isn't read as part of
+            // the escape.
+            _ => out.push_str(&format!("\\{c:03}")),
+        }
+    }
+
+    out.push('"');
+}
+
+fn write_table(out: &mut String, table: &LuaTable) {
+    out.push('{');
+
+    let sequence_len = table.sequence().count();
+    let mut first = true;
+
+    for value in table.sequence() {
+        if !first {
+            out.push(',');
+        }
+        first = false;
+
+        write_value(out, value);
+    }
+
+    for (key, value) in table.iter() {
+        let in_sequence = key
+            .as_i64()
+            .is_some_and(|i| i >= 1 && i as usize <= sequence_len);
+
+        if in_sequence {
+            continue;
+        }
+
+        if !first {
+            out.push(',');
+        }
+        first = false;
+
+        match key.as_str().filter(|name| is_identifier(name)) {
+            Some(name) => out.push_str(name),
+            None => {
+                out.push('[');
+                write_value(out, key);
+                out.push(']');
+            }
+        }
+
+        out.push('=');
+        write_value(out, value);
+    }
+
+    out.push('}');
+}
+
+fn is_identifier(s: &str) -> bool {
+    const KEYWORDS: &[&str] = &[
+        "and", "break", "do", "else", "elseif", "end", "false", "for", "function", "goto", "if",
+        "in", "local", "nil", "not", "or", "repeat", "return", "then", "true", "until", "while",
+    ];
+
+    let mut chars = s.chars();
+    let starts_well = chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_');
+
+    starts_well && chars.all(|c| c.is_ascii_alphanumeric() || c == '_') && !KEYWORDS.contains(&s)
+}
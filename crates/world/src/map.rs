@@ -1,32 +1,196 @@
 use std::{
-    collections::HashMap,
-    io::{Cursor, Read},
+    collections::{BTreeSet, HashMap},
+    fmt,
+    io::{Cursor, ErrorKind, Read},
+    mem::size_of,
     string::FromUtf8Error,
-    sync::Mutex,
+    sync::{Mutex, OnceLock},
 };
 
 use glam::IVec3;
 
-// TODO: split this
+use crate::metadata::*;
+use crate::palette::NodeStorage;
+use crate::serialize::*;
+
 #[derive(thiserror::Error, Debug)]
 pub enum MapError {
     #[error("block not found")]
     BlockNotFound,
 
-    #[error("unsupported block version: {0}")]
-    UnsupportedVersion(u8),
+    #[error("node position {0} is outside of the block")]
+    OutOfBounds(IVec3),
 
-    #[error("unexpected line format: {0}")]
-    UnexpectedFormat(String),
+    #[error(transparent)]
+    Parse(#[from] ParseError),
 
-    #[error("invalid utf-8: {0}")]
-    InvalidUtf8(#[from] FromUtf8Error),
+    #[error(transparent)]
+    Backend(#[from] BackendError),
 
     #[error("I/O error: {0}")]
     Io(#[from] std::io::Error),
+}
+
+impl MapError {
+    /// Adds the position of the block to parse errors.
+    pub fn at(self, pos: IVec3) -> Self {
+        match self {
+            Self::Parse(err) => Self::Parse(err.at(pos)),
+            err => err,
+        }
+    }
+}
 
+/// Errors of the databases that store the blocks.
+#[derive(thiserror::Error, Debug)]
+pub enum BackendError {
     #[error("sqlite error: {0}")]
     Sqlite(#[from] rusqlite::Error),
+
+    #[cfg(feature = "leveldb")]
+    #[error("leveldb error: {0}")]
+    LevelDb(#[from] leveldb::error::Error),
+
+    #[cfg(feature = "postgres")]
+    #[error("postgres error: {0}")]
+    Postgres(#[from] postgres::Error),
+
+    #[error("invalid block key: {0}")]
+    InvalidKey(String),
+}
+
+impl From<rusqlite::Error> for MapError {
+    fn from(err: rusqlite::Error) -> Self {
+        Self::Backend(err.into())
+    }
+}
+
+#[cfg(feature = "leveldb")]
+impl From<leveldb::error::Error> for MapError {
+    fn from(err: leveldb::error::Error) -> Self {
+        Self::Backend(err.into())
+    }
+}
+
+#[cfg(feature = "postgres")]
+impl From<postgres::Error> for MapError {
+    fn from(err: postgres::Error) -> Self {
+        Self::Backend(err.into())
+    }
+}
+
+/// Invalid serialized block data, and where in the data it was found.
+#[derive(Debug)]
+pub struct ParseError {
+    /// Position of the block, if known.
+    pub pos: Option<IVec3>,
+
+    pub section: Section,
+
+    /// Offset in bytes. For [`Section::Version`] and [`Section::Compression`]
+    /// it's counted in the stored data, for all other sections in the
+    /// decompressed data.
+    pub offset: u64,
+
+    pub kind: ParseErrorKind,
+}
+
+impl ParseError {
+    fn new(section: Section, offset: u64, kind: ParseErrorKind) -> Self {
+        Self {
+            pos: None,
+            section,
+            offset,
+            kind,
+        }
+    }
+
+    pub fn at(self, pos: IVec3) -> Self {
+        Self {
+            pos: Some(pos),
+            ..self
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(pos) = self.pos {
+            write!(f, "block {pos}: ")?;
+        }
+
+        write!(
+            f,
+            "{} in {} at byte {}",
+            self.kind, self.section, self.offset
+        )
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// Sections of serialized block data, in the order they are stored.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Section {
+    Version,
+    Compression,
+    Header,
+    Mappings,
+    Nodes,
+    NodeMetadata,
+    StaticObjects,
+    NodeTimers,
+}
+
+impl fmt::Display for Section {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Version => "version",
+            Self::Compression => "compressed data",
+            Self::Header => "header",
+            Self::Mappings => "name mappings",
+            Self::Nodes => "nodes",
+            Self::NodeMetadata => "node metadata",
+            Self::StaticObjects => "static objects",
+            Self::NodeTimers => "node timers",
+        })
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum ParseErrorKind {
+    #[error("unsupported block version {0}")]
+    UnsupportedVersion(u8),
+
+    #[error("unexpected end of data")]
+    UnexpectedEnd,
+
+    #[error("invalid compressed data ({0})")]
+    Decompress(std::io::Error),
+
+    #[error("more than {} bytes of data", Block::MAX_PAYLOAD_SIZE)]
+    TooLarge,
+
+    #[error("invalid utf-8")]
+    InvalidUtf8,
+
+    #[error("{0}")]
+    Invalid(String),
+}
+
+impl From<std::io::Error> for ParseErrorKind {
+    fn from(err: std::io::Error) -> Self {
+        match err.kind() {
+            ErrorKind::UnexpectedEof => Self::UnexpectedEnd,
+            _ => Self::Invalid(err.to_string()),
+        }
+    }
+}
+
+impl From<FromUtf8Error> for ParseErrorKind {
+    fn from(_: FromUtf8Error) -> Self {
+        Self::InvalidUtf8
+    }
 }
 
 pub struct Map {
@@ -41,20 +205,187 @@ impl Map {
     }
 
     pub fn get_block(&self, pos: IVec3) -> Result<Block, MapError> {
-        let data = self.backend.lock().unwrap().get_block_data(pos)?;
-        Block::parse_data(&data)
+        let data = self.get_block_data(pos)?;
+        Block::parse_data(&data).map_err(|err| err.at(pos).into())
+    }
+
+    pub fn get_block_data(&self, pos: IVec3) -> Result<Vec<u8>, MapError> {
+        self.backend.lock().unwrap().get_block_data(pos)
+    }
+
+    pub fn set_block_data(&self, pos: IVec3, data: &[u8]) -> Result<(), MapError> {
+        self.backend.lock().unwrap().set_block_data(pos, data)
+    }
+
+    /// Reads the data of the blocks at `positions` in one go, leaving out
+    /// blocks that don't exist.
+    pub fn get_blocks(&self, positions: &[IVec3]) -> Result<Vec<(IVec3, Vec<u8>)>, MapError> {
+        self.backend.lock().unwrap().get_blocks(positions)
+    }
+
+    /// Reads the data of all blocks between `min` and `max` (block positions,
+    /// inclusive) in one go.
+    pub fn get_blocks_in_box(
+        &self,
+        min: IVec3,
+        max: IVec3,
+    ) -> Result<Vec<(IVec3, Vec<u8>)>, MapError> {
+        self.backend.lock().unwrap().get_blocks_in_box(min, max)
+    }
+
+    pub fn delete_block(&self, pos: IVec3) -> Result<(), MapError> {
+        self.backend.lock().unwrap().delete_block(pos)
+    }
+
+    /// Positions of all stored blocks. A position is listed more than once if
+    /// the backend holds duplicate entries for it.
+    pub fn list_blocks(&self) -> Result<Vec<IVec3>, MapError> {
+        self.backend.lock().unwrap().list_blocks()
+    }
+
+    pub fn set_block(&self, pos: IVec3, block: &Block) -> Result<(), MapError> {
+        let data = block.serialize_data()?;
+        self.backend.lock().unwrap().set_block_data(pos, &data)
+    }
+
+    /// Groups the writes up to the matching [`Map::end_save`] into a single
+    /// transaction, where the backend supports it.
+    pub fn begin_save(&self) -> Result<(), MapError> {
+        self.backend.lock().unwrap().begin_save()
+    }
+
+    pub fn end_save(&self) -> Result<(), MapError> {
+        self.backend.lock().unwrap().end_save()
+    }
+
+    /// A value that changes whenever another connection modifies the map, or
+    /// `None` if the backend can't tell.
+    pub fn data_version(&self) -> Result<Option<u64>, MapError> {
+        self.backend.lock().unwrap().data_version()
+    }
+
+    /// Gives space freed by deleted or shrunk blocks back to the file system,
+    /// where the backend supports it.
+    pub fn vacuum(&self) -> Result<(), MapError> {
+        self.backend.lock().unwrap().vacuum()
+    }
+
+    /// Space taken up by the map on disk in bytes, or `None` if the backend
+    /// can't tell.
+    pub fn size_on_disk(&self) -> Result<Option<u64>, MapError> {
+        self.backend.lock().unwrap().size_on_disk()
     }
 }
 
-pub trait MapBackend: 'static {
+/// Storage of serialized blocks. Backends must be `Send` so that a [`Map`]
+/// can be shared with a [`MapLoader`](crate::MapLoader) thread.
+pub trait MapBackend: Send + 'static {
     fn get_block_data(&mut self, pos: IVec3) -> Result<Vec<u8>, MapError>;
+
+    fn set_block_data(&mut self, pos: IVec3, data: &[u8]) -> Result<(), MapError>;
+
+    fn delete_block(&mut self, pos: IVec3) -> Result<(), MapError>;
+
+    fn list_blocks(&mut self) -> Result<Vec<IVec3>, MapError>;
+
+    fn get_blocks(&mut self, positions: &[IVec3]) -> Result<Vec<(IVec3, Vec<u8>)>, MapError> {
+        let mut blocks = Vec::with_capacity(positions.len());
+
+        for &pos in positions {
+            match self.get_block_data(pos) {
+                Ok(data) => blocks.push((pos, data)),
+                Err(MapError::BlockNotFound) => {}
+                Err(err) => return Err(err),
+            }
+        }
+
+        Ok(blocks)
+    }
+
+    fn get_blocks_in_box(
+        &mut self,
+        min: IVec3,
+        max: IVec3,
+    ) -> Result<Vec<(IVec3, Vec<u8>)>, MapError> {
+        let mut positions = Vec::new();
+
+        for z in min.z..=max.z {
+            for y in min.y..=max.y {
+                for x in min.x..=max.x {
+                    positions.push(IVec3::new(x, y, z));
+                }
+            }
+        }
+
+        self.get_blocks(&positions)
+    }
+
+    fn begin_save(&mut self) -> Result<(), MapError> {
+        Ok(())
+    }
+
+    fn end_save(&mut self) -> Result<(), MapError> {
+        Ok(())
+    }
+
+    fn data_version(&mut self) -> Result<Option<u64>, MapError> {
+        Ok(None)
+    }
+
+    fn vacuum(&mut self) -> Result<(), MapError> {
+        Ok(())
+    }
+
+    fn size_on_disk(&mut self) -> Result<Option<u64>, MapError> {
+        Ok(None)
+    }
 }
 
+#[derive(Clone)]
 pub struct Block {
-    node_data: Vec<u8>,
-    mappings: HashMap<u16, String>,
+    flags: u8,
+    lighting_complete: u16,
+    timestamp: u32,
+    nodes: NodeStorage,
+
+    /// Sorted by id.
+    mappings: Vec<(u16, String)>,
+
+    /// Node metadata, static objects and node timers, decoded from
+    /// `extras_data` on first use.
+    extras: OnceLock<Extras>,
+
+    /// The serialized extras of blocks parsed with [`Block::parse_data_lazy`]
+    /// whose extras weren't modified.
+    extras_data: Option<Vec<u8>>,
+}
+
+#[derive(Clone, Default)]
+struct Extras {
+    metadata: HashMap<u16, NodeMetadata>,
+    static_objects: Vec<StaticObject>,
+    node_timers: HashMap<u16, NodeTimer>,
 }
 
+impl Extras {
+    fn read(cur: &mut Cursor<&[u8]>) -> Result<Self, ParseError> {
+        Ok(Self {
+            metadata: read_section(cur, Section::NodeMetadata, read_node_metadata)?,
+            static_objects: read_section(cur, Section::StaticObjects, read_static_objects)?,
+            node_timers: read_section(cur, Section::NodeTimers, read_node_timers)?,
+        })
+    }
+
+    fn write(&self, w: &mut Vec<u8>) -> Result<(), MapError> {
+        write_node_metadata(w, &self.metadata)?;
+        write_static_objects(w, &self.static_objects)?;
+        write_node_timers(w, &self.node_timers)?;
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Node {
     pub id: u16,
     pub param1: u8,
@@ -62,100 +393,555 @@ pub struct Node {
 }
 
 impl Block {
-    const VOLUME: usize = 16 * 16 * 16;
+    pub(crate) const VOLUME: usize = 16 * 16 * 16;
+    pub(crate) const SERIALIZATION_VERSION: u8 = 29;
 
-    pub fn parse_data(data: &[u8]) -> Result<Self, MapError> {
-        let mut cur = Cursor::new(data);
-        let version = read_u8(&mut cur)?;
+    /// Largest decompressed block accepted.
+    pub(crate) const MAX_PAYLOAD_SIZE: usize = 64 << 20;
 
-        if version < 29 {
-            return Err(MapError::UnsupportedVersion(version));
+    pub const FLAG_UNDERGROUND: u8 = 0x01;
+    pub const FLAG_DAY_NIGHT_DIFFERS: u8 = 0x02;
+    pub const FLAG_GENERATED: u8 = 0x08;
+
+    pub const LIGHTING_COMPLETE: u16 = 0xffff;
+
+    /// Timestamp of blocks that were never saved by an active server.
+    pub const TIMESTAMP_UNDEFINED: u32 = u32::MAX;
+
+    /// An empty block filled with air, as the engine creates it before the
+    /// map generator runs.
+    pub fn new() -> Self {
+        Self {
+            flags: 0,
+            lighting_complete: 0,
+            timestamp: Self::TIMESTAMP_UNDEFINED,
+            nodes: NodeStorage::Uniform(Node {
+                id: 0,
+                param1: 0,
+                param2: 0,
+            }),
+            mappings: vec![(0, "air".to_string())],
+            extras: OnceLock::from(Extras::default()),
+            extras_data: None,
         }
+    }
 
-        let mut decoder = zstd::Decoder::new(&mut cur)?;
+    pub fn parse_data(data: &[u8]) -> Result<Self, ParseError> {
+        let (mut block, payload, extras_offset) = Self::parse_header_and_nodes(data)?;
 
-        let mut buf = Vec::new();
-        decoder.read_to_end(&mut buf)?;
+        let mut cur = Cursor::new(payload.as_slice());
+        cur.set_position(extras_offset as u64);
+        block.extras = OnceLock::from(Extras::read(&mut cur)?);
 
-        let mut cur = Cursor::new(buf);
-        let _flags = read_u8(&mut cur)?;
-        let _lighting_complete = read_u16(&mut cur)?;
-        let _timestamp = read_u32(&mut cur)?;
-        let _mapping_version = read_u8(&mut cur)?;
+        Ok(block)
+    }
 
-        let mappings_count = read_u16(&mut cur)?;
+    /// Parses the block header and nodes, and decodes node metadata, static
+    /// objects and node timers only once they are first accessed. Meant for
+    /// viewers that keep many blocks around but rarely look at their
+    /// metadata.
+    ///
+    /// Unlike [`Block::parse_data`], damaged trailing sections aren't
+    /// reported. They read as empty, and are written back unchanged by
+    /// [`Block::serialize_data`] unless they were modified.
+    pub fn parse_data_lazy(data: &[u8]) -> Result<Self, ParseError> {
+        let (mut block, mut extras_data, extras_offset) = Self::parse_header_and_nodes(data)?;
 
-        let mut mappings = HashMap::new();
+        extras_data.drain(..extras_offset);
+        extras_data.shrink_to_fit();
 
-        for _ in 0..mappings_count {
-            let id = read_u16(&mut cur)?;
-            let name = read_string(&mut cur)?;
+        block.extras = OnceLock::new();
+        block.extras_data = Some(extras_data);
 
-            mappings.insert(id, name);
-        }
+        Ok(block)
+    }
 
-        let _content_width = read_u8(&mut cur);
-        let _params_width = read_u8(&mut cur);
+    /// Parses the block header and nodes only, leaving out node metadata,
+    /// static objects and node timers. Useful for salvaging blocks with
+    /// damaged trailing sections.
+    pub fn parse_nodes(data: &[u8]) -> Result<Self, ParseError> {
+        let (block, _, _) = Self::parse_header_and_nodes(data)?;
+        Ok(block)
+    }
 
-        let mut node_data = vec![0; Self::VOLUME * 4];
-        cur.read_exact(&mut node_data)?;
+    /// Reads only the timestamp from serialized block data, without
+    /// decompressing the rest of the block.
+    pub fn parse_timestamp(data: &[u8]) -> Result<u32, ParseError> {
+        let mut cur = Cursor::new(data);
+        Self::check_version(&mut cur)?;
+
+        let read_header = |cur: &mut Cursor<&[u8]>| -> std::io::Result<u32> {
+            let mut decoder = zstd::Decoder::new(cur)?;
+            let _flags = read_u8(&mut decoder)?;
+            let _lighting_complete = read_u16(&mut decoder)?;
+            read_u32(&mut decoder)
+        };
+
+        read_header(&mut cur).map_err(|err| match err.kind() {
+            ErrorKind::UnexpectedEof => {
+                ParseError::new(Section::Header, 0, ParseErrorKind::UnexpectedEnd)
+            }
+            _ => ParseError::new(Section::Compression, 1, ParseErrorKind::Decompress(err)),
+        })
+    }
+
+    /// Parses a block as servers send it to clients in `TOCLIENT_BLOCKDATA`,
+    /// starting at the serialization version.
+    ///
+    /// Network blocks have no mappings of their own: node ids are the
+    /// server's content ids, which are named by `names` from its node
+    /// definitions. Ids missing from `names` read as `unknown`. Servers
+    /// don't send timestamps, static objects or node timers, so these are
+    /// left empty.
+    pub fn parse_network_data(
+        data: &[u8],
+        names: &HashMap<u16, String>,
+    ) -> Result<Self, ParseError> {
+        let mut cur = Cursor::new(data);
+        Self::check_version(&mut cur)?;
+
+        // The block is followed by data that only concerns the network, so
+        // decoding has to stop at the end of the first frame.
+        let payload = Self::read_payload(zstd::Decoder::new(&mut cur).map(|d| d.single_frame()))?;
+        let mut cur = Cursor::new(payload.as_slice());
+
+        let (flags, lighting_complete) = read_section(&mut cur, Section::Header, |cur| {
+            let flags = read_u8(cur)?;
+            let lighting_complete = read_u16(cur)?;
+            Ok((flags, lighting_complete))
+        })?;
+
+        let nodes = read_section(&mut cur, Section::Nodes, Self::read_nodes)?;
+        let metadata = read_section(&mut cur, Section::NodeMetadata, read_node_metadata)?;
+
+        let used_ids: BTreeSet<u16> = (0..Self::VOLUME).map(|i| nodes.get(i).id).collect();
+        let mappings = used_ids
+            .into_iter()
+            .map(|id| {
+                let name = names.get(&id).map_or("unknown", String::as_str);
+                (id, name.to_string())
+            })
+            .collect();
 
         Ok(Self {
-            node_data,
+            flags,
+            lighting_complete,
+            timestamp: Self::TIMESTAMP_UNDEFINED,
+            nodes,
             mappings,
+            extras: OnceLock::from(Extras {
+                metadata,
+                ..Extras::default()
+            }),
+            extras_data: None,
         })
     }
 
+    /// Returns the decompressed payload of serialized block data, which
+    /// holds everything after the version.
+    pub(crate) fn decompress(data: &[u8]) -> Result<Vec<u8>, ParseError> {
+        let mut cur = Cursor::new(data);
+        Self::check_version(&mut cur)?;
+
+        Self::read_payload(zstd::Decoder::new(&mut cur))
+    }
+
+    /// Like [`Block::decompress`], for data compressed with a zstd
+    /// dictionary.
+    pub(crate) fn decompress_with_dictionary(
+        data: &[u8],
+        dictionary: &[u8],
+    ) -> Result<Vec<u8>, ParseError> {
+        let mut cur = Cursor::new(data);
+        Self::check_version(&mut cur)?;
+
+        Self::read_payload(zstd::Decoder::with_dictionary(&mut cur, dictionary))
+    }
+
+    fn read_payload(decoder: std::io::Result<impl Read>) -> Result<Vec<u8>, ParseError> {
+        let mut payload = Vec::new();
+        let result = decoder.and_then(|decoder| {
+            // Compressed data can expand enormously, so stop reading once it
+            // can't be a real block anymore.
+            decoder
+                .take(Self::MAX_PAYLOAD_SIZE as u64 + 1)
+                .read_to_end(&mut payload)
+        });
+
+        if let Err(err) = result {
+            let kind = match err.kind() {
+                ErrorKind::UnexpectedEof => ParseErrorKind::UnexpectedEnd,
+                _ => ParseErrorKind::Decompress(err),
+            };
+
+            return Err(ParseError::new(Section::Compression, 1, kind));
+        }
+
+        if payload.len() > Self::MAX_PAYLOAD_SIZE {
+            return Err(ParseError::new(
+                Section::Compression,
+                1,
+                ParseErrorKind::TooLarge,
+            ));
+        }
+
+        Ok(payload)
+    }
+
+    fn check_version(cur: &mut Cursor<&[u8]>) -> Result<(), ParseError> {
+        let version = read_section(cur, Section::Version, |cur| Ok(read_u8(cur)?))?;
+
+        if version != Self::SERIALIZATION_VERSION {
+            return Err(ParseError::new(
+                Section::Version,
+                0,
+                ParseErrorKind::UnsupportedVersion(version),
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Returns the block without its extras, the decompressed payload and the
+    /// offset of the extras in it.
+    fn parse_header_and_nodes(data: &[u8]) -> Result<(Self, Vec<u8>, usize), ParseError> {
+        let payload = Self::decompress(data)?;
+        let mut cur = Cursor::new(payload.as_slice());
+
+        let (flags, lighting_complete, timestamp) =
+            read_section(&mut cur, Section::Header, |cur| {
+                let flags = read_u8(cur)?;
+                let lighting_complete = read_u16(cur)?;
+                let timestamp = read_u32(cur)?;
+                Ok((flags, lighting_complete, timestamp))
+            })?;
+
+        let mappings = read_section(&mut cur, Section::Mappings, |cur| {
+            let _mapping_version = read_u8(cur)?;
+            let mappings_count = read_u16(cur)?;
+
+            let mut mappings: Vec<(u16, String)> = Vec::with_capacity(mappings_count as usize);
+
+            for _ in 0..mappings_count {
+                let id = read_u16(cur)?;
+                let name = read_string(cur)?;
+
+                // Later entries for the same id win.
+                match mappings.binary_search_by_key(&id, |(id, _)| *id) {
+                    Ok(index) => mappings[index].1 = name,
+                    Err(index) => mappings.insert(index, (id, name)),
+                }
+            }
+
+            Ok(mappings)
+        })?;
+
+        let nodes = read_section(&mut cur, Section::Nodes, Self::read_nodes)?;
+
+        let block = Self {
+            flags,
+            lighting_complete,
+            timestamp,
+            nodes,
+            mappings,
+            extras: OnceLock::from(Extras::default()),
+            extras_data: None,
+        };
+
+        let extras_offset = cur.position() as usize;
+
+        Ok((block, payload, extras_offset))
+    }
+
+    fn read_nodes(cur: &mut Cursor<&[u8]>) -> Result<NodeStorage, ParseErrorKind> {
+        let content_width = read_u8(cur)?;
+        let params_width = read_u8(cur)?;
+
+        if content_width != 2 || params_width != 2 {
+            return Err(ParseErrorKind::Invalid(format!(
+                "unsupported content width {content_width} and params width {params_width}"
+            )));
+        }
+
+        let mut node_data = vec![0; Self::VOLUME * 4];
+        cur.read_exact(&mut node_data)?;
+
+        Ok(NodeStorage::from_serialized(&node_data))
+    }
+
+    pub fn serialize_data(&self) -> Result<Vec<u8>, MapError> {
+        let mut buf = Vec::new();
+        write_u8(&mut buf, self.flags)?;
+        write_u16(&mut buf, self.lighting_complete)?;
+        write_u32(&mut buf, self.timestamp)?;
+        write_u8(&mut buf, 0)?;
+
+        // Only names that are still in use are written out.
+        let used_ids: BTreeSet<u16> = (0..Self::VOLUME).map(|i| self.nodes.get(i).id).collect();
+
+        write_u16(&mut buf, used_ids.len() as u16)?;
+        for id in used_ids {
+            let name = self.get_name_by_id(id).unwrap_or("unknown");
+            write_u16(&mut buf, id)?;
+            write_string(&mut buf, name)?;
+        }
+
+        write_u8(&mut buf, 2)?;
+        write_u8(&mut buf, 2)?;
+        self.nodes.write_serialized(&mut buf);
+
+        // Reading the extras leaves the original data in place, as damaged
+        // ones read as empty; only modifying them drops it.
+        match &self.extras_data {
+            Some(extras_data) => buf.extend_from_slice(extras_data),
+            None => self.extras().write(&mut buf)?,
+        }
+
+        let mut data = vec![Self::SERIALIZATION_VERSION];
+        zstd::stream::copy_encode(buf.as_slice(), &mut data, 0)?;
+
+        Ok(data)
+    }
+
+    pub fn flags(&self) -> u8 {
+        self.flags
+    }
+
+    pub fn set_flags(&mut self, flags: u8) {
+        self.flags = flags;
+    }
+
+    pub fn lighting_complete(&self) -> u16 {
+        self.lighting_complete
+    }
+
+    pub fn set_lighting_complete(&mut self, lighting_complete: u16) {
+        self.lighting_complete = lighting_complete;
+    }
+
+    pub fn timestamp(&self) -> u32 {
+        self.timestamp
+    }
+
+    pub fn set_timestamp(&mut self, timestamp: u32) {
+        self.timestamp = timestamp;
+    }
+
+    /// Advances the timestamp by a second to mark the block as edited, so
+    /// that comparing timestamps notices the change. An undefined timestamp
+    /// is kept, as the engine sets its game time the next time it saves the
+    /// block anyway.
+    pub fn touch(&mut self) {
+        self.timestamp = Self::touched_timestamp(self.timestamp);
+    }
+
+    pub(crate) fn touched_timestamp(timestamp: u32) -> u32 {
+        match timestamp {
+            Self::TIMESTAMP_UNDEFINED => timestamp,
+            _ => (timestamp + 1).min(Self::TIMESTAMP_UNDEFINED - 1),
+        }
+    }
+
     pub fn get_name_by_id(&self, id: u16) -> Option<&str> {
-        self.mappings.get(&id).map(|s| s.as_str())
+        let index = self.mapping_index(id).ok()?;
+        Some(&self.mappings[index].1)
+    }
+
+    pub fn get_id_by_name(&self, name: &str) -> Option<u16> {
+        self.mappings
+            .iter()
+            .find(|(_, n)| n.as_str() == name)
+            .map(|(id, _)| *id)
     }
 
-    pub fn get_node(&self, pos: IVec3) -> Node {
-        let node_index = Self::node_index(pos);
+    /// Returns the id for `name`, adding it to the mappings if necessary.
+    pub fn get_or_insert_id(&mut self, name: &str) -> u16 {
+        if let Some(id) = self.get_id_by_name(name) {
+            return id;
+        }
+
+        // Mappings are sorted, so the first free id is at the first gap.
+        let id = self
+            .mappings
+            .iter()
+            .enumerate()
+            .find(|(index, (id, _))| *index as u16 != *id)
+            .map_or(self.mappings.len() as u16, |(index, _)| index as u16);
+
+        self.set_name(id, name);
 
-        let id_hi = self.node_data[2 * node_index] as u16;
-        let id_lo = self.node_data[2 * node_index + 1] as u16;
-        let param1 = self.node_data[Self::VOLUME * 2 + node_index];
-        let param2 = self.node_data[Self::VOLUME * 3 + node_index];
+        id
+    }
 
-        Node {
-            id: (id_hi << 8) | id_lo,
-            param1,
-            param2,
+    /// Assigns `name` to `id`, replacing any name the id had before.
+    pub fn set_name(&mut self, id: u16, name: &str) {
+        match self.mapping_index(id) {
+            Ok(index) => self.mappings[index].1 = name.to_string(),
+            Err(index) => self.mappings.insert(index, (id, name.to_string())),
         }
     }
 
-    fn node_index(pos: IVec3) -> usize {
-        assert!(pos.x >= 0 && pos.x < 16);
-        assert!(pos.y >= 0 && pos.y < 16);
-        assert!(pos.z >= 0 && pos.z < 16);
+    /// Replaces all mappings. Later entries for the same id win.
+    #[cfg(feature = "serde")]
+    pub(crate) fn set_mappings(&mut self, mappings: Vec<(u16, String)>) {
+        self.mappings.clear();
+        for (id, name) in mappings {
+            self.set_name(id, &name);
+        }
+    }
 
-        pos.z as usize * 16 * 16 + pos.y as usize * 16 + pos.x as usize
+    pub fn mappings(&self) -> impl Iterator<Item = (u16, &str)> {
+        self.mappings.iter().map(|(id, name)| (*id, name.as_str()))
     }
-}
 
-fn read_u8(r: &mut impl Read) -> Result<u8, std::io::Error> {
-    let mut buf = [0; 1];
-    r.read_exact(&mut buf)?;
-    Ok(buf[0])
-}
+    /// The node at `pos` within the block, or `None` if `pos` lies outside
+    /// of it.
+    pub fn get_node(&self, pos: IVec3) -> Option<Node> {
+        Some(self.nodes.get(Self::node_index(pos)?))
+    }
 
-fn read_u16(r: &mut impl Read) -> Result<u16, std::io::Error> {
-    let mut buf = [0; 2];
-    r.read_exact(&mut buf)?;
-    Ok(u16::from_be_bytes(buf))
-}
+    pub fn set_node(&mut self, pos: IVec3, node: Node) -> Result<(), MapError> {
+        let index = Self::node_index(pos).ok_or(MapError::OutOfBounds(pos))?;
+        self.nodes.set(index, node);
+        Ok(())
+    }
+
+    /// All nodes with their positions within the block.
+    pub fn nodes(&self) -> impl Iterator<Item = (IVec3, Node)> {
+        (0..Self::VOLUME).map(|index| (Self::node_pos(index), self.nodes.get(index)))
+    }
+
+    /// The node at `index`, which is `z * 256 + y * 16 + x`. Must be less
+    /// than 4096.
+    pub(crate) fn node_at(&self, index: usize) -> Node {
+        self.nodes.get(index)
+    }
+
+    pub(crate) fn set_node_at(&mut self, index: usize, node: Node) {
+        self.nodes.set(index, node);
+    }
 
-fn read_u32(r: &mut impl Read) -> Result<u32, std::io::Error> {
-    let mut buf = [0; 4];
-    r.read_exact(&mut buf)?;
-    Ok(u32::from_be_bytes(buf))
+    /// The node filling the whole block, if all its nodes are the same.
+    pub fn uniform_node(&self) -> Option<Node> {
+        match self.nodes {
+            NodeStorage::Uniform(node) => Some(node),
+            NodeStorage::Palette(_) => None,
+        }
+    }
+
+    /// Approximate memory used by the block in bytes, not counting decoded
+    /// node metadata.
+    pub fn memory_usage(&self) -> usize {
+        let mappings: usize = self
+            .mappings
+            .iter()
+            .map(|(_, name)| size_of::<(u16, String)>() + name.capacity())
+            .sum();
+
+        size_of::<Self>()
+            + self.nodes.heap_size()
+            + mappings
+            + self.extras_data.as_ref().map_or(0, |data| data.capacity())
+    }
+
+    pub fn get_metadata(&self, pos: IVec3) -> Option<&NodeMetadata> {
+        let index = Self::node_index(pos)?;
+        self.extras().metadata.get(&(index as u16))
+    }
+
+    pub fn get_metadata_mut(&mut self, pos: IVec3) -> Option<&mut NodeMetadata> {
+        let index = Self::node_index(pos)?;
+        self.extras_mut().metadata.get_mut(&(index as u16))
+    }
+
+    pub fn set_metadata(&mut self, pos: IVec3, metadata: NodeMetadata) -> Result<(), MapError> {
+        let index = Self::node_index(pos).ok_or(MapError::OutOfBounds(pos))?;
+        self.extras_mut().metadata.insert(index as u16, metadata);
+        Ok(())
+    }
+
+    pub fn remove_metadata(&mut self, pos: IVec3) -> Option<NodeMetadata> {
+        let index = Self::node_index(pos)?;
+        self.extras_mut().metadata.remove(&(index as u16))
+    }
+
+    pub fn metadata(&self) -> impl Iterator<Item = (IVec3, &NodeMetadata)> {
+        self.extras()
+            .metadata
+            .iter()
+            .map(|(index, metadata)| (Self::node_pos(*index as usize), metadata))
+    }
+
+    pub fn static_objects(&self) -> &[StaticObject] {
+        &self.extras().static_objects
+    }
+
+    pub fn static_objects_mut(&mut self) -> &mut Vec<StaticObject> {
+        &mut self.extras_mut().static_objects
+    }
+
+    pub fn get_node_timer(&self, pos: IVec3) -> Option<NodeTimer> {
+        let index = Self::node_index(pos)?;
+        self.extras().node_timers.get(&(index as u16)).copied()
+    }
+
+    pub fn set_node_timer(&mut self, pos: IVec3, timer: NodeTimer) -> Result<(), MapError> {
+        let index = Self::node_index(pos).ok_or(MapError::OutOfBounds(pos))?;
+        self.extras_mut().node_timers.insert(index as u16, timer);
+        Ok(())
+    }
+
+    pub fn remove_node_timer(&mut self, pos: IVec3) -> Option<NodeTimer> {
+        let index = Self::node_index(pos)?;
+        self.extras_mut().node_timers.remove(&(index as u16))
+    }
+
+    pub fn node_timers(&self) -> impl Iterator<Item = (IVec3, NodeTimer)> {
+        self.extras()
+            .node_timers
+            .iter()
+            .map(|(index, timer)| (Self::node_pos(*index as usize), *timer))
+    }
+
+    fn extras(&self) -> &Extras {
+        self.extras.get_or_init(|| {
+            let data = self.extras_data.as_deref().unwrap_or_default();
+            Extras::read(&mut Cursor::new(data)).unwrap_or_default()
+        })
+    }
+
+    /// Decodes the extras if necessary. Once modified, they are serialized
+    /// anew instead of being copied from the original data.
+    fn extras_mut(&mut self) -> &mut Extras {
+        self.extras();
+        self.extras_data = None;
+        self.extras.get_mut().expect("extras were decoded above")
+    }
+
+    fn mapping_index(&self, id: u16) -> Result<usize, usize> {
+        self.mappings.binary_search_by_key(&id, |(id, _)| *id)
+    }
+
+    pub(crate) fn node_index(pos: IVec3) -> Option<usize> {
+        if pos.cmplt(IVec3::ZERO).any() || pos.cmpge(IVec3::splat(16)).any() {
+            return None;
+        }
+
+        Some(pos.z as usize * 16 * 16 + pos.y as usize * 16 + pos.x as usize)
+    }
+
+    pub(crate) fn node_pos(node_index: usize) -> IVec3 {
+        let index = node_index as i32;
+        IVec3::new(index % 16, (index / 16) % 16, index / (16 * 16))
+    }
 }
 
-fn read_string(r: &mut impl Read) -> Result<String, MapError> {
-    let len = read_u16(r)?;
-    let mut data = vec![0; len as usize];
-    r.read_exact(&mut data)?;
-    let string = String::from_utf8(data)?;
-    Ok(string)
+/// Runs `read` on a section of block data, adding the section and offset to
+/// its errors.
+pub(crate) fn read_section<'a, T>(
+    cur: &mut Cursor<&'a [u8]>,
+    section: Section,
+    read: impl FnOnce(&mut Cursor<&'a [u8]>) -> Result<T, ParseErrorKind>,
+) -> Result<T, ParseError> {
+    read(cur).map_err(|kind| ParseError::new(section, cur.position(), kind))
 }
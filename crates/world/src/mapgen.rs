@@ -0,0 +1,324 @@
+use std::collections::HashSet;
+
+use glam::{IVec3, ivec3};
+use noise::{Fbm, MultiFractal, NoiseFn, Perlin};
+
+use crate::{Block, LIGHT_SUN, LightBank, Map, MapError, Node, NodeArea};
+
+const BATCH_SIZE: usize = 1024;
+
+/// Nodes of dirt between the grass and the stone.
+const DIRT_DEPTH: i32 = 3;
+
+/// Caves stay at least this many nodes below the surface, so they never open
+/// up to the sky or the sea.
+const CAVE_ROOF: i32 = 4;
+
+/// How close to zero both cave noises must be for a node to be carved out.
+/// Larger values make wider tunnels.
+const CAVE_WIDTH: f64 = 0.07;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Terrain {
+    /// Level ground with its surface at the ground level.
+    Flat,
+
+    /// Hills and valleys rising and falling by up to the height range around
+    /// the ground level.
+    Hills,
+
+    /// Islands scattered over an open sea. The ground level is not used, the
+    /// sea floor lies up to the height range below the water level.
+    Islands,
+}
+
+/// Names of the nodes the terrain is built from.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TerrainNodes {
+    pub stone: String,
+    pub dirt: String,
+
+    /// Topmost node of the ground above the water level.
+    pub grass: String,
+    pub water: String,
+}
+
+impl Default for TerrainNodes {
+    fn default() -> Self {
+        Self {
+            stone: "default:stone".to_string(),
+            dirt: "default:dirt".to_string(),
+            grass: "default:dirt_with_grass".to_string(),
+            water: "default:water_source".to_string(),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct GeneratorOptions {
+    /// The same seed and options always produce the same terrain.
+    pub seed: u32,
+    pub terrain: Terrain,
+    pub nodes: TerrainNodes,
+
+    /// Height of the surface of flat terrain, and the average height of
+    /// hills.
+    pub ground_level: i32,
+
+    /// Height of the topmost water node. Terrain below it is flooded.
+    pub water_level: i32,
+
+    /// How far hills and the sea floor of islands reach from their average
+    /// height, in nodes.
+    pub height_range: i32,
+
+    /// Carve tunnels into the stone. Flat terrain never has caves.
+    pub caves: bool,
+}
+
+impl Default for GeneratorOptions {
+    fn default() -> Self {
+        Self {
+            seed: 0,
+            terrain: Terrain::Hills,
+            nodes: TerrainNodes::default(),
+            ground_level: 8,
+            water_level: 1,
+            height_range: 32,
+            caves: true,
+        }
+    }
+}
+
+/// Generates terrain one block at a time.
+///
+/// Sunlight is filled in for the air and water above the ground, so blocks
+/// are stored fully lit and can be played right away.
+pub struct Generator {
+    options: GeneratorOptions,
+    height: Fbm<Perlin>,
+    caves: [Perlin; 2],
+}
+
+/// Mapping ids used by generated blocks.
+struct TerrainIds {
+    air: u16,
+    stone: u16,
+    dirt: u16,
+    grass: u16,
+    water: u16,
+}
+
+impl Generator {
+    pub fn new(options: GeneratorOptions) -> Self {
+        let frequency = match options.terrain {
+            Terrain::Islands => 1.0 / 384.0,
+            Terrain::Flat | Terrain::Hills => 1.0 / 256.0,
+        };
+
+        let height = Fbm::<Perlin>::new(options.seed)
+            .set_octaves(5)
+            .set_frequency(frequency);
+
+        let caves = [
+            Perlin::new(options.seed.wrapping_add(1)),
+            Perlin::new(options.seed.wrapping_add(2)),
+        ];
+
+        Self {
+            options,
+            height,
+            caves,
+        }
+    }
+
+    pub fn options(&self) -> &GeneratorOptions {
+        &self.options
+    }
+
+    /// Generates the block at `pos`. The block is flagged as generated, and
+    /// as underground if it lies entirely below the surface.
+    pub fn generate_block(&self, pos: IVec3) -> Block {
+        let mut block = Block::new();
+
+        let nodes = &self.options.nodes;
+        let ids = TerrainIds {
+            air: 0,
+            stone: block.get_or_insert_id(&nodes.stone),
+            dirt: block.get_or_insert_id(&nodes.dirt),
+            grass: block.get_or_insert_id(&nodes.grass),
+            water: block.get_or_insert_id(&nodes.water),
+        };
+
+        let origin = pos * 16;
+        let mut lowest_surface = i32::MAX;
+        let mut sunlit = false;
+
+        for z in 0..16 {
+            for x in 0..16 {
+                let surface = self.surface_height(origin.x + x, origin.z + z);
+                lowest_surface = lowest_surface.min(surface);
+
+                for y in 0..16 {
+                    let node_pos = origin + ivec3(x, y, z);
+                    let node = self.node_at(node_pos, surface, &ids);
+                    sunlit |= node.light(LightBank::Day) > 0;
+
+                    let index = (z * 256 + y * 16 + x) as usize;
+                    block.set_node_at(index, node);
+                }
+            }
+        }
+
+        let mut flags = Block::FLAG_GENERATED;
+        if origin.y + 15 <= lowest_surface {
+            flags |= Block::FLAG_UNDERGROUND;
+        }
+        if sunlit {
+            flags |= Block::FLAG_DAY_NIGHT_DIFFERS;
+        }
+
+        block.set_flags(flags);
+        block.set_lighting_complete(Block::LIGHTING_COMPLETE);
+
+        block
+    }
+
+    /// Height of the topmost ground node of the column at `x`, `z`.
+    pub fn surface_height(&self, x: i32, z: i32) -> i32 {
+        let options = &self.options;
+        let range = options.height_range as f64;
+        let noise = || self.height.get([x as f64, z as f64]);
+
+        match options.terrain {
+            Terrain::Flat => options.ground_level,
+            Terrain::Hills => options.ground_level + (noise() * range).round() as i32,
+            Terrain::Islands => {
+                // Most of the noise lies between -0.5 and 0.5, so shifting it
+                // down leaves only the peaks above water.
+                let height = (noise() - 0.25) * range * 2.0;
+                options.water_level + (height.round() as i32).max(-options.height_range)
+            }
+        }
+    }
+
+    fn node_at(&self, pos: IVec3, surface: i32, ids: &TerrainIds) -> Node {
+        let water_level = self.options.water_level;
+
+        let (id, light) = if pos.y > surface {
+            if pos.y <= water_level {
+                // Sunlight loses one level with every node of water.
+                let depth = (water_level - pos.y + 1).min(LIGHT_SUN as i32) as u8;
+                (ids.water, LIGHT_SUN.saturating_sub(depth))
+            } else {
+                (ids.air, LIGHT_SUN)
+            }
+        } else if pos.y <= surface - CAVE_ROOF && self.is_cave(pos) {
+            (ids.air, 0)
+        } else if pos.y == surface {
+            if surface >= water_level {
+                (ids.grass, 0)
+            } else {
+                (ids.dirt, 0)
+            }
+        } else if pos.y > surface - DIRT_DEPTH {
+            (ids.dirt, 0)
+        } else {
+            (ids.stone, 0)
+        };
+
+        let mut node = Node {
+            id,
+            param1: 0,
+            param2: 0,
+        };
+        node.set_light(LightBank::Day, light);
+
+        node
+    }
+
+    /// Tunnels run where both cave noises are close to zero, which makes
+    /// them long and winding rather than round.
+    fn is_cave(&self, pos: IVec3) -> bool {
+        if !self.options.caves || self.options.terrain == Terrain::Flat {
+            return false;
+        }
+
+        let point = [
+            pos.x as f64 / 48.0,
+            pos.y as f64 / 32.0,
+            pos.z as f64 / 48.0,
+        ];
+        self.caves
+            .iter()
+            .all(|noise| noise.get(point).abs() < CAVE_WIDTH)
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct GenerateProgress {
+    pub done: usize,
+    pub total: usize,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct GenerateReport {
+    pub generated: usize,
+
+    /// Blocks that already existed and were left as they are.
+    pub skipped: usize,
+}
+
+/// Generates all blocks touching `area` and writes them to `map`, including
+/// blocks of nothing but air, so that the engine doesn't generate its own
+/// terrain there. Existing blocks are only replaced if `overwrite` is set.
+pub fn generate_map(
+    map: &Map,
+    generator: &Generator,
+    area: NodeArea,
+    overwrite: bool,
+    mut on_progress: impl FnMut(GenerateProgress),
+) -> Result<GenerateReport, MapError> {
+    let mut report = GenerateReport::default();
+
+    let min = area.min.div_euclid(IVec3::splat(16));
+    let max = area.max.div_euclid(IVec3::splat(16));
+
+    let existing: HashSet<IVec3> = if overwrite {
+        HashSet::new()
+    } else {
+        map.list_blocks()?.into_iter().collect()
+    };
+
+    let mut positions = Vec::new();
+    for z in min.z..=max.z {
+        for y in min.y..=max.y {
+            for x in min.x..=max.x {
+                positions.push(ivec3(x, y, z));
+            }
+        }
+    }
+
+    for (batch_index, batch) in positions.chunks(BATCH_SIZE).enumerate() {
+        map.begin_save()?;
+
+        for &pos in batch {
+            if existing.contains(&pos) {
+                report.skipped += 1;
+                continue;
+            }
+
+            map.set_block(pos, &generator.generate_block(pos))?;
+            report.generated += 1;
+        }
+
+        map.end_save()?;
+
+        on_progress(GenerateProgress {
+            done: batch_index * BATCH_SIZE + batch.len(),
+            total: positions.len(),
+        });
+    }
+
+    Ok(report)
+}
@@ -14,6 +14,12 @@ pub enum MetaError {
 }
 
 impl WorldMeta {
+    pub fn new() -> Self {
+        Self {
+            values: HashMap::new(),
+        }
+    }
+
     pub fn open(path: impl AsRef<Path>) -> Result<Self, MetaError> {
         let data = std::fs::read_to_string(path)?;
 
@@ -35,7 +41,32 @@ impl WorldMeta {
         Ok(Self { values })
     }
 
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), MetaError> {
+        let mut keys: Vec<_> = self.values.keys().collect();
+        keys.sort();
+
+        let mut data = String::new();
+        for key in keys {
+            data.push_str(&format!("{key} = {}\n", self.values[key]));
+        }
+
+        std::fs::write(path, data)?;
+
+        Ok(())
+    }
+
     pub fn get_str(&self, key: &str) -> Option<&str> {
         self.values.get(key).map(|s| s.as_str())
     }
+
+    /// All settings, in no particular order.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.values
+            .iter()
+            .map(|(key, value)| (key.as_str(), value.as_str()))
+    }
+
+    pub fn set_str(&mut self, key: &str, value: &str) {
+        self.values.insert(key.to_string(), value.to_string());
+    }
 }
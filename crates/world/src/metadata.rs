@@ -0,0 +1,309 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    io::{Read, Write},
+};
+
+use glam::DVec3;
+
+use crate::serialize::*;
+use crate::{Inventory, MapError, ParseErrorKind};
+
+/// Key-value storage and inventory attached to a single node.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct NodeMetadata {
+    pub fields: BTreeMap<String, MetadataField>,
+
+    pub inventory: Inventory,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MetadataField {
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_impls::bytes"))]
+    pub value: Vec<u8>,
+    pub private: bool,
+}
+
+impl NodeMetadata {
+    pub fn get_str(&self, key: &str) -> Option<&str> {
+        self.fields
+            .get(key)
+            .and_then(|field| std::str::from_utf8(&field.value).ok())
+    }
+
+    pub fn set_str(&mut self, key: &str, value: &str) {
+        let field = self.fields.entry(key.to_string()).or_default();
+        field.value = value.as_bytes().to_vec();
+    }
+}
+
+/// Inactive object stored in a block, such as a dropped item or a mob.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct StaticObject {
+    pub ty: u8,
+
+    /// Position in engine units, 10 per node.
+    pub pos: DVec3,
+
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_impls::bytes"))]
+    pub data: Vec<u8>,
+}
+
+impl StaticObject {
+    pub const TYPE_LUA_ENTITY: u8 = 7;
+
+    /// Name and static data of a Lua entity. The static data is whatever the
+    /// entity's `get_staticdata` returned, often a `minetest.serialize`d table
+    /// that [`crate::LuaValue::deserialize`] can decode.
+    pub fn lua_entity(&self) -> Option<LuaEntity> {
+        if self.ty != Self::TYPE_LUA_ENTITY {
+            return None;
+        }
+
+        let mut r = self.data.as_slice();
+
+        let _version = read_u8(&mut r).ok()?;
+        let name = read_string(&mut r).ok()?;
+        let staticdata = read_bytes32(&mut r).ok()?;
+
+        Some(LuaEntity { name, staticdata })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LuaEntity {
+    pub name: String,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_impls::bytes"))]
+    pub staticdata: Vec<u8>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct NodeTimer {
+    pub timeout_ms: i32,
+    pub elapsed_ms: i32,
+}
+
+const NODE_METADATA_VERSION: u8 = 2;
+const STATIC_OBJECTS_VERSION: u8 = 0;
+const NODE_TIMER_SIZE: u8 = 2 + 4 + 4;
+
+pub(crate) fn read_node_metadata(
+    r: &mut impl Read,
+) -> Result<HashMap<u16, NodeMetadata>, ParseErrorKind> {
+    let mut list = HashMap::new();
+
+    let version = read_u8(r)?;
+    if version == 0 {
+        return Ok(list);
+    }
+
+    if version > NODE_METADATA_VERSION {
+        return Err(ParseErrorKind::Invalid(format!(
+            "unsupported node metadata version {version}"
+        )));
+    }
+
+    let count = read_u16(r)?;
+
+    for _ in 0..count {
+        let index = read_u16(r)?;
+        check_node_index(index, "node metadata")?;
+
+        let mut metadata = NodeMetadata::default();
+
+        let num_vars = read_u32(r)?;
+        for _ in 0..num_vars {
+            let key = read_string(r)?;
+            let value = read_bytes32(r)?;
+            let private = version >= 2 && read_u8(r)? != 0;
+
+            metadata
+                .fields
+                .insert(key, MetadataField { value, private });
+        }
+
+        let inventory = read_inventory_text(r)?;
+        metadata.inventory =
+            Inventory::parse(&inventory).map_err(|err| ParseErrorKind::Invalid(err.to_string()))?;
+
+        list.insert(index, metadata);
+    }
+
+    Ok(list)
+}
+
+pub(crate) fn write_node_metadata(
+    w: &mut impl Write,
+    list: &HashMap<u16, NodeMetadata>,
+) -> Result<(), MapError> {
+    if list.is_empty() {
+        write_u8(w, 0)?;
+        return Ok(());
+    }
+
+    write_u8(w, NODE_METADATA_VERSION)?;
+    write_u16(w, list.len() as u16)?;
+
+    let mut indices: Vec<_> = list.keys().copied().collect();
+    indices.sort();
+
+    for index in indices {
+        let metadata = &list[&index];
+
+        write_u16(w, index)?;
+        write_u32(w, metadata.fields.len() as u32)?;
+
+        for (key, field) in &metadata.fields {
+            write_string(w, key)?;
+            write_bytes32(w, &field.value)?;
+            write_u8(w, field.private as u8)?;
+        }
+
+        w.write_all(metadata.inventory.serialize().as_bytes())?;
+    }
+
+    Ok(())
+}
+
+pub(crate) fn read_static_objects(r: &mut impl Read) -> Result<Vec<StaticObject>, ParseErrorKind> {
+    let version = read_u8(r)?;
+    if version != STATIC_OBJECTS_VERSION {
+        return Err(ParseErrorKind::Invalid(format!(
+            "unsupported static object version {version}"
+        )));
+    }
+
+    let count = read_u16(r)?;
+
+    let mut objects = Vec::with_capacity(count as usize);
+    for _ in 0..count {
+        let ty = read_u8(r)?;
+        let x = read_i32(r)?;
+        let y = read_i32(r)?;
+        let z = read_i32(r)?;
+        let data = read_bytes16(r)?;
+
+        objects.push(StaticObject {
+            ty,
+            pos: DVec3::new(x as f64, y as f64, z as f64) / 1000.0,
+            data,
+        });
+    }
+
+    Ok(objects)
+}
+
+pub(crate) fn write_static_objects(
+    w: &mut impl Write,
+    objects: &[StaticObject],
+) -> Result<(), MapError> {
+    write_u8(w, STATIC_OBJECTS_VERSION)?;
+    write_u16(w, objects.len() as u16)?;
+
+    for object in objects {
+        let pos = (object.pos * 1000.0).round();
+
+        write_u8(w, object.ty)?;
+        write_i32(w, pos.x as i32)?;
+        write_i32(w, pos.y as i32)?;
+        write_i32(w, pos.z as i32)?;
+        write_bytes16(w, &object.data)?;
+    }
+
+    Ok(())
+}
+
+pub(crate) fn read_node_timers(
+    r: &mut impl Read,
+) -> Result<HashMap<u16, NodeTimer>, ParseErrorKind> {
+    let size = read_u8(r)?;
+    if size != NODE_TIMER_SIZE {
+        return Err(ParseErrorKind::Invalid(format!(
+            "unexpected node timer size {size}"
+        )));
+    }
+
+    let count = read_u16(r)?;
+
+    let mut timers = HashMap::new();
+    for _ in 0..count {
+        let index = read_u16(r)?;
+        check_node_index(index, "node timer")?;
+
+        let timeout_ms = read_i32(r)?;
+        let elapsed_ms = read_i32(r)?;
+
+        timers.insert(
+            index,
+            NodeTimer {
+                timeout_ms,
+                elapsed_ms,
+            },
+        );
+    }
+
+    Ok(timers)
+}
+
+pub(crate) fn write_node_timers(
+    w: &mut impl Write,
+    timers: &HashMap<u16, NodeTimer>,
+) -> Result<(), MapError> {
+    write_u8(w, NODE_TIMER_SIZE)?;
+    write_u16(w, timers.len() as u16)?;
+
+    let mut indices: Vec<_> = timers.keys().copied().collect();
+    indices.sort();
+
+    for index in indices {
+        let timer = timers[&index];
+
+        write_u16(w, index)?;
+        write_i32(w, timer.timeout_ms)?;
+        write_i32(w, timer.elapsed_ms)?;
+    }
+
+    Ok(())
+}
+
+fn check_node_index(index: u16, section: &str) -> Result<(), ParseErrorKind> {
+    if index as usize >= 16 * 16 * 16 {
+        return Err(ParseErrorKind::Invalid(format!(
+            "{section} position {index} is outside of the block"
+        )));
+    }
+
+    Ok(())
+}
+
+fn read_inventory_text(r: &mut impl Read) -> Result<String, ParseErrorKind> {
+    let mut inventory = String::new();
+
+    loop {
+        let line = read_line(r)?;
+
+        if line.trim() == "EndInventory" {
+            return Ok(inventory);
+        }
+
+        inventory.push_str(&line);
+        inventory.push('\n');
+    }
+}
+
+fn read_line(r: &mut impl Read) -> Result<String, ParseErrorKind> {
+    let mut line = Vec::new();
+
+    loop {
+        match read_u8(r)? {
+            b'\n' => break,
+            byte => line.push(byte),
+        }
+    }
+
+    Ok(String::from_utf8(line)?)
+}
@@ -0,0 +1,137 @@
+use std::{collections::HashSet, path::Path};
+
+use glam::IVec3;
+
+use crate::{Error, Map, MapError, World, open_map};
+
+/// Number of blocks written between [`Map::begin_save`] and [`Map::end_save`].
+const SAVE_BATCH: usize = 1024;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MigrateStage {
+    Copy,
+    Verify,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct MigrateProgress {
+    pub stage: MigrateStage,
+    pub done: usize,
+    pub total: usize,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct MigrateReport {
+    pub blocks: usize,
+}
+
+/// Copies the raw data of every block in `src` to `dst`, one block at a time.
+/// Returns the positions of the copied blocks.
+pub fn copy_map(
+    src: &Map,
+    dst: &Map,
+    mut on_progress: impl FnMut(MigrateProgress),
+) -> Result<Vec<IVec3>, MapError> {
+    let positions = src.list_blocks()?;
+    let total = positions.len();
+
+    for (batch_index, batch) in positions.chunks(SAVE_BATCH).enumerate() {
+        dst.begin_save()?;
+
+        for &pos in batch {
+            let data = src.get_block_data(pos)?;
+            dst.set_block_data(pos, &data)?;
+        }
+
+        dst.end_save()?;
+
+        on_progress(MigrateProgress {
+            stage: MigrateStage::Copy,
+            done: batch_index * SAVE_BATCH + batch.len(),
+            total,
+        });
+    }
+
+    Ok(positions)
+}
+
+/// Compares the raw data of blocks at `positions` in both maps. Returns the
+/// positions where the data differs or is missing from `dst`, followed by
+/// the positions of blocks in `dst` that are not in `positions`.
+pub fn verify_map(
+    src: &Map,
+    dst: &Map,
+    positions: &[IVec3],
+    mut on_progress: impl FnMut(MigrateProgress),
+) -> Result<Vec<IVec3>, MapError> {
+    let mut mismatches = Vec::new();
+
+    for (i, &pos) in positions.iter().enumerate() {
+        let expected = src.get_block_data(pos)?;
+
+        match dst.get_block_data(pos) {
+            Ok(data) if data == expected => {}
+            Ok(_) | Err(MapError::BlockNotFound) => mismatches.push(pos),
+            Err(err) => return Err(err),
+        }
+
+        if (i + 1) % SAVE_BATCH == 0 || i + 1 == positions.len() {
+            on_progress(MigrateProgress {
+                stage: MigrateStage::Verify,
+                done: i + 1,
+                total: positions.len(),
+            });
+        }
+    }
+
+    let expected: HashSet<IVec3> = positions.iter().copied().collect();
+    mismatches.extend(
+        dst.list_blocks()?
+            .into_iter()
+            .filter(|pos| !expected.contains(pos)),
+    );
+
+    Ok(mismatches)
+}
+
+/// Moves the map of the world at `path` to `backend`.
+///
+/// Every block is copied and then compared against the original. Only if all
+/// blocks match is `backend` in `world.mt` updated. The old database is left
+/// in place.
+///
+/// Fails without copying anything if the world already has a non-empty
+/// database for `backend`, such as one left over from an earlier migration.
+pub fn migrate_world(
+    path: impl AsRef<Path>,
+    backend: &str,
+    mut on_progress: impl FnMut(MigrateProgress),
+) -> Result<MigrateReport, Error> {
+    let path = path.as_ref();
+    let mut world = World::open(path)?;
+
+    if world.meta.get_str("backend") == Some(backend) {
+        return Err(Error::SameBackend(backend.to_string()));
+    }
+
+    let dst = open_map(path, &world.meta, backend, true)?;
+
+    let existing = dst.list_blocks()?.len();
+    if existing > 0 {
+        return Err(Error::DestinationNotEmpty(backend.to_string(), existing));
+    }
+
+    let positions = copy_map(&world.map, &dst, &mut on_progress)?;
+    let mismatches = verify_map(&world.map, &dst, &positions, &mut on_progress)?;
+
+    if !mismatches.is_empty() {
+        return Err(Error::VerificationFailed(mismatches.len()));
+    }
+
+    world.meta.set_str("backend", backend);
+    world.meta.save(path.join("world.mt"))?;
+
+    Ok(MigrateReport {
+        blocks: positions.len(),
+    })
+}
@@ -0,0 +1,289 @@
+use std::io::{Cursor, Read};
+
+use crate::ParseErrorKind;
+
+/// Deepest nesting of lists and compounds accepted, as in Minecraft.
+const MAX_DEPTH: usize = 512;
+
+const TAG_END: u8 = 0;
+const TAG_BYTE: u8 = 1;
+const TAG_SHORT: u8 = 2;
+const TAG_INT: u8 = 3;
+const TAG_LONG: u8 = 4;
+const TAG_FLOAT: u8 = 5;
+const TAG_DOUBLE: u8 = 6;
+const TAG_BYTE_ARRAY: u8 = 7;
+const TAG_STRING: u8 = 8;
+const TAG_LIST: u8 = 9;
+const TAG_COMPOUND: u8 = 10;
+const TAG_INT_ARRAY: u8 = 11;
+const TAG_LONG_ARRAY: u8 = 12;
+
+/// A value in Minecraft's Named Binary Tag format, which region files and
+/// Sponge schematics are made of.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum Nbt {
+    Byte(i8),
+    Short(i16),
+    Int(i32),
+    Long(i64),
+    Float(f32),
+    Double(f64),
+    ByteArray(Vec<u8>),
+    String(String),
+    List(Vec<Nbt>),
+
+    /// Entries in the order they were read.
+    Compound(Vec<(String, Nbt)>),
+    IntArray(Vec<i32>),
+    LongArray(Vec<i64>),
+}
+
+impl Nbt {
+    /// Reads an uncompressed root compound, ignoring its name.
+    pub(crate) fn read(data: &[u8]) -> Result<Self, ParseErrorKind> {
+        let mut cur = Cursor::new(data);
+
+        if read_array::<1>(&mut cur)?[0] != TAG_COMPOUND {
+            return Err(ParseErrorKind::Invalid(
+                "NBT data doesn't start with a compound".to_string(),
+            ));
+        }
+
+        let _name = read_string(&mut cur)?;
+        read_payload(&mut cur, TAG_COMPOUND, 0)
+    }
+
+    /// Writes the value as an uncompressed root compound named `name`. Must
+    /// be a compound.
+    pub(crate) fn write(&self, name: &str) -> Vec<u8> {
+        let mut out = vec![TAG_COMPOUND];
+        write_string(&mut out, name);
+        self.write_payload(&mut out);
+        out
+    }
+
+    pub(crate) fn get(&self, key: &str) -> Option<&Nbt> {
+        match self {
+            Nbt::Compound(entries) => entries.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    /// Any integer type, widened.
+    pub(crate) fn as_i64(&self) -> Option<i64> {
+        match self {
+            Nbt::Byte(n) => Some(*n as i64),
+            Nbt::Short(n) => Some(*n as i64),
+            Nbt::Int(n) => Some(*n as i64),
+            Nbt::Long(n) => Some(*n),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn as_str(&self) -> Option<&str> {
+        match self {
+            Nbt::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn as_list(&self) -> Option<&[Nbt]> {
+        match self {
+            Nbt::List(list) => Some(list),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn as_compound(&self) -> Option<&[(String, Nbt)]> {
+        match self {
+            Nbt::Compound(entries) => Some(entries),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn as_bytes(&self) -> Option<&[u8]> {
+        match self {
+            Nbt::ByteArray(bytes) => Some(bytes),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn as_long_array(&self) -> Option<&[i64]> {
+        match self {
+            Nbt::LongArray(longs) => Some(longs),
+            _ => None,
+        }
+    }
+
+    fn tag(&self) -> u8 {
+        match self {
+            Nbt::Byte(_) => TAG_BYTE,
+            Nbt::Short(_) => TAG_SHORT,
+            Nbt::Int(_) => TAG_INT,
+            Nbt::Long(_) => TAG_LONG,
+            Nbt::Float(_) => TAG_FLOAT,
+            Nbt::Double(_) => TAG_DOUBLE,
+            Nbt::ByteArray(_) => TAG_BYTE_ARRAY,
+            Nbt::String(_) => TAG_STRING,
+            Nbt::List(_) => TAG_LIST,
+            Nbt::Compound(_) => TAG_COMPOUND,
+            Nbt::IntArray(_) => TAG_INT_ARRAY,
+            Nbt::LongArray(_) => TAG_LONG_ARRAY,
+        }
+    }
+
+    fn write_payload(&self, out: &mut Vec<u8>) {
+        match self {
+            Nbt::Byte(n) => out.push(*n as u8),
+            Nbt::Short(n) => out.extend_from_slice(&n.to_be_bytes()),
+            Nbt::Int(n) => out.extend_from_slice(&n.to_be_bytes()),
+            Nbt::Long(n) => out.extend_from_slice(&n.to_be_bytes()),
+            Nbt::Float(n) => out.extend_from_slice(&n.to_be_bytes()),
+            Nbt::Double(n) => out.extend_from_slice(&n.to_be_bytes()),
+            Nbt::ByteArray(bytes) => {
+                out.extend_from_slice(&(bytes.len() as i32).to_be_bytes());
+                out.extend_from_slice(bytes);
+            }
+            Nbt::String(s) => write_string(out, s),
+            Nbt::List(list) => {
+                out.push(list.first().map_or(TAG_END, Nbt::tag));
+                out.extend_from_slice(&(list.len() as i32).to_be_bytes());
+                for value in list {
+                    value.write_payload(out);
+                }
+            }
+            Nbt::Compound(entries) => {
+                for (key, value) in entries {
+                    out.push(value.tag());
+                    write_string(out, key);
+                    value.write_payload(out);
+                }
+                out.push(TAG_END);
+            }
+            Nbt::IntArray(ints) => {
+                out.extend_from_slice(&(ints.len() as i32).to_be_bytes());
+                for n in ints {
+                    out.extend_from_slice(&n.to_be_bytes());
+                }
+            }
+            Nbt::LongArray(longs) => {
+                out.extend_from_slice(&(longs.len() as i32).to_be_bytes());
+                for n in longs {
+                    out.extend_from_slice(&n.to_be_bytes());
+                }
+            }
+        }
+    }
+}
+
+fn read_payload(cur: &mut Cursor<&[u8]>, tag: u8, depth: usize) -> Result<Nbt, ParseErrorKind> {
+    if depth > MAX_DEPTH {
+        return Err(ParseErrorKind::Invalid("NBT nested too deeply".to_string()));
+    }
+
+    Ok(match tag {
+        TAG_BYTE => Nbt::Byte(i8::from_be_bytes(read_array(cur)?)),
+        TAG_SHORT => Nbt::Short(i16::from_be_bytes(read_array(cur)?)),
+        TAG_INT => Nbt::Int(i32::from_be_bytes(read_array(cur)?)),
+        TAG_LONG => Nbt::Long(i64::from_be_bytes(read_array(cur)?)),
+        TAG_FLOAT => Nbt::Float(f32::from_be_bytes(read_array(cur)?)),
+        TAG_DOUBLE => Nbt::Double(f64::from_be_bytes(read_array(cur)?)),
+        TAG_BYTE_ARRAY => {
+            let len = read_len(cur, 1)?;
+            Nbt::ByteArray(read_bytes(cur, len)?)
+        }
+        TAG_STRING => Nbt::String(read_string(cur)?),
+        TAG_LIST => {
+            let element_tag = read_array::<1>(cur)?[0];
+            // Every element takes at least one byte.
+            let len = read_len(cur, 1)?;
+
+            if element_tag == TAG_END && len > 0 {
+                return Err(ParseErrorKind::Invalid("NBT list of end tags".to_string()));
+            }
+
+            let mut list = Vec::new();
+            for _ in 0..len {
+                list.push(read_payload(cur, element_tag, depth + 1)?);
+            }
+            Nbt::List(list)
+        }
+        TAG_COMPOUND => {
+            let mut entries = Vec::new();
+            loop {
+                let tag = read_array::<1>(cur)?[0];
+                if tag == TAG_END {
+                    break;
+                }
+
+                let key = read_string(cur)?;
+                let value = read_payload(cur, tag, depth + 1)?;
+                entries.push((key, value));
+            }
+            Nbt::Compound(entries)
+        }
+        TAG_INT_ARRAY => {
+            let len = read_len(cur, 4)?;
+            let bytes = read_bytes(cur, len * 4)?;
+            Nbt::IntArray(
+                bytes
+                    .chunks_exact(4)
+                    .map(|n| i32::from_be_bytes(n.try_into().unwrap()))
+                    .collect(),
+            )
+        }
+        TAG_LONG_ARRAY => {
+            let len = read_len(cur, 8)?;
+            let bytes = read_bytes(cur, len * 8)?;
+            Nbt::LongArray(
+                bytes
+                    .chunks_exact(8)
+                    .map(|n| i64::from_be_bytes(n.try_into().unwrap()))
+                    .collect(),
+            )
+        }
+        _ => return Err(ParseErrorKind::Invalid(format!("unknown NBT tag {tag}"))),
+    })
+}
+
+fn read_array<const N: usize>(cur: &mut Cursor<&[u8]>) -> Result<[u8; N], ParseErrorKind> {
+    let mut buf = [0; N];
+    cur.read_exact(&mut buf)?;
+    Ok(buf)
+}
+
+/// Reads an array or list length, checking that the remaining data can hold
+/// that many elements of at least `element_size` bytes.
+fn read_len(cur: &mut Cursor<&[u8]>, element_size: usize) -> Result<usize, ParseErrorKind> {
+    let len = i32::from_be_bytes(read_array(cur)?);
+    let remaining = cur.get_ref().len() - cur.position() as usize;
+
+    match usize::try_from(len) {
+        Ok(len) if len.saturating_mul(element_size) <= remaining => Ok(len),
+        Ok(_) => Err(ParseErrorKind::UnexpectedEnd),
+        Err(_) => Err(ParseErrorKind::Invalid(format!(
+            "negative NBT length {len}"
+        ))),
+    }
+}
+
+fn read_bytes(cur: &mut Cursor<&[u8]>, len: usize) -> Result<Vec<u8>, ParseErrorKind> {
+    let mut bytes = vec![0; len];
+    cur.read_exact(&mut bytes)?;
+    Ok(bytes)
+}
+
+/// Strings are Java's modified UTF-8, which only differs from UTF-8 for NUL
+/// and characters outside the Basic Multilingual Plane. Those are replaced.
+fn read_string(cur: &mut Cursor<&[u8]>) -> Result<String, ParseErrorKind> {
+    let len = u16::from_be_bytes(read_array(cur)?);
+    let bytes = read_bytes(cur, len as usize)?;
+    Ok(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+fn write_string(out: &mut Vec<u8>, s: &str) {
+    let bytes = &s.as_bytes()[..s.len().min(u16::MAX as usize)];
+    out.extend_from_slice(&(bytes.len() as u16).to_be_bytes());
+    out.extend_from_slice(bytes);
+}
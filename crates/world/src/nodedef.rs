@@ -0,0 +1,313 @@
+use std::{collections::HashMap, path::Path};
+
+use serde::{Deserialize, Deserializer};
+
+#[derive(thiserror::Error, Debug)]
+pub enum NodeDefError {
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("invalid node definition dump: {0}")]
+    Json(#[from] serde_json::Error),
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DrawType {
+    #[default]
+    Normal,
+    Airlike,
+    Liquid,
+    #[serde(rename = "flowingliquid")]
+    FlowingLiquid,
+    Glasslike,
+    GlasslikeFramed,
+    GlasslikeFramedOptional,
+    Allfaces,
+    AllfacesOptional,
+    Torchlike,
+    Signlike,
+    Plantlike,
+    Firelike,
+    Fencelike,
+    Raillike,
+    Nodebox,
+    Mesh,
+    PlantlikeRooted,
+
+    /// A drawtype added to the engine after this list, drawn like `Normal`
+    /// but not taken to hide its neighbours.
+    #[serde(other)]
+    Unknown,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ParamType {
+    #[default]
+    None,
+    Light,
+
+    /// A paramtype added to the engine after this list, taken as `None`.
+    #[serde(other)]
+    Unknown,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ParamType2 {
+    #[default]
+    None,
+    Full,
+    FlowingLiquid,
+    Wallmounted,
+    Facedir,
+    #[serde(rename = "4dir")]
+    FourDir,
+    Leveled,
+    Degrotate,
+    MeshOptions,
+    Color,
+    ColorFacedir,
+    #[serde(rename = "color4dir")]
+    ColorFourDir,
+    ColorWallmounted,
+    GlasslikeLiquidLevel,
+    ColorDegrotate,
+
+    /// A paramtype2 added to the engine after this list, whose param2 is
+    /// left as it is.
+    #[serde(other)]
+    Unknown,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NodeBoxType {
+    #[default]
+    Regular,
+    Fixed,
+    Leveled,
+    Wallmounted,
+    Connected,
+
+    /// A node box type added to the engine after this list.
+    #[serde(other)]
+    Unknown,
+}
+
+/// Boxes are `[x1, y1, z1, x2, y2, z2]` in node-local coordinates, where the
+/// full node spans -0.5..0.5 on every axis.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct NodeBox {
+    #[serde(rename = "type")]
+    pub ty: NodeBoxType,
+
+    #[serde(deserialize_with = "deserialize_boxes")]
+    pub fixed: Vec<[f32; 6]>,
+
+    #[serde(deserialize_with = "deserialize_boxes")]
+    pub wall_top: Vec<[f32; 6]>,
+
+    #[serde(deserialize_with = "deserialize_boxes")]
+    pub wall_bottom: Vec<[f32; 6]>,
+
+    #[serde(deserialize_with = "deserialize_boxes")]
+    pub wall_side: Vec<[f32; 6]>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct NodeDef {
+    #[serde(skip)]
+    pub name: String,
+
+    pub drawtype: DrawType,
+
+    #[serde(deserialize_with = "deserialize_tiles")]
+    pub tiles: Vec<String>,
+
+    pub walkable: bool,
+    pub light_source: u8,
+    pub sunlight_propagates: bool,
+    pub paramtype: ParamType,
+    pub paramtype2: ParamType2,
+
+    #[serde(deserialize_with = "deserialize_groups")]
+    pub groups: HashMap<String, i32>,
+
+    pub node_box: Option<NodeBox>,
+}
+
+impl Default for NodeDef {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            drawtype: DrawType::Normal,
+            tiles: Vec::new(),
+            walkable: true,
+            light_source: 0,
+            sunlight_propagates: false,
+            paramtype: ParamType::None,
+            paramtype2: ParamType2::None,
+            groups: HashMap::new(),
+            node_box: None,
+        }
+    }
+}
+
+impl NodeDef {
+    fn builtin_airlike(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            drawtype: DrawType::Airlike,
+            walkable: false,
+            sunlight_propagates: true,
+            paramtype: ParamType::Light,
+            ..Default::default()
+        }
+    }
+
+    /// Whether the node produces any geometry at all.
+    pub fn is_visible(&self) -> bool {
+        self.drawtype != DrawType::Airlike
+    }
+
+    /// Whether the node fully hides the faces of its neighbours.
+    pub fn is_opaque(&self) -> bool {
+        self.drawtype == DrawType::Normal
+    }
+
+    pub fn group(&self, name: &str) -> i32 {
+        self.groups.get(name).copied().unwrap_or(0)
+    }
+}
+
+/// Node definitions keyed by node name.
+///
+/// Definitions are imported from a JSON object that maps node names to a
+/// subset of their Lua definition tables, as exported by a companion mod.
+/// `air` and `ignore` are always present, and nodes missing from the dump
+/// resolve to an opaque "unknown node" definition like they do in the engine.
+pub struct NodeDefRegistry {
+    defs: HashMap<String, NodeDef>,
+    unknown: NodeDef,
+}
+
+impl NodeDefRegistry {
+    pub fn new() -> Self {
+        let mut registry = Self {
+            defs: HashMap::new(),
+            unknown: NodeDef {
+                name: "unknown".to_string(),
+                ..Default::default()
+            },
+        };
+
+        registry.insert(NodeDef::builtin_airlike("air"));
+        registry.insert(NodeDef::builtin_airlike("ignore"));
+
+        registry
+    }
+
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, NodeDefError> {
+        let data = std::fs::read_to_string(path)?;
+        Self::from_json(&data)
+    }
+
+    pub fn from_json(data: &str) -> Result<Self, NodeDefError> {
+        let defs: HashMap<String, NodeDef> = serde_json::from_str(data)?;
+
+        let mut registry = Self::new();
+
+        for (name, mut def) in defs {
+            def.name = name;
+            registry.insert(def);
+        }
+
+        Ok(registry)
+    }
+
+    pub fn insert(&mut self, def: NodeDef) {
+        self.defs.insert(def.name.clone(), def);
+    }
+
+    pub fn get(&self, name: &str) -> Option<&NodeDef> {
+        self.defs.get(name)
+    }
+
+    pub fn get_or_unknown(&self, name: &str) -> &NodeDef {
+        self.defs.get(name).unwrap_or(&self.unknown)
+    }
+
+    pub fn len(&self) -> usize {
+        self.defs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.defs.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &NodeDef> {
+        self.defs.values()
+    }
+}
+
+/// Whether nodes named `name` are empty space: airlike nodes according to
+/// `node_defs`, or without definitions, `air` and `ignore`.
+pub(crate) fn is_air(name: &str, node_defs: Option<&NodeDefRegistry>) -> bool {
+    match node_defs {
+        Some(node_defs) => !node_defs.get_or_unknown(name).is_visible(),
+        None => name == "air" || name == "ignore",
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Tile {
+    Name(String),
+    Table { name: String },
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Boxes {
+    Single([f32; 6]),
+    Multiple(Vec<[f32; 6]>),
+}
+
+// Lua tables without entries have no way to say whether they are arrays or
+// maps, so empty groups may show up as `[]` in the dump.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Groups {
+    Map(HashMap<String, i32>),
+    Empty([(); 0]),
+}
+
+fn deserialize_tiles<'de, D: Deserializer<'de>>(d: D) -> Result<Vec<String>, D::Error> {
+    let tiles = Option::<Vec<Tile>>::deserialize(d)?.unwrap_or_default();
+
+    Ok(tiles
+        .into_iter()
+        .map(|tile| match tile {
+            Tile::Name(name) | Tile::Table { name } => name,
+        })
+        .collect())
+}
+
+fn deserialize_boxes<'de, D: Deserializer<'de>>(d: D) -> Result<Vec<[f32; 6]>, D::Error> {
+    Ok(match Option::<Boxes>::deserialize(d)? {
+        Some(Boxes::Single(b)) => vec![b],
+        Some(Boxes::Multiple(boxes)) => boxes,
+        None => Vec::new(),
+    })
+}
+
+fn deserialize_groups<'de, D: Deserializer<'de>>(d: D) -> Result<HashMap<String, i32>, D::Error> {
+    Ok(match Option::<Groups>::deserialize(d)? {
+        Some(Groups::Map(groups)) => groups,
+        Some(Groups::Empty(_)) | None => HashMap::new(),
+    })
+}
@@ -0,0 +1,180 @@
+use std::{collections::HashMap, mem::size_of};
+
+use crate::Node;
+
+const VOLUME: usize = 16 * 16 * 16;
+
+/// The nodes of a block.
+///
+/// Blocks usually contain only a handful of distinct nodes, so instead of
+/// four bytes per node, each node is stored as an index into a palette of
+/// distinct nodes, packed into as few bits as the palette size allows,
+/// rounded up to a power of two so that indices are quick to find.
+/// Blocks made of a single node, such as air or stone underground, don't
+/// store any indices at all.
+#[derive(Clone, Debug)]
+pub(crate) enum NodeStorage {
+    Uniform(Node),
+    Palette(PalettedNodes),
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct PalettedNodes {
+    palette: Vec<Node>,
+    bits: u32,
+    words: Vec<u64>,
+}
+
+impl NodeStorage {
+    /// Reads nodes in the serialized layout: 4096 big-endian ids followed by
+    /// 4096 `param1` and 4096 `param2` values.
+    pub(crate) fn from_serialized(data: &[u8]) -> Self {
+        let node_at = |i: usize| Node {
+            id: u16::from_be_bytes([data[2 * i], data[2 * i + 1]]),
+            param1: data[VOLUME * 2 + i],
+            param2: data[VOLUME * 3 + i],
+        };
+
+        let first = node_at(0);
+        if (1..VOLUME).all(|i| node_at(i) == first) {
+            return Self::Uniform(first);
+        }
+
+        let mut palette = Vec::new();
+        let mut palette_indices = HashMap::new();
+        let mut indices = Vec::with_capacity(VOLUME);
+
+        for i in 0..VOLUME {
+            let node = node_at(i);
+            let index = *palette_indices.entry(node).or_insert_with(|| {
+                palette.push(node);
+                palette.len() as u32 - 1
+            });
+            indices.push(index);
+        }
+
+        let bits = bits_for(palette.len());
+        let mut nodes = PalettedNodes {
+            palette,
+            bits,
+            words: vec![0; words_for(bits)],
+        };
+
+        for (i, index) in indices.into_iter().enumerate() {
+            nodes.set_index(i, index);
+        }
+
+        Self::Palette(nodes)
+    }
+
+    /// Writes nodes in the serialized layout.
+    pub(crate) fn write_serialized(&self, out: &mut Vec<u8>) {
+        let start = out.len();
+        out.resize(start + VOLUME * 4, 0);
+        let data = &mut out[start..];
+
+        for i in 0..VOLUME {
+            let node = self.get(i);
+            data[2 * i..2 * i + 2].copy_from_slice(&node.id.to_be_bytes());
+            data[VOLUME * 2 + i] = node.param1;
+            data[VOLUME * 3 + i] = node.param2;
+        }
+    }
+
+    pub(crate) fn get(&self, index: usize) -> Node {
+        match self {
+            Self::Uniform(node) => *node,
+            Self::Palette(nodes) => nodes.palette[nodes.get_index(index) as usize],
+        }
+    }
+
+    pub(crate) fn set(&mut self, index: usize, node: Node) {
+        match self {
+            Self::Uniform(uniform) if *uniform == node => {}
+            Self::Uniform(uniform) => {
+                let mut nodes = PalettedNodes {
+                    palette: vec![*uniform, node],
+                    bits: 1,
+                    words: vec![0; words_for(1)],
+                };
+                nodes.set_index(index, 1);
+                *self = Self::Palette(nodes);
+            }
+            Self::Palette(nodes) => {
+                let palette_index = match nodes.palette.iter().position(|n| *n == node) {
+                    Some(palette_index) => palette_index,
+                    None => nodes.push(node),
+                };
+                nodes.set_index(index, palette_index as u32);
+            }
+        }
+    }
+
+    /// Heap memory in bytes.
+    pub(crate) fn heap_size(&self) -> usize {
+        match self {
+            Self::Uniform(_) => 0,
+            Self::Palette(nodes) => {
+                nodes.palette.capacity() * size_of::<Node>()
+                    + nodes.words.capacity() * size_of::<u64>()
+            }
+        }
+    }
+}
+
+impl PalettedNodes {
+    /// Adds a node to the palette, widening the indices if it no longer fits.
+    /// Nodes that are no longer used stay in the palette until the block is
+    /// serialized and parsed again.
+    fn push(&mut self, node: Node) -> usize {
+        self.palette.push(node);
+
+        let bits = bits_for(self.palette.len());
+        if bits != self.bits {
+            let indices: Vec<u32> = (0..VOLUME).map(|i| self.get_index(i)).collect();
+
+            self.bits = bits;
+            self.words = vec![0; words_for(bits)];
+
+            for (i, index) in indices.into_iter().enumerate() {
+                self.set_index(i, index);
+            }
+        }
+
+        self.palette.len() - 1
+    }
+
+    fn get_index(&self, i: usize) -> u32 {
+        let (word, shift) = self.locate(i);
+        let mask = (1 << self.bits) - 1;
+
+        ((self.words[word] >> shift) & mask) as u32
+    }
+
+    fn set_index(&mut self, i: usize, index: u32) {
+        let (word, shift) = self.locate(i);
+        let mask = ((1 << self.bits) - 1) << shift;
+
+        let word = &mut self.words[word];
+        *word = (*word & !mask) | ((index as u64) << shift);
+    }
+
+    /// Word and bit offset of the `i`th index. Each word holds `64 / bits`
+    /// indices, which is a power of two.
+    fn locate(&self, i: usize) -> (usize, u32) {
+        let per_word_log2 = 6 - self.bits.trailing_zeros();
+        let word = i >> per_word_log2;
+        let slot = i & ((1 << per_word_log2) - 1);
+
+        (word, slot as u32 * self.bits)
+    }
+}
+
+fn bits_for(palette_len: usize) -> u32 {
+    let bits = usize::BITS - (palette_len - 1).leading_zeros();
+    bits.max(1).next_power_of_two()
+}
+
+fn words_for(bits: u32) -> usize {
+    VOLUME.div_ceil(64 / bits as usize)
+}
@@ -0,0 +1,509 @@
+use std::ops::Mul;
+
+use glam::{IVec3, Mat3};
+
+use crate::ParamType2;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Axis {
+    X,
+    Y,
+    Z,
+}
+
+/// Integer rotation matrix, stored as the images of the unit axes.
+///
+/// Quarter turns around every axis follow the facedir convention: a positive
+/// turn around Y takes +Z to +X, around X takes +Y to +Z and around Z takes
+/// +X to +Y.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Rotation {
+    x: IVec3,
+    y: IVec3,
+    z: IVec3,
+}
+
+impl Rotation {
+    pub const IDENTITY: Self = Self {
+        x: IVec3::X,
+        y: IVec3::Y,
+        z: IVec3::Z,
+    };
+
+    pub fn quarter_turns(axis: Axis, turns: i32) -> Self {
+        let single = match axis {
+            Axis::X => Self {
+                x: IVec3::X,
+                y: IVec3::Z,
+                z: IVec3::NEG_Y,
+            },
+            Axis::Y => Self {
+                x: IVec3::NEG_Z,
+                y: IVec3::Y,
+                z: IVec3::X,
+            },
+            Axis::Z => Self {
+                x: IVec3::Y,
+                y: IVec3::NEG_X,
+                z: IVec3::Z,
+            },
+        };
+
+        let mut rotation = Self::IDENTITY;
+        for _ in 0..turns.rem_euclid(4) {
+            rotation = single * rotation;
+        }
+
+        rotation
+    }
+
+    // Not a rotation, only used when flipping.
+    fn mirror(axis: Axis) -> Self {
+        let mut mirror = Self::IDENTITY;
+
+        match axis {
+            Axis::X => mirror.x = IVec3::NEG_X,
+            Axis::Y => mirror.y = IVec3::NEG_Y,
+            Axis::Z => mirror.z = IVec3::NEG_Z,
+        }
+
+        mirror
+    }
+
+    pub fn apply(&self, v: IVec3) -> IVec3 {
+        self.x * v.x + self.y * v.y + self.z * v.z
+    }
+
+    pub fn matrix(&self) -> Mat3 {
+        Mat3::from_cols(self.x.as_vec3(), self.y.as_vec3(), self.z.as_vec3())
+    }
+}
+
+impl Mul for Rotation {
+    type Output = Rotation;
+
+    /// Applies `rhs` first, then `self`.
+    fn mul(self, rhs: Rotation) -> Rotation {
+        Rotation {
+            x: self.apply(rhs.x),
+            y: self.apply(rhs.y),
+            z: self.apply(rhs.z),
+        }
+    }
+}
+
+/// Rotation or reflection of a region, as done by WorldEdit's `//rotate` and
+/// `//flip`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Transform {
+    Rotate { axis: Axis, turns: i32 },
+    Flip(Axis),
+}
+
+impl Transform {
+    pub fn apply(&self, v: IVec3) -> IVec3 {
+        match *self {
+            Transform::Rotate { axis, turns } => Rotation::quarter_turns(axis, turns).apply(v),
+            Transform::Flip(axis) => Rotation::mirror(axis).apply(v),
+        }
+    }
+
+    /// Orientation of a node after its surroundings were transformed.
+    ///
+    /// A mirrored node can't be expressed as a rotation, so flips assume that
+    /// node models are symmetric across their own X axis, which holds for
+    /// stairs, slabs and most other oriented nodes.
+    pub fn apply_rotation(&self, rotation: Rotation) -> Rotation {
+        match *self {
+            Transform::Rotate { axis, turns } => Rotation::quarter_turns(axis, turns) * rotation,
+            Transform::Flip(axis) => Rotation::mirror(axis) * rotation * Rotation::mirror(Axis::X),
+        }
+    }
+}
+
+/// `facedir` orientation: an axis direction (`+Y`, `+Z`, `-Z`, `+X`, `-X`,
+/// `-Y`) for the node's top, combined with one of four turns around it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Facedir(u8);
+
+impl Facedir {
+    const AXES: [Rotation; 6] = [
+        Rotation::IDENTITY,
+        Rotation {
+            x: IVec3::X,
+            y: IVec3::Z,
+            z: IVec3::NEG_Y,
+        },
+        Rotation {
+            x: IVec3::X,
+            y: IVec3::NEG_Z,
+            z: IVec3::Y,
+        },
+        Rotation {
+            x: IVec3::NEG_Y,
+            y: IVec3::X,
+            z: IVec3::Z,
+        },
+        Rotation {
+            x: IVec3::Y,
+            y: IVec3::NEG_X,
+            z: IVec3::Z,
+        },
+        Rotation {
+            x: IVec3::NEG_X,
+            y: IVec3::NEG_Y,
+            z: IVec3::Z,
+        },
+    ];
+
+    /// Only the lower 5 bits count, and values above 23 wrap around, like
+    /// the engine does.
+    pub fn new(param2: u8) -> Self {
+        Self((param2 & 0x1f) % 24)
+    }
+
+    pub fn from_axis_rotation(axis: u8, rotation: u8) -> Self {
+        Self::new((axis % 6) * 4 + rotation % 4)
+    }
+
+    pub fn value(&self) -> u8 {
+        self.0
+    }
+
+    pub fn axis(&self) -> u8 {
+        self.0 / 4
+    }
+
+    pub fn rotation(&self) -> u8 {
+        self.0 % 4
+    }
+
+    pub fn rotation_matrix(&self) -> Rotation {
+        Self::AXES[self.axis() as usize] * Rotation::quarter_turns(Axis::Y, self.rotation() as i32)
+    }
+
+    /// Direction the node's back (+Z) faces, same as `minetest.facedir_to_dir`.
+    pub fn direction(&self) -> IVec3 {
+        self.rotation_matrix().apply(IVec3::Z)
+    }
+
+    pub fn from_rotation_matrix(rotation: Rotation) -> Self {
+        (0..24)
+            .map(Self)
+            .find(|facedir| facedir.rotation_matrix() == rotation)
+            .expect("every rotation has a facedir")
+    }
+
+    pub fn transformed(&self, transform: Transform) -> Self {
+        Self::from_rotation_matrix(transform.apply_rotation(self.rotation_matrix()))
+    }
+}
+
+/// `4dir` orientation: the first four facedir values, turns around +Y only.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct FourDir(u8);
+
+impl FourDir {
+    pub fn new(param2: u8) -> Self {
+        Self(param2 & 0x03)
+    }
+
+    pub fn value(&self) -> u8 {
+        self.0
+    }
+
+    pub fn rotation_matrix(&self) -> Rotation {
+        Rotation::quarter_turns(Axis::Y, self.0 as i32)
+    }
+
+    pub fn direction(&self) -> IVec3 {
+        self.rotation_matrix().apply(IVec3::Z)
+    }
+
+    /// Returns `None` if the node would end up tilted or upside down.
+    pub fn transformed(&self, transform: Transform) -> Option<Self> {
+        let rotation = transform.apply_rotation(self.rotation_matrix());
+        (0..4)
+            .map(Self)
+            .find(|dir| dir.rotation_matrix() == rotation)
+    }
+}
+
+/// `wallmounted` orientation: which side of the node it is attached to.
+/// Values 6 and 7 are the ceiling and floor turned by 90 degrees.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Wallmounted(u8);
+
+impl Wallmounted {
+    const TO_FACEDIR: [u8; 8] = [20, 0, 17, 15, 8, 6, 21, 1];
+
+    pub fn new(param2: u8) -> Self {
+        Self(param2 & 0x07)
+    }
+
+    pub fn value(&self) -> u8 {
+        self.0
+    }
+
+    pub fn to_facedir(&self) -> Facedir {
+        Facedir(Self::TO_FACEDIR[self.0 as usize])
+    }
+
+    pub fn rotation_matrix(&self) -> Rotation {
+        self.to_facedir().rotation_matrix()
+    }
+
+    /// Direction of the surface the node is attached to, same as
+    /// `minetest.wallmounted_to_dir`.
+    pub fn direction(&self) -> IVec3 {
+        self.rotation_matrix().apply(IVec3::NEG_Y)
+    }
+
+    pub fn transformed(&self, transform: Transform) -> Self {
+        let rotation = transform.apply_rotation(self.rotation_matrix());
+        let direction = rotation.apply(IVec3::NEG_Y);
+
+        // Ceiling and floor nodes only store their turn modulo a half turn,
+        // so match that to keep it, otherwise settle for the attachment side.
+        let half_turned = rotation * Rotation::quarter_turns(Axis::Y, 2);
+        (0..8)
+            .map(Self)
+            .find(|w| w.rotation_matrix() == rotation || w.rotation_matrix() == half_turned)
+            .or_else(|| (0..6).map(Self).find(|w| w.direction() == direction))
+            .expect("every direction has a wallmounted value")
+    }
+}
+
+/// `degrotate` orientation: a turn around +Y in steps of 1.5 degrees.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Degrotate(u8);
+
+impl Degrotate {
+    const STEPS: i32 = 240;
+
+    pub fn new(param2: u8) -> Self {
+        Self((param2 as i32 % Self::STEPS) as u8)
+    }
+
+    pub fn value(&self) -> u8 {
+        self.0
+    }
+
+    pub fn degrees(&self) -> f32 {
+        self.0 as f32 * 1.5
+    }
+
+    pub fn rotation_matrix(&self) -> Mat3 {
+        Mat3::from_rotation_y(self.degrees().to_radians())
+    }
+
+    /// Returns `None` if the node would end up tilted or upside down.
+    pub fn transformed(&self, transform: Transform) -> Option<Self> {
+        let value = self.0 as i32;
+
+        let value = match transform {
+            Transform::Rotate {
+                axis: Axis::Y,
+                turns,
+            } => value + turns * Self::STEPS / 4,
+            Transform::Rotate { turns, .. } if turns.rem_euclid(4) == 0 => value,
+            Transform::Rotate { .. } => return None,
+            Transform::Flip(Axis::X) => -value,
+            Transform::Flip(Axis::Z) => Self::STEPS / 2 - value,
+            Transform::Flip(Axis::Y) => return None,
+        };
+
+        Some(Self(value.rem_euclid(Self::STEPS) as u8))
+    }
+}
+
+/// Decoded param2, depending on the node's `paramtype2`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Param2 {
+    Raw(u8),
+    FlowingLiquid { level: u8, flowing_down: bool },
+    Wallmounted(Wallmounted),
+    Facedir(Facedir),
+    FourDir(FourDir),
+    Leveled(u8),
+    Degrotate(Degrotate),
+    MeshOptions(MeshOptions),
+    Color(u8),
+    ColorFacedir { color: u8, facedir: Facedir },
+    ColorFourDir { color: u8, dir: FourDir },
+    ColorWallmounted { color: u8, wallmounted: Wallmounted },
+    ColorDegrotate { color: u8, degrotate: Degrotate },
+    GlasslikeLiquidLevel(u8),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum MeshShape {
+    X,
+    Plus,
+    Star,
+    Hash,
+    HashOutward,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct MeshOptions(u8);
+
+impl MeshOptions {
+    pub fn new(param2: u8) -> Self {
+        Self(param2)
+    }
+
+    pub fn value(&self) -> u8 {
+        self.0
+    }
+
+    /// Returns `None` for shapes the engine doesn't know about.
+    pub fn shape(&self) -> Option<MeshShape> {
+        match self.0 & 0x07 {
+            0 => Some(MeshShape::X),
+            1 => Some(MeshShape::Plus),
+            2 => Some(MeshShape::Star),
+            3 => Some(MeshShape::Hash),
+            4 => Some(MeshShape::HashOutward),
+            _ => None,
+        }
+    }
+
+    pub fn random_offset(&self) -> bool {
+        self.0 & 0x08 != 0
+    }
+
+    pub fn taller(&self) -> bool {
+        self.0 & 0x10 != 0
+    }
+
+    pub fn random_offset_y(&self) -> bool {
+        self.0 & 0x20 != 0
+    }
+}
+
+impl ParamType2 {
+    pub fn decode(self, param2: u8) -> Param2 {
+        match self {
+            ParamType2::None | ParamType2::Full | ParamType2::Unknown => Param2::Raw(param2),
+            ParamType2::FlowingLiquid => Param2::FlowingLiquid {
+                level: param2 & 0x07,
+                flowing_down: param2 & 0x08 != 0,
+            },
+            ParamType2::Wallmounted => Param2::Wallmounted(Wallmounted::new(param2)),
+            ParamType2::Facedir => Param2::Facedir(Facedir::new(param2)),
+            ParamType2::FourDir => Param2::FourDir(FourDir::new(param2)),
+            ParamType2::Leveled => Param2::Leveled(param2 & 0x7f),
+            ParamType2::Degrotate => Param2::Degrotate(Degrotate::new(param2)),
+            ParamType2::MeshOptions => Param2::MeshOptions(MeshOptions::new(param2)),
+            ParamType2::Color => Param2::Color(param2),
+            ParamType2::ColorFacedir => Param2::ColorFacedir {
+                color: param2 >> 5,
+                facedir: Facedir::new(param2),
+            },
+            ParamType2::ColorFourDir => Param2::ColorFourDir {
+                color: param2 >> 2,
+                dir: FourDir::new(param2),
+            },
+            ParamType2::ColorWallmounted => Param2::ColorWallmounted {
+                color: param2 >> 3,
+                wallmounted: Wallmounted::new(param2),
+            },
+            ParamType2::ColorDegrotate => Param2::ColorDegrotate {
+                color: param2 >> 5,
+                degrotate: Degrotate::new((param2 & 0x1f).min(23) * 10),
+            },
+            ParamType2::GlasslikeLiquidLevel => Param2::GlasslikeLiquidLevel(param2 & 0x3f),
+        }
+    }
+
+    /// Applies `transform` to the orientation stored in `param2`, keeping
+    /// color and other bits intact. Returns `None` if the new orientation
+    /// can't be expressed with this `paramtype2`.
+    pub fn transform(self, param2: u8, transform: Transform) -> Option<u8> {
+        self.decode(param2)
+            .transformed(transform)
+            .map(|decoded| decoded.encode(param2))
+    }
+}
+
+impl Param2 {
+    /// Encodes the value back, taking the bits that this param2 type doesn't
+    /// use from `original`.
+    pub fn encode(&self, original: u8) -> u8 {
+        match *self {
+            Param2::Raw(value) => value,
+            Param2::FlowingLiquid {
+                level,
+                flowing_down,
+            } => (original & !0x0f) | (level & 0x07) | if flowing_down { 0x08 } else { 0 },
+            Param2::Wallmounted(w) => (original & !0x07) | w.value(),
+            Param2::Facedir(facedir) => (original & !0x1f) | facedir.value(),
+            Param2::FourDir(dir) => (original & !0x03) | dir.value(),
+            Param2::Leveled(level) => (original & 0x80) | (level & 0x7f),
+            Param2::Degrotate(degrotate) => degrotate.value(),
+            Param2::MeshOptions(options) => options.value(),
+            Param2::Color(color) => color,
+            Param2::ColorFacedir { color, facedir } => (color << 5) | facedir.value(),
+            Param2::ColorFourDir { color, dir } => (color << 2) | dir.value(),
+            Param2::ColorWallmounted { color, wallmounted } => (color << 3) | wallmounted.value(),
+            Param2::ColorDegrotate { color, degrotate } => (color << 5) | (degrotate.value() / 10),
+            Param2::GlasslikeLiquidLevel(level) => (original & 0xc0) | (level & 0x3f),
+        }
+    }
+
+    /// Rotation that renderers should apply to the node's model.
+    pub fn rotation_matrix(&self) -> Mat3 {
+        match self {
+            Param2::Wallmounted(wallmounted) | Param2::ColorWallmounted { wallmounted, .. } => {
+                wallmounted.rotation_matrix().matrix()
+            }
+            Param2::Facedir(facedir) | Param2::ColorFacedir { facedir, .. } => {
+                facedir.rotation_matrix().matrix()
+            }
+            Param2::FourDir(dir) | Param2::ColorFourDir { dir, .. } => {
+                dir.rotation_matrix().matrix()
+            }
+            Param2::Degrotate(degrotate) | Param2::ColorDegrotate { degrotate, .. } => {
+                degrotate.rotation_matrix()
+            }
+            _ => Mat3::IDENTITY,
+        }
+    }
+
+    pub fn transformed(&self, transform: Transform) -> Option<Param2> {
+        let transformed = match *self {
+            Param2::Wallmounted(wallmounted) => {
+                Param2::Wallmounted(wallmounted.transformed(transform))
+            }
+            Param2::Facedir(facedir) => Param2::Facedir(facedir.transformed(transform)),
+            Param2::FourDir(dir) => Param2::FourDir(dir.transformed(transform)?),
+            Param2::Degrotate(degrotate) => Param2::Degrotate(degrotate.transformed(transform)?),
+            Param2::ColorFacedir { color, facedir } => Param2::ColorFacedir {
+                color,
+                facedir: facedir.transformed(transform),
+            },
+            Param2::ColorFourDir { color, dir } => Param2::ColorFourDir {
+                color,
+                dir: dir.transformed(transform)?,
+            },
+            Param2::ColorWallmounted { color, wallmounted } => Param2::ColorWallmounted {
+                color,
+                wallmounted: wallmounted.transformed(transform),
+            },
+            Param2::ColorDegrotate { color, degrotate } => {
+                let degrotate = degrotate.transformed(transform)?;
+
+                // Only every tenth degrotate step fits into five bits.
+                if degrotate.value() % 10 != 0 {
+                    return None;
+                }
+
+                Param2::ColorDegrotate { color, degrotate }
+            }
+            other => other,
+        };
+
+        Some(transformed)
+    }
+}
@@ -0,0 +1,168 @@
+use glam::{IVec3, ivec3};
+use postgres::{Client, NoTls, Row};
+
+use crate::{MapBackend, MapError};
+
+/// Map stored in the `blocks` table of a PostgreSQL database, as configured by
+/// `pgsql_connection` in `world.mt`.
+pub struct PostgresBackend {
+    client: Client,
+}
+
+impl PostgresBackend {
+    pub fn new(connection: &str) -> Result<Self, MapError> {
+        let client = Client::connect(connection, NoTls)?;
+
+        Ok(Self { client })
+    }
+
+    pub fn create(connection: &str) -> Result<Self, MapError> {
+        const SQL: &str = "
+            CREATE TABLE IF NOT EXISTS blocks (
+                posX INT NOT NULL,
+                posY INT NOT NULL,
+                posZ INT NOT NULL,
+                data BYTEA,
+                PRIMARY KEY (posX, posY, posZ)
+            )";
+
+        let mut client = Client::connect(connection, NoTls)?;
+        client.batch_execute(SQL)?;
+
+        Ok(Self { client })
+    }
+}
+
+impl MapBackend for PostgresBackend {
+    fn get_block_data(&mut self, pos: IVec3) -> Result<Vec<u8>, MapError> {
+        const SQL: &str = "
+            SELECT data
+            FROM blocks
+            WHERE posX = $1
+              AND posY = $2
+              AND posZ = $3";
+
+        let row = self.client.query_opt(SQL, &[&pos.x, &pos.y, &pos.z])?;
+
+        match row {
+            Some(row) => block_data(&row, 0)?.ok_or(MapError::BlockNotFound),
+            None => Err(MapError::BlockNotFound),
+        }
+    }
+
+    fn set_block_data(&mut self, pos: IVec3, data: &[u8]) -> Result<(), MapError> {
+        const SQL: &str = "
+            INSERT INTO blocks (posX, posY, posZ, data)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT ON CONSTRAINT blocks_pkey
+            DO UPDATE SET data = $4";
+
+        self.client.execute(SQL, &[&pos.x, &pos.y, &pos.z, &data])?;
+
+        Ok(())
+    }
+
+    fn delete_block(&mut self, pos: IVec3) -> Result<(), MapError> {
+        const SQL: &str = "
+            DELETE FROM blocks
+            WHERE posX = $1
+              AND posY = $2
+              AND posZ = $3";
+
+        self.client.execute(SQL, &[&pos.x, &pos.y, &pos.z])?;
+
+        Ok(())
+    }
+
+    fn list_blocks(&mut self) -> Result<Vec<IVec3>, MapError> {
+        const SQL: &str = "
+            SELECT posX, posY, posZ
+            FROM blocks
+            WHERE data IS NOT NULL";
+
+        let rows = self.client.query(SQL, &[])?;
+
+        Ok(rows
+            .iter()
+            .map(|row| ivec3(row.get(0), row.get(1), row.get(2)))
+            .collect())
+    }
+
+    fn get_blocks(&mut self, positions: &[IVec3]) -> Result<Vec<(IVec3, Vec<u8>)>, MapError> {
+        const SQL: &str = "
+            SELECT posX, posY, posZ, data
+            FROM blocks
+            WHERE (posX, posY, posZ) IN (
+                SELECT * FROM unnest($1::INT[], $2::INT[], $3::INT[])
+            )";
+
+        let xs: Vec<i32> = positions.iter().map(|pos| pos.x).collect();
+        let ys: Vec<i32> = positions.iter().map(|pos| pos.y).collect();
+        let zs: Vec<i32> = positions.iter().map(|pos| pos.z).collect();
+
+        let rows = self.client.query(SQL, &[&xs, &ys, &zs])?;
+
+        rows.iter()
+            .filter_map(|row| block_row(row).transpose())
+            .collect()
+    }
+
+    fn get_blocks_in_box(
+        &mut self,
+        min: IVec3,
+        max: IVec3,
+    ) -> Result<Vec<(IVec3, Vec<u8>)>, MapError> {
+        const SQL: &str = "
+            SELECT posX, posY, posZ, data
+            FROM blocks
+            WHERE posX BETWEEN $1 AND $2
+              AND posY BETWEEN $3 AND $4
+              AND posZ BETWEEN $5 AND $6";
+
+        let rows = self
+            .client
+            .query(SQL, &[&min.x, &max.x, &min.y, &max.y, &min.z, &max.z])?;
+
+        rows.iter()
+            .filter_map(|row| block_row(row).transpose())
+            .collect()
+    }
+
+    fn begin_save(&mut self) -> Result<(), MapError> {
+        self.client.batch_execute("BEGIN")?;
+        Ok(())
+    }
+
+    fn end_save(&mut self) -> Result<(), MapError> {
+        self.client.batch_execute("COMMIT")?;
+        Ok(())
+    }
+
+    /// Rewrites the table with `VACUUM FULL`, which locks it for the
+    /// duration. A plain `VACUUM` only makes free space reusable.
+    fn vacuum(&mut self) -> Result<(), MapError> {
+        self.client.batch_execute("VACUUM FULL blocks")?;
+        Ok(())
+    }
+
+    fn size_on_disk(&mut self) -> Result<Option<u64>, MapError> {
+        let row = self
+            .client
+            .query_one("SELECT pg_total_relation_size('blocks')", &[])?;
+        let size: i64 = row.get(0);
+
+        Ok(Some(size as u64))
+    }
+}
+
+/// Reads the `data` column at `index`. The engine's schema allows it to be
+/// NULL, which is treated like a missing block.
+fn block_data(row: &Row, index: usize) -> Result<Option<Vec<u8>>, MapError> {
+    Ok(row.try_get(index)?)
+}
+
+/// Reads a `posX, posY, posZ, data` row, skipping blocks without data.
+fn block_row(row: &Row) -> Result<Option<(IVec3, Vec<u8>)>, MapError> {
+    let pos = ivec3(row.try_get(0)?, row.try_get(1)?, row.try_get(2)?);
+    Ok(block_data(row, 3)?.map(|data| (pos, data)))
+}
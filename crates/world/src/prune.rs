@@ -0,0 +1,222 @@
+use glam::IVec3;
+
+use crate::{Block, Map, MapError, NodeDefRegistry, nodedef::is_air};
+
+const BATCH_SIZE: usize = 1024;
+
+/// Box of nodes between `min` and `max` (node positions, inclusive).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct NodeArea {
+    pub min: IVec3,
+    pub max: IVec3,
+}
+
+impl NodeArea {
+    /// Creates an area spanning both corners, in any order.
+    pub fn new(a: IVec3, b: IVec3) -> Self {
+        Self {
+            min: a.min(b),
+            max: a.max(b),
+        }
+    }
+
+    /// Whether every node of the block at `pos` lies inside the area.
+    pub fn contains_block(&self, pos: IVec3) -> bool {
+        let (min, max) = block_nodes(pos);
+        min.cmpge(self.min).all() && max.cmple(self.max).all()
+    }
+
+    /// Whether any node of the block at `pos` lies inside the area.
+    pub fn intersects_block(&self, pos: IVec3) -> bool {
+        let (min, max) = block_nodes(pos);
+        max.cmpge(self.min).all() && min.cmple(self.max).all()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum PruneRule {
+    /// Blocks lying entirely farther than `radius` nodes from `center`.
+    OutsideRadius { center: IVec3, radius: u32 },
+
+    /// Blocks lying entirely outside an area.
+    OutsideArea(NodeArea),
+
+    /// Blocks that contain nothing but air, without metadata or objects.
+    /// With node definitions, all airlike nodes count as air.
+    AirOnly,
+
+    /// Blocks whose timestamp is older than the given game time in seconds.
+    /// Blocks that were generated but never saved by an active server have
+    /// no timestamp and always match.
+    NotModifiedSince(u32),
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct PruneOptions {
+    /// A block is deleted only if it matches all of these. Without rules
+    /// nothing is deleted.
+    pub rules: Vec<PruneRule>,
+
+    /// Blocks touching any of these areas are never deleted.
+    pub protected: Vec<NodeArea>,
+
+    /// Only report the blocks that would be deleted.
+    pub dry_run: bool,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct PruneReport {
+    pub blocks: usize,
+
+    /// Blocks that matched the rules and were deleted, or would have been
+    /// with `dry_run`.
+    pub pruned: usize,
+
+    /// Blocks that matched the rules but lie in a protected area.
+    pub protected: usize,
+
+    /// Blocks that couldn't be parsed to check the rules, which are kept.
+    pub skipped: usize,
+}
+
+/// Deletes all blocks of `map` that match `options.rules`, passing each
+/// pruned position to `on_prune`. This is `//deleteblocks` for a whole world,
+/// without a running server. `node_defs` tells which nodes are air; without
+/// them, only `air` and `ignore` are.
+///
+/// Blocks that can't be parsed are counted and kept rather than failing
+/// halfway through, after earlier batches were already deleted.
+pub fn prune_map(
+    map: &Map,
+    options: &PruneOptions,
+    node_defs: Option<&NodeDefRegistry>,
+    mut on_prune: impl FnMut(IVec3),
+) -> Result<PruneReport, MapError> {
+    let mut report = PruneReport::default();
+
+    if options.rules.is_empty() {
+        return Ok(report);
+    }
+
+    let mut positions = map.list_blocks()?;
+    positions.sort_by_key(|pos| (pos.z, pos.y, pos.x));
+
+    report.blocks = positions.len();
+
+    for batch in positions.chunks(BATCH_SIZE) {
+        let mut pruned = Vec::new();
+
+        for &pos in batch {
+            match matches_rules(map, pos, &options.rules, node_defs) {
+                Ok(true) => {}
+                Ok(false) => continue,
+                Err(MapError::Parse(_)) => {
+                    report.skipped += 1;
+                    continue;
+                }
+                Err(err) => return Err(err),
+            }
+
+            if options
+                .protected
+                .iter()
+                .any(|area| area.intersects_block(pos))
+            {
+                report.protected += 1;
+                continue;
+            }
+
+            pruned.push(pos);
+        }
+
+        report.pruned += pruned.len();
+
+        if !options.dry_run && !pruned.is_empty() {
+            map.begin_save()?;
+            for &pos in &pruned {
+                map.delete_block(pos)?;
+            }
+            map.end_save()?;
+        }
+
+        pruned.into_iter().for_each(&mut on_prune);
+    }
+
+    Ok(report)
+}
+
+fn matches_rules(
+    map: &Map,
+    pos: IVec3,
+    rules: &[PruneRule],
+    node_defs: Option<&NodeDefRegistry>,
+) -> Result<bool, MapError> {
+    // Rules that only need the position are checked before reading the block.
+    for rule in rules {
+        let matches = match rule {
+            PruneRule::OutsideRadius { center, radius } => {
+                let (min, max) = block_nodes(pos);
+                let nearest = center.clamp(min, max);
+                let distance = (nearest - center).as_dvec3().length();
+                distance > *radius as f64
+            }
+            PruneRule::OutsideArea(area) => !area.intersects_block(pos),
+            PruneRule::AirOnly | PruneRule::NotModifiedSince(_) => true,
+        };
+
+        if !matches {
+            return Ok(false);
+        }
+    }
+
+    let reads_data = rules
+        .iter()
+        .any(|rule| matches!(rule, PruneRule::AirOnly | PruneRule::NotModifiedSince(_)));
+
+    if !reads_data {
+        return Ok(true);
+    }
+
+    let data = map.get_block_data(pos)?;
+
+    for rule in rules {
+        if let PruneRule::NotModifiedSince(time) = rule {
+            let timestamp = Block::parse_timestamp(&data).map_err(|err| err.at(pos))?;
+            if timestamp != Block::TIMESTAMP_UNDEFINED && timestamp >= *time {
+                return Ok(false);
+            }
+        }
+    }
+
+    if rules.contains(&PruneRule::AirOnly) {
+        let block = Block::parse_data(&data).map_err(|err| err.at(pos))?;
+        if !is_air_only(&block, node_defs) {
+            return Ok(false);
+        }
+    }
+
+    Ok(true)
+}
+
+fn is_air_only(block: &Block, node_defs: Option<&NodeDefRegistry>) -> bool {
+    let air: Vec<u16> = block
+        .mappings()
+        .filter(|(_, name)| is_air(name, node_defs))
+        .map(|(id, _)| id)
+        .collect();
+
+    // Unused mappings are allowed, so check the nodes if there are others.
+    let nodes_are_air = air.len() == block.mappings().count()
+        || block.nodes().all(|(_, node)| air.contains(&node.id));
+
+    nodes_are_air
+        && block.metadata().next().is_none()
+        && block.static_objects().is_empty()
+        && block.node_timers().next().is_none()
+}
+
+/// First and last node of the block at `pos`.
+fn block_nodes(pos: IVec3) -> (IVec3, IVec3) {
+    let min = pos * 16;
+    (min, min + 15)
+}
@@ -0,0 +1,225 @@
+use crate::{Block, Map, MapError, ParseError};
+
+const BATCH_SIZE: usize = 1024;
+
+/// Most blocks a dictionary is trained on. More samples barely improve it.
+const MAX_DICTIONARY_SAMPLES: usize = 16384;
+
+#[derive(Clone, Debug)]
+pub struct RecompressOptions {
+    /// zstd level from 1 to 22. The engine writes blocks at
+    /// `map_compression_level_disk`, which defaults to zstd's default of 3.
+    pub level: i32,
+
+    /// Run [`Map::vacuum`] afterwards, so the saved space is given back to
+    /// the file system.
+    pub vacuum: bool,
+}
+
+impl Default for RecompressOptions {
+    fn default() -> Self {
+        Self {
+            level: 19,
+            vacuum: true,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct RecompressProgress {
+    pub done: usize,
+    pub total: usize,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct RecompressReport {
+    pub blocks: usize,
+
+    /// Blocks that were written compressed anew. In place, only blocks that
+    /// got smaller are.
+    pub recompressed: usize,
+
+    /// Blocks in an older format, which are left as they are.
+    pub skipped: usize,
+
+    /// Total size of all block data before and after.
+    pub data_before: u64,
+    pub data_after: u64,
+
+    /// Size of the map on disk before and after, if the backend can tell.
+    /// For archives, these are the sizes of the source and of the target.
+    pub disk_before: Option<u64>,
+    pub disk_after: Option<u64>,
+}
+
+/// Compresses every block of `map` anew at `options.level`. Blocks are only
+/// written back if they get smaller.
+pub fn recompress_map(
+    map: &Map,
+    options: &RecompressOptions,
+    on_progress: impl FnMut(RecompressProgress),
+) -> Result<RecompressReport, MapError> {
+    let mut compressor = zstd::bulk::Compressor::new(options.level)?;
+
+    let mut report = rewrite_blocks(map, None, on_progress, |data| {
+        decompress(data, None)?
+            .map(|payload| compress(&mut compressor, &payload))
+            .transpose()
+    })?;
+
+    if options.vacuum {
+        map.vacuum()?;
+    }
+
+    report.disk_after = map.size_on_disk()?;
+
+    Ok(report)
+}
+
+/// Copies every block of `map` into `archive`, compressed at `level` with a
+/// dictionary trained with [`train_dictionary`]. Blocks in older formats are
+/// copied as they are.
+///
+/// Blocks compressed with a dictionary can only be decompressed with the
+/// same dictionary. Neither the engine nor [`Block::parse_data`] can read
+/// them, so they are never written to a map that is played; use
+/// [`restore_archive`] to get a playable map back.
+pub fn archive_map(
+    map: &Map,
+    archive: &Map,
+    level: i32,
+    dictionary: &[u8],
+    on_progress: impl FnMut(RecompressProgress),
+) -> Result<RecompressReport, MapError> {
+    let mut compressor = zstd::bulk::Compressor::with_dictionary(level, dictionary)?;
+
+    rewrite_blocks(map, Some(archive), on_progress, |data| {
+        decompress(data, None)?
+            .map(|payload| compress(&mut compressor, &payload))
+            .transpose()
+    })
+}
+
+/// Copies every block of an archive written by [`archive_map`] into `map`,
+/// compressed at `level` without a dictionary so that the engine can read
+/// them again.
+pub fn restore_archive(
+    archive: &Map,
+    map: &Map,
+    level: i32,
+    dictionary: &[u8],
+    on_progress: impl FnMut(RecompressProgress),
+) -> Result<RecompressReport, MapError> {
+    let mut compressor = zstd::bulk::Compressor::new(level)?;
+
+    rewrite_blocks(archive, Some(map), on_progress, |data| {
+        decompress(data, Some(dictionary))?
+            .map(|payload| compress(&mut compressor, &payload))
+            .transpose()
+    })
+}
+
+/// Passes every block of `source` through `rewrite`, which returns `None`
+/// for blocks it leaves as they are, and writes the results to `target`. If
+/// there is no target, rewritten blocks are written back to `source` if they
+/// got smaller.
+fn rewrite_blocks(
+    source: &Map,
+    target: Option<&Map>,
+    mut on_progress: impl FnMut(RecompressProgress),
+    mut rewrite: impl FnMut(&[u8]) -> Result<Option<Vec<u8>>, MapError>,
+) -> Result<RecompressReport, MapError> {
+    let mut report = RecompressReport {
+        disk_before: source.size_on_disk()?,
+        ..Default::default()
+    };
+
+    let positions = source.list_blocks()?;
+    report.blocks = positions.len();
+
+    let output = target.unwrap_or(source);
+
+    for (batch_index, batch) in positions.chunks(BATCH_SIZE).enumerate() {
+        output.begin_save()?;
+
+        for &pos in batch {
+            let data = source.get_block_data(pos)?;
+            report.data_before += data.len() as u64;
+
+            let rewritten = match rewrite(&data).map_err(|err| err.at(pos))? {
+                Some(rewritten) if target.is_some() || rewritten.len() < data.len() => {
+                    report.recompressed += 1;
+                    Some(rewritten)
+                }
+                Some(_) => None,
+                None => {
+                    report.skipped += 1;
+                    None
+                }
+            };
+
+            match (rewritten, target) {
+                (Some(rewritten), _) => {
+                    output.set_block_data(pos, &rewritten)?;
+                    report.data_after += rewritten.len() as u64;
+                }
+                (None, Some(target)) => {
+                    target.set_block_data(pos, &data)?;
+                    report.data_after += data.len() as u64;
+                }
+                (None, None) => report.data_after += data.len() as u64,
+            }
+        }
+
+        output.end_save()?;
+
+        on_progress(RecompressProgress {
+            done: batch_index * BATCH_SIZE + batch.len(),
+            total: positions.len(),
+        });
+    }
+
+    report.disk_after = output.size_on_disk()?;
+
+    Ok(report)
+}
+
+/// Trains a zstd dictionary of at most `max_size` bytes on the blocks of
+/// `map`, for [`archive_map`].
+pub fn train_dictionary(map: &Map, max_size: usize) -> Result<Vec<u8>, MapError> {
+    let positions = map.list_blocks()?;
+
+    // Spread the samples over the whole map rather than taking the first
+    // blocks, which all lie in the same area.
+    let step = positions.len().div_ceil(MAX_DICTIONARY_SAMPLES).max(1);
+
+    let mut samples = Vec::new();
+
+    for &pos in positions.iter().step_by(step) {
+        let data = map.get_block_data(pos)?;
+        if let Some(payload) = decompress(&data, None).map_err(|err| err.at(pos))? {
+            samples.push(payload);
+        }
+    }
+
+    Ok(zstd::dict::from_samples(&samples, max_size)?)
+}
+
+/// Returns the uncompressed payload of a block, or `None` for older block
+/// versions, which compress each section separately.
+fn decompress(data: &[u8], dictionary: Option<&[u8]>) -> Result<Option<Vec<u8>>, ParseError> {
+    if data.first() != Some(&Block::SERIALIZATION_VERSION) {
+        return Ok(None);
+    }
+
+    match dictionary {
+        Some(dictionary) => Block::decompress_with_dictionary(data, dictionary).map(Some),
+        None => Block::decompress(data).map(Some),
+    }
+}
+
+fn compress(compressor: &mut zstd::bulk::Compressor, payload: &[u8]) -> Result<Vec<u8>, MapError> {
+    let mut data = vec![Block::SERIALIZATION_VERSION];
+    data.extend(compressor.compress(payload)?);
+    Ok(data)
+}
@@ -0,0 +1,509 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    io::{Cursor, Read, Write},
+};
+
+use glam::{IVec3, ivec3};
+
+use crate::{
+    Block, LuaError, Map, MapError, Node, NodeArea, NodeDefRegistry, ParseErrorKind,
+    nodedef::is_air,
+    serialize::{read_bytes16, read_u16, read_u32, write_bytes16, write_u16, write_u32},
+};
+
+const MTS_SIGNATURE: u32 = u32::from_be_bytes(*b"MTSM");
+const MTS_VERSION: u16 = 4;
+
+/// Version whose probabilities range up to 0xff instead of 0x7f.
+const MTS_VERSION_WIDE_PROBABILITIES: u16 = 3;
+
+const BATCH_SIZE: usize = 1024;
+
+/// Errors reading or writing schematic files of any format.
+#[derive(thiserror::Error, Debug)]
+pub enum SchematicError {
+    #[error("not a schematic of this format")]
+    InvalidSignature,
+
+    #[error("unsupported format version {0}")]
+    UnsupportedVersion(u32),
+
+    #[error(transparent)]
+    Parse(#[from] ParseErrorKind),
+
+    #[error(transparent)]
+    Lua(#[from] LuaError),
+
+    #[error("schematic too large for the format: {0}")]
+    TooLarge(String),
+
+    #[error("color table line {line}: {message}")]
+    ColorTable { line: usize, message: String },
+
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+/// Outcome of converting between nodes and the blocks of formats made for
+/// other games.
+#[derive(Clone, Debug, Default)]
+pub struct TranslationReport {
+    /// Nodes exported or placed.
+    pub nodes: usize,
+
+    /// Number of nodes, or blocks of the other format, that had no
+    /// translation, by name.
+    pub unmapped: BTreeMap<String, usize>,
+}
+
+/// A box of nodes that can be cut out of a map and placed elsewhere, as used
+/// by `minetest.place_schematic`.
+///
+/// `param1` of the nodes holds the probability of placing them rather than
+/// light: from [`Schematic::PROBABILITY_NEVER`] to
+/// [`Schematic::PROBABILITY_ALWAYS`], with [`Schematic::FORCE_PLACE`] set for
+/// nodes that replace whatever is in their way.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Schematic {
+    size: IVec3,
+
+    /// Indexed by node id.
+    names: Vec<String>,
+
+    /// Ordered by z, then y, then x.
+    nodes: Vec<Node>,
+
+    /// Probability of placing each y slice.
+    slice_probabilities: Vec<u8>,
+}
+
+impl Schematic {
+    pub const PROBABILITY_NEVER: u8 = 0x00;
+    pub const PROBABILITY_ALWAYS: u8 = 0x7f;
+    pub const FORCE_PLACE: u8 = 0x80;
+
+    /// A schematic of `size` nodes full of air that is never placed.
+    pub fn new(size: IVec3) -> Self {
+        let size = size.max(IVec3::ZERO);
+
+        Self {
+            size,
+            names: vec!["air".to_string()],
+            nodes: vec![
+                Node {
+                    id: 0,
+                    param1: Self::PROBABILITY_NEVER,
+                    param2: 0,
+                };
+                size.element_product() as usize
+            ],
+            slice_probabilities: vec![Self::PROBABILITY_ALWAYS; size.y as usize],
+        }
+    }
+
+    pub fn size(&self) -> IVec3 {
+        self.size
+    }
+
+    /// Node names, indexed by id.
+    pub fn names(&self) -> &[String] {
+        &self.names
+    }
+
+    pub fn get_name_by_id(&self, id: u16) -> Option<&str> {
+        self.names.get(id as usize).map(String::as_str)
+    }
+
+    /// Returns the id for `name`, adding it to the names if necessary.
+    pub fn get_or_insert_id(&mut self, name: &str) -> u16 {
+        if let Some(id) = self.names.iter().position(|n| n == name) {
+            return id as u16;
+        }
+
+        self.names.push(name.to_string());
+        self.names.len() as u16 - 1
+    }
+
+    /// The node at `pos` within the schematic, or `None` if `pos` lies
+    /// outside of it.
+    pub fn get_node(&self, pos: IVec3) -> Option<Node> {
+        Some(self.nodes[self.node_index(pos)?])
+    }
+
+    pub fn set_node(&mut self, pos: IVec3, node: Node) -> Result<(), MapError> {
+        let index = self.node_index(pos).ok_or(MapError::OutOfBounds(pos))?;
+        self.nodes[index] = node;
+        Ok(())
+    }
+
+    /// All nodes with their positions within the schematic.
+    pub fn nodes(&self) -> impl Iterator<Item = (IVec3, Node)> {
+        self.nodes
+            .iter()
+            .enumerate()
+            .map(|(index, node)| (self.node_pos(index), *node))
+    }
+
+    /// Probability of placing the slice of nodes at height `y`, or `None` if
+    /// `y` lies outside of the schematic.
+    pub fn slice_probability(&self, y: i32) -> Option<u8> {
+        self.slice_probabilities
+            .get(usize::try_from(y).ok()?)
+            .copied()
+    }
+
+    pub fn set_slice_probability(&mut self, y: i32, probability: u8) -> Option<()> {
+        *self.slice_probabilities.get_mut(usize::try_from(y).ok()?)? = probability;
+        Some(())
+    }
+
+    /// Reads a schematic in the MTS format written by
+    /// `minetest.create_schematic`. Probabilities of version 3 files are
+    /// scaled to the range of version 4.
+    pub fn read_mts(data: &[u8]) -> Result<Self, SchematicError> {
+        let mut cur = Cursor::new(data);
+
+        if read_u32(&mut cur).map_err(ParseErrorKind::from)? != MTS_SIGNATURE {
+            return Err(SchematicError::InvalidSignature);
+        }
+
+        let version = read_u16(&mut cur).map_err(ParseErrorKind::from)?;
+        if !(MTS_VERSION_WIDE_PROBABILITIES..=MTS_VERSION).contains(&version) {
+            return Err(SchematicError::UnsupportedVersion(version as u32));
+        }
+
+        let mut schematic = read_mts_body(&mut cur)?;
+
+        if version == MTS_VERSION_WIDE_PROBABILITIES {
+            for node in &mut schematic.nodes {
+                node.param1 >>= 1;
+            }
+            for probability in &mut schematic.slice_probabilities {
+                *probability >>= 1;
+            }
+        }
+
+        Ok(schematic)
+    }
+
+    /// Writes the schematic in version 4 of the MTS format.
+    pub fn write_mts(&self) -> Result<Vec<u8>, SchematicError> {
+        if self.size.cmpgt(IVec3::splat(u16::MAX as i32)).any() {
+            return Err(SchematicError::TooLarge(format!("size {}", self.size)));
+        }
+
+        if self.names.len() > u16::MAX as usize {
+            return Err(SchematicError::TooLarge(format!(
+                "{} node names",
+                self.names.len()
+            )));
+        }
+
+        let mut data = Vec::new();
+        write_u32(&mut data, MTS_SIGNATURE)?;
+        write_u16(&mut data, MTS_VERSION)?;
+
+        for size in self.size.to_array() {
+            write_u16(&mut data, size as u16)?;
+        }
+
+        data.extend_from_slice(&self.slice_probabilities);
+
+        write_u16(&mut data, self.names.len() as u16)?;
+        for name in &self.names {
+            write_bytes16(&mut data, name.as_bytes())?;
+        }
+
+        let mut encoder = flate2::write::ZlibEncoder::new(data, flate2::Compression::default());
+        for node in &self.nodes {
+            encoder.write_all(&node.id.to_be_bytes())?;
+        }
+        encoder.write_all(&self.nodes.iter().map(|n| n.param1).collect::<Vec<_>>())?;
+        encoder.write_all(&self.nodes.iter().map(|n| n.param2).collect::<Vec<_>>())?;
+
+        Ok(encoder.finish()?)
+    }
+
+    /// Copies the nodes of `area` out of `map`, to be placed every time.
+    /// Nodes in blocks that don't exist become air that is never placed.
+    pub fn from_map(map: &Map, area: NodeArea) -> Result<Self, MapError> {
+        let mut schematic = Self::new(area.max - area.min + 1);
+
+        let min_block = area.min.div_euclid(IVec3::splat(16));
+        let max_block = area.max.div_euclid(IVec3::splat(16));
+
+        for (block_pos, data) in map.get_blocks_in_box(min_block, max_block)? {
+            let block = Block::parse_nodes(&data).map_err(|err| err.at(block_pos))?;
+
+            let block_min = (block_pos * 16).max(area.min);
+            let block_max = (block_pos * 16 + 15).min(area.max);
+
+            // Schematic ids of the block's ids.
+            let mut ids = HashMap::new();
+
+            for z in block_min.z..=block_max.z {
+                for y in block_min.y..=block_max.y {
+                    for x in block_min.x..=block_max.x {
+                        let pos = ivec3(x, y, z);
+                        let Some(node) = block.get_node(pos - block_pos * 16) else {
+                            continue;
+                        };
+
+                        let id = *ids.entry(node.id).or_insert_with(|| {
+                            let name = block.get_name_by_id(node.id).unwrap_or("unknown");
+                            schematic.get_or_insert_id(name)
+                        });
+
+                        schematic.set_node(
+                            pos - area.min,
+                            Node {
+                                id,
+                                param1: Self::PROBABILITY_ALWAYS,
+                                param2: node.param2,
+                            },
+                        )?;
+                    }
+                }
+            }
+        }
+
+        Ok(schematic)
+    }
+
+    /// Places the schematic into `map` with its minimum corner at `pos`, the
+    /// way `minetest.place_schematic` does without rotation. Returns the
+    /// number of nodes placed.
+    ///
+    /// Probabilities aren't rolled: every node and slice that may be placed
+    /// at all is placed. Unless `force_placement` is set, only air is
+    /// replaced, apart from nodes flagged with [`Schematic::FORCE_PLACE`].
+    /// `node_defs` tells which nodes are air; without them, only `air` and
+    /// `ignore` are, as in the engine.
+    ///
+    /// Blocks the schematic touches are marked as not fully lit. Blocks that
+    /// don't exist yet are created full of air and flagged as generated, so
+    /// that the engine doesn't generate terrain over the schematic.
+    pub fn place(
+        &self,
+        map: &Map,
+        pos: IVec3,
+        force_placement: bool,
+        node_defs: Option<&NodeDefRegistry>,
+    ) -> Result<usize, MapError> {
+        if self.nodes.is_empty() {
+            return Ok(0);
+        }
+
+        let min_block = pos.div_euclid(IVec3::splat(16));
+        let max_block = (pos + self.size - 1).div_euclid(IVec3::splat(16));
+
+        let mut positions = Vec::new();
+        for z in min_block.z..=max_block.z {
+            for y in min_block.y..=max_block.y {
+                for x in min_block.x..=max_block.x {
+                    positions.push(ivec3(x, y, z));
+                }
+            }
+        }
+
+        modify_blocks(map, &positions, |block_pos, block| {
+            self.place_in_block(block, block_pos, pos, force_placement, node_defs)
+        })
+    }
+
+    fn place_in_block(
+        &self,
+        block: &mut Block,
+        block_pos: IVec3,
+        pos: IVec3,
+        force_placement: bool,
+        node_defs: Option<&NodeDefRegistry>,
+    ) -> usize {
+        let block_min = (block_pos * 16).max(pos);
+        let block_max = (block_pos * 16 + 15).min(pos + self.size - 1);
+
+        // Block ids of the schematic's ids, and of the nodes that can be
+        // replaced without force placement.
+        let mut ids = HashMap::new();
+        let replaceable: Vec<u16> = block
+            .mappings()
+            .filter(|(_, name)| is_air(name, node_defs))
+            .map(|(id, _)| id)
+            .collect();
+
+        let mut placed = 0;
+
+        for z in block_min.z..=block_max.z {
+            for y in block_min.y..=block_max.y {
+                if self.slice_probabilities[(y - pos.y) as usize] == Self::PROBABILITY_NEVER {
+                    continue;
+                }
+
+                for x in block_min.x..=block_max.x {
+                    let node_pos = ivec3(x, y, z);
+                    let index = self.node_index(node_pos - pos).unwrap();
+                    let node = self.nodes[index];
+
+                    if node.param1 & Self::PROBABILITY_ALWAYS == Self::PROBABILITY_NEVER {
+                        continue;
+                    }
+
+                    let block_index = Block::node_index(node_pos - block_pos * 16).unwrap();
+
+                    if !force_placement && node.param1 & Self::FORCE_PLACE == 0 {
+                        let current = block.node_at(block_index).id;
+                        if !replaceable.contains(&current) {
+                            continue;
+                        }
+                    }
+
+                    let id = *ids.entry(node.id).or_insert_with(|| {
+                        let name = self.get_name_by_id(node.id).unwrap_or("unknown");
+                        block.get_or_insert_id(name)
+                    });
+
+                    block.set_node_at(
+                        block_index,
+                        Node {
+                            id,
+                            param1: 0,
+                            param2: node.param2,
+                        },
+                    );
+                    placed += 1;
+                }
+            }
+        }
+
+        placed
+    }
+
+    fn node_index(&self, pos: IVec3) -> Option<usize> {
+        if pos.cmplt(IVec3::ZERO).any() || pos.cmpge(self.size).any() {
+            return None;
+        }
+
+        let size = self.size.as_usizevec3();
+        let pos = pos.as_usizevec3();
+        Some((pos.z * size.y + pos.y) * size.x + pos.x)
+    }
+
+    fn node_pos(&self, index: usize) -> IVec3 {
+        let index = index as i32;
+        ivec3(
+            index % self.size.x,
+            (index / self.size.x) % self.size.y,
+            index / (self.size.x * self.size.y),
+        )
+    }
+}
+
+/// Runs `modify` on the blocks at `positions` and writes back the blocks it
+/// changed nodes in, marking them as touched and not fully lit. `modify` returns the
+/// number of nodes it changed, and the total is returned.
+///
+/// Blocks that don't exist yet are created full of air and flagged as
+/// generated, so that the engine doesn't generate terrain over the changes.
+pub(crate) fn modify_blocks(
+    map: &Map,
+    positions: &[IVec3],
+    mut modify: impl FnMut(IVec3, &mut Block) -> usize,
+) -> Result<usize, MapError> {
+    let mut changed = 0;
+
+    for batch in positions.chunks(BATCH_SIZE) {
+        let mut blocks: HashMap<IVec3, Block> = HashMap::new();
+        for (block_pos, data) in map.get_blocks(batch)? {
+            let block = Block::parse_data_lazy(&data).map_err(|err| err.at(block_pos))?;
+            blocks.insert(block_pos, block);
+        }
+
+        map.begin_save()?;
+
+        for &block_pos in batch {
+            let block = blocks.entry(block_pos).or_insert_with(|| {
+                let mut block = Block::new();
+                block.set_flags(Block::FLAG_GENERATED);
+                block
+            });
+
+            let changed_in_block = modify(block_pos, block);
+            if changed_in_block == 0 {
+                continue;
+            }
+
+            block.touch();
+            block.set_lighting_complete(0);
+            map.set_block(block_pos, block)?;
+            changed += changed_in_block;
+        }
+
+        map.end_save()?;
+    }
+
+    Ok(changed)
+}
+
+/// Reads everything after the version.
+fn read_mts_body(cur: &mut Cursor<&[u8]>) -> Result<Schematic, ParseErrorKind> {
+    let size = ivec3(
+        read_u16(cur)? as i32,
+        read_u16(cur)? as i32,
+        read_u16(cur)? as i32,
+    );
+
+    let count = size.as_usizevec3().element_product();
+    if count * 4 > Block::MAX_PAYLOAD_SIZE {
+        return Err(ParseErrorKind::TooLarge);
+    }
+
+    let mut slice_probabilities = vec![0; size.y as usize];
+    cur.read_exact(&mut slice_probabilities)?;
+
+    let names_count = read_u16(cur)?;
+    let mut names = Vec::with_capacity(names_count as usize);
+    for _ in 0..names_count {
+        let name = String::from_utf8(read_bytes16(cur)?)?;
+
+        // Version 1 used ignore for nodes that aren't placed, which the
+        // engine still reads as air.
+        names.push(if name == "ignore" {
+            "air".to_string()
+        } else {
+            name
+        });
+    }
+
+    let mut data = Vec::with_capacity(count * 4);
+    flate2::read::ZlibDecoder::new(cur)
+        .take(count as u64 * 4)
+        .read_to_end(&mut data)
+        .map_err(ParseErrorKind::Decompress)?;
+
+    if data.len() < count * 4 {
+        return Err(ParseErrorKind::UnexpectedEnd);
+    }
+
+    let mut nodes = Vec::with_capacity(count);
+    for i in 0..count {
+        let id = u16::from_be_bytes([data[2 * i], data[2 * i + 1]]);
+        if id as usize >= names.len() {
+            return Err(ParseErrorKind::Invalid(format!(
+                "node id {id} missing from the name table"
+            )));
+        }
+
+        nodes.push(Node {
+            id,
+            param1: data[count * 2 + i],
+            param2: data[count * 3 + i],
+        });
+    }
+
+    Ok(Schematic {
+        size,
+        names,
+        nodes,
+        slice_probabilities,
+    })
+}
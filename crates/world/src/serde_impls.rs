@@ -0,0 +1,173 @@
+//! Serde support for blocks and world.mt, enabled by the `serde` feature.
+//!
+//! Blocks are written with their header, mappings, all 4096 nodes in the
+//! order `z * 256 + y * 16 + x`, and node metadata, static objects and node
+//! timers. Metadata and timers list their positions within the block:
+//!
+//! ```json
+//! {
+//!   "flags": 8,
+//!   "lighting_complete": 65535,
+//!   "timestamp": 1200,
+//!   "mappings": { "0": "air", "1": "default:chest" },
+//!   "nodes": [{ "id": 0, "param1": 15, "param2": 0 }, ...],
+//!   "metadata": [{ "pos": [3, 0, 7], "fields": { ... }, "inventory": { ... } }],
+//!   "static_objects": [],
+//!   "node_timers": [{ "pos": [3, 0, 7], "timeout_ms": 5000, "elapsed_ms": 0 }]
+//! }
+//! ```
+
+use std::collections::{BTreeMap, HashMap};
+
+use glam::IVec3;
+use serde::{Deserialize, Deserializer, Serialize, Serializer, de::Error as _};
+
+use crate::{Block, Node, NodeMetadata, NodeTimer, StaticObject, WorldMeta};
+
+#[derive(Serialize, Deserialize)]
+struct BlockDump {
+    flags: u8,
+    lighting_complete: u16,
+    timestamp: u32,
+    mappings: BTreeMap<u16, String>,
+    nodes: Vec<Node>,
+    metadata: Vec<PositionedMetadata>,
+    static_objects: Vec<StaticObject>,
+    node_timers: Vec<PositionedTimer>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct PositionedMetadata {
+    pos: IVec3,
+
+    #[serde(flatten)]
+    metadata: NodeMetadata,
+}
+
+#[derive(Serialize, Deserialize)]
+struct PositionedTimer {
+    pos: IVec3,
+
+    #[serde(flatten)]
+    timer: NodeTimer,
+}
+
+impl Serialize for Block {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        // Sorted by position, so that dumps of equal blocks are equal.
+        let mut metadata: Vec<_> = self
+            .metadata()
+            .map(|(pos, metadata)| PositionedMetadata {
+                pos,
+                metadata: metadata.clone(),
+            })
+            .collect();
+        metadata.sort_by_key(|entry| Block::node_index(entry.pos));
+
+        let mut node_timers: Vec<_> = self
+            .node_timers()
+            .map(|(pos, timer)| PositionedTimer { pos, timer })
+            .collect();
+        node_timers.sort_by_key(|entry| Block::node_index(entry.pos));
+
+        BlockDump {
+            flags: self.flags(),
+            lighting_complete: self.lighting_complete(),
+            timestamp: self.timestamp(),
+            mappings: self
+                .mappings()
+                .map(|(id, name)| (id, name.to_string()))
+                .collect(),
+            nodes: self.nodes().map(|(_, node)| node).collect(),
+            metadata,
+            static_objects: self.static_objects().to_vec(),
+            node_timers,
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Block {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let dump = BlockDump::deserialize(deserializer)?;
+
+        if dump.nodes.len() != Block::VOLUME {
+            return Err(D::Error::invalid_length(dump.nodes.len(), &"4096 nodes"));
+        }
+
+        let mut block = Block::new();
+        block.set_flags(dump.flags);
+        block.set_lighting_complete(dump.lighting_complete);
+        block.set_timestamp(dump.timestamp);
+        block.set_mappings(dump.mappings.into_iter().collect());
+
+        for (index, node) in dump.nodes.into_iter().enumerate() {
+            block.set_node_at(index, node);
+        }
+
+        for entry in dump.metadata {
+            block
+                .set_metadata(entry.pos, entry.metadata)
+                .map_err(D::Error::custom)?;
+        }
+
+        *block.static_objects_mut() = dump.static_objects;
+
+        for entry in dump.node_timers {
+            block
+                .set_node_timer(entry.pos, entry.timer)
+                .map_err(D::Error::custom)?;
+        }
+
+        Ok(block)
+    }
+}
+
+/// Written as a map of its settings, sorted by key.
+impl Serialize for WorldMeta {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let values: BTreeMap<&str, &str> = self.iter().collect();
+        values.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for WorldMeta {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let values = HashMap::<String, String>::deserialize(deserializer)?;
+
+        let mut meta = WorldMeta::new();
+        for (key, value) in values {
+            meta.set_str(&key, &value);
+        }
+
+        Ok(meta)
+    }
+}
+
+/// Byte strings such as metadata values, which are mostly text. They are
+/// written as strings if they are valid UTF-8 and as arrays of bytes
+/// otherwise, and both are read.
+pub(crate) mod bytes {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Bytes {
+        Text(String),
+        Binary(Vec<u8>),
+    }
+
+    pub fn serialize<S: Serializer>(value: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        match std::str::from_utf8(value) {
+            Ok(text) => serializer.serialize_str(text),
+            Err(_) => serializer.collect_seq(value),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        Ok(match Bytes::deserialize(deserializer)? {
+            Bytes::Text(text) => text.into_bytes(),
+            Bytes::Binary(bytes) => bytes,
+        })
+    }
+}
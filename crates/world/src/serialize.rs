@@ -0,0 +1,95 @@
+use std::io::{ErrorKind, Read, Write};
+
+use crate::ParseErrorKind;
+
+pub(crate) fn read_u8(r: &mut impl Read) -> Result<u8, std::io::Error> {
+    let mut buf = [0; 1];
+    r.read_exact(&mut buf)?;
+    Ok(buf[0])
+}
+
+pub(crate) fn read_u16(r: &mut impl Read) -> Result<u16, std::io::Error> {
+    let mut buf = [0; 2];
+    r.read_exact(&mut buf)?;
+    Ok(u16::from_be_bytes(buf))
+}
+
+pub(crate) fn read_u32(r: &mut impl Read) -> Result<u32, std::io::Error> {
+    let mut buf = [0; 4];
+    r.read_exact(&mut buf)?;
+    Ok(u32::from_be_bytes(buf))
+}
+
+pub(crate) fn read_i32(r: &mut impl Read) -> Result<i32, std::io::Error> {
+    let mut buf = [0; 4];
+    r.read_exact(&mut buf)?;
+    Ok(i32::from_be_bytes(buf))
+}
+
+pub(crate) fn read_string(r: &mut impl Read) -> Result<String, ParseErrorKind> {
+    let data = read_bytes16(r)?;
+    let string = String::from_utf8(data)?;
+    Ok(string)
+}
+
+pub(crate) fn read_bytes16(r: &mut impl Read) -> Result<Vec<u8>, std::io::Error> {
+    let len = read_u16(r)?;
+    read_bytes(r, len as usize)
+}
+
+pub(crate) fn read_bytes32(r: &mut impl Read) -> Result<Vec<u8>, std::io::Error> {
+    let len = read_u32(r)?;
+    read_bytes(r, len as usize)
+}
+
+/// Reads `len` bytes without allocating them all up front, since `len` comes
+/// from the data and may be far larger than what's left of it.
+fn read_bytes(r: &mut impl Read, len: usize) -> Result<Vec<u8>, std::io::Error> {
+    let mut data = Vec::new();
+    r.take(len as u64).read_to_end(&mut data)?;
+
+    if data.len() != len {
+        return Err(ErrorKind::UnexpectedEof.into());
+    }
+
+    Ok(data)
+}
+
+pub(crate) fn write_u8(w: &mut impl Write, value: u8) -> Result<(), std::io::Error> {
+    w.write_all(&[value])
+}
+
+pub(crate) fn write_u16(w: &mut impl Write, value: u16) -> Result<(), std::io::Error> {
+    w.write_all(&value.to_be_bytes())
+}
+
+pub(crate) fn write_u32(w: &mut impl Write, value: u32) -> Result<(), std::io::Error> {
+    w.write_all(&value.to_be_bytes())
+}
+
+pub(crate) fn write_i32(w: &mut impl Write, value: i32) -> Result<(), std::io::Error> {
+    w.write_all(&value.to_be_bytes())
+}
+
+pub(crate) fn write_string(w: &mut impl Write, value: &str) -> Result<(), std::io::Error> {
+    write_bytes16(w, value.as_bytes())
+}
+
+pub(crate) fn write_bytes16(w: &mut impl Write, value: &[u8]) -> Result<(), std::io::Error> {
+    let len = u16::try_from(value.len()).map_err(|_| too_long(value.len()))?;
+    write_u16(w, len)?;
+    w.write_all(value)
+}
+
+pub(crate) fn write_bytes32(w: &mut impl Write, value: &[u8]) -> Result<(), std::io::Error> {
+    let len = u32::try_from(value.len()).map_err(|_| too_long(value.len()))?;
+    write_u32(w, len)?;
+    w.write_all(value)
+}
+
+fn too_long(len: usize) -> std::io::Error {
+    std::io::Error::new(
+        ErrorKind::InvalidInput,
+        format!("{len} bytes are too long to be serialized"),
+    )
+}
@@ -0,0 +1,413 @@
+use std::{
+    collections::HashMap,
+    io::{Read, Write},
+};
+
+use flate2::{Compression, read::GzDecoder, write::GzEncoder};
+use glam::{IVec3, ivec3};
+
+use crate::{
+    Block, BlockMapping, Map, MapError, NodeArea, NodeDefRegistry, ParseErrorKind, SchematicError,
+    TranslationReport,
+    anvil::{MappedNode, parse_state, unmapped_key, write_mapped_nodes},
+    nbt::Nbt,
+    nodedef::is_air,
+};
+
+/// Minecraft version the block states of exported schematics are named
+/// after, 1.20.4.
+const SPONGE_DATA_VERSION: i32 = 3700;
+
+const AIR: &str = "minecraft:air";
+
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// A schematic in the Sponge format used by WorldEdit for Minecraft and
+/// other Minecraft tools, versions 2 and 3. Only blocks are kept; biomes,
+/// entities and block entities are left out.
+///
+/// Positions are in Minecraft's axes, whose z points south. Reading from and
+/// placing into a map mirrors z within the box, so that the compass
+/// directions stay the same.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SpongeSchematic {
+    size: IVec3,
+
+    /// Block states, such as `minecraft:oak_log[axis=x]`.
+    palette: Vec<String>,
+
+    /// Indices into the palette in y, z, x order.
+    blocks: Vec<u32>,
+}
+
+impl SpongeSchematic {
+    pub const VERSIONS: [u32; 2] = [2, 3];
+
+    /// A schematic of air.
+    pub fn new(size: IVec3) -> Self {
+        let size = size.max(IVec3::ZERO);
+        Self {
+            size,
+            palette: vec![AIR.to_string()],
+            blocks: vec![0; size.x as usize * size.y as usize * size.z as usize],
+        }
+    }
+
+    pub fn size(&self) -> IVec3 {
+        self.size
+    }
+
+    pub fn palette(&self) -> &[String] {
+        &self.palette
+    }
+
+    /// The block state at `pos`, or `None` if `pos` lies outside of the
+    /// schematic.
+    pub fn get_block(&self, pos: IVec3) -> Option<&str> {
+        let index = self.index(pos)?;
+        Some(&self.palette[self.blocks[index] as usize])
+    }
+
+    pub fn set_block(&mut self, pos: IVec3, state: &str) -> Result<(), MapError> {
+        let index = self.index(pos).ok_or(MapError::OutOfBounds(pos))?;
+
+        let id = match self.palette.iter().position(|s| s == state) {
+            Some(id) => id,
+            None => {
+                self.palette.push(state.to_string());
+                self.palette.len() - 1
+            }
+        };
+        self.blocks[index] = id as u32;
+
+        Ok(())
+    }
+
+    fn index(&self, pos: IVec3) -> Option<usize> {
+        if pos.cmplt(IVec3::ZERO).any() || pos.cmpge(self.size).any() {
+            return None;
+        }
+        Some(((pos.y * self.size.z + pos.z) * self.size.x + pos.x) as usize)
+    }
+
+    /// Reads a gzipped schematic of version 2 or 3.
+    pub fn read(data: &[u8]) -> Result<Self, SchematicError> {
+        if !data.starts_with(&GZIP_MAGIC) {
+            return Err(SchematicError::InvalidSignature);
+        }
+
+        let mut decompressed = Vec::new();
+        GzDecoder::new(data)
+            .take(Block::MAX_PAYLOAD_SIZE as u64 + 1)
+            .read_to_end(&mut decompressed)
+            .map_err(ParseErrorKind::Decompress)?;
+        if decompressed.len() > Block::MAX_PAYLOAD_SIZE {
+            return Err(SchematicError::Parse(ParseErrorKind::TooLarge));
+        }
+
+        let root = Nbt::read(&decompressed)?;
+
+        // Version 3 wraps everything in a `Schematic` compound, version 2
+        // has it at the root.
+        let schematic = root.get("Schematic").unwrap_or(&root);
+        let version = schematic
+            .get("Version")
+            .and_then(Nbt::as_i64)
+            .ok_or(SchematicError::InvalidSignature)?;
+        let version = u32::try_from(version).unwrap_or(u32::MAX);
+
+        let (palette, data) = match version {
+            2 => (schematic.get("Palette"), schematic.get("BlockData")),
+            3 => {
+                let blocks = schematic.get("Blocks");
+                (
+                    blocks.and_then(|blocks| blocks.get("Palette")),
+                    blocks.and_then(|blocks| blocks.get("Data")),
+                )
+            }
+            _ => return Err(SchematicError::UnsupportedVersion(version)),
+        };
+
+        let dimension = |key: &str| {
+            // Stored as shorts, but meant to be unsigned.
+            schematic
+                .get(key)
+                .and_then(Nbt::as_i64)
+                .map(|n| n as u16 as i32)
+                .ok_or_else(|| invalid(&format!("missing `{key}`")))
+        };
+        let size = ivec3(
+            dimension("Width")?,
+            dimension("Height")?,
+            dimension("Length")?,
+        );
+        let volume = size.x as usize * size.y as usize * size.z as usize;
+        if volume > Block::MAX_PAYLOAD_SIZE {
+            return Err(SchematicError::TooLarge(format!("size {size}")));
+        }
+
+        // Schematics without blocks are allowed in version 3.
+        let (Some(palette), Some(data)) = (palette, data) else {
+            return Ok(Self::new(size));
+        };
+
+        let entries = palette
+            .as_compound()
+            .ok_or_else(|| invalid("invalid palette"))?;
+        let mut states = vec![None; entries.len()];
+        for (state, id) in entries {
+            let slot = id
+                .as_i64()
+                .and_then(|id| usize::try_from(id).ok())
+                .and_then(|id| states.get_mut(id))
+                .ok_or_else(|| invalid(&format!("invalid palette id of `{state}`")))?;
+            *slot = Some(state.clone());
+        }
+        let palette = states
+            .into_iter()
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(|| invalid("palette ids with gaps"))?;
+
+        let data = data
+            .as_bytes()
+            .ok_or_else(|| invalid("invalid block data"))?;
+        let blocks = read_varints(data, volume)?;
+        if let Some(&id) = blocks.iter().find(|&&id| id as usize >= palette.len()) {
+            return Err(invalid(&format!("block {id} not in the palette")));
+        }
+
+        Ok(Self {
+            size,
+            palette,
+            blocks,
+        })
+    }
+
+    /// Writes the schematic gzipped in version 2 or 3 of the format.
+    pub fn write(&self, version: u32) -> Result<Vec<u8>, SchematicError> {
+        if !Self::VERSIONS.contains(&version) {
+            return Err(SchematicError::UnsupportedVersion(version));
+        }
+        if self.size.cmpgt(IVec3::splat(u16::MAX as i32)).any() {
+            return Err(SchematicError::TooLarge(format!(
+                "size {} exceeds {}",
+                self.size,
+                u16::MAX
+            )));
+        }
+
+        let palette = Nbt::Compound(
+            self.palette
+                .iter()
+                .enumerate()
+                .map(|(id, state)| (state.clone(), Nbt::Int(id as i32)))
+                .collect(),
+        );
+
+        let mut data = Vec::with_capacity(self.blocks.len());
+        for &id in &self.blocks {
+            write_varint(&mut data, id);
+        }
+
+        let mut fields = vec![
+            ("Version".to_string(), Nbt::Int(version as i32)),
+            ("DataVersion".to_string(), Nbt::Int(SPONGE_DATA_VERSION)),
+            ("Width".to_string(), Nbt::Short(self.size.x as u16 as i16)),
+            ("Height".to_string(), Nbt::Short(self.size.y as u16 as i16)),
+            ("Length".to_string(), Nbt::Short(self.size.z as u16 as i16)),
+            ("Offset".to_string(), Nbt::IntArray(vec![0; 3])),
+        ];
+
+        // Version 2 names the root compound, version 3 has a nameless one
+        // around it.
+        let (name, root) = if version == 2 {
+            fields.extend([
+                (
+                    "PaletteMax".to_string(),
+                    Nbt::Int(self.palette.len() as i32),
+                ),
+                ("Palette".to_string(), palette),
+                ("BlockData".to_string(), Nbt::ByteArray(data)),
+            ]);
+            ("Schematic", Nbt::Compound(fields))
+        } else {
+            fields.push((
+                "Blocks".to_string(),
+                Nbt::Compound(vec![
+                    ("Palette".to_string(), palette),
+                    ("Data".to_string(), Nbt::ByteArray(data)),
+                ]),
+            ));
+            (
+                "",
+                Nbt::Compound(vec![("Schematic".to_string(), Nbt::Compound(fields))]),
+            )
+        };
+
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&root.write(name))?;
+        Ok(encoder.finish()?)
+    }
+
+    /// Copies `area` out of `map`, turning nodes into block states with
+    /// `mapping`. Blocks that don't exist become air, and so do nodes the
+    /// mapping has no block state for; those are reported unless they are
+    /// air according to `node_defs`, or without them, `air` or `ignore`.
+    pub fn from_map(
+        map: &Map,
+        area: NodeArea,
+        mapping: &BlockMapping,
+        node_defs: Option<&NodeDefRegistry>,
+    ) -> Result<(Self, TranslationReport), MapError> {
+        let min_block = area.min.div_euclid(IVec3::splat(16));
+        let max_block = area.max.div_euclid(IVec3::splat(16));
+
+        let mut blocks = HashMap::new();
+        for (block_pos, data) in map.get_blocks_in_box(min_block, max_block)? {
+            let block = Block::parse_data_lazy(&data).map_err(|err| err.at(block_pos))?;
+            blocks.insert(block_pos, block);
+        }
+
+        let mut schematic = Self::new(area.max - area.min + 1);
+        let mut report = TranslationReport::default();
+
+        // Palette ids by node, and whether the node is unmapped.
+        let mut ids: HashMap<(&str, u8), (u32, bool)> = HashMap::new();
+
+        for (index, id) in schematic.blocks.iter_mut().enumerate() {
+            let pos = area.min + schematic_to_map(schematic.size, index);
+            let block_pos = pos.div_euclid(IVec3::splat(16));
+
+            let (name, param2) = match blocks.get(&block_pos) {
+                Some(block) => {
+                    let node = block
+                        .get_node(pos - block_pos * 16)
+                        .expect("node lies in its block");
+                    let name = block.get_name_by_id(node.id).unwrap_or("unknown");
+                    (name, node.param2)
+                }
+                None => ("air", 0),
+            };
+
+            let (node_id, unmapped) = *ids.entry((name, param2)).or_insert_with(|| {
+                let (state, unmapped) = match mapping.reverse_lookup(name, param2) {
+                    Some(state) => (state, false),
+                    None => (AIR, !is_air(name, node_defs)),
+                };
+
+                let id = match schematic.palette.iter().position(|s| s == state) {
+                    Some(id) => id,
+                    None => {
+                        schematic.palette.push(state.to_string());
+                        schematic.palette.len() - 1
+                    }
+                };
+                (id as u32, unmapped)
+            });
+
+            *id = node_id;
+            if unmapped {
+                *report.unmapped.entry(name.to_string()).or_default() += 1;
+            } else {
+                report.nodes += 1;
+            }
+        }
+
+        Ok((schematic, report))
+    }
+
+    /// Places the schematic into `map` with its minimum corner at `pos`,
+    /// turning block states into nodes with `mapping`. Every node replaces
+    /// whatever was there, including air. Block states the mapping has no
+    /// node for leave the map as it is.
+    pub fn place(
+        &self,
+        map: &Map,
+        pos: IVec3,
+        mapping: &BlockMapping,
+    ) -> Result<TranslationReport, MapError> {
+        let mut report = TranslationReport::default();
+
+        let nodes: Vec<Result<MappedNode, String>> = self
+            .palette
+            .iter()
+            .map(|state| match parse_state(state) {
+                Some((name, properties)) => mapping
+                    .lookup(&name, &properties)
+                    .ok_or_else(|| unmapped_key(mapping, &name, &properties)),
+                None => Err(state.clone()),
+            })
+            .collect();
+
+        let mut by_block: HashMap<IVec3, Vec<(usize, MappedNode)>> = HashMap::new();
+        for (index, &id) in self.blocks.iter().enumerate() {
+            let node = match &nodes[id as usize] {
+                Ok(node) => *node,
+                Err(key) => {
+                    *report.unmapped.entry(key.clone()).or_default() += 1;
+                    continue;
+                }
+            };
+
+            let node_pos = pos + schematic_to_map(self.size, index);
+            let block_pos = node_pos.div_euclid(IVec3::splat(16));
+            let index =
+                Block::node_index(node_pos - block_pos * 16).expect("node lies in its block");
+            by_block.entry(block_pos).or_default().push((index, node));
+        }
+
+        report.nodes = write_mapped_nodes(map, &by_block)?;
+        Ok(report)
+    }
+}
+
+/// Position relative to the minimum corner in the map of the block at
+/// `index`, with z mirrored.
+fn schematic_to_map(size: IVec3, index: usize) -> IVec3 {
+    let index = index as i32;
+    let x = index % size.x;
+    let z = index / size.x % size.z;
+    let y = index / (size.x * size.z);
+    ivec3(x, y, size.z - 1 - z)
+}
+
+/// Reads `count` unsigned LEB128 varints, as block data is stored.
+fn read_varints(data: &[u8], count: usize) -> Result<Vec<u32>, SchematicError> {
+    let mut values = Vec::with_capacity(count.min(data.len()));
+    let mut bytes = data.iter();
+
+    for _ in 0..count {
+        let mut value = 0u32;
+        let mut shift = 0;
+        loop {
+            let &byte = bytes.next().ok_or(ParseErrorKind::UnexpectedEnd)?;
+            if shift > 28 {
+                return Err(invalid("varint too long"));
+            }
+            value |= ((byte & 0x7f) as u32) << shift;
+            shift += 7;
+            if byte & 0x80 == 0 {
+                break;
+            }
+        }
+        values.push(value);
+    }
+
+    if bytes.next().is_some() {
+        return Err(invalid("more block data than blocks"));
+    }
+
+    Ok(values)
+}
+
+fn write_varint(out: &mut Vec<u8>, mut value: u32) {
+    while value >= 0x80 {
+        out.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn invalid(message: &str) -> SchematicError {
+    SchematicError::Parse(ParseErrorKind::Invalid(message.to_string()))
+}
@@ -1,8 +1,8 @@
 use std::path::Path;
 
-use rusqlite::Connection;
+use rusqlite::{Connection, OptionalExtension};
 
-use crate::{MapError, MapBackend};
+use crate::{MapBackend, MapError};
 
 pub struct SqliteBackend {
     conn: Connection,
@@ -14,6 +14,22 @@ impl SqliteBackend {
 
         Ok(Self { conn })
     }
+
+    pub fn create(path: impl AsRef<Path>) -> Result<Self, MapError> {
+        const SQL: &str = "
+            CREATE TABLE IF NOT EXISTS blocks (
+                x INTEGER,
+                y INTEGER,
+                z INTEGER,
+                data BLOB NOT NULL,
+                PRIMARY KEY (x, z, y)
+            )";
+
+        let conn = Connection::open(path)?;
+        conn.execute(SQL, [])?;
+
+        Ok(Self { conn })
+    }
 }
 
 impl MapBackend for SqliteBackend {
@@ -28,8 +44,135 @@ impl MapBackend for SqliteBackend {
 
         let data = self
             .conn
-            .query_one(SQL, [&pos.x, &pos.y, &pos.z], |row| row.get(0))?;
+            .query_one(SQL, [&pos.x, &pos.y, &pos.z], |row| row.get(0))
+            .optional()?;
+
+        data.ok_or(MapError::BlockNotFound)
+    }
+
+    fn set_block_data(&mut self, pos: glam::IVec3, data: &[u8]) -> Result<(), MapError> {
+        const SQL: &str = "
+            INSERT OR REPLACE INTO blocks (x, y, z, data)
+            VALUES (?, ?, ?, ?)";
+
+        self.conn
+            .execute(SQL, rusqlite::params![pos.x, pos.y, pos.z, data])?;
+
+        Ok(())
+    }
+    fn delete_block(&mut self, pos: glam::IVec3) -> Result<(), MapError> {
+        const SQL: &str = "
+            DELETE FROM blocks
+            WHERE x = ?
+              AND y = ?
+              AND z = ?";
+
+        self.conn.execute(SQL, [&pos.x, &pos.y, &pos.z])?;
+
+        Ok(())
+    }
+
+    fn list_blocks(&mut self) -> Result<Vec<glam::IVec3>, MapError> {
+        const SQL: &str = "
+            SELECT x, y, z
+            FROM blocks";
+
+        let mut stmt = self.conn.prepare(SQL)?;
+        let positions = stmt
+            .query_map([], |row| {
+                Ok(glam::ivec3(row.get(0)?, row.get(1)?, row.get(2)?))
+            })?
+            .collect::<Result<_, _>>()?;
+
+        Ok(positions)
+    }
+
+    /// Looks the blocks up one at a time with the same prepared statement.
+    /// SQLite runs in-process, so each lookup only costs a primary key
+    /// search, and parsing the query, which is most of the cost of reading
+    /// a single block, happens once.
+    fn get_blocks(
+        &mut self,
+        positions: &[glam::IVec3],
+    ) -> Result<Vec<(glam::IVec3, Vec<u8>)>, MapError> {
+        const SQL: &str = "
+            SELECT data
+            FROM blocks
+            WHERE x = ?
+              AND y = ?
+              AND z = ?
+            LIMIT 1";
+
+        let mut stmt = self.conn.prepare_cached(SQL)?;
+        let mut blocks = Vec::with_capacity(positions.len());
+
+        for &pos in positions {
+            let data = stmt
+                .query_row([&pos.x, &pos.y, &pos.z], |row| row.get(0))
+                .optional()?;
+
+            if let Some(data) = data {
+                blocks.push((pos, data));
+            }
+        }
+
+        Ok(blocks)
+    }
+
+    fn get_blocks_in_box(
+        &mut self,
+        min: glam::IVec3,
+        max: glam::IVec3,
+    ) -> Result<Vec<(glam::IVec3, Vec<u8>)>, MapError> {
+        const SQL: &str = "
+            SELECT x, y, z, data
+            FROM blocks
+            WHERE x BETWEEN ? AND ?
+              AND y BETWEEN ? AND ?
+              AND z BETWEEN ? AND ?";
+
+        let mut stmt = self.conn.prepare_cached(SQL)?;
+        let blocks = stmt
+            .query_map([&min.x, &max.x, &min.y, &max.y, &min.z, &max.z], |row| {
+                let pos = glam::ivec3(row.get(0)?, row.get(1)?, row.get(2)?);
+                Ok((pos, row.get(3)?))
+            })?
+            .collect::<Result<_, _>>()?;
+
+        Ok(blocks)
+    }
+
+    fn begin_save(&mut self) -> Result<(), MapError> {
+        self.conn.execute_batch("BEGIN")?;
+        Ok(())
+    }
+
+    fn end_save(&mut self) -> Result<(), MapError> {
+        self.conn.execute_batch("COMMIT")?;
+        Ok(())
+    }
+
+    fn data_version(&mut self) -> Result<Option<u64>, MapError> {
+        let version: i64 = self
+            .conn
+            .query_row("PRAGMA data_version", [], |row| row.get(0))?;
+
+        Ok(Some(version as u64))
+    }
+
+    fn vacuum(&mut self) -> Result<(), MapError> {
+        self.conn.execute_batch("VACUUM")?;
+        Ok(())
+    }
+
+    fn size_on_disk(&mut self) -> Result<Option<u64>, MapError> {
+        let page_count: i64 = self
+            .conn
+            .query_row("PRAGMA page_count", [], |row| row.get(0))?;
+        let page_size: i64 = self
+            .conn
+            .query_row("PRAGMA page_size", [], |row| row.get(0))?;
 
-        Ok(data)
+        Ok(Some((page_count * page_size) as u64))
     }
 }
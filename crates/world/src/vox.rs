@@ -0,0 +1,633 @@
+use std::collections::HashMap;
+
+use glam::{IVec3, ivec3};
+
+use crate::{
+    Block, Map, MapError, NodeArea, NodeDefRegistry, ParseErrorKind, SchematicError,
+    TranslationReport,
+    anvil::{MappedNode, write_mapped_nodes},
+    nodedef::is_air,
+};
+
+/// Table of colors for common Minetest Game nodes.
+const MINETEST_GAME_COLORS: &str = include_str!("vox_colors.txt");
+
+const VOX_SIGNATURE: &[u8; 4] = b"VOX ";
+
+/// Version written by MagicaVoxel 0.98 and earlier.
+const VOX_VERSION: i32 = 150;
+
+/// Version written by current MagicaVoxel, which adds the scene graph.
+const VOX_VERSION_SCENE: i32 = 200;
+
+/// Largest size of a model along each axis.
+const MAX_MODEL_SIZE: i32 = 256;
+
+/// A color table that translates nodes into the colors of MagicaVoxel
+/// models and back.
+///
+/// The table is text with one node per line, followed by its color as six
+/// hexadecimal digits:
+///
+/// ```text
+/// # Comments start with a hash.
+/// default:stone   8a8a8a
+/// ```
+///
+/// Voxels become the node of the nearest color in the table. Nodes listed
+/// several times get the color of their first line, and colors listed
+/// several times the node of their first line.
+#[derive(Clone, Debug, Default)]
+pub struct NodeColors {
+    entries: Vec<(String, [u8; 3])>,
+}
+
+impl NodeColors {
+    pub fn parse(text: &str) -> Result<Self, SchematicError> {
+        let mut entries = Vec::new();
+
+        for (index, line) in text.lines().enumerate() {
+            let error = |message: &str| SchematicError::ColorTable {
+                line: index + 1,
+                message: message.to_string(),
+            };
+
+            let line = line.split('#').next().unwrap_or_default();
+            let mut fields = line.split_whitespace();
+            let Some(node) = fields.next() else {
+                continue;
+            };
+
+            let color = fields
+                .next()
+                .and_then(parse_color)
+                .ok_or_else(|| error("expected a color such as 8a8a8a"))?;
+            if fields.next().is_some() {
+                return Err(error("unexpected text after the color"));
+            }
+
+            entries.push((node.to_string(), color));
+        }
+
+        Ok(Self { entries })
+    }
+
+    /// The built-in table for Minetest Game.
+    pub fn minetest_game() -> Self {
+        Self::parse(MINETEST_GAME_COLORS).expect("built-in color table is valid")
+    }
+
+    /// The text of the built-in table, as a starting point for custom ones.
+    pub fn minetest_game_text() -> &'static str {
+        MINETEST_GAME_COLORS
+    }
+
+    pub fn color(&self, node: &str) -> Option<[u8; 3]> {
+        self.entries
+            .iter()
+            .find(|(name, _)| name == node)
+            .map(|(_, color)| *color)
+    }
+
+    /// The node of the nearest color, and whether the color matched exactly.
+    pub fn nearest_node(&self, color: [u8; 3]) -> Option<(&str, bool)> {
+        let distance = |other: &[u8; 3]| -> i32 {
+            (0..3)
+                .map(|i| (color[i] as i32 - other[i] as i32).pow(2))
+                .sum()
+        };
+
+        let (name, nearest) = self
+            .entries
+            .iter()
+            .min_by_key(|(_, other)| distance(other))?;
+        Some((name, *nearest == color))
+    }
+}
+
+fn parse_color(s: &str) -> Option<[u8; 3]> {
+    if s.len() != 6 {
+        return None;
+    }
+
+    let rgb = u32::from_str_radix(s, 16).ok()?;
+    let [_, r, g, b] = rgb.to_be_bytes();
+    Some([r, g, b])
+}
+
+fn format_color(color: [u8; 3]) -> String {
+    format!("{:02x}{:02x}{:02x}", color[0], color[1], color[2])
+}
+
+/// A voxel of a [`VoxSchematic`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Voxel {
+    /// Position relative to the schematic's origin, in Minetest's axes.
+    pub pos: IVec3,
+    pub color: [u8; 3],
+}
+
+/// A model made in MagicaVoxel, in the `.vox` format.
+///
+/// MagicaVoxel's z axis points up, so its y and z are swapped to get
+/// Minetest positions. Models placed in a scene are put together at their
+/// translations, but rotations in the scene aren't applied.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct VoxSchematic {
+    pub size: IVec3,
+    pub voxels: Vec<Voxel>,
+}
+
+/// A model in the file, in MagicaVoxel's axes.
+struct Model {
+    size: IVec3,
+    voxels: Vec<([u8; 3], u8)>,
+}
+
+enum SceneNode {
+    Transform { child: i32, translation: IVec3 },
+    Group { children: Vec<i32> },
+    Shape { models: Vec<i32> },
+}
+
+impl VoxSchematic {
+    /// Reads files of versions 150 and 200 of the format.
+    pub fn read(data: &[u8]) -> Result<Self, SchematicError> {
+        let mut reader = Reader { data, offset: 0 };
+
+        if reader.bytes(4)? != VOX_SIGNATURE {
+            return Err(SchematicError::InvalidSignature);
+        }
+        let version = reader.i32()?;
+        if !(VOX_VERSION..=VOX_VERSION_SCENE).contains(&version) {
+            return Err(SchematicError::UnsupportedVersion(version as u32));
+        }
+
+        let main = reader.chunk()?;
+        if main.id != *b"MAIN" || !main.content.is_empty() {
+            return Err(invalid("missing MAIN chunk"));
+        }
+
+        let mut models = Vec::new();
+        let mut size = None;
+        let mut palette = default_palette();
+        let mut scene = HashMap::new();
+
+        let mut reader = Reader {
+            data: main.children,
+            offset: 0,
+        };
+        while !reader.is_empty() {
+            let chunk = reader.chunk()?;
+            let mut content = Reader {
+                data: chunk.content,
+                offset: 0,
+            };
+
+            match &chunk.id {
+                b"SIZE" => {
+                    let model_size = ivec3(content.i32()?, content.i32()?, content.i32()?);
+                    if model_size.cmplt(IVec3::ZERO).any()
+                        || model_size.cmpgt(IVec3::splat(MAX_MODEL_SIZE)).any()
+                    {
+                        return Err(invalid(&format!("invalid model size {model_size}")));
+                    }
+                    size = Some(model_size);
+                }
+                b"XYZI" => {
+                    let size = size
+                        .take()
+                        .ok_or_else(|| invalid("XYZI chunk without SIZE"))?;
+                    let count = content.len(4)?;
+                    let mut voxels = Vec::with_capacity(count);
+                    for _ in 0..count {
+                        let voxel = content.bytes(4)?;
+                        voxels.push(([voxel[0], voxel[1], voxel[2]], voxel[3]));
+                    }
+                    models.push(Model { size, voxels });
+                }
+                b"RGBA" => {
+                    for color in palette.iter_mut().skip(1) {
+                        let rgba = content.bytes(4)?;
+                        *color = [rgba[0], rgba[1], rgba[2]];
+                    }
+                }
+                b"nTRN" => {
+                    let id = content.i32()?;
+                    content.dict()?;
+                    let child = content.i32()?;
+                    let _reserved = content.i32()?;
+                    let _layer = content.i32()?;
+                    let frames = content.i32()?;
+
+                    // Only the first frame of animations is used.
+                    let mut translation = IVec3::ZERO;
+                    if frames > 0 {
+                        let frame = content.dict()?;
+                        if let Some((_, t)) = frame.iter().find(|(key, _)| key == "_t") {
+                            translation = parse_translation(t)
+                                .ok_or_else(|| invalid(&format!("invalid translation `{t}`")))?;
+                        }
+                    }
+
+                    scene.insert(id, SceneNode::Transform { child, translation });
+                }
+                b"nGRP" => {
+                    let id = content.i32()?;
+                    content.dict()?;
+                    let count = content.len(4)?;
+                    let children = (0..count)
+                        .map(|_| content.i32())
+                        .collect::<Result<_, _>>()?;
+                    scene.insert(id, SceneNode::Group { children });
+                }
+                b"nSHP" => {
+                    let id = content.i32()?;
+                    content.dict()?;
+                    let count = content.len(4)?;
+                    let mut models = Vec::with_capacity(count);
+                    for _ in 0..count {
+                        models.push(content.i32()?);
+                        content.dict()?;
+                    }
+                    scene.insert(id, SceneNode::Shape { models });
+                }
+                // Materials, layers, cameras and the like don't matter here.
+                _ => {}
+            }
+        }
+
+        // Minimum corners of the models in MagicaVoxel's axes. Without a
+        // scene, all models start at the origin.
+        let mut placements = Vec::new();
+        if scene.is_empty() {
+            placements.extend((0..models.len()).map(|model| (model, IVec3::ZERO)));
+        } else {
+            collect_placements(&scene, &models, 0, IVec3::ZERO, 0, &mut placements)?;
+        }
+
+        let mut schematic = Self::default();
+        let Some(min) = placements.iter().map(|(_, min)| *min).reduce(IVec3::min) else {
+            return Ok(schematic);
+        };
+
+        for (model, model_min) in placements {
+            let model = &models[model];
+            let offset = model_min - min;
+            schematic.size = schematic.size.max(swap_axes(offset + model.size));
+
+            for &([x, y, z], color) in &model.voxels {
+                let pos = offset + ivec3(x as i32, y as i32, z as i32);
+                if pos.cmpge(offset + model.size).any() {
+                    return Err(invalid(&format!("voxel {pos} outside of its model")));
+                }
+                schematic.voxels.push(Voxel {
+                    pos: swap_axes(pos),
+                    color: palette[color as usize],
+                });
+            }
+        }
+
+        Ok(schematic)
+    }
+
+    /// Writes the schematic as a single model in version 150 of the format.
+    pub fn write(&self) -> Result<Vec<u8>, SchematicError> {
+        let size = swap_axes(self.size);
+        if size.cmpgt(IVec3::splat(MAX_MODEL_SIZE)).any() {
+            return Err(SchematicError::TooLarge(format!(
+                "size {} exceeds {MAX_MODEL_SIZE}",
+                self.size
+            )));
+        }
+
+        let mut palette: Vec<[u8; 3]> = Vec::new();
+        let mut xyzi = Vec::with_capacity(4 + self.voxels.len() * 4);
+        xyzi.extend_from_slice(&(self.voxels.len() as i32).to_le_bytes());
+
+        for voxel in &self.voxels {
+            let pos = swap_axes(voxel.pos);
+            if pos.cmplt(IVec3::ZERO).any() || pos.cmpge(size).any() {
+                return Err(SchematicError::TooLarge(format!(
+                    "voxel {} outside of the size",
+                    voxel.pos
+                )));
+            }
+
+            let index = match palette.iter().position(|color| *color == voxel.color) {
+                Some(index) => index,
+                None => {
+                    palette.push(voxel.color);
+                    palette.len() - 1
+                }
+            };
+            // Color 0 means empty, and there are 255 others.
+            if index >= 255 {
+                return Err(SchematicError::TooLarge("more than 255 colors".to_string()));
+            }
+
+            xyzi.extend_from_slice(&[pos.x as u8, pos.y as u8, pos.z as u8, index as u8 + 1]);
+        }
+
+        let mut rgba = Vec::with_capacity(256 * 4);
+        for index in 0..256 {
+            let [r, g, b] = palette.get(index).copied().unwrap_or_default();
+            rgba.extend_from_slice(&[r, g, b, 0xff]);
+        }
+
+        let mut size_chunk = Vec::with_capacity(12);
+        for n in size.to_array() {
+            size_chunk.extend_from_slice(&n.to_le_bytes());
+        }
+
+        let mut children = Vec::new();
+        write_chunk(&mut children, b"SIZE", &size_chunk, &[]);
+        write_chunk(&mut children, b"XYZI", &xyzi, &[]);
+        write_chunk(&mut children, b"RGBA", &rgba, &[]);
+
+        let mut out = Vec::with_capacity(children.len() + 20);
+        out.extend_from_slice(VOX_SIGNATURE);
+        out.extend_from_slice(&VOX_VERSION.to_le_bytes());
+        write_chunk(&mut out, b"MAIN", &[], &children);
+        Ok(out)
+    }
+
+    /// Copies the nodes of `area` out of `map` as voxels colored by
+    /// `colors`, relative to the minimum corner of `area`. Air and nodes
+    /// without a color are left out; uncolored nodes are reported unless
+    /// they are air according to `node_defs`, or without them, `air` or
+    /// `ignore`.
+    pub fn from_map(
+        map: &Map,
+        area: NodeArea,
+        colors: &NodeColors,
+        node_defs: Option<&NodeDefRegistry>,
+    ) -> Result<(Self, TranslationReport), MapError> {
+        let min_block = area.min.div_euclid(IVec3::splat(16));
+        let max_block = area.max.div_euclid(IVec3::splat(16));
+
+        let mut schematic = Self {
+            size: area.max - area.min + 1,
+            voxels: Vec::new(),
+        };
+        let mut report = TranslationReport::default();
+
+        for (block_pos, data) in map.get_blocks_in_box(min_block, max_block)? {
+            let block = Block::parse_data_lazy(&data).map_err(|err| err.at(block_pos))?;
+
+            // Colors by node id, or the name of nodes without one.
+            let mut block_colors: HashMap<u16, Result<[u8; 3], &str>> = HashMap::new();
+
+            for (local_pos, node) in block.nodes() {
+                let pos = block_pos * 16 + local_pos;
+                if pos.cmplt(area.min).any() || pos.cmpgt(area.max).any() {
+                    continue;
+                }
+
+                let color = *block_colors.entry(node.id).or_insert_with(|| {
+                    let name = block.get_name_by_id(node.id).unwrap_or("unknown");
+                    colors.color(name).ok_or(name)
+                });
+
+                match color {
+                    Ok(color) => {
+                        schematic.voxels.push(Voxel {
+                            pos: pos - area.min,
+                            color,
+                        });
+                        report.nodes += 1;
+                    }
+                    Err(name) if is_air(name, node_defs) => {}
+                    Err(name) => *report.unmapped.entry(name.to_string()).or_default() += 1,
+                }
+            }
+        }
+
+        // Blocks come in the order of the backend.
+        schematic
+            .voxels
+            .sort_unstable_by_key(|voxel| (voxel.pos.y, voxel.pos.z, voxel.pos.x));
+
+        Ok((schematic, report))
+    }
+
+    /// Places the voxels into `map` relative to `origin` as the nodes of the
+    /// nearest colors in `colors`. Every voxel replaces whatever was in the
+    /// way, while empty space leaves the map as it is.
+    ///
+    /// Colors without an exact match are counted in the report's
+    /// [`TranslationReport::unmapped`], in hexadecimal.
+    pub fn place(
+        &self,
+        map: &Map,
+        origin: IVec3,
+        colors: &NodeColors,
+    ) -> Result<TranslationReport, MapError> {
+        let mut report = TranslationReport::default();
+        let mut nodes: HashMap<[u8; 3], Option<(&str, bool)>> = HashMap::new();
+
+        let mut by_block: HashMap<IVec3, Vec<(usize, MappedNode)>> = HashMap::new();
+        for voxel in &self.voxels {
+            let nearest = *nodes
+                .entry(voxel.color)
+                .or_insert_with(|| colors.nearest_node(voxel.color));
+
+            let Some((name, exact)) = nearest else {
+                *report
+                    .unmapped
+                    .entry(format_color(voxel.color))
+                    .or_default() += 1;
+                continue;
+            };
+            if !exact {
+                *report
+                    .unmapped
+                    .entry(format_color(voxel.color))
+                    .or_default() += 1;
+            }
+
+            let pos = origin + voxel.pos;
+            let block_pos = pos.div_euclid(IVec3::splat(16));
+            let index = Block::node_index(pos - block_pos * 16).expect("node lies in its block");
+            by_block
+                .entry(block_pos)
+                .or_default()
+                .push((index, (name, 0)));
+        }
+
+        report.nodes = write_mapped_nodes(map, &by_block)?;
+        Ok(report)
+    }
+}
+
+/// Walks the scene graph from `id`, adding the models of shapes with the
+/// minimum corners they end up at.
+fn collect_placements(
+    scene: &HashMap<i32, SceneNode>,
+    models: &[Model],
+    id: i32,
+    translation: IVec3,
+    depth: usize,
+    placements: &mut Vec<(usize, IVec3)>,
+) -> Result<(), SchematicError> {
+    if depth > scene.len() {
+        return Err(invalid("cycle in the scene graph"));
+    }
+
+    match scene.get(&id) {
+        Some(SceneNode::Transform {
+            child,
+            translation: t,
+        }) => collect_placements(
+            scene,
+            models,
+            *child,
+            translation + *t,
+            depth + 1,
+            placements,
+        )?,
+        Some(SceneNode::Group { children }) => {
+            for &child in children {
+                collect_placements(scene, models, child, translation, depth + 1, placements)?;
+            }
+        }
+        Some(SceneNode::Shape { models: shape }) => {
+            for &model in shape {
+                let model_size = usize::try_from(model)
+                    .ok()
+                    .and_then(|model| models.get(model))
+                    .map(|model| model.size)
+                    .ok_or_else(|| invalid(&format!("shape of missing model {model}")))?;
+
+                // Translations put the center of models in place.
+                placements.push((model as usize, translation - model_size / 2));
+            }
+        }
+        None => return Err(invalid(&format!("missing scene node {id}"))),
+    }
+
+    Ok(())
+}
+
+fn parse_translation(s: &str) -> Option<IVec3> {
+    let mut parts = s.split_whitespace().map(|part| part.parse().ok());
+    let translation = ivec3(parts.next()??, parts.next()??, parts.next()??);
+    parts.next().is_none().then_some(translation)
+}
+
+/// Converts between MagicaVoxel's axes, with z pointing up, and Minetest's.
+fn swap_axes(pos: IVec3) -> IVec3 {
+    ivec3(pos.x, pos.z, pos.y)
+}
+
+fn write_chunk(out: &mut Vec<u8>, id: &[u8; 4], content: &[u8], children: &[u8]) {
+    out.extend_from_slice(id);
+    out.extend_from_slice(&(content.len() as i32).to_le_bytes());
+    out.extend_from_slice(&(children.len() as i32).to_le_bytes());
+    out.extend_from_slice(content);
+    out.extend_from_slice(children);
+}
+
+/// The palette of files without an `RGBA` chunk: a cube of 6 shades of
+/// each channel without black, followed by ramps of red, green, blue and
+/// gray. Index 0 is empty.
+fn default_palette() -> [[u8; 3]; 256] {
+    const CUBE: [u8; 6] = [0xff, 0xcc, 0x99, 0x66, 0x33, 0x00];
+    const RAMP: [u8; 10] = [0xee, 0xdd, 0xbb, 0xaa, 0x88, 0x77, 0x55, 0x44, 0x22, 0x11];
+
+    let mut palette = [[0; 3]; 256];
+    let mut colors = palette.iter_mut().skip(1);
+
+    for r in CUBE {
+        for g in CUBE {
+            for b in CUBE {
+                if [r, g, b] != [0; 3] {
+                    *colors.next().unwrap() = [r, g, b];
+                }
+            }
+        }
+    }
+    for channel in [0, 1, 2] {
+        for value in RAMP {
+            let mut color = [0; 3];
+            color[channel] = value;
+            *colors.next().unwrap() = color;
+        }
+    }
+    for value in RAMP {
+        *colors.next().unwrap() = [value; 3];
+    }
+
+    palette
+}
+
+struct Chunk<'a> {
+    id: [u8; 4],
+    content: &'a [u8],
+    children: &'a [u8],
+}
+
+/// Reads the little-endian data of `.vox` files.
+struct Reader<'a> {
+    data: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn is_empty(&self) -> bool {
+        self.offset >= self.data.len()
+    }
+
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8], ParseErrorKind> {
+        let bytes = self
+            .data
+            .get(self.offset..self.offset.saturating_add(len))
+            .ok_or(ParseErrorKind::UnexpectedEnd)?;
+        self.offset += len;
+        Ok(bytes)
+    }
+
+    fn i32(&mut self) -> Result<i32, ParseErrorKind> {
+        Ok(i32::from_le_bytes(self.bytes(4)?.try_into().unwrap()))
+    }
+
+    /// Reads a count of elements, checking that the remaining data can hold
+    /// that many elements of at least `element_size` bytes.
+    fn len(&mut self, element_size: usize) -> Result<usize, ParseErrorKind> {
+        let len = self.i32()?;
+        let remaining = self.data.len() - self.offset;
+
+        match usize::try_from(len) {
+            Ok(len) if len.saturating_mul(element_size) <= remaining => Ok(len),
+            Ok(_) => Err(ParseErrorKind::UnexpectedEnd),
+            Err(_) => Err(ParseErrorKind::Invalid(format!("negative length {len}"))),
+        }
+    }
+
+    fn string(&mut self) -> Result<String, ParseErrorKind> {
+        let len = self.len(1)?;
+        Ok(String::from_utf8_lossy(self.bytes(len)?).into_owned())
+    }
+
+    fn dict(&mut self) -> Result<Vec<(String, String)>, ParseErrorKind> {
+        let len = self.len(8)?;
+        (0..len)
+            .map(|_| Ok((self.string()?, self.string()?)))
+            .collect()
+    }
+
+    fn chunk(&mut self) -> Result<Chunk<'a>, ParseErrorKind> {
+        let id = self.bytes(4)?.try_into().unwrap();
+        let content_len = self.len(1)?;
+        let children_len = self.len(1)?;
+        Ok(Chunk {
+            id,
+            content: self.bytes(content_len)?,
+            children: self.bytes(children_len)?,
+        })
+    }
+}
+
+fn invalid(message: &str) -> SchematicError {
+    SchematicError::Parse(ParseErrorKind::Invalid(message.to_string()))
+}
@@ -0,0 +1,249 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        Arc,
+        mpsc::{self, TryRecvError},
+    },
+    thread,
+    time::Duration,
+};
+
+use glam::IVec3;
+
+use crate::{Block, Map, MapError};
+
+/// Blocks that were written by someone else since the last poll.
+#[derive(Clone, Debug, Default)]
+pub struct BlocksChanged {
+    /// Watched positions that didn't have a block before.
+    pub added: Vec<IVec3>,
+
+    /// Watched blocks that were saved again.
+    pub modified: Vec<IVec3>,
+
+    pub removed: Vec<IVec3>,
+}
+
+impl BlocksChanged {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.modified.is_empty() && self.removed.is_empty()
+    }
+
+    pub fn contains(&self, pos: IVec3) -> bool {
+        self.positions().any(|p| p == pos)
+    }
+
+    pub fn positions(&self) -> impl Iterator<Item = IVec3> {
+        self.added
+            .iter()
+            .chain(&self.modified)
+            .chain(&self.removed)
+            .copied()
+    }
+}
+
+/// Detects changes made to a map by another process, such as a running
+/// server.
+///
+/// Only blocks loaded through the watcher or passed to one of the `track`
+/// methods are watched, whether they exist or not, so the map is never
+/// listed. A block counts as modified when the timestamp in its header
+/// changes, which the engine sets to the game time whenever it saves the
+/// block; only the header is decompressed to read it. On backends that expose
+/// a data version, polling is free until something is written. Otherwise
+/// every poll rereads the watched blocks, so callers should poll at a modest
+/// rate and away from UI and render loops.
+pub struct MapWatcher {
+    data_version: Option<u64>,
+
+    /// Header timestamps of watched blocks, `None` for missing blocks.
+    timestamps: HashMap<IVec3, Option<u32>>,
+}
+
+impl MapWatcher {
+    pub fn new(map: &Map) -> Result<Self, MapError> {
+        Ok(Self {
+            data_version: map.data_version()?,
+            timestamps: HashMap::new(),
+        })
+    }
+
+    /// Reads a block and starts watching it. Node metadata is decoded
+    /// lazily, see [`Block::parse_data_lazy`]. A missing block is watched
+    /// for its appearance.
+    pub fn load_block(&mut self, map: &Map, pos: IVec3) -> Result<Block, MapError> {
+        let data = match map.get_block_data(pos) {
+            Err(MapError::BlockNotFound) => {
+                self.track_missing(pos);
+                return Err(MapError::BlockNotFound);
+            }
+            result => result?,
+        };
+
+        self.track(pos, &data);
+        Block::parse_data_lazy(&data).map_err(|err| err.at(pos).into())
+    }
+
+    /// Watches a block whose data was read elsewhere.
+    pub fn track(&mut self, pos: IVec3, data: &[u8]) {
+        self.timestamps.insert(pos, Some(header_timestamp(data)));
+    }
+
+    /// Watches a position without a block for one to appear.
+    pub fn track_missing(&mut self, pos: IVec3) {
+        self.timestamps.insert(pos, None);
+    }
+
+    /// Watches all positions between `min` and `max` (block positions,
+    /// inclusive), which are read in one go.
+    pub fn track_box(&mut self, map: &Map, min: IVec3, max: IVec3) -> Result<(), MapError> {
+        for z in min.z..=max.z {
+            for y in min.y..=max.y {
+                for x in min.x..=max.x {
+                    self.track_missing(IVec3::new(x, y, z));
+                }
+            }
+        }
+
+        for (pos, data) in map.get_blocks_in_box(min, max)? {
+            self.track(pos, &data);
+        }
+
+        Ok(())
+    }
+
+    pub fn untrack(&mut self, pos: IVec3) {
+        self.timestamps.remove(&pos);
+    }
+
+    pub fn poll(&mut self, map: &Map) -> Result<BlocksChanged, MapError> {
+        let mut changes = BlocksChanged::default();
+
+        let data_version = map.data_version()?;
+        if data_version.is_some() && data_version == self.data_version {
+            return Ok(changes);
+        }
+
+        self.data_version = data_version;
+
+        let positions: Vec<IVec3> = self.timestamps.keys().copied().collect();
+        let current: HashMap<IVec3, u32> = map
+            .get_blocks(&positions)?
+            .into_iter()
+            .map(|(pos, data)| (pos, header_timestamp(&data)))
+            .collect();
+
+        for (pos, timestamp) in &mut self.timestamps {
+            let new = current.get(pos).copied();
+
+            match (*timestamp, new) {
+                (None, Some(_)) => changes.added.push(*pos),
+                (Some(_), None) => changes.removed.push(*pos),
+                (Some(old), Some(new)) if old != new => changes.modified.push(*pos),
+                _ => {}
+            }
+
+            *timestamp = new;
+        }
+
+        Ok(changes)
+    }
+
+    /// Polls every `interval` on a thread of its own, which reports changes
+    /// and errors through the returned handle. The thread exits at the next
+    /// poll once the handle is dropped.
+    pub fn spawn(mut self, map: Arc<Map>, interval: Duration) -> WatcherHandle {
+        let (sender, changes) = mpsc::channel();
+        let (commands, receiver) = mpsc::channel();
+
+        thread::spawn(move || {
+            loop {
+                thread::sleep(interval);
+
+                let mut tracked = Vec::new();
+                loop {
+                    match receiver.try_recv() {
+                        Ok(WatchCommand::Track(pos)) => tracked.push(pos),
+                        Ok(WatchCommand::Untrack(pos)) => self.untrack(pos),
+                        Err(TryRecvError::Empty) => break,
+                        Err(TryRecvError::Disconnected) => return,
+                    }
+                }
+
+                let result = self
+                    .track_blocks(&map, &tracked)
+                    .and_then(|()| self.poll(&map));
+                if matches!(&result, Ok(changes) if changes.is_empty()) {
+                    continue;
+                }
+
+                if sender.send(result).is_err() {
+                    return;
+                }
+            }
+        });
+
+        WatcherHandle { changes, commands }
+    }
+
+    /// Watches `positions`, reading the blocks in one go.
+    fn track_blocks(&mut self, map: &Map, positions: &[IVec3]) -> Result<(), MapError> {
+        if positions.is_empty() {
+            return Ok(());
+        }
+
+        for &pos in positions {
+            self.track_missing(pos);
+        }
+
+        for (pos, data) in map.get_blocks(positions)? {
+            self.track(pos, &data);
+        }
+
+        Ok(())
+    }
+}
+
+enum WatchCommand {
+    Track(IVec3),
+    Untrack(IVec3),
+}
+
+/// A [`MapWatcher`] polling on its own thread, as started by
+/// [`MapWatcher::spawn`]. Dropping the handle stops the thread.
+pub struct WatcherHandle {
+    changes: mpsc::Receiver<Result<BlocksChanged, MapError>>,
+    commands: mpsc::Sender<WatchCommand>,
+}
+
+impl WatcherHandle {
+    /// Starts watching a block from the next poll on, such as one that was
+    /// just loaded.
+    pub fn track(&self, pos: IVec3) {
+        // The thread only stops once the handle is dropped.
+        let _ = self.commands.send(WatchCommand::Track(pos));
+    }
+
+    pub fn untrack(&self, pos: IVec3) {
+        let _ = self.commands.send(WatchCommand::Untrack(pos));
+    }
+
+    /// Changes and errors found since the last call, without waiting.
+    pub fn try_iter(&self) -> mpsc::TryIter<'_, Result<BlocksChanged, MapError>> {
+        self.changes.try_iter()
+    }
+
+    /// Waits up to `timeout` for the next changes.
+    pub fn recv_timeout(
+        &self,
+        timeout: Duration,
+    ) -> Result<Result<BlocksChanged, MapError>, mpsc::RecvTimeoutError> {
+        self.changes.recv_timeout(timeout)
+    }
+}
+
+/// Blocks in older formats, or with a broken header, read as undefined and
+/// only count as modified once that changes.
+fn header_timestamp(data: &[u8]) -> u32 {
+    Block::parse_timestamp(data).unwrap_or(Block::TIMESTAMP_UNDEFINED)
+}
@@ -0,0 +1,348 @@
+use std::collections::HashMap;
+
+use glam::{IVec3, ivec3};
+
+use crate::{
+    Block, InventoryError, InventoryList, LuaTable, LuaValue, Map, MapError, MetadataField, Node,
+    NodeArea, NodeDefRegistry, NodeMetadata, ParseErrorKind, SchematicError, nodedef::is_air,
+    schematic::modify_blocks,
+};
+
+/// Version written by current WorldEdit, which added the header and made
+/// `param1`, `param2` and `meta` optional.
+const WE_VERSION: u32 = 5;
+
+/// Version that wrote the same list without a header.
+const WE_VERSION_WITHOUT_HEADER: u32 = 4;
+
+/// A node of a [`WorldEditSchematic`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct WorldEditNode {
+    /// Position relative to the schematic's origin.
+    pub pos: IVec3,
+    pub name: String,
+    pub param1: u8,
+    pub param2: u8,
+    pub metadata: Option<NodeMetadata>,
+}
+
+/// The contents of a `.we` file written by WorldEdit's `//save`: a list of
+/// nodes relative to an origin, leaving out air.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct WorldEditSchematic {
+    pub nodes: Vec<WorldEditNode>,
+}
+
+impl WorldEditSchematic {
+    /// Reads versions 4 and 5 of the format. The data is parsed, never
+    /// executed.
+    pub fn read(data: &[u8]) -> Result<Self, SchematicError> {
+        let (version, content) = split_header(data);
+
+        if version != WE_VERSION && version != WE_VERSION_WITHOUT_HEADER {
+            return Err(SchematicError::UnsupportedVersion(version));
+        }
+
+        let value = LuaValue::deserialize(content)?;
+        let list = value
+            .as_table()
+            .ok_or_else(|| invalid("expected a list of nodes"))?;
+
+        let nodes = list
+            .sequence()
+            .map(|node| {
+                node.as_table()
+                    .ok_or_else(|| invalid("expected a node table"))
+                    .and_then(read_node)
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self { nodes })
+    }
+
+    /// Writes the schematic in version 5 of the format, as `//save` does.
+    pub fn write(&self) -> Vec<u8> {
+        let mut list = LuaTable::new();
+
+        for (index, node) in self.nodes.iter().enumerate() {
+            let mut table = LuaTable::new();
+            table.insert("x", node.pos.x as i64);
+            table.insert("y", node.pos.y as i64);
+            table.insert("z", node.pos.z as i64);
+            table.insert("name", node.name.as_str());
+
+            if node.param1 != 0 {
+                table.insert("param1", node.param1 as i64);
+            }
+            if node.param2 != 0 {
+                table.insert("param2", node.param2 as i64);
+            }
+            if let Some(metadata) = &node.metadata {
+                table.insert("meta", write_metadata(metadata));
+            }
+
+            list.insert(index as i64 + 1, table);
+        }
+
+        let serialized = LuaValue::Table(list).serialize();
+        format!("{WE_VERSION}:{serialized}").into_bytes()
+    }
+
+    /// Size of the box from the origin to the farthest node.
+    pub fn size(&self) -> IVec3 {
+        self.nodes
+            .iter()
+            .fold(IVec3::ZERO, |size, node| size.max(node.pos + 1))
+    }
+
+    /// Copies all nodes of `area` apart from air out of `map`, relative to
+    /// the minimum corner of `area`. Blocks that don't exist are left out.
+    /// `node_defs` tells which nodes are air; without them, only `air` and
+    /// `ignore` are.
+    pub fn from_map(
+        map: &Map,
+        area: NodeArea,
+        node_defs: Option<&NodeDefRegistry>,
+    ) -> Result<Self, MapError> {
+        let min_block = area.min.div_euclid(IVec3::splat(16));
+        let max_block = area.max.div_euclid(IVec3::splat(16));
+
+        let mut blocks = HashMap::new();
+        for (block_pos, data) in map.get_blocks_in_box(min_block, max_block)? {
+            let block = Block::parse_data_lazy(&data).map_err(|err| err.at(block_pos))?;
+            blocks.insert(block_pos, block);
+        }
+
+        let mut nodes = Vec::new();
+
+        // The same order as WorldEdit, so that saving an area gives the same
+        // file.
+        for x in area.min.x..=area.max.x {
+            for y in area.min.y..=area.max.y {
+                for z in area.min.z..=area.max.z {
+                    let pos = ivec3(x, y, z);
+                    let block_pos = pos.div_euclid(IVec3::splat(16));
+                    let Some(block) = blocks.get(&block_pos) else {
+                        continue;
+                    };
+
+                    let local_pos = pos - block_pos * 16;
+                    let Some(node) = block.get_node(local_pos) else {
+                        continue;
+                    };
+
+                    let name = block.get_name_by_id(node.id).unwrap_or("unknown");
+                    if is_air(name, node_defs) {
+                        continue;
+                    }
+
+                    nodes.push(WorldEditNode {
+                        pos: pos - area.min,
+                        name: name.to_string(),
+                        param1: node.param1,
+                        param2: node.param2,
+                        metadata: block.get_metadata(local_pos).cloned(),
+                    });
+                }
+            }
+        }
+
+        Ok(Self { nodes })
+    }
+
+    /// Places the nodes into `map` relative to `origin`, the way `//load`
+    /// does: every node replaces whatever was there, including its metadata
+    /// and node timer. Returns the number of nodes placed.
+    ///
+    /// Blocks are changed as by [`crate::Schematic::place`].
+    pub fn place(&self, map: &Map, origin: IVec3) -> Result<usize, MapError> {
+        let mut by_block: HashMap<IVec3, Vec<&WorldEditNode>> = HashMap::new();
+        for node in &self.nodes {
+            let block_pos = (origin + node.pos).div_euclid(IVec3::splat(16));
+            by_block.entry(block_pos).or_default().push(node);
+        }
+
+        let mut positions: Vec<IVec3> = by_block.keys().copied().collect();
+        positions.sort_by_key(|pos| (pos.z, pos.y, pos.x));
+
+        modify_blocks(map, &positions, |block_pos, block| {
+            let nodes = &by_block[&block_pos];
+
+            for node in nodes {
+                let local_pos = origin + node.pos - block_pos * 16;
+                let id = block.get_or_insert_id(&node.name);
+                let index = Block::node_index(local_pos).expect("node lies in its block");
+                block.set_node_at(
+                    index,
+                    Node {
+                        id,
+                        param1: node.param1,
+                        param2: node.param2,
+                    },
+                );
+
+                block.remove_node_timer(local_pos);
+                match &node.metadata {
+                    Some(metadata) => block
+                        .set_metadata(local_pos, metadata.clone())
+                        .expect("node lies in its block"),
+                    None => {
+                        block.remove_metadata(local_pos);
+                    }
+                }
+            }
+
+            nodes.len()
+        })
+    }
+}
+
+/// Splits off the header, returning the version and the rest of the data.
+/// Files without a header are told apart the way WorldEdit does.
+fn split_header(data: &[u8]) -> (u32, &[u8]) {
+    let digits = data.iter().take_while(|c| c.is_ascii_digit()).count();
+
+    if digits > 0 && matches!(data.get(digits), Some(b',' | b':')) {
+        let version = std::str::from_utf8(&data[..digits])
+            .ok()
+            .and_then(|version| version.parse().ok())
+            .unwrap_or(u32::MAX);
+
+        // Extra header fields after the version are ignored.
+        let end = data.iter().position(|&c| c == b':').unwrap_or(data.len());
+        return (version, data.get(end + 1..).unwrap_or_default());
+    }
+
+    let contains = |needle: &[u8]| data.windows(needle.len()).any(|w| w == needle);
+
+    let version = if data.trim_ascii_start().starts_with(b"return") {
+        WE_VERSION_WITHOUT_HEADER
+    } else if !contains(b"{") {
+        3
+    } else if contains(b"[\"meta\"]") {
+        2
+    } else {
+        1
+    };
+
+    (version, data)
+}
+
+fn read_node(table: &LuaTable) -> Result<WorldEditNode, SchematicError> {
+    let coordinate = |key: &str| {
+        table
+            .get(key)
+            .and_then(LuaValue::as_i64)
+            .and_then(|c| i32::try_from(c).ok())
+            .ok_or_else(|| invalid(&format!("node without a valid `{key}`")))
+    };
+
+    let param = |key: &str| match table.get(key) {
+        None => Ok(0),
+        Some(value) => value
+            .as_i64()
+            .and_then(|param| u8::try_from(param).ok())
+            .ok_or_else(|| invalid(&format!("invalid `{key}`"))),
+    };
+
+    let name = table
+        .get("name")
+        .and_then(LuaValue::as_str)
+        .ok_or_else(|| invalid("node without a name"))?;
+
+    let metadata = match table.get("meta") {
+        None => None,
+        Some(meta) => Some(read_metadata(
+            meta.as_table().ok_or_else(|| invalid("invalid `meta`"))?,
+        )?),
+    };
+
+    Ok(WorldEditNode {
+        pos: ivec3(coordinate("x")?, coordinate("y")?, coordinate("z")?),
+        name: name.to_string(),
+        param1: param("param1")?,
+        param2: param("param2")?,
+        metadata,
+    })
+}
+
+/// Reads metadata in the form of `NodeMetaRef:to_table`, with inventories
+/// holding item strings.
+fn read_metadata(table: &LuaTable) -> Result<NodeMetadata, SchematicError> {
+    let mut metadata = NodeMetadata::default();
+
+    if let Some(fields) = table.get("fields").and_then(LuaValue::as_table) {
+        for (key, value) in fields.iter() {
+            let key = key
+                .as_str()
+                .ok_or_else(|| invalid("invalid metadata key"))?;
+            let value = match value {
+                LuaValue::String(value) => value.clone(),
+                LuaValue::Number(n) => match value.as_i64() {
+                    Some(n) => n.to_string().into_bytes(),
+                    None => n.to_string().into_bytes(),
+                },
+                _ => return Err(invalid(&format!("invalid value of metadata `{key}`"))),
+            };
+
+            metadata.fields.insert(
+                key.to_string(),
+                MetadataField {
+                    value,
+                    private: false,
+                },
+            );
+        }
+    }
+
+    if let Some(lists) = table.get("inventory").and_then(LuaValue::as_table) {
+        for (name, items) in lists.iter() {
+            let name = name
+                .as_str()
+                .ok_or_else(|| invalid("invalid inventory list name"))?;
+            let items = items
+                .as_table()
+                .ok_or_else(|| invalid(&format!("invalid inventory list `{name}`")))?;
+
+            let mut list = InventoryList::new(name, 0);
+            for item in items.sequence() {
+                let item = item
+                    .as_str()
+                    .ok_or_else(|| invalid(&format!("invalid item in inventory list `{name}`")))?;
+                list.items.push(
+                    item.parse()
+                        .map_err(|err: InventoryError| invalid(&err.to_string()))?,
+                );
+            }
+
+            metadata.inventory.set_list(list);
+        }
+    }
+
+    Ok(metadata)
+}
+
+fn write_metadata(metadata: &NodeMetadata) -> LuaTable {
+    let mut fields = LuaTable::new();
+    for (key, field) in &metadata.fields {
+        fields.insert(key.as_str(), LuaValue::String(field.value.clone()));
+    }
+
+    let mut inventory = LuaTable::new();
+    for list in &metadata.inventory.lists {
+        let mut items = LuaTable::new();
+        for (index, item) in list.items.iter().enumerate() {
+            items.insert(index as i64 + 1, item.to_string());
+        }
+        inventory.insert(list.name.as_str(), items);
+    }
+
+    let mut table = LuaTable::new();
+    table.insert("fields", fields);
+    table.insert("inventory", inventory);
+    table
+}
+
+fn invalid(message: &str) -> SchematicError {
+    SchematicError::Parse(ParseErrorKind::Invalid(message.to_string()))
+}
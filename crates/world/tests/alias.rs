@@ -0,0 +1,110 @@
+use std::collections::BTreeMap;
+
+use glam::{IVec3, ivec3};
+use world::{Block, Map, Node, SqliteBackend, parse_register_aliases, rename_nodes};
+
+fn memory_map() -> Map {
+    Map::new(SqliteBackend::create(":memory:").unwrap())
+}
+
+fn block_with(names: &[&str]) -> Block {
+    let mut block = Block::new();
+    for (i, name) in names.iter().enumerate() {
+        let id = block.get_or_insert_id(name);
+        block
+            .set_node(
+                ivec3(i as i32, 0, 0),
+                Node {
+                    id,
+                    param1: 0,
+                    param2: 7,
+                },
+            )
+            .unwrap();
+    }
+    block.set_timestamp(100);
+    block
+}
+
+fn aliases(pairs: &[(&str, &str)]) -> BTreeMap<String, String> {
+    pairs
+        .iter()
+        .map(|(old, new)| (old.to_string(), new.to_string()))
+        .collect()
+}
+
+#[test]
+fn register_alias_calls() {
+    let source = r#"
+        minetest.register_alias("mapgen_stone", "default:stone")
+        core.register_alias_force('old:dirt', "default:dirt")
+        minetest.register_alias("computed", name)
+        minetest.register_alias("mapgen_stone", "default:desert_stone")
+    "#;
+
+    assert_eq!(
+        parse_register_aliases(source),
+        aliases(&[
+            ("mapgen_stone", "default:desert_stone"),
+            ("old:dirt", "default:dirt"),
+        ])
+    );
+}
+
+#[test]
+fn rename() {
+    let map = memory_map();
+    map.set_block(IVec3::ZERO, &block_with(&["old:stone", "old:dirt"]))
+        .unwrap();
+    map.set_block(IVec3::X, &block_with(&["old:dirt"])).unwrap();
+    map.set_block(IVec3::Y, &block_with(&["default:stone"]))
+        .unwrap();
+
+    // Aliases aren't followed, so old:dirt doesn't end up as default:stone.
+    let aliases = aliases(&[("old:stone", "default:stone"), ("old:dirt", "old:stone")]);
+    let report = rename_nodes(&map, &aliases, false).unwrap();
+
+    assert_eq!(report.blocks, 3);
+    assert_eq!(report.renamed_blocks, 2);
+    assert_eq!(report.skipped, 0);
+    assert_eq!(report.aliases["old:stone"], 1);
+    assert_eq!(report.aliases["old:dirt"], 2);
+
+    let block = map.get_block(IVec3::ZERO).unwrap();
+    let names: Vec<_> = (0..2)
+        .map(|x| {
+            let node = block.get_node(ivec3(x, 0, 0)).unwrap();
+            assert_eq!(node.param2, 7);
+            block.get_name_by_id(node.id).unwrap().to_string()
+        })
+        .collect();
+    assert_eq!(names, ["default:stone", "old:stone"]);
+    assert_eq!(block.timestamp(), 101);
+
+    let untouched = map.get_block(IVec3::Y).unwrap();
+    assert_eq!(untouched.timestamp(), 100);
+}
+
+#[test]
+fn dry_run() {
+    let map = memory_map();
+    map.set_block(IVec3::ZERO, &block_with(&["old:stone"]))
+        .unwrap();
+    let data = map.get_block_data(IVec3::ZERO).unwrap();
+
+    let report = rename_nodes(&map, &aliases(&[("old:stone", "default:stone")]), true).unwrap();
+
+    assert_eq!(report.renamed_blocks, 1);
+    assert_eq!(map.get_block_data(IVec3::ZERO).unwrap(), data);
+}
+
+#[test]
+fn older_versions_skipped() {
+    let map = memory_map();
+    map.set_block_data(IVec3::ZERO, &[28, 0, 0]).unwrap();
+
+    let report = rename_nodes(&map, &aliases(&[("old:stone", "default:stone")]), false).unwrap();
+
+    assert_eq!(report.skipped, 1);
+    assert_eq!(map.get_block_data(IVec3::ZERO).unwrap(), [28, 0, 0]);
+}
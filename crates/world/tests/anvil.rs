@@ -0,0 +1,252 @@
+use std::path::PathBuf;
+
+use glam::{IVec3, ivec3};
+use world::{AnvilImportOptions, BlockMapping, Map, NodeArea, SqliteBackend, import_anvil};
+
+/// Data version of Minecraft 1.15, whose block states span two longs.
+const DATA_VERSION_SPANNING: i32 = 2230;
+
+/// Data version of Minecraft 1.20.
+const DATA_VERSION_PADDED: i32 = 3465;
+
+/// Just enough NBT to write chunks.
+enum Nbt {
+    Byte(i8),
+    Int(i32),
+    String(&'static str),
+    List(Vec<Nbt>),
+    Compound(Vec<(&'static str, Nbt)>),
+    LongArray(Vec<i64>),
+}
+
+impl Nbt {
+    fn tag(&self) -> u8 {
+        match self {
+            Nbt::Byte(_) => 1,
+            Nbt::Int(_) => 3,
+            Nbt::String(_) => 8,
+            Nbt::List(_) => 9,
+            Nbt::Compound(_) => 10,
+            Nbt::LongArray(_) => 12,
+        }
+    }
+
+    fn write_payload(&self, out: &mut Vec<u8>) {
+        match self {
+            Nbt::Byte(n) => out.push(*n as u8),
+            Nbt::Int(n) => out.extend(n.to_be_bytes()),
+            Nbt::String(s) => write_string(out, s),
+            Nbt::List(list) => {
+                out.push(list.first().map_or(0, Nbt::tag));
+                out.extend((list.len() as i32).to_be_bytes());
+                for item in list {
+                    item.write_payload(out);
+                }
+            }
+            Nbt::Compound(entries) => {
+                for (name, value) in entries {
+                    out.push(value.tag());
+                    write_string(out, name);
+                    value.write_payload(out);
+                }
+                out.push(0);
+            }
+            Nbt::LongArray(longs) => {
+                out.extend((longs.len() as i32).to_be_bytes());
+                for long in longs {
+                    out.extend(long.to_be_bytes());
+                }
+            }
+        }
+    }
+}
+
+fn write_string(out: &mut Vec<u8>, s: &str) {
+    out.extend((s.len() as u16).to_be_bytes());
+    out.extend(s.as_bytes());
+}
+
+fn temp_dir(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("world-test-{name}-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&path);
+    std::fs::create_dir_all(&path).unwrap();
+    path
+}
+
+/// Writes `r.0.0.mca` holding `chunk` as chunk 0, 0, uncompressed.
+fn write_region(dir: &std::path::Path, chunk: &Nbt) {
+    let mut nbt = vec![10];
+    write_string(&mut nbt, "");
+    chunk.write_payload(&mut nbt);
+
+    let mut region = vec![0; 8192];
+    let sectors = (nbt.len() + 5).div_ceil(4096);
+    region[..4].copy_from_slice(&[0, 0, 2, sectors as u8]);
+
+    region.extend((nbt.len() as u32 + 1).to_be_bytes());
+    region.push(3);
+    region.extend(nbt);
+    region.resize(8192 + sectors * 4096, 0);
+
+    std::fs::write(dir.join("r.0.0.mca"), region).unwrap();
+}
+
+/// Packs palette indices the way Minecraft 1.16 and later does, leaving the
+/// top bits of each long unused.
+fn pack_padded(states: &[u64], bits: usize) -> Vec<i64> {
+    let per_long = 64 / bits;
+    let mut longs = vec![0u64; states.len().div_ceil(per_long)];
+    for (index, &state) in states.iter().enumerate() {
+        longs[index / per_long] |= state << (index % per_long * bits);
+    }
+    longs.into_iter().map(|long| long as i64).collect()
+}
+
+/// Packs palette indices the way older versions do, letting indices span
+/// two longs.
+fn pack_spanning(states: &[u64], bits: usize) -> Vec<i64> {
+    let mut longs = vec![0u64; (states.len() * bits).div_ceil(64)];
+    for (index, &state) in states.iter().enumerate() {
+        let bit = index * bits;
+        longs[bit / 64] |= state << (bit % 64);
+        if bit % 64 + bits > 64 {
+            longs[bit / 64 + 1] |= state >> (64 - bit % 64);
+        }
+    }
+    longs.into_iter().map(|long| long as i64).collect()
+}
+
+const NAMES: [&str; 17] = [
+    "test:b0", "test:b1", "test:b2", "test:b3", "test:b4", "test:b5", "test:b6", "test:b7",
+    "test:b8", "test:b9", "test:b10", "test:b11", "test:b12", "test:b13", "test:b14", "test:b15",
+    "test:b16",
+];
+
+/// 17 states need 5 bits per index, which doesn't divide 64.
+fn states() -> Vec<u64> {
+    (0..4096).map(|i| (i * 7 % 17) as u64).collect()
+}
+
+fn palette() -> Nbt {
+    Nbt::List(
+        NAMES
+            .iter()
+            .map(|name| Nbt::Compound(vec![("Name", Nbt::String(name))]))
+            .collect(),
+    )
+}
+
+fn mapping() -> BlockMapping {
+    // The last state is left unmapped.
+    let text: String = (0..16)
+        .map(|i| format!("test:b{i} test:n{i} {i}\n"))
+        .collect();
+    BlockMapping::parse(&text).unwrap()
+}
+
+fn import(dir: &std::path::Path) -> (Map, world::AnvilImportReport) {
+    let map = Map::new(SqliteBackend::create(":memory:").unwrap());
+    let options = AnvilImportOptions {
+        area: NodeArea::new(IVec3::ZERO, IVec3::splat(15)),
+        offset: IVec3::ZERO,
+    };
+    let report = import_anvil(&map, dir, &mapping(), &options, |_| {}).unwrap();
+    (map, report)
+}
+
+fn check_nodes(map: &Map) {
+    // The z axis is flipped, so the chunk ends up in the block at z = -1.
+    let block = map.get_block(ivec3(0, 0, -1)).unwrap();
+
+    for (index, state) in states().into_iter().enumerate() {
+        let index = index as i32;
+        let pos = ivec3(index & 15, index >> 8, 15 - ((index >> 4) & 15));
+        let node = block.get_node(pos).unwrap();
+
+        match state {
+            16 => assert_eq!(block.get_name_by_id(node.id), Some("air"), "{pos}"),
+            _ => {
+                let name = format!("test:n{state}");
+                assert_eq!(block.get_name_by_id(node.id), Some(name.as_str()), "{pos}");
+                assert_eq!(node.param2, state as u8, "{pos}");
+            }
+        }
+    }
+}
+
+#[test]
+fn padded_states() {
+    let dir = temp_dir("anvil-padded");
+
+    let section = Nbt::Compound(vec![
+        ("Y", Nbt::Byte(0)),
+        (
+            "block_states",
+            Nbt::Compound(vec![
+                ("palette", palette()),
+                ("data", Nbt::LongArray(pack_padded(&states(), 5))),
+            ]),
+        ),
+    ]);
+    let chunk = Nbt::Compound(vec![
+        ("DataVersion", Nbt::Int(DATA_VERSION_PADDED)),
+        ("Status", Nbt::String("minecraft:full")),
+        ("sections", Nbt::List(vec![section])),
+    ]);
+    write_region(&dir, &chunk);
+
+    let (map, report) = import(&dir);
+    check_nodes(&map);
+
+    let unmapped = states().iter().filter(|&&s| s == 16).count();
+    assert_eq!(report.chunks, 1);
+    assert_eq!(report.nodes, 4096 - unmapped);
+    assert_eq!(report.unmapped["test:b16"], unmapped);
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn spanning_states() {
+    let dir = temp_dir("anvil-spanning");
+
+    let section = Nbt::Compound(vec![
+        ("Y", Nbt::Byte(0)),
+        ("Palette", palette()),
+        ("BlockStates", Nbt::LongArray(pack_spanning(&states(), 5))),
+    ]);
+    let level = Nbt::Compound(vec![
+        ("Status", Nbt::String("full")),
+        ("Sections", Nbt::List(vec![section])),
+    ]);
+    let chunk = Nbt::Compound(vec![
+        ("DataVersion", Nbt::Int(DATA_VERSION_SPANNING)),
+        ("Level", level),
+    ]);
+    write_region(&dir, &chunk);
+
+    let (map, report) = import(&dir);
+    check_nodes(&map);
+    assert_eq!(report.chunks, 1);
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn incomplete_chunks_skipped() {
+    let dir = temp_dir("anvil-incomplete");
+
+    let chunk = Nbt::Compound(vec![
+        ("DataVersion", Nbt::Int(DATA_VERSION_PADDED)),
+        ("Status", Nbt::String("minecraft:features")),
+        ("sections", Nbt::List(Vec::new())),
+    ]);
+    write_region(&dir, &chunk);
+
+    let (map, report) = import(&dir);
+    assert_eq!(report.chunks, 0);
+    assert_eq!(report.skipped_chunks, 1);
+    assert!(map.list_blocks().unwrap().is_empty());
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
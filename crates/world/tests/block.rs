@@ -0,0 +1,102 @@
+use glam::{IVec3, ivec3};
+use world::{Block, Node, NodeMetadata};
+
+fn decompress(data: &[u8]) -> Vec<u8> {
+    assert_eq!(data[0], 29);
+    zstd::stream::decode_all(&data[1..]).unwrap()
+}
+
+fn compress(payload: &[u8]) -> Vec<u8> {
+    let mut data = vec![29];
+    zstd::stream::copy_encode(payload, &mut data, 0).unwrap();
+    data
+}
+
+#[test]
+fn damaged_extras_survive_reading() {
+    let mut block = Block::new();
+    let mut metadata = NodeMetadata::default();
+    metadata.set_str("infotext", "chest");
+    block.set_metadata(IVec3::new(1, 2, 3), metadata).unwrap();
+
+    // Cutting off the end of the node timers makes the extras unreadable.
+    let mut payload = decompress(&block.serialize_data().unwrap());
+    payload.pop();
+    let data = compress(&payload);
+
+    assert!(Block::parse_data(&data).is_err());
+    let block = Block::parse_data_lazy(&data).unwrap();
+    assert_eq!(block.metadata().count(), 0);
+
+    let reserialized = decompress(&block.serialize_data().unwrap());
+    assert_eq!(reserialized, payload);
+}
+
+#[test]
+fn modified_extras_replace_damaged_ones() {
+    let mut block = Block::new();
+    let mut metadata = NodeMetadata::default();
+    metadata.set_str("infotext", "chest");
+    block
+        .set_metadata(IVec3::new(1, 2, 3), metadata.clone())
+        .unwrap();
+
+    let mut payload = decompress(&block.serialize_data().unwrap());
+    payload.pop();
+
+    let mut block = Block::parse_data_lazy(&compress(&payload)).unwrap();
+    block.set_metadata(IVec3::new(1, 2, 3), metadata).unwrap();
+
+    let reparsed = Block::parse_data(&block.serialize_data().unwrap()).unwrap();
+    let (pos, metadata) = reparsed.metadata().next().unwrap();
+    assert_eq!(pos, IVec3::new(1, 2, 3));
+    assert_eq!(metadata.get_str("infotext"), Some("chest"));
+}
+
+fn node(id: u16) -> Node {
+    Node {
+        id,
+        param1: id as u8,
+        param2: (id >> 8) as u8,
+    }
+}
+
+#[test]
+fn uniform_nodes() {
+    let mut block = Block::new();
+    let air = block.uniform_node().unwrap();
+
+    block.set_node(ivec3(3, 4, 5), air).unwrap();
+    assert_eq!(block.uniform_node(), Some(air));
+
+    block.set_node(ivec3(3, 4, 5), node(1)).unwrap();
+    assert_eq!(block.uniform_node(), None);
+    assert_eq!(block.get_node(ivec3(3, 4, 5)), Some(node(1)));
+    assert_eq!(block.get_node(ivec3(4, 4, 5)), Some(air));
+
+    // Parsing packs the nodes again, which drops unused palette entries.
+    block.set_node(ivec3(3, 4, 5), air).unwrap();
+    let block = Block::parse_data(&block.serialize_data().unwrap()).unwrap();
+    assert_eq!(block.uniform_node(), Some(air));
+}
+
+#[test]
+fn growing_palette() {
+    let mut block = Block::new();
+    let positions: Vec<IVec3> = block.nodes().map(|(pos, _)| pos).collect();
+
+    // Crosses every index width up to 16 bits, keeping earlier nodes intact.
+    for (i, &pos) in positions.iter().enumerate() {
+        block.set_node(pos, node(i as u16 % 300 + 1)).unwrap();
+
+        if i.is_power_of_two() || i == 299 {
+            for (j, &pos) in positions[..=i].iter().enumerate() {
+                assert_eq!(block.get_node(pos), Some(node(j as u16 % 300 + 1)));
+            }
+        }
+    }
+
+    let parsed = Block::parse_data(&block.serialize_data().unwrap()).unwrap();
+    assert!(parsed.nodes().eq(block.nodes()));
+    assert!(parsed.memory_usage() > Block::new().memory_usage());
+}
@@ -0,0 +1,130 @@
+use glam::{IVec3, ivec3};
+use world::{
+    Block, CheckOptions, Map, Node, Problem, ProblemKind, RepairAction, SqliteBackend, check_map,
+};
+
+const STONE: Node = Node {
+    id: 1,
+    param1: 0,
+    param2: 0,
+};
+
+fn memory_map() -> Map {
+    Map::new(SqliteBackend::create(":memory:").unwrap())
+}
+
+fn stone_block() -> Block {
+    let mut block = Block::new();
+    assert_eq!(block.get_or_insert_id("default:stone"), STONE.id);
+    block.set_node(IVec3::ZERO, STONE).unwrap();
+    block.set_timestamp(100);
+    block
+}
+
+fn payload(block: &Block) -> Vec<u8> {
+    let data = block.serialize_data().unwrap();
+    zstd::decode_all(&data[1..]).unwrap()
+}
+
+fn data(payload: &[u8]) -> Vec<u8> {
+    let mut data = vec![29];
+    data.extend(zstd::encode_all(payload, 0).unwrap());
+    data
+}
+
+// Header, mapping count, (0, "air") and (1, "default:stone"), node widths.
+const NODES_OFFSET: usize = 8 + 2 + 7 + 17 + 2;
+const EXTRAS_OFFSET: usize = NODES_OFFSET + 4096 * 4;
+
+fn check(map: &Map, repair: bool) -> Vec<Problem> {
+    let mut problems = Vec::new();
+    let options = CheckOptions { repair };
+    check_map(map, &options, |problem| problems.push(problem.clone())).unwrap();
+    problems
+}
+
+#[test]
+fn healthy_map() {
+    let map = memory_map();
+    map.set_block(ivec3(0, 0, 0), &stone_block()).unwrap();
+    map.set_block(ivec3(0, 1, 0), &Block::new()).unwrap();
+
+    let mut problems = Vec::new();
+    let summary = check_map(&map, &CheckOptions::default(), |p| problems.push(p.clone())).unwrap();
+
+    assert!(problems.is_empty());
+    assert_eq!(summary.blocks, 2);
+    assert_eq!(summary.problems, 0);
+}
+
+#[test]
+fn truncated_nodes() {
+    let map = memory_map();
+    let payload = payload(&stone_block());
+    map.set_block_data(IVec3::ZERO, &data(&payload[..NODES_OFFSET + 100]))
+        .unwrap();
+
+    let problems = check(&map, false);
+    assert_eq!(problems.len(), 1);
+    assert_eq!(problems[0].kind, ProblemKind::Truncated);
+    assert_eq!(problems[0].repair, RepairAction::DeleteBlock);
+    assert!(!problems[0].repaired);
+    assert_eq!(map.list_blocks().unwrap(), vec![IVec3::ZERO]);
+
+    let problems = check(&map, true);
+    assert!(problems[0].repaired);
+    assert!(map.list_blocks().unwrap().is_empty());
+}
+
+#[test]
+fn unsupported_version() {
+    let map = memory_map();
+    let mut data = stone_block().serialize_data().unwrap();
+    data[0] = 20;
+    map.set_block_data(IVec3::ZERO, &data).unwrap();
+
+    let problems = check(&map, true);
+    assert_eq!(problems[0].kind, ProblemKind::UnsupportedVersion);
+    assert_eq!(problems[0].repair, RepairAction::None);
+    assert!(!problems[0].repaired);
+    assert_eq!(map.get_block_data(IVec3::ZERO).unwrap(), data);
+}
+
+#[test]
+fn invalid_metadata() {
+    let map = memory_map();
+    let payload = payload(&stone_block());
+    map.set_block_data(IVec3::ZERO, &data(&payload[..EXTRAS_OFFSET + 1]))
+        .unwrap();
+
+    let problems = check(&map, true);
+    assert_eq!(problems[0].kind, ProblemKind::InvalidMetadata);
+    assert_eq!(problems[0].repair, RepairAction::DropMetadata);
+    assert!(problems[0].repaired);
+
+    let block = map.get_block(IVec3::ZERO).unwrap();
+    assert_eq!(block.get_node(IVec3::ZERO), Some(STONE));
+    assert_eq!(block.timestamp(), 101);
+    assert!(check(&map, false).is_empty());
+}
+
+#[test]
+fn missing_mapping() {
+    let map = memory_map();
+
+    // Drop the mapping of `default:stone`.
+    let mut payload = payload(&stone_block());
+    payload[8..10].copy_from_slice(&1u16.to_be_bytes());
+    payload.drain(17..34);
+    map.set_block_data(IVec3::ZERO, &data(&payload)).unwrap();
+
+    let problems = check(&map, true);
+    assert_eq!(problems[0].kind, ProblemKind::MissingMapping);
+    assert_eq!(problems[0].message, "node ids without a name: 1");
+    assert_eq!(problems[0].repair, RepairAction::MapToAir);
+    assert!(problems[0].repaired);
+
+    let block = map.get_block(IVec3::ZERO).unwrap();
+    assert_eq!(block.get_name_by_id(STONE.id), Some("air"));
+    assert_eq!(block.timestamp(), 101);
+}
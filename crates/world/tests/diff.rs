@@ -0,0 +1,149 @@
+use glam::{IVec3, ivec3};
+use world::{
+    Block, BlockChange, BlockChangeKind, DiffOptions, DiffSummary, Map, NamedNode, Node,
+    SqliteBackend, diff_maps,
+};
+
+fn memory_map() -> Map {
+    Map::new(SqliteBackend::create(":memory:").unwrap())
+}
+
+fn block_with(name: &str, pos: IVec3, timestamp: u32) -> Block {
+    let mut block = Block::new();
+    let id = block.get_or_insert_id(name);
+    block
+        .set_node(
+            pos,
+            Node {
+                id,
+                param1: 0,
+                param2: 0,
+            },
+        )
+        .unwrap();
+    block.set_timestamp(timestamp);
+    block
+}
+
+fn diff(old: &Map, new: &Map, options: &DiffOptions) -> (Vec<BlockChange>, DiffSummary) {
+    let mut changes = Vec::new();
+    let summary = diff_maps(old, new, options, |change| changes.push(change.clone())).unwrap();
+    (changes, summary)
+}
+
+#[test]
+fn added_removed_and_modified() {
+    let old = memory_map();
+    let new = memory_map();
+
+    old.set_block(ivec3(0, 0, 0), &Block::new()).unwrap();
+    new.set_block(
+        ivec3(0, 0, 0),
+        &block_with("default:stone", ivec3(1, 2, 3), 0),
+    )
+    .unwrap();
+    old.set_block(ivec3(1, 0, 0), &Block::new()).unwrap();
+    new.set_block(ivec3(2, 0, 0), &Block::new()).unwrap();
+    old.set_block(ivec3(3, 0, 0), &Block::new()).unwrap();
+    new.set_block(ivec3(3, 0, 0), &Block::new()).unwrap();
+
+    let (changes, summary) = diff(&old, &new, &DiffOptions::default());
+
+    let kinds: Vec<_> = changes.iter().map(|c| (c.pos, c.kind)).collect();
+    assert_eq!(
+        kinds,
+        vec![
+            (ivec3(0, 0, 0), BlockChangeKind::Modified),
+            (ivec3(1, 0, 0), BlockChangeKind::Removed),
+            (ivec3(2, 0, 0), BlockChangeKind::Added),
+        ]
+    );
+
+    let nodes = &changes[0].nodes;
+    assert_eq!(nodes.len(), 1);
+    assert_eq!(nodes[0].pos, ivec3(1, 2, 3));
+    assert_eq!(nodes[0].old.name, "air");
+    assert_eq!(
+        nodes[0].new,
+        NamedNode {
+            name: "default:stone".to_string(),
+            param1: 0,
+            param2: 0,
+        }
+    );
+
+    assert_eq!(summary.added_blocks, 1);
+    assert_eq!(summary.removed_blocks, 1);
+    assert_eq!(summary.modified_blocks, 1);
+    assert_eq!(summary.unchanged_blocks, 1);
+
+    let stone = &summary.nodes["default:stone"];
+    assert_eq!((stone.added, stone.removed), (1, 0));
+    let air = &summary.nodes["air"];
+    assert_eq!((air.added, air.removed), (4096, 4097));
+}
+
+#[test]
+fn param1_ignored_by_default() {
+    let old = memory_map();
+    let new = memory_map();
+
+    let mut lit = Block::new();
+    lit.set_node(
+        IVec3::ZERO,
+        Node {
+            id: 0,
+            param1: 0xff,
+            param2: 0,
+        },
+    )
+    .unwrap();
+
+    old.set_block(IVec3::ZERO, &Block::new()).unwrap();
+    new.set_block(IVec3::ZERO, &lit).unwrap();
+
+    let (changes, summary) = diff(&old, &new, &DiffOptions::default());
+    assert!(changes.is_empty());
+    assert_eq!(summary.unchanged_blocks, 1);
+
+    let options = DiffOptions {
+        compare_param1: true,
+        ..Default::default()
+    };
+    let (changes, _) = diff(&old, &new, &options);
+    assert_eq!(changes.len(), 1);
+    assert_eq!(changes[0].nodes[0].new.param1, 0xff);
+}
+
+#[test]
+fn trusted_timestamps() {
+    let old = memory_map();
+    let new = memory_map();
+
+    old.set_block(IVec3::ZERO, &Block::new()).unwrap();
+    new.set_block(
+        IVec3::ZERO,
+        &block_with("default:stone", IVec3::ZERO, u32::MAX),
+    )
+    .unwrap();
+
+    let mut old_block = Block::new();
+    old_block.set_timestamp(100);
+    old.set_block(IVec3::X, &old_block).unwrap();
+    new.set_block(IVec3::X, &block_with("default:stone", IVec3::ZERO, 100))
+        .unwrap();
+
+    let options = DiffOptions {
+        trust_timestamps: true,
+        ..Default::default()
+    };
+
+    // Undefined timestamps are compared anyway, equal ones are not.
+    let (changes, summary) = diff(&old, &new, &options);
+    assert_eq!(changes.len(), 1);
+    assert_eq!(changes[0].pos, IVec3::ZERO);
+    assert_eq!(summary.unchanged_blocks, 1);
+
+    let (changes, _) = diff(&old, &new, &DiffOptions::default());
+    assert_eq!(changes.len(), 2);
+}
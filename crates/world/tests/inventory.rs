@@ -0,0 +1,67 @@
+use world::{Inventory, ItemStack};
+
+#[test]
+fn item_strings() {
+    for s in [
+        "default:dirt",
+        "default:dirt 99",
+        "default:pick_steel 1 3000",
+        "\"weird name\" 2",
+    ] {
+        let stack: ItemStack = s.parse().unwrap();
+        assert_eq!(stack.to_string(), s);
+    }
+
+    let stack: ItemStack = "default:pick_steel 1 3000".parse().unwrap();
+    assert_eq!(stack.name, "default:pick_steel");
+    assert_eq!(stack.count, 1);
+    assert_eq!(stack.wear, 3000);
+
+    assert!("".parse::<ItemStack>().unwrap().is_empty());
+}
+
+#[test]
+fn item_metadata() {
+    let s = r#"default:pick_steel 1 3000 "\u0001description\u0002Pick\u0003""#;
+    let stack: ItemStack = s.parse().unwrap();
+
+    assert_eq!(stack.metadata["description"], "Pick");
+    assert_eq!(stack.to_string(), s);
+}
+
+#[test]
+fn legacy_item_metadata() {
+    // Items from before metadata had fields keep a single string.
+    let s = r#"default:book 1 0 "Some text""#;
+    let stack: ItemStack = s.parse().unwrap();
+
+    assert_eq!(stack.metadata[""], "Some text");
+    assert_eq!(stack.to_string(), s);
+}
+
+#[test]
+fn inventory_round_trip() {
+    let text = "\
+List main 4
+Width 2
+Item default:dirt 99
+Empty
+Item default:pick_steel 1 3000
+Empty
+EndInventoryList
+List craft 1
+Width 0
+Empty
+EndInventoryList
+EndInventory
+";
+
+    let inventory = Inventory::parse(text).unwrap();
+    let main = inventory.list("main").unwrap();
+    assert_eq!(main.width, 2);
+    assert_eq!(main.items.len(), 4);
+    assert_eq!(main.items[0].count, 99);
+    assert!(main.items[1].is_empty());
+
+    assert_eq!(inventory.serialize(), text);
+}
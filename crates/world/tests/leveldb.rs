@@ -0,0 +1,47 @@
+#![cfg(feature = "leveldb")]
+
+use glam::{IVec3, ivec3};
+use world::{LevelDbBackend, Map};
+
+fn temp_dir(name: &str) -> std::path::PathBuf {
+    let path = std::env::temp_dir().join(format!("world-test-{name}-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&path);
+    path
+}
+
+fn blocks_in_box(map: &Map, min: IVec3, max: IVec3) -> Vec<IVec3> {
+    let mut positions: Vec<_> = map
+        .get_blocks_in_box(min, max)
+        .unwrap()
+        .into_iter()
+        .map(|(pos, _)| pos)
+        .collect();
+    positions.sort_by_key(|pos| (pos.z, pos.y, pos.x));
+    positions
+}
+
+#[test]
+fn box_spanning_digit_counts() {
+    let path = temp_dir("digits");
+    let map = Map::new(LevelDbBackend::create(&path).unwrap());
+
+    for x in -80..=80 {
+        map.set_block_data(ivec3(x, 0, 0), &[29]).unwrap();
+    }
+
+    // Keys from 5 to 60 and from -60 to -5 have one and two digits.
+    let positions = blocks_in_box(&map, ivec3(5, 0, 0), ivec3(60, 0, 0));
+    let expected: Vec<_> = (5..=60).map(|x| ivec3(x, 0, 0)).collect();
+    assert_eq!(positions, expected);
+
+    let positions = blocks_in_box(&map, ivec3(-60, 0, 0), ivec3(-5, 0, 0));
+    let expected: Vec<_> = (-60..=-5).map(|x| ivec3(x, 0, 0)).collect();
+    assert_eq!(positions, expected);
+
+    let positions = blocks_in_box(&map, ivec3(-12, 0, 0), ivec3(12, 0, 0));
+    let expected: Vec<_> = (-12..=12).map(|x| ivec3(x, 0, 0)).collect();
+    assert_eq!(positions, expected);
+
+    drop(map);
+    std::fs::remove_dir_all(path).unwrap();
+}
@@ -0,0 +1,119 @@
+use glam::{IVec3, ivec3};
+use world::{Block, LIGHT_SUN, LightBank, Map, Node, NodeDefRegistry, SqliteBackend, relight};
+
+fn memory_map() -> Map {
+    Map::new(SqliteBackend::create(":memory:").unwrap())
+}
+
+fn node_defs() -> NodeDefRegistry {
+    NodeDefRegistry::from_json(
+        r#"{
+            "default:torch": {
+                "drawtype": "torchlike",
+                "paramtype": "light",
+                "sunlight_propagates": true,
+                "walkable": false,
+                "light_source": 14
+            }
+        }"#,
+    )
+    .unwrap()
+}
+
+fn air_block(flags: u8) -> Block {
+    let mut block = Block::new();
+    block.set_flags(flags);
+    block.set_lighting_complete(Block::LIGHTING_COMPLETE);
+    block
+}
+
+fn light_at(map: &Map, block_pos: IVec3, pos: IVec3, bank: LightBank) -> u8 {
+    map.get_block(block_pos)
+        .unwrap()
+        .get_node(pos)
+        .unwrap()
+        .light(bank)
+}
+
+#[test]
+fn light_source() {
+    let map = memory_map();
+
+    let mut block = air_block(Block::FLAG_UNDERGROUND);
+    let torch = block.get_or_insert_id("default:torch");
+    block
+        .set_node(
+            ivec3(8, 8, 8),
+            Node {
+                id: torch,
+                param1: 0,
+                param2: 0,
+            },
+        )
+        .unwrap();
+    map.set_block(IVec3::ZERO, &block).unwrap();
+
+    relight(&map, &node_defs(), IVec3::ZERO, IVec3::ZERO).unwrap();
+
+    for bank in [LightBank::Day, LightBank::Night] {
+        assert_eq!(light_at(&map, IVec3::ZERO, ivec3(8, 8, 8), bank), 14);
+        assert_eq!(light_at(&map, IVec3::ZERO, ivec3(10, 8, 8), bank), 12);
+        assert_eq!(light_at(&map, IVec3::ZERO, ivec3(9, 7, 9), bank), 11);
+        assert_eq!(light_at(&map, IVec3::ZERO, ivec3(0, 0, 0), bank), 0);
+    }
+
+    let block = map.get_block(IVec3::ZERO).unwrap();
+    assert_eq!(block.lighting_complete(), Block::LIGHTING_COMPLETE);
+}
+
+#[test]
+fn sunlight_below_gap() {
+    let map = memory_map();
+    map.set_block(ivec3(0, 2, 0), &air_block(0)).unwrap();
+    map.set_block(ivec3(0, 0, 0), &air_block(0)).unwrap();
+
+    relight(&map, &node_defs(), IVec3::ZERO, ivec3(0, 2, 0)).unwrap();
+
+    for block_pos in [ivec3(0, 2, 0), IVec3::ZERO] {
+        assert_eq!(
+            light_at(&map, block_pos, ivec3(5, 0, 5), LightBank::Day),
+            LIGHT_SUN
+        );
+        assert_eq!(
+            light_at(&map, block_pos, ivec3(5, 0, 5), LightBank::Night),
+            0
+        );
+    }
+}
+
+#[test]
+fn underground_without_block_above() {
+    let map = memory_map();
+    map.set_block(IVec3::ZERO, &air_block(Block::FLAG_UNDERGROUND))
+        .unwrap();
+
+    relight(&map, &node_defs(), IVec3::ZERO, IVec3::ZERO).unwrap();
+
+    assert_eq!(
+        light_at(&map, IVec3::ZERO, ivec3(5, 5, 5), LightBank::Day),
+        0
+    );
+}
+
+#[test]
+fn neighbours_marked_incomplete() {
+    let map = memory_map();
+    map.set_block(IVec3::ZERO, &air_block(0)).unwrap();
+    map.set_block(ivec3(1, 0, 0), &air_block(0)).unwrap();
+    map.set_block(ivec3(0, -1, 0), &air_block(0)).unwrap();
+    map.set_block(ivec3(1, 1, 0), &air_block(0)).unwrap();
+
+    relight(&map, &node_defs(), IVec3::ZERO, IVec3::ZERO).unwrap();
+
+    let lighting_complete = |pos| map.get_block(pos).unwrap().lighting_complete();
+
+    assert_eq!(lighting_complete(IVec3::ZERO), Block::LIGHTING_COMPLETE);
+    assert_eq!(lighting_complete(ivec3(1, 0, 0)), 0);
+    assert_eq!(lighting_complete(ivec3(0, -1, 0)), 0);
+    assert_eq!(lighting_complete(ivec3(1, 1, 0)), Block::LIGHTING_COMPLETE);
+}
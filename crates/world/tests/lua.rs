@@ -0,0 +1,48 @@
+use world::{LuaError, LuaValue};
+
+#[test]
+fn long_runs_of_minus_signs() {
+    let data = format!("return {}1", "- ".repeat(200_000));
+    let value = LuaValue::deserialize(data.as_bytes()).unwrap();
+    assert_eq!(value, LuaValue::Number(1.0));
+
+    let data = format!("return {}1", "- ".repeat(200_001));
+    let value = LuaValue::deserialize(data.as_bytes()).unwrap();
+    assert_eq!(value, LuaValue::Number(-1.0));
+}
+
+#[test]
+fn long_chains_of_references() {
+    let mut data = String::from("local _={};");
+    for i in 1..200_000 {
+        data += &format!("_[{i}]=_[{}];", i + 1);
+    }
+    data += "_[200000]=1;return _[1]";
+
+    let result = LuaValue::deserialize(data.as_bytes());
+    assert!(matches!(result, Err(LuaError::TooComplex)));
+}
+
+#[test]
+fn short_chains_of_references() {
+    let data = "local _={};_[1]=_[2];_[2]=_[3];_[3]={1,2};return {a=_[1],b=_[2]}";
+    let value = LuaValue::deserialize(data.as_bytes()).unwrap();
+
+    let table = value.as_table().unwrap();
+    let shared = table.get("a").unwrap().as_table().unwrap();
+    assert_eq!(shared.sequence().count(), 2);
+    assert_eq!(table.get("a"), table.get("b"));
+}
+
+#[test]
+fn push_after_removing_from_the_sequence() {
+    let mut table = world::LuaTable::new();
+    for i in 0..5 {
+        table.push(i);
+    }
+    table.insert(3, LuaValue::Nil);
+    table.push(10);
+
+    let sequence: Vec<_> = table.sequence().filter_map(LuaValue::as_i64).collect();
+    assert_eq!(sequence, [0, 1, 10, 3, 4]);
+}
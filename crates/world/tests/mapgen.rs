@@ -0,0 +1,131 @@
+use glam::{IVec3, ivec3};
+use world::{
+    Block, Generator, GeneratorOptions, LIGHT_SUN, LightBank, Map, NodeArea, SqliteBackend,
+    Terrain, generate_map,
+};
+
+fn memory_map() -> Map {
+    Map::new(SqliteBackend::create(":memory:").unwrap())
+}
+
+fn name_at(block: &Block, pos: IVec3) -> &str {
+    block
+        .get_name_by_id(block.get_node(pos).unwrap().id)
+        .unwrap()
+}
+
+#[test]
+fn flat_layers() {
+    let generator = Generator::new(GeneratorOptions {
+        terrain: Terrain::Flat,
+        ground_level: 8,
+        water_level: -20,
+        ..Default::default()
+    });
+
+    let block = generator.generate_block(IVec3::ZERO);
+    assert_eq!(name_at(&block, ivec3(5, 9, 5)), "air");
+    assert_eq!(name_at(&block, ivec3(5, 8, 5)), "default:dirt_with_grass");
+    assert_eq!(name_at(&block, ivec3(5, 6, 5)), "default:dirt");
+    assert_eq!(name_at(&block, ivec3(5, 5, 5)), "default:stone");
+
+    let air = block.get_node(ivec3(5, 9, 5)).unwrap();
+    assert_eq!(air.light(LightBank::Day), LIGHT_SUN);
+    assert_eq!(air.light(LightBank::Night), 0);
+
+    let flags = Block::FLAG_GENERATED | Block::FLAG_DAY_NIGHT_DIFFERS;
+    assert_eq!(block.flags(), flags);
+    assert_eq!(block.lighting_complete(), Block::LIGHTING_COMPLETE);
+
+    let below = generator.generate_block(-IVec3::Y);
+    assert_eq!(
+        below.flags(),
+        Block::FLAG_GENERATED | Block::FLAG_UNDERGROUND
+    );
+    assert!(
+        below
+            .nodes()
+            .all(|(pos, _)| name_at(&below, pos) == "default:stone")
+    );
+}
+
+#[test]
+fn flooded_below_water_level() {
+    let generator = Generator::new(GeneratorOptions {
+        terrain: Terrain::Flat,
+        ground_level: -3,
+        water_level: 1,
+        ..Default::default()
+    });
+
+    let block = generator.generate_block(IVec3::ZERO);
+    assert_eq!(name_at(&block, ivec3(0, 1, 0)), "default:water_source");
+    assert_eq!(name_at(&block, ivec3(0, 2, 0)), "air");
+
+    let light = |y| {
+        block
+            .get_node(ivec3(0, y, 0))
+            .unwrap()
+            .light(LightBank::Day)
+    };
+    assert_eq!(light(1), LIGHT_SUN - 1);
+    assert_eq!(light(0), LIGHT_SUN - 2);
+
+    let below = generator.generate_block(-IVec3::Y);
+    assert_eq!(name_at(&below, ivec3(0, 13, 0)), "default:dirt");
+}
+
+#[test]
+fn same_seed_same_terrain() {
+    let options = |seed| GeneratorOptions {
+        seed,
+        terrain: Terrain::Hills,
+        ..Default::default()
+    };
+
+    let a = Generator::new(options(7));
+    let b = Generator::new(options(7));
+    let c = Generator::new(options(8));
+
+    let heights = |generator: &Generator| -> Vec<i32> {
+        (0..64)
+            .map(|i| generator.surface_height(i * 37, i * -53))
+            .collect()
+    };
+
+    assert_eq!(heights(&a), heights(&b));
+    assert_ne!(heights(&a), heights(&c));
+
+    let pos = ivec3(3, -2, -5);
+    assert_eq!(
+        a.generate_block(pos).serialize_data().unwrap(),
+        b.generate_block(pos).serialize_data().unwrap()
+    );
+
+    let range = GeneratorOptions::default().height_range;
+    assert!(heights(&a).iter().all(|h| (h - 8).abs() <= range));
+}
+
+#[test]
+fn existing_blocks_kept() {
+    let map = memory_map();
+    let generator = Generator::new(GeneratorOptions::default());
+
+    let mut existing = Block::new();
+    existing.set_timestamp(42);
+    map.set_block(IVec3::ZERO, &existing).unwrap();
+
+    let area = NodeArea::new(ivec3(0, 0, 0), ivec3(31, 15, 0));
+    let mut progress = Vec::new();
+    let report = generate_map(&map, &generator, area, false, |p| progress.push(p.done)).unwrap();
+
+    assert_eq!(report.generated, 1);
+    assert_eq!(report.skipped, 1);
+    assert_eq!(progress, [2]);
+    assert_eq!(map.get_block(IVec3::ZERO).unwrap().timestamp(), 42);
+    assert_eq!(map.list_blocks().unwrap().len(), 2);
+
+    let report = generate_map(&map, &generator, area, true, |_| {}).unwrap();
+    assert_eq!(report.generated, 2);
+    assert_ne!(map.get_block(IVec3::ZERO).unwrap().timestamp(), 42);
+}
@@ -0,0 +1,185 @@
+use std::io::Write;
+
+use glam::{IVec3, ivec3};
+use world::{Block, Map, Node, NodeArea, Schematic, SchematicError, SqliteBackend};
+
+/// Builds an MTS file by hand, with nodes given as (id, param1, param2).
+fn mts(
+    version: u16,
+    size: IVec3,
+    slices: &[u8],
+    names: &[&str],
+    nodes: &[(u16, u8, u8)],
+) -> Vec<u8> {
+    let mut data = b"MTSM".to_vec();
+    data.extend(version.to_be_bytes());
+    for size in size.to_array() {
+        data.extend((size as u16).to_be_bytes());
+    }
+    data.extend(slices);
+
+    data.extend((names.len() as u16).to_be_bytes());
+    for name in names {
+        data.extend((name.len() as u16).to_be_bytes());
+        data.extend(name.as_bytes());
+    }
+
+    let mut body = Vec::new();
+    body.extend(nodes.iter().flat_map(|(id, _, _)| id.to_be_bytes()));
+    body.extend(nodes.iter().map(|(_, param1, _)| param1));
+    body.extend(nodes.iter().map(|(_, _, param2)| param2));
+
+    let mut encoder = flate2::write::ZlibEncoder::new(data, flate2::Compression::default());
+    encoder.write_all(&body).unwrap();
+    encoder.finish().unwrap()
+}
+
+#[test]
+fn version_4() {
+    let data = mts(
+        4,
+        ivec3(2, 1, 2),
+        &[0x7f],
+        &["air", "default:stone"],
+        &[(0, 0x00, 0), (1, 0x7f, 0), (1, 0xff, 3), (0, 0x40, 0)],
+    );
+
+    let schematic = Schematic::read_mts(&data).unwrap();
+    assert_eq!(schematic.size(), ivec3(2, 1, 2));
+    assert_eq!(schematic.names(), ["air", "default:stone"]);
+    assert_eq!(
+        schematic.slice_probability(0),
+        Some(Schematic::PROBABILITY_ALWAYS)
+    );
+    assert_eq!(schematic.slice_probability(1), None);
+
+    // Nodes are ordered by z, then y, then x.
+    assert_eq!(
+        schematic.get_node(ivec3(1, 0, 0)),
+        Some(Node {
+            id: 1,
+            param1: Schematic::PROBABILITY_ALWAYS,
+            param2: 0,
+        })
+    );
+    assert_eq!(
+        schematic.get_node(ivec3(0, 0, 1)),
+        Some(Node {
+            id: 1,
+            param1: Schematic::FORCE_PLACE | Schematic::PROBABILITY_ALWAYS,
+            param2: 3,
+        })
+    );
+    assert_eq!(schematic.get_node(ivec3(1, 0, 1)).unwrap().param1, 0x40);
+
+    assert_eq!(
+        Schematic::read_mts(&schematic.write_mts().unwrap()).unwrap(),
+        schematic
+    );
+}
+
+#[test]
+fn version_3_probabilities() {
+    let data = mts(
+        3,
+        ivec3(1, 2, 1),
+        &[0xff, 0x80],
+        &["ignore", "default:stone"],
+        &[(0, 0x00, 0), (1, 0xfe, 0)],
+    );
+
+    let schematic = Schematic::read_mts(&data).unwrap();
+    assert_eq!(schematic.names(), ["air", "default:stone"]);
+    assert_eq!(schematic.slice_probability(0), Some(0x7f));
+    assert_eq!(schematic.slice_probability(1), Some(0x40));
+    assert_eq!(schematic.get_node(ivec3(0, 1, 0)).unwrap().param1, 0x7f);
+
+    // Written back as version 4.
+    let written = schematic.write_mts().unwrap();
+    assert_eq!(&written[4..6], &4u16.to_be_bytes());
+    assert_eq!(Schematic::read_mts(&written).unwrap(), schematic);
+}
+
+#[test]
+fn invalid_files() {
+    assert!(matches!(
+        Schematic::read_mts(b"WESM\0\x04"),
+        Err(SchematicError::InvalidSignature)
+    ));
+
+    let data = mts(2, ivec3(1, 1, 1), &[], &["air"], &[(0, 0x7f, 0)]);
+    assert!(matches!(
+        Schematic::read_mts(&data),
+        Err(SchematicError::UnsupportedVersion(2))
+    ));
+
+    let data = mts(4, ivec3(1, 1, 1), &[0x7f], &["air"], &[(1, 0x7f, 0)]);
+    assert!(matches!(
+        Schematic::read_mts(&data),
+        Err(SchematicError::Parse(_))
+    ));
+
+    let data = mts(4, ivec3(2, 2, 2), &[0x7f; 2], &["air"], &[(0, 0, 0); 4]);
+    assert!(matches!(
+        Schematic::read_mts(&data),
+        Err(SchematicError::Parse(_))
+    ));
+}
+
+#[test]
+fn place_and_copy() {
+    let map = Map::new(SqliteBackend::create(":memory:").unwrap());
+
+    let mut schematic = Schematic::new(ivec3(2, 2, 2));
+    let stone = schematic.get_or_insert_id("default:stone");
+    for (pos, _) in schematic.clone().nodes() {
+        let param1 = match pos {
+            IVec3::ZERO => Schematic::PROBABILITY_NEVER,
+            _ => Schematic::PROBABILITY_ALWAYS,
+        };
+        let node = Node {
+            id: stone,
+            param1,
+            param2: pos.x as u8,
+        };
+        schematic.set_node(pos, node).unwrap();
+    }
+
+    // The schematic spans eight blocks, none of which exist yet. Nothing is
+    // placed in the first one, so it isn't created.
+    let placed = schematic
+        .place(&map, IVec3::splat(15), false, None)
+        .unwrap();
+    assert_eq!(placed, 7);
+    assert_eq!(map.list_blocks().unwrap().len(), 7);
+
+    let block = map.get_block(IVec3::ONE).unwrap();
+    assert_eq!(block.flags() & Block::FLAG_GENERATED, Block::FLAG_GENERATED);
+    assert_eq!(block.lighting_complete(), 0);
+
+    let area = NodeArea::new(IVec3::splat(15), IVec3::splat(16));
+    let copy = Schematic::from_map(&map, area).unwrap();
+    assert_eq!(copy.size(), ivec3(2, 2, 2));
+
+    for (pos, node) in copy.nodes() {
+        let (name, param1) = match pos {
+            IVec3::ZERO => ("air", Schematic::PROBABILITY_NEVER),
+            _ => ("default:stone", Schematic::PROBABILITY_ALWAYS),
+        };
+        assert_eq!(copy.get_name_by_id(node.id), Some(name), "{pos}");
+        assert_eq!(node.param1, param1, "{pos}");
+    }
+    assert_eq!(copy.get_node(IVec3::ONE).unwrap().param2, 1);
+
+    // Only air is replaced without forcing.
+    let mut dirt = Schematic::new(IVec3::ONE);
+    let node = Node {
+        id: dirt.get_or_insert_id("default:dirt"),
+        param1: Schematic::PROBABILITY_ALWAYS,
+        param2: 0,
+    };
+    dirt.set_node(IVec3::ZERO, node).unwrap();
+
+    assert_eq!(dirt.place(&map, IVec3::splat(16), false, None).unwrap(), 0);
+    assert_eq!(dirt.place(&map, IVec3::splat(16), true, None).unwrap(), 1);
+}
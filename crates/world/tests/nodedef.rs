@@ -0,0 +1,41 @@
+use world::{DrawType, NodeBoxType, NodeDefRegistry, ParamType, ParamType2};
+
+#[test]
+fn unknown_enum_values() {
+    let data = r#"{
+        "future:node": {
+            "drawtype": "hologram",
+            "paramtype": "shimmer",
+            "paramtype2": "color8dir",
+            "node_box": {"type": "spherical"}
+        }
+    }"#;
+
+    let registry = NodeDefRegistry::from_json(data).unwrap();
+    let def = registry.get("future:node").unwrap();
+
+    assert_eq!(def.drawtype, DrawType::Unknown);
+    assert_eq!(def.paramtype, ParamType::Unknown);
+    assert_eq!(def.paramtype2, ParamType2::Unknown);
+    assert_eq!(def.node_box.as_ref().unwrap().ty, NodeBoxType::Unknown);
+}
+
+#[test]
+fn known_enum_values() {
+    let data = r#"{
+        "default:torch": {
+            "drawtype": "nodebox",
+            "paramtype": "light",
+            "paramtype2": "4dir",
+            "node_box": {"type": "wallmounted"}
+        }
+    }"#;
+
+    let registry = NodeDefRegistry::from_json(data).unwrap();
+    let def = registry.get("default:torch").unwrap();
+
+    assert_eq!(def.drawtype, DrawType::Nodebox);
+    assert_eq!(def.paramtype, ParamType::Light);
+    assert_eq!(def.paramtype2, ParamType2::FourDir);
+    assert_eq!(def.node_box.as_ref().unwrap().ty, NodeBoxType::Wallmounted);
+}
@@ -0,0 +1,195 @@
+use glam::{IVec3, ivec3};
+use world::{Axis, Facedir, FourDir, Param2, ParamType2, Rotation, Transform, Wallmounted};
+
+// `minetest.facedir_to_dir` from builtin/game/item.lua.
+const FACEDIR_TO_DIR: [IVec3; 24] = [
+    ivec3(0, 0, 1),
+    ivec3(1, 0, 0),
+    ivec3(0, 0, -1),
+    ivec3(-1, 0, 0),
+    ivec3(0, -1, 0),
+    ivec3(1, 0, 0),
+    ivec3(0, 1, 0),
+    ivec3(-1, 0, 0),
+    ivec3(0, 1, 0),
+    ivec3(1, 0, 0),
+    ivec3(0, -1, 0),
+    ivec3(-1, 0, 0),
+    ivec3(0, 0, 1),
+    ivec3(0, -1, 0),
+    ivec3(0, 0, -1),
+    ivec3(0, 1, 0),
+    ivec3(0, 0, 1),
+    ivec3(0, 1, 0),
+    ivec3(0, 0, -1),
+    ivec3(0, -1, 0),
+    ivec3(0, 0, 1),
+    ivec3(-1, 0, 0),
+    ivec3(0, 0, -1),
+    ivec3(1, 0, 0),
+];
+
+// `minetest.wallmounted_to_dir` from builtin/game/item.lua.
+const WALLMOUNTED_TO_DIR: [IVec3; 8] = [
+    ivec3(0, 1, 0),
+    ivec3(0, -1, 0),
+    ivec3(1, 0, 0),
+    ivec3(-1, 0, 0),
+    ivec3(0, 0, 1),
+    ivec3(0, 0, -1),
+    ivec3(0, 1, 0),
+    ivec3(0, -1, 0),
+];
+
+const TRANSFORMS: [Transform; 6] = [
+    Transform::Rotate {
+        axis: Axis::X,
+        turns: 1,
+    },
+    Transform::Rotate {
+        axis: Axis::Y,
+        turns: 1,
+    },
+    Transform::Rotate {
+        axis: Axis::Z,
+        turns: 3,
+    },
+    Transform::Flip(Axis::X),
+    Transform::Flip(Axis::Y),
+    Transform::Flip(Axis::Z),
+];
+
+#[test]
+fn facedir_directions() {
+    for (param2, dir) in FACEDIR_TO_DIR.into_iter().enumerate() {
+        assert_eq!(
+            Facedir::new(param2 as u8).direction(),
+            dir,
+            "facedir {param2}"
+        );
+    }
+}
+
+#[test]
+fn facedir_wraps_like_the_engine() {
+    assert_eq!(Facedir::new(24).value(), 0);
+    assert_eq!(Facedir::new(0x20 | 5).value(), 5);
+    assert_eq!(Facedir::from_axis_rotation(3, 2).value(), 14);
+}
+
+#[test]
+fn facedir_rotations_are_distinct() {
+    for param2 in 0..24 {
+        let facedir = Facedir::new(param2);
+        assert_eq!(
+            Facedir::from_rotation_matrix(facedir.rotation_matrix()),
+            facedir
+        );
+    }
+}
+
+#[test]
+fn wallmounted_directions() {
+    for (param2, dir) in WALLMOUNTED_TO_DIR.into_iter().enumerate() {
+        let wallmounted = Wallmounted::new(param2 as u8);
+        assert_eq!(wallmounted.direction(), dir, "wallmounted {param2}");
+    }
+}
+
+#[test]
+fn facedir_transforms_follow_positions() {
+    for transform in TRANSFORMS {
+        for param2 in 0..24 {
+            let facedir = Facedir::new(param2);
+            let transformed = facedir.transformed(transform);
+
+            assert_eq!(
+                transformed.direction(),
+                transform.apply(facedir.direction()),
+                "facedir {param2} after {transform:?}"
+            );
+        }
+    }
+}
+
+#[test]
+fn facedir_full_turn_is_identity() {
+    for axis in [Axis::X, Axis::Y, Axis::Z] {
+        assert_eq!(Rotation::quarter_turns(axis, 4), Rotation::IDENTITY);
+        assert_eq!(
+            Rotation::quarter_turns(axis, -1),
+            Rotation::quarter_turns(axis, 3)
+        );
+    }
+
+    let turn = Transform::Rotate {
+        axis: Axis::Y,
+        turns: 1,
+    };
+    assert_eq!(Facedir::new(0).transformed(turn).value(), 1);
+    assert_eq!(Facedir::new(3).transformed(turn).value(), 0);
+}
+
+#[test]
+fn wallmounted_transforms_follow_positions() {
+    for transform in TRANSFORMS {
+        for param2 in 0..8 {
+            let wallmounted = Wallmounted::new(param2);
+            let transformed = wallmounted.transformed(transform);
+
+            assert_eq!(
+                transformed.direction(),
+                transform.apply(wallmounted.direction()),
+                "wallmounted {param2} after {transform:?}"
+            );
+        }
+    }
+}
+
+#[test]
+fn wallmounted_keeps_turn_on_ceiling() {
+    let turn = Transform::Rotate {
+        axis: Axis::Y,
+        turns: 1,
+    };
+
+    let back = Transform::Rotate {
+        axis: Axis::Y,
+        turns: -1,
+    };
+
+    assert_eq!(Wallmounted::new(0).transformed(turn).value(), 6);
+    assert_eq!(Wallmounted::new(0).transformed(back).value(), 6);
+    assert_eq!(Wallmounted::new(6).transformed(turn).value(), 0);
+    assert_eq!(Wallmounted::new(1).transformed(turn).value(), 7);
+}
+
+#[test]
+fn four_dir_rejects_tilt() {
+    let tilt = Transform::Rotate {
+        axis: Axis::X,
+        turns: 1,
+    };
+    let turn = Transform::Rotate {
+        axis: Axis::Y,
+        turns: 2,
+    };
+
+    assert_eq!(FourDir::new(1).transformed(tilt), None);
+    assert_eq!(FourDir::new(1).transformed(turn), Some(FourDir::new(3)));
+}
+
+#[test]
+fn decode_color_facedir() {
+    assert_eq!(
+        ParamType2::ColorFacedir.decode(0b101_00011),
+        Param2::ColorFacedir {
+            color: 5,
+            facedir: Facedir::new(3)
+        }
+    );
+    assert_eq!(
+        ParamType2::Wallmounted.decode(0xfa),
+        Param2::Wallmounted(Wallmounted::new(2))
+    );
+}
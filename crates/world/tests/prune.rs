@@ -0,0 +1,85 @@
+use glam::{IVec3, ivec3};
+use world::{Block, Map, Node, NodeArea, PruneOptions, PruneRule, SqliteBackend};
+
+fn memory_map() -> Map {
+    Map::new(SqliteBackend::create(":memory:").unwrap())
+}
+
+fn stone_block() -> Block {
+    let mut block = Block::new();
+    let id = block.get_or_insert_id("default:stone");
+    block
+        .set_node(
+            IVec3::ZERO,
+            Node {
+                id,
+                param1: 0,
+                param2: 0,
+            },
+        )
+        .unwrap();
+    block
+}
+
+fn prune(map: &Map, options: &PruneOptions) -> (Vec<IVec3>, world::PruneReport) {
+    let mut pruned = Vec::new();
+    let report = world::prune_map(map, options, None, |pos| pruned.push(pos)).unwrap();
+    (pruned, report)
+}
+
+#[test]
+fn air_only_blocks() {
+    let map = memory_map();
+    map.set_block(ivec3(0, 0, 0), &Block::new()).unwrap();
+    map.set_block(ivec3(1, 0, 0), &stone_block()).unwrap();
+
+    let options = PruneOptions {
+        rules: vec![PruneRule::AirOnly],
+        ..Default::default()
+    };
+    let (pruned, report) = prune(&map, &options);
+
+    assert_eq!(pruned, vec![ivec3(0, 0, 0)]);
+    assert_eq!(report.blocks, 2);
+    assert_eq!(map.list_blocks().unwrap(), vec![ivec3(1, 0, 0)]);
+}
+
+#[test]
+fn unreadable_blocks_are_kept() {
+    let map = memory_map();
+    map.set_block(ivec3(0, 0, 0), &Block::new()).unwrap();
+    map.set_block_data(ivec3(0, 1, 0), &[29, 1, 2, 3]).unwrap();
+    map.set_block(ivec3(0, 2, 0), &Block::new()).unwrap();
+
+    let options = PruneOptions {
+        rules: vec![PruneRule::AirOnly],
+        ..Default::default()
+    };
+    let (pruned, report) = prune(&map, &options);
+
+    assert_eq!(pruned, vec![ivec3(0, 0, 0), ivec3(0, 2, 0)]);
+    assert_eq!(report.skipped, 1);
+    assert_eq!(map.list_blocks().unwrap(), vec![ivec3(0, 1, 0)]);
+}
+
+#[test]
+fn protected_areas_and_dry_runs() {
+    let map = memory_map();
+    for x in 0..4 {
+        map.set_block(ivec3(x, 0, 0), &Block::new()).unwrap();
+    }
+
+    let options = PruneOptions {
+        rules: vec![PruneRule::OutsideArea(NodeArea::new(
+            ivec3(0, 0, 0),
+            ivec3(15, 15, 15),
+        ))],
+        protected: vec![NodeArea::new(ivec3(40, 0, 0), ivec3(40, 0, 0))],
+        dry_run: true,
+    };
+    let (pruned, report) = prune(&map, &options);
+
+    assert_eq!(pruned, vec![ivec3(1, 0, 0), ivec3(3, 0, 0)]);
+    assert_eq!(report.protected, 1);
+    assert_eq!(map.list_blocks().unwrap().len(), 4);
+}
@@ -0,0 +1,142 @@
+use std::io::Write;
+
+use glam::{IVec3, ivec3};
+use world::{BlockMapping, Map, NodeArea, SchematicError, SpongeSchematic, SqliteBackend};
+
+/// Writes a gzipped version 2 schematic of `size` by hand, with the palette
+/// ids in order and `data` as the block data.
+fn version_2(size: IVec3, palette: &[String], data: &[u8]) -> Vec<u8> {
+    fn named(out: &mut Vec<u8>, tag: u8, name: &str) {
+        out.push(tag);
+        out.extend((name.len() as u16).to_be_bytes());
+        out.extend(name.as_bytes());
+    }
+
+    let mut nbt = Vec::new();
+    named(&mut nbt, 10, "Schematic");
+
+    named(&mut nbt, 3, "Version");
+    nbt.extend(2i32.to_be_bytes());
+    for (name, size) in ["Width", "Height", "Length"]
+        .into_iter()
+        .zip(size.to_array())
+    {
+        named(&mut nbt, 2, name);
+        nbt.extend((size as i16).to_be_bytes());
+    }
+
+    named(&mut nbt, 10, "Palette");
+    for (id, state) in palette.iter().enumerate() {
+        named(&mut nbt, 3, state);
+        nbt.extend((id as i32).to_be_bytes());
+    }
+    nbt.push(0);
+
+    named(&mut nbt, 7, "BlockData");
+    nbt.extend((data.len() as i32).to_be_bytes());
+    nbt.extend(data);
+    nbt.push(0);
+
+    let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+    encoder.write_all(&nbt).unwrap();
+    encoder.finish().unwrap()
+}
+
+fn states(count: usize) -> Vec<String> {
+    (0..count).map(|i| format!("test:s{i}")).collect()
+}
+
+#[test]
+fn multi_byte_varints() {
+    let palette = states(130);
+
+    // 129 takes two bytes, 5 takes one.
+    let data = version_2(ivec3(2, 1, 1), &palette, &[0x81, 0x01, 0x05]);
+    let schematic = SpongeSchematic::read(&data).unwrap();
+    assert_eq!(schematic.size(), ivec3(2, 1, 1));
+    assert_eq!(schematic.get_block(ivec3(0, 0, 0)), Some("test:s129"));
+    assert_eq!(schematic.get_block(ivec3(1, 0, 0)), Some("test:s5"));
+
+    let truncated = version_2(ivec3(2, 1, 1), &palette, &[0x81, 0x01, 0x85]);
+    assert!(matches!(
+        SpongeSchematic::read(&truncated),
+        Err(SchematicError::Parse(_))
+    ));
+
+    let trailing = version_2(ivec3(2, 1, 1), &palette, &[0x81, 0x01, 0x05, 0x00]);
+    assert!(SpongeSchematic::read(&trailing).is_err());
+
+    let out_of_palette = version_2(ivec3(2, 1, 1), &palette, &[0x82, 0x01, 0x05]);
+    assert!(SpongeSchematic::read(&out_of_palette).is_err());
+}
+
+#[test]
+fn round_trip() {
+    let mut schematic = SpongeSchematic::new(ivec3(20, 2, 10));
+    let palette = states(300);
+
+    for y in 0..2 {
+        for z in 0..10 {
+            for x in 0..20 {
+                let state = &palette[((y * 10 + z) * 20 + x) as usize % palette.len()];
+                schematic.set_block(ivec3(x, y, z), state).unwrap();
+            }
+        }
+    }
+
+    for version in SpongeSchematic::VERSIONS {
+        let written = schematic.write(version).unwrap();
+        assert_eq!(SpongeSchematic::read(&written).unwrap(), schematic);
+    }
+
+    assert!(matches!(
+        schematic.write(1),
+        Err(SchematicError::UnsupportedVersion(1))
+    ));
+    assert!(matches!(
+        SpongeSchematic::read(b"not gzip"),
+        Err(SchematicError::InvalidSignature)
+    ));
+}
+
+#[test]
+fn place_and_copy() {
+    let map = Map::new(SqliteBackend::create(":memory:").unwrap());
+    let mapping = BlockMapping::minetest_game();
+
+    let mut schematic = SpongeSchematic::new(ivec3(1, 1, 3));
+    schematic
+        .set_block(ivec3(0, 0, 0), "minecraft:stone")
+        .unwrap();
+    schematic
+        .set_block(ivec3(0, 0, 1), "minecraft:oak_log[axis=z]")
+        .unwrap();
+    schematic
+        .set_block(ivec3(0, 0, 2), "minecraft:unknown_block")
+        .unwrap();
+
+    let report = schematic.place(&map, IVec3::ZERO, &mapping).unwrap();
+    assert_eq!(report.nodes, 2);
+    assert_eq!(report.unmapped["minecraft:unknown_block"], 1);
+
+    // Minecraft's z points south, so the first row ends up northmost.
+    let block = map.get_block(IVec3::ZERO).unwrap();
+    let node = |z| {
+        let node = block.get_node(ivec3(0, 0, z)).unwrap();
+        (block.get_name_by_id(node.id).unwrap(), node.param2)
+    };
+    assert_eq!(node(2), ("default:stone", 0));
+    assert_eq!(node(1), ("default:tree", 4));
+    assert_eq!(node(0), ("air", 0));
+
+    let area = NodeArea::new(IVec3::ZERO, ivec3(0, 0, 2));
+    let (copy, report) = SpongeSchematic::from_map(&map, area, &mapping, None).unwrap();
+    assert_eq!(report.nodes, 3);
+    assert!(report.unmapped.is_empty());
+    assert_eq!(copy.get_block(ivec3(0, 0, 0)), Some("minecraft:stone"));
+    assert_eq!(
+        copy.get_block(ivec3(0, 0, 1)),
+        Some("minecraft:oak_log[axis=z]")
+    );
+    assert_eq!(copy.get_block(ivec3(0, 0, 2)), Some("minecraft:air"));
+}
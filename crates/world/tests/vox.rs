@@ -0,0 +1,159 @@
+use glam::{IVec3, ivec3};
+use world::{Map, NodeArea, NodeColors, SqliteBackend, VoxSchematic, Voxel};
+
+fn chunk(id: &[u8; 4], content: &[u8], children: &[u8]) -> Vec<u8> {
+    let mut out = id.to_vec();
+    out.extend_from_slice(&(content.len() as i32).to_le_bytes());
+    out.extend_from_slice(&(children.len() as i32).to_le_bytes());
+    out.extend_from_slice(content);
+    out.extend_from_slice(children);
+    out
+}
+
+/// A version 150 file with one model whose voxels lie along MagicaVoxel's x
+/// axis, colored by palette index.
+fn vox_file(colors: &[u8], rgba: Option<&[[u8; 4]]>) -> Vec<u8> {
+    let mut size = Vec::new();
+    for n in [colors.len() as i32, 1, 1] {
+        size.extend_from_slice(&n.to_le_bytes());
+    }
+
+    let mut xyzi = (colors.len() as i32).to_le_bytes().to_vec();
+    for (x, &color) in colors.iter().enumerate() {
+        xyzi.extend_from_slice(&[x as u8, 0, 0, color]);
+    }
+
+    let mut children = chunk(b"SIZE", &size, &[]);
+    children.extend(chunk(b"XYZI", &xyzi, &[]));
+    if let Some(rgba) = rgba {
+        children.extend(chunk(b"RGBA", rgba.as_flattened(), &[]));
+    }
+
+    let mut out = b"VOX ".to_vec();
+    out.extend_from_slice(&150i32.to_le_bytes());
+    out.extend(chunk(b"MAIN", &[], &children));
+    out
+}
+
+fn colors(schematic: &VoxSchematic) -> Vec<(IVec3, [u8; 3])> {
+    schematic
+        .voxels
+        .iter()
+        .map(|voxel| (voxel.pos, voxel.color))
+        .collect()
+}
+
+#[test]
+fn default_palette() {
+    let data = vox_file(&[1, 2, 215, 216, 226, 236, 246, 255], None);
+    let schematic = VoxSchematic::read(&data).unwrap();
+
+    let expected = [
+        [0xff, 0xff, 0xff],
+        [0xff, 0xff, 0xcc],
+        [0x00, 0x00, 0x33],
+        // Ramps of red, green, blue and gray.
+        [0xee, 0x00, 0x00],
+        [0x00, 0xee, 0x00],
+        [0x00, 0x00, 0xee],
+        [0xee, 0xee, 0xee],
+        [0x11, 0x11, 0x11],
+    ];
+    let expected: Vec<_> = expected
+        .into_iter()
+        .enumerate()
+        .map(|(x, color)| (ivec3(x as i32, 0, 0), color))
+        .collect();
+    assert_eq!(colors(&schematic), expected);
+}
+
+#[test]
+fn rgba_offset() {
+    // The first RGBA entry is the color of index 1, and the last one is
+    // never used.
+    let mut rgba = vec![[0x10, 0x20, 0x30, 0xff]; 256];
+    rgba[0] = [0xff, 0x00, 0x00, 0xff];
+    rgba[1] = [0x00, 0xff, 0x00, 0xff];
+    rgba[254] = [0x00, 0x00, 0xff, 0xff];
+    rgba[255] = [0xff, 0xff, 0xff, 0xff];
+
+    let data = vox_file(&[1, 2, 255], Some(&rgba));
+    let schematic = VoxSchematic::read(&data).unwrap();
+
+    assert_eq!(
+        colors(&schematic),
+        [
+            (ivec3(0, 0, 0), [0xff, 0x00, 0x00]),
+            (ivec3(1, 0, 0), [0x00, 0xff, 0x00]),
+            (ivec3(2, 0, 0), [0x00, 0x00, 0xff]),
+        ]
+    );
+}
+
+#[test]
+fn write_round_trip() {
+    let voxel = |pos, color| Voxel { pos, color };
+    let schematic = VoxSchematic {
+        size: ivec3(3, 4, 2),
+        voxels: vec![
+            voxel(ivec3(0, 0, 0), [1, 2, 3]),
+            voxel(ivec3(2, 3, 1), [4, 5, 6]),
+            voxel(ivec3(1, 3, 0), [1, 2, 3]),
+        ],
+    };
+
+    let data = schematic.write().unwrap();
+    assert_eq!(VoxSchematic::read(&data).unwrap(), schematic);
+
+    // MagicaVoxel's z axis points up.
+    assert_eq!(&data[..4], b"VOX ");
+    let size = &data[32..44];
+    assert_eq!(size, [3, 0, 0, 0, 2, 0, 0, 0, 4, 0, 0, 0]);
+
+    let outside = VoxSchematic {
+        size: IVec3::ONE,
+        voxels: vec![voxel(IVec3::Y, [1, 2, 3])],
+    };
+    assert!(outside.write().is_err());
+}
+
+#[test]
+fn place_and_copy() {
+    let map = Map::new(SqliteBackend::create(":memory:").unwrap());
+    let colors = NodeColors::parse("default:stone 808080\ndefault:dirt 6b4a2b\n").unwrap();
+
+    let schematic = VoxSchematic {
+        size: ivec3(2, 1, 1),
+        voxels: vec![
+            Voxel {
+                pos: ivec3(0, 0, 0),
+                color: [0x80, 0x80, 0x80],
+            },
+            Voxel {
+                pos: ivec3(1, 0, 0),
+                color: [0x70, 0x50, 0x30],
+            },
+        ],
+    };
+
+    let report = schematic.place(&map, ivec3(-1, 0, 0), &colors).unwrap();
+    assert_eq!(report.nodes, 2);
+    assert_eq!(report.unmapped["705030"], 1);
+
+    let area = NodeArea::new(ivec3(-1, 0, 0), IVec3::ZERO);
+    let (copy, report) = VoxSchematic::from_map(&map, area, &colors, None).unwrap();
+    assert_eq!(report.nodes, 2);
+    assert_eq!(
+        copy.voxels,
+        [
+            Voxel {
+                pos: ivec3(0, 0, 0),
+                color: [0x80, 0x80, 0x80],
+            },
+            Voxel {
+                pos: ivec3(1, 0, 0),
+                color: [0x6b, 0x4a, 0x2b],
+            },
+        ]
+    );
+}
@@ -0,0 +1,73 @@
+use std::{
+    sync::Arc,
+    thread,
+    time::{Duration, Instant},
+};
+
+use glam::ivec3;
+use world::{Block, Map, MapWatcher, SqliteBackend};
+
+const INTERVAL: Duration = Duration::from_millis(10);
+
+fn temp_map(name: &str) -> std::path::PathBuf {
+    let path =
+        std::env::temp_dir().join(format!("world-test-{name}-{}.sqlite", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    path
+}
+
+#[test]
+fn tracks_blocks_after_spawning() {
+    let path = temp_map("watch-track");
+    let map = Arc::new(Map::new(SqliteBackend::create(&path).unwrap()));
+    let server = Map::new(SqliteBackend::new(&path).unwrap());
+
+    let watcher = MapWatcher::new(&map)
+        .unwrap()
+        .spawn(Arc::clone(&map), INTERVAL);
+    watcher.track(ivec3(1, 2, 3));
+    thread::sleep(INTERVAL * 5);
+
+    let mut block = Block::new();
+    block.set_timestamp(10);
+    server.set_block(ivec3(1, 2, 3), &block).unwrap();
+    server.set_block(ivec3(4, 5, 6), &block).unwrap();
+
+    let changes = watcher
+        .recv_timeout(Duration::from_secs(5))
+        .unwrap()
+        .unwrap();
+    assert_eq!(changes.added, vec![ivec3(1, 2, 3)]);
+
+    block.set_timestamp(11);
+    server.set_block(ivec3(1, 2, 3), &block).unwrap();
+
+    let changes = watcher
+        .recv_timeout(Duration::from_secs(5))
+        .unwrap()
+        .unwrap();
+    assert_eq!(changes.modified, vec![ivec3(1, 2, 3)]);
+}
+
+#[test]
+fn stops_when_dropped() {
+    let path = temp_map("watch-stop");
+    let map = Arc::new(Map::new(SqliteBackend::create(&path).unwrap()));
+
+    let watcher = MapWatcher::new(&map)
+        .unwrap()
+        .spawn(Arc::clone(&map), INTERVAL);
+    assert_eq!(Arc::strong_count(&map), 2);
+    drop(watcher);
+
+    // The thread drops its reference to the map when it exits, even though
+    // nothing changed that it could fail to send.
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while Arc::strong_count(&map) > 1 {
+        assert!(
+            Instant::now() < deadline,
+            "the watcher thread is still running"
+        );
+        thread::sleep(INTERVAL);
+    }
+}
@@ -0,0 +1,111 @@
+use glam::{IVec3, ivec3};
+use world::{
+    ItemStack, Map, NodeArea, NodeMetadata, SchematicError, SqliteBackend, WorldEditNode,
+    WorldEditSchematic,
+};
+
+const VERSION_5: &str = r#"5:return {{["y"] = 0, ["x"] = 0, ["name"] = "default:stone", ["z"] = 0}, {["y"] = 1, ["x"] = 2, ["name"] = "default:chest", ["z"] = 0, ["param2"] = 3, ["meta"] = {["fields"] = {["infotext"] = "Chest", ["count"] = 4}, ["inventory"] = {["main"] = {"default:stone 5", ""}}}}}"#;
+
+const VERSION_4: &str = r#"return {{["y"] = 0, ["x"] = 0, ["name"] = "default:stone", ["z"] = 0, ["param1"] = 0, ["param2"] = 0, ["meta"] = {["fields"] = {}, ["inventory"] = {}}}, {["y"] = 1, ["x"] = 2, ["name"] = "default:torch", ["z"] = 0, ["param1"] = 14, ["param2"] = 1}}"#;
+
+#[test]
+fn version_5() {
+    let schematic = WorldEditSchematic::read(VERSION_5.as_bytes()).unwrap();
+
+    assert_eq!(schematic.nodes.len(), 2);
+    assert_eq!(schematic.size(), ivec3(3, 2, 1));
+
+    let stone = &schematic.nodes[0];
+    assert_eq!(stone.pos, IVec3::ZERO);
+    assert_eq!(stone.name, "default:stone");
+    assert_eq!((stone.param1, stone.param2), (0, 0));
+    assert_eq!(stone.metadata, None);
+
+    let chest = &schematic.nodes[1];
+    assert_eq!(chest.pos, ivec3(2, 1, 0));
+    assert_eq!(chest.param2, 3);
+
+    let metadata = chest.metadata.as_ref().unwrap();
+    assert_eq!(metadata.get_str("infotext"), Some("Chest"));
+    assert_eq!(metadata.get_str("count"), Some("4"));
+
+    let main = metadata.inventory.list("main").unwrap();
+    assert_eq!(
+        main.items,
+        [ItemStack::new("default:stone", 5), ItemStack::empty()]
+    );
+
+    let written = schematic.write();
+    assert!(written.starts_with(b"5:return {"));
+    assert_eq!(WorldEditSchematic::read(&written).unwrap(), schematic);
+}
+
+#[test]
+fn version_4() {
+    let schematic = WorldEditSchematic::read(VERSION_4.as_bytes()).unwrap();
+
+    assert_eq!(schematic.nodes.len(), 2);
+    assert_eq!(schematic.nodes[0].metadata, Some(NodeMetadata::default()));
+
+    let torch = &schematic.nodes[1];
+    assert_eq!(torch.name, "default:torch");
+    assert_eq!((torch.param1, torch.param2), (14, 1));
+
+    // Read back as version 5.
+    let written = schematic.write();
+    assert!(written.starts_with(b"5:"));
+    assert_eq!(WorldEditSchematic::read(&written).unwrap(), schematic);
+}
+
+#[test]
+fn unsupported_versions() {
+    // Version 3 is a plain list of coordinates and names.
+    let version_3 = b"0 0 0 default:stone 0 0\n";
+    assert!(matches!(
+        WorldEditSchematic::read(version_3),
+        Err(SchematicError::UnsupportedVersion(3))
+    ));
+
+    assert!(matches!(
+        WorldEditSchematic::read(b"6:return {}"),
+        Err(SchematicError::UnsupportedVersion(6))
+    ));
+
+    // Extra header fields are ignored.
+    let schematic = WorldEditSchematic::read(b"5,extra:return {}").unwrap();
+    assert!(schematic.nodes.is_empty());
+}
+
+#[test]
+fn place_and_copy() {
+    let map = Map::new(SqliteBackend::create(":memory:").unwrap());
+    let schematic = WorldEditSchematic::read(VERSION_5.as_bytes()).unwrap();
+
+    let origin = ivec3(14, -1, 3);
+    assert_eq!(schematic.place(&map, origin).unwrap(), 2);
+    assert_eq!(map.list_blocks().unwrap().len(), 2);
+
+    let block = map.get_block(ivec3(1, 0, 0)).unwrap();
+    assert_eq!(block.lighting_complete(), 0);
+    let chest = block.get_metadata(ivec3(0, 0, 3)).unwrap();
+    assert_eq!(chest.get_str("infotext"), Some("Chest"));
+
+    let area = NodeArea::new(origin, origin + schematic.size() - 1);
+    let copy = WorldEditSchematic::from_map(&map, area, None).unwrap();
+    assert_eq!(copy, schematic);
+
+    // Placing again replaces nodes along with their metadata.
+    let stone = WorldEditSchematic {
+        nodes: vec![WorldEditNode {
+            pos: ivec3(2, 1, 0),
+            name: "default:stone".to_string(),
+            param1: 0,
+            param2: 0,
+            metadata: None,
+        }],
+    };
+    stone.place(&map, origin).unwrap();
+
+    let block = map.get_block(ivec3(1, 0, 0)).unwrap();
+    assert!(block.get_metadata(ivec3(0, 0, 3)).is_none());
+}
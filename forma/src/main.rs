@@ -2,13 +2,17 @@ pub mod ui;
 pub mod world_manager;
 
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
 use egui::ThemePreference;
 use egui_wgpu::WgpuConfiguration;
 use render::VoxelRenderer;
 
 use crate::ui::View;
-use crate::world_manager::WorldManager;
+use crate::world_manager::{POLL_INTERVAL, WorldManager};
+
+/// How often worlds being opened in the background are checked.
+const OPENING_POLL_INTERVAL: Duration = Duration::from_millis(50);
 
 struct App {
     world_manager: Arc<Mutex<WorldManager>>,
@@ -43,7 +47,35 @@ impl App {
 
 impl eframe::App for App {
     fn update(&mut self, ctx: &egui::Context, frame: &mut eframe::Frame) {
+        let (opened, still_opening) = {
+            let mut world_manager = self.world_manager.lock().unwrap();
+            (world_manager.poll_opening(), world_manager.any_opening())
+        };
+
+        for (_, result) in &opened {
+            if let Err(err) = result {
+                eprintln!("{err:#}");
+            }
+        }
+
+        if !opened.is_empty() {
+            ctx.request_repaint();
+        }
+
+        if still_opening {
+            ctx.request_repaint_after(OPENING_POLL_INTERVAL);
+        }
+
+        // World views don't keep any block data yet, so there is nothing to
+        // rebuild; drawing them again is enough.
+        let changed = self.world_manager.lock().unwrap().poll_changes();
+        if !changed.is_empty() {
+            ctx.request_repaint();
+        }
+
         self.ui.ui(ctx);
+
+        ctx.request_repaint_after(POLL_INTERVAL);
     }
 }
 
@@ -10,6 +10,7 @@ use egui::{
 use egui_tiles::{Behavior, Container, ContainerKind, SimplificationOptions, Tile, Tree};
 use render::VoxelRenderer;
 use uuid::Uuid;
+use world::Terrain;
 
 use crate::world_manager::WorldManager;
 
@@ -41,6 +42,28 @@ impl View {
         egui::TopBottomPanel::top("top panel").show(ctx, |ui| {
             egui::MenuBar::new().ui(ui, |ui| {
                 ui.menu_button("File", |ui| {
+                    if ui.button("New world...").clicked() {
+                        if let Ok(world_id) = self.controller.create_world() {
+                            self.insert_pane(Pane::World(world_id));
+                        }
+                    }
+
+                    ui.menu_button("New world with terrain", |ui| {
+                        for (label, terrain) in [
+                            ("Flat...", Terrain::Flat),
+                            ("Hills...", Terrain::Hills),
+                            ("Islands...", Terrain::Islands),
+                        ] {
+                            if ui.button(label).clicked() {
+                                if let Ok(world_id) =
+                                    self.controller.create_world_with_terrain(terrain)
+                                {
+                                    self.insert_pane(Pane::World(world_id));
+                                }
+                            }
+                        }
+                    });
+
                     if ui.button("Open world...").clicked() {
                         if let Ok(world_id) = self.controller.open_world() {
                             self.insert_pane(Pane::World(world_id));
@@ -136,6 +159,19 @@ impl Behavior<Pane> for TreeController {
     ) -> egui_tiles::UiResponse {
         match pane {
             Pane::World(id) => {
+                let world_manager = self.world_manager.lock().unwrap();
+                if world_manager.world_by_id(*id).is_none() {
+                    ui.centered_and_justified(|ui| {
+                        if world_manager.is_opening(*id) {
+                            ui.spinner();
+                        } else {
+                            ui.label("Unable to open world");
+                        }
+                    });
+                    return Default::default();
+                }
+                drop(world_manager);
+
                 let rect = ui.available_rect_before_wrap();
                 ui.scope_builder(UiBuilder::new().max_rect(rect), |ui| {
                     let (response, painter) =
@@ -166,7 +202,7 @@ impl Behavior<Pane> for TreeController {
                 .unwrap()
                 .world_by_id(*id)
                 .map(|world| world.name.as_str().into())
-                .unwrap_or("unknown".into()),
+                .unwrap_or("opening...".into()),
         }
     }
 
@@ -204,6 +240,28 @@ impl Controller {
         Ok(id)
     }
 
+    pub fn create_world(&self) -> Result<Uuid> {
+        let path = rfd::FileDialog::new()
+            .set_title("New world")
+            .save_file()
+            .ok_or(anyhow!("canceled"))?;
+        let id = self.world_manager.lock().unwrap().create(path)?;
+        Ok(id)
+    }
+
+    pub fn create_world_with_terrain(&self, terrain: Terrain) -> Result<Uuid> {
+        let path = rfd::FileDialog::new()
+            .set_title("New world")
+            .save_file()
+            .ok_or(anyhow!("canceled"))?;
+        let id = self
+            .world_manager
+            .lock()
+            .unwrap()
+            .create_with_terrain(path, terrain)?;
+        Ok(id)
+    }
+
     pub fn execute_command(&mut self, command: String) {
         println!("command: {command}");
 
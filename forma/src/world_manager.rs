@@ -1,23 +1,51 @@
 use std::collections::HashMap;
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
 
 use anyhow::{Context, Result};
+use glam::IVec3;
 use uuid::Uuid;
-use world::World;
+use world::{
+    BlocksChanged, Generator, GeneratorOptions, MapWatcher, NodeArea, Task, Terrain, WatcherHandle,
+    World, WorldOptions,
+};
+
+/// Area filled with terrain in new worlds, in node positions. Its blocks are
+/// also the ones watched for changes.
+const TERRAIN_AREA: NodeArea = NodeArea {
+    min: IVec3::new(-128, -64, -128),
+    max: IVec3::new(127, 63, 127),
+};
+
+/// How often open worlds are checked for changes made by a running server.
+pub const POLL_INTERVAL: Duration = Duration::from_secs(1);
 
 pub struct WorldManager {
     worlds: HashMap<Uuid, World>,
+
+    /// Changes found by the watcher threads of open worlds.
+    changes: HashMap<Uuid, WatcherHandle>,
+
     path_to_id: HashMap<PathBuf, Uuid>,
+
+    /// Worlds that are still being opened in the background.
+    opening: HashMap<Uuid, Task<Result<(World, MapWatcher)>>>,
 }
 
 impl WorldManager {
     pub fn new() -> Self {
         Self {
             worlds: HashMap::new(),
+            changes: HashMap::new(),
             path_to_id: HashMap::new(),
+            opening: HashMap::new(),
         }
     }
 
+    /// Starts opening the world at `path` in the background and returns its
+    /// id right away. The world becomes available once [`Self::poll_opening`]
+    /// reports it.
     pub fn open(&mut self, path: impl AsRef<Path>) -> Result<Uuid> {
         let path = path.as_ref().canonicalize()?.to_path_buf();
 
@@ -25,10 +53,65 @@ impl WorldManager {
             return Ok(*id);
         }
 
-        let world = World::open(&path).context("Unable to open world")?;
+        let id = Uuid::new_v4();
+        let world_path = path.clone();
+        let task = Task::spawn(move || {
+            let world = World::open(&world_path).context("Unable to open world")?;
+            let watcher = watch(&world)?;
+            Ok((world, watcher))
+        });
+
+        self.opening.insert(id, task);
+        self.path_to_id.insert(path, id);
+
+        Ok(id)
+    }
+
+    pub fn create(&mut self, path: impl AsRef<Path>) -> Result<Uuid> {
+        let path = path.as_ref();
+
+        let world =
+            World::create(path, WorldOptions::default()).context("Unable to create world")?;
+        let watcher = watch(&world)?;
 
         let id = Uuid::new_v4();
+        self.changes
+            .insert(id, watcher.spawn(Arc::clone(&world.map), POLL_INTERVAL));
         self.worlds.insert(id, world);
+        self.path_to_id.insert(path.canonicalize()?, id);
+
+        Ok(id)
+    }
+
+    /// Creates a world and fills it with terrain in the background, with a
+    /// random seed. Like [`Self::open`], the world becomes available once
+    /// [`Self::poll_opening`] reports it.
+    pub fn create_with_terrain(
+        &mut self,
+        path: impl AsRef<Path>,
+        terrain: Terrain,
+    ) -> Result<Uuid> {
+        let path = path.as_ref();
+
+        let world =
+            World::create(path, WorldOptions::default()).context("Unable to create world")?;
+
+        let id = Uuid::new_v4();
+        let generator = Generator::new(GeneratorOptions {
+            seed: id.as_u128() as u32,
+            terrain,
+            ..Default::default()
+        });
+
+        let task = Task::spawn(move || {
+            world::generate_map(&world.map, &generator, TERRAIN_AREA, false, |_| {})
+                .context("Unable to generate terrain")?;
+            let watcher = watch(&world)?;
+            Ok((world, watcher))
+        });
+
+        self.opening.insert(id, task);
+        self.path_to_id.insert(path.canonicalize()?, id);
 
         Ok(id)
     }
@@ -36,4 +119,79 @@ impl WorldManager {
     pub fn world_by_id(&self, id: Uuid) -> Option<&World> {
         self.worlds.get(&id)
     }
+
+    pub fn is_opening(&self, id: Uuid) -> bool {
+        self.opening.contains_key(&id)
+    }
+
+    pub fn any_opening(&self) -> bool {
+        !self.opening.is_empty()
+    }
+
+    /// Moves worlds that finished opening in the background to the open
+    /// worlds. Returns the ids of all worlds that finished, along with the
+    /// error if opening failed.
+    pub fn poll_opening(&mut self) -> Vec<(Uuid, Result<()>)> {
+        let mut finished = Vec::new();
+
+        for (id, task) in &mut self.opening {
+            if let Some(result) = task.try_take() {
+                finished.push((*id, result));
+            }
+        }
+
+        finished
+            .into_iter()
+            .map(|(id, result)| {
+                self.opening.remove(&id);
+
+                match result {
+                    Ok((world, watcher)) => {
+                        self.changes
+                            .insert(id, watcher.spawn(Arc::clone(&world.map), POLL_INTERVAL));
+                        self.worlds.insert(id, world);
+                        (id, Ok(()))
+                    }
+                    Err(err) => {
+                        self.path_to_id.retain(|_, path_id| *path_id != id);
+                        (id, Err(err))
+                    }
+                }
+            })
+            .collect()
+    }
+
+    /// Collects the blocks that the watcher threads of open worlds found
+    /// written by another process, such as a server running on the same
+    /// world. Never blocks.
+    pub fn poll_changes(&mut self) -> Vec<(Uuid, BlocksChanged)> {
+        let mut changed = Vec::new();
+
+        for (id, changes) in &self.changes {
+            for result in changes.try_iter() {
+                match result {
+                    Ok(changes) => changed.push((*id, changes)),
+                    Err(err) => eprintln!(
+                        "unable to check {} for changes: {err}",
+                        self.worlds[id].name
+                    ),
+                }
+            }
+        }
+
+        changed
+    }
+}
+
+/// Creates a watcher for the blocks of [`TERRAIN_AREA`] in `world`.
+fn watch(world: &World) -> Result<MapWatcher> {
+    let min = TERRAIN_AREA.min.div_euclid(IVec3::splat(16));
+    let max = TERRAIN_AREA.max.div_euclid(IVec3::splat(16));
+
+    let mut watcher = MapWatcher::new(&world.map).context("Unable to watch world")?;
+    watcher
+        .track_box(&world.map, min, max)
+        .context("Unable to watch world")?;
+
+    Ok(watcher)
 }
@@ -1,9 +1,9 @@
 #![allow(clippy::new_without_default)]
 #![allow(clippy::single_match)]
 
-use std::{error::Error, path::PathBuf};
+use std::{error::Error, path::PathBuf, sync::Arc};
 
-use glam::{Vec3, ivec3};
+use glam::{IVec3, Vec3, ivec3};
 use winit::dpi::PhysicalSize;
 use winit::event::{DeviceEvent, DeviceId};
 use winit::event_loop::ControlFlow;
@@ -14,39 +14,67 @@ use winit::{
     event_loop::{ActiveEventLoop, EventLoop},
     window::{Window, WindowId},
 };
-use world::{Block, Map, SqliteBackend, WorldMeta};
+use world::{Block, Map, NodeDefRegistry, SqliteBackend, WorldMeta};
 
 use crate::camera::Camera;
 use crate::input::Input;
 use crate::node::GlobalMapping;
 use crate::render::DataBuffer;
 use crate::render::Renderer;
+use crate::source::BlockSource;
 
 pub mod camera;
 pub mod input;
 pub mod node;
 pub mod render;
+pub mod source;
+
+const VIEW_BLOCK: IVec3 = ivec3(0, 2, 0);
 
 struct App {
     renderer: Option<Renderer>,
     camera: Camera,
     input: Input,
-    map: Map,
+    source: BlockSource,
+    node_defs: NodeDefRegistry,
     global_mapping: GlobalMapping,
     grid: Option<DataBuffer>,
 }
 
 impl App {
-    pub fn new(map: Map) -> Self {
+    pub fn new(source: BlockSource, node_defs: NodeDefRegistry) -> Self {
         Self {
             renderer: None,
             camera: Camera::new(),
             input: Input::new(),
-            map,
+            source,
+            node_defs,
             global_mapping: GlobalMapping::new(),
             grid: None,
         }
     }
+
+    /// Rebuilds the grid from blocks that arrived since the last frame.
+    fn update_blocks(&mut self) {
+        let Some(renderer) = &self.renderer else {
+            return;
+        };
+
+        // Only the viewed block is drawn, so it's the only one to rebuild.
+        for (pos, result) in self.source.received_blocks() {
+            if pos != VIEW_BLOCK {
+                continue;
+            }
+
+            match result {
+                Ok(block) => {
+                    let grid = block_to_grid(&block, &self.node_defs, &mut self.global_mapping);
+                    self.grid = Some(renderer.create_data_buffer(bytemuck::cast_slice(&grid)));
+                }
+                Err(err) => eprintln!("unable to load block {pos}: {err}"),
+            }
+        }
+    }
 }
 
 impl ApplicationHandler for App {
@@ -67,8 +95,9 @@ impl ApplicationHandler for App {
         let air_id = self.global_mapping.get_or_insert_id("air");
         assert_eq!(air_id, 0);
 
-        let block = self.map.get_block(ivec3(0, 2, 0)).unwrap();
-        let grid = block_to_grid(&block, &mut self.global_mapping);
+        // Drawn empty until the block is loaded.
+        self.source.request_block(VIEW_BLOCK);
+        let grid = block_to_grid(&Block::new(), &self.node_defs, &mut self.global_mapping);
         let grid = renderer.create_data_buffer(bytemuck::cast_slice(&grid));
 
         self.renderer = Some(renderer);
@@ -111,6 +140,8 @@ impl ApplicationHandler for App {
     }
 
     fn about_to_wait(&mut self, event_loop: &ActiveEventLoop) {
+        self.update_blocks();
+
         let Some(renderer) = &mut self.renderer else {
             return;
         };
@@ -165,12 +196,29 @@ fn main() -> Result<(), Box<dyn Error>> {
         std::process::exit(1);
     };
 
+    // Blocks can also come from a running server, without access to its
+    // files: light --server <address> <name> [password]
+    if world_path == "--server" {
+        let mut args = std::env::args().skip(2);
+        let (Some(address), Some(name)) = (args.next(), args.next()) else {
+            eprintln!("server address and player name required");
+            std::process::exit(1);
+        };
+        let password = args.next().unwrap_or_default();
+
+        let source = BlockSource::server(address, name, password, VIEW_BLOCK);
+        return run(source, NodeDefRegistry::new());
+    }
+
     let world_path = PathBuf::from(world_path);
     let world_meta_path = world_path.join("world.mt");
 
     let world_meta = WorldMeta::open(world_meta_path)?;
 
-    let backend = world_meta.get_str("backend").unwrap();
+    let Some(backend) = world_meta.get_str("backend") else {
+        eprintln!("world.mt has no backend setting");
+        std::process::exit(1);
+    };
 
     let map = match backend {
         "sqlite3" => {
@@ -187,33 +235,49 @@ fn main() -> Result<(), Box<dyn Error>> {
         }
     };
 
+    // Node definitions exported from the game; without them every node except
+    // air is drawn as a solid cube.
+    let node_defs = match std::env::args().nth(2) {
+        Some(path) => NodeDefRegistry::open(path)?,
+        None => NodeDefRegistry::new(),
+    };
+
+    run(BlockSource::world(Arc::new(map), VIEW_BLOCK)?, node_defs)
+}
+
+fn run(source: BlockSource, node_defs: NodeDefRegistry) -> Result<(), Box<dyn Error>> {
     let event_loop = EventLoop::new()?;
-    let mut app = App::new(map);
+
+    let mut app = App::new(source, node_defs);
 
     event_loop.run_app(&mut app)?;
 
     Ok(())
 }
 
-fn block_to_grid(block: &Block, global_mapping: &mut GlobalMapping) -> Vec<u32> {
+fn block_to_grid(
+    block: &Block,
+    node_defs: &NodeDefRegistry,
+    global_mapping: &mut GlobalMapping,
+) -> Vec<u32> {
     let mut data = vec![0; 16 * 16 * 16];
 
-    for z in 0..16 {
-        for y in 0..16 {
-            for x in 0..16 {
-                let node = block.get_node(ivec3(x, y, z));
-                let name = block.get_name_by_id(node.id).unwrap();
-                let global_id = global_mapping.get_or_insert_id(name);
+    for (index, (_, node)) in block.nodes().enumerate() {
+        let name = block.get_name_by_id(node.id).unwrap_or("unknown");
 
-                let mut value = 0;
-                value |= (global_id as u32) << 16;
-                value |= node.param1 as u32;
-                value |= node.param2 as u32;
+        // The shader treats global id 0 (air) as empty space.
+        let global_id = if node_defs.get_or_unknown(name).is_visible() {
+            global_mapping.get_or_insert_id(name)
+        } else {
+            0
+        };
 
-                let index = (z * 16 * 16 + y * 16 + x) as usize;
-                data[index] = value;
-            }
-        }
+        let mut value = 0;
+        value |= (global_id as u32) << 16;
+        value |= node.param1 as u32;
+        value |= node.param2 as u32;
+
+        data[index] = value;
     }
 
     data
@@ -0,0 +1,115 @@
+use std::{
+    sync::{
+        Arc,
+        mpsc::{self, Receiver},
+    },
+    thread,
+    time::Duration,
+};
+
+use glam::IVec3;
+use net::{Client, NetError};
+use world::{Block, Map, MapError, MapLoader, MapWatcher, WatcherHandle};
+
+/// How often the map is checked for changes made by a running server.
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Where viewed blocks come from.
+pub enum BlockSource {
+    /// A world on disk, which a running server may be changing. Blocks are
+    /// loaded and the requested ones watched on other threads.
+    World {
+        loader: MapLoader,
+        watcher: WatcherHandle,
+    },
+
+    /// A server that's connected to as a player on another thread.
+    Server { blocks: Receiver<(IVec3, Block)> },
+}
+
+impl BlockSource {
+    /// Opens a world and watches `view_block` for changes.
+    pub fn world(map: Arc<Map>, view_block: IVec3) -> Result<Self, MapError> {
+        let mut watcher = MapWatcher::new(&map)?;
+        watcher.track_box(&map, view_block, view_block)?;
+
+        let watcher = watcher.spawn(Arc::clone(&map), POLL_INTERVAL);
+
+        Ok(Self::World {
+            loader: MapLoader::new(map),
+            watcher,
+        })
+    }
+
+    /// Connects to a server and asks for the blocks around `view_block`.
+    /// Connection errors are printed, after which no more blocks arrive.
+    pub fn server(address: String, name: String, password: String, view_block: IVec3) -> Self {
+        let (sender, blocks) = mpsc::channel();
+
+        thread::spawn(move || {
+            let result = (|| -> Result<(), NetError> {
+                let mut client = Client::connect(&address, &name, &password)?;
+                eprintln!("connected to {address} as {name}");
+
+                let center = view_block * 16 + 8;
+                client.set_position(center.as_vec3())?;
+
+                loop {
+                    if let Some(block) = client.next_block(Duration::from_secs(1))?
+                        && sender.send(block).is_err()
+                    {
+                        // The viewer was closed.
+                        return Ok(());
+                    }
+                }
+            })();
+
+            if let Err(err) = result {
+                eprintln!("connection to {address} failed: {err}");
+            }
+        });
+
+        Self::Server { blocks }
+    }
+
+    /// Asks for the block at `pos`, which arrives through
+    /// [`Self::received_blocks`] and again whenever it changes. Blocks of
+    /// servers arrive on their own.
+    pub fn request_block(&mut self, pos: IVec3) {
+        match self {
+            Self::World { loader, watcher } => {
+                loader.request(pos);
+                watcher.track(pos);
+            }
+            Self::Server { .. } => {}
+        }
+    }
+
+    /// Blocks that arrived since the last call, without waiting for more:
+    /// requested blocks, and blocks that changed since. Missing blocks are
+    /// empty.
+    pub fn received_blocks(&mut self) -> Vec<(IVec3, Result<Block, MapError>)> {
+        match self {
+            Self::World { loader, watcher } => {
+                for changes in watcher.try_iter() {
+                    match changes {
+                        Ok(changes) => changes.positions().for_each(|pos| loader.request(pos)),
+                        Err(err) => eprintln!("unable to check for changed blocks: {err}"),
+                    }
+                }
+
+                loader
+                    .loaded()
+                    .map(|loaded| match loaded.result {
+                        Err(MapError::BlockNotFound) => (loaded.pos, Ok(Block::new())),
+                        result => (loaded.pos, result),
+                    })
+                    .collect()
+            }
+            Self::Server { blocks } => blocks
+                .try_iter()
+                .map(|(pos, block)| (pos, Ok(block)))
+                .collect(),
+        }
+    }
+}
@@ -0,0 +1,54 @@
+use std::{error::Error, path::Path, process::ExitCode};
+
+use crate::schematic;
+use glam::IVec3;
+use world::{AnvilImportOptions, AnvilImportProgress, NodeArea, World, WorldOptions};
+
+pub struct Args<'a> {
+    pub world: &'a Path,
+    pub minecraft_world: &'a Path,
+    pub area: NodeArea,
+    pub offset: IVec3,
+    pub mapping: Option<&'a Path>,
+}
+
+pub fn run(args: Args) -> Result<ExitCode, Box<dyn Error>> {
+    let mapping = schematic::read_mapping(args.mapping)?;
+
+    // Accept both the world directory and its region directory.
+    let region_dir = args.minecraft_world.join("region");
+    let region_dir = if region_dir.is_dir() {
+        &region_dir
+    } else {
+        args.minecraft_world
+    };
+
+    let world = if args.world.join("world.mt").exists() {
+        World::open(args.world)?
+    } else {
+        let world = World::create(args.world, WorldOptions::default())?;
+        eprintln!("created world {}", world.name);
+        world
+    };
+
+    let options = AnvilImportOptions {
+        area: args.area,
+        offset: args.offset,
+    };
+    let report = world::import_anvil(&world.map, region_dir, &mapping, &options, print_progress)?;
+
+    eprintln!(
+        "imported {} nodes from {} chunks, skipped {} missing or unfinished chunks",
+        report.nodes, report.chunks, report.skipped_chunks
+    );
+
+    schematic::print_unmapped("block states", &report.unmapped);
+
+    Ok(ExitCode::SUCCESS)
+}
+
+fn print_progress(progress: AnvilImportProgress) {
+    if progress.done.is_multiple_of(256) || progress.done == progress.total {
+        eprintln!("imported {}/{} chunks", progress.done, progress.total);
+    }
+}
@@ -0,0 +1,162 @@
+use std::{error::Error, path::Path, process::ExitCode, sync::Arc};
+
+use serde_json::json;
+use world::{BlockChange, BlockChangeKind, DiffOptions, DiffSummary, Map, SqliteBackend, World};
+
+pub struct Args<'a> {
+    pub old: &'a Path,
+    pub new: &'a Path,
+    pub nodes: bool,
+    pub param1: bool,
+    pub trust_timestamps: bool,
+    pub json: bool,
+}
+
+pub fn run(args: Args) -> Result<ExitCode, Box<dyn Error>> {
+    let old = open_map(args.old)?;
+    let new = open_map(args.new)?;
+
+    let options = DiffOptions {
+        trust_timestamps: args.trust_timestamps,
+        compare_param1: args.param1,
+    };
+
+    let summary = world::diff_maps(&old, &new, &options, |change| {
+        if args.json {
+            print_change_json(change, args.nodes);
+        } else {
+            print_change(change, args.nodes);
+        }
+    })?;
+
+    if args.json {
+        print_summary_json(&summary);
+    } else {
+        print_summary(&summary);
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Opens either a world directory or a bare `map.sqlite`, such as a backup.
+fn open_map(path: &Path) -> Result<Arc<Map>, Box<dyn Error>> {
+    if path.is_file() {
+        return Ok(Arc::new(Map::new(SqliteBackend::new(path)?)));
+    }
+
+    Ok(World::open(path)?.map)
+}
+
+fn kind_str(kind: BlockChangeKind) -> &'static str {
+    match kind {
+        BlockChangeKind::Added => "added",
+        BlockChangeKind::Removed => "removed",
+        BlockChangeKind::Modified => "modified",
+    }
+}
+
+fn print_change(change: &BlockChange, nodes: bool) {
+    let pos = change.pos;
+    let kind = kind_str(change.kind);
+
+    match change.kind {
+        BlockChangeKind::Modified => println!(
+            "{kind} block ({}, {}, {}): {} nodes",
+            pos.x,
+            pos.y,
+            pos.z,
+            change.nodes.len()
+        ),
+        _ => println!("{kind} block ({}, {}, {})", pos.x, pos.y, pos.z),
+    }
+
+    if !nodes {
+        return;
+    }
+
+    for node in &change.nodes {
+        println!(
+            "  ({}, {}, {}) {} {} {} -> {} {} {}",
+            node.pos.x,
+            node.pos.y,
+            node.pos.z,
+            node.old.name,
+            node.old.param1,
+            node.old.param2,
+            node.new.name,
+            node.new.param1,
+            node.new.param2,
+        );
+    }
+}
+
+fn print_change_json(change: &BlockChange, nodes: bool) {
+    let mut line = json!({
+        "type": "block",
+        "pos": change.pos.to_array(),
+        "change": kind_str(change.kind),
+    });
+
+    if change.kind == BlockChangeKind::Modified {
+        line["changed_nodes"] = json!(change.nodes.len());
+    }
+
+    if nodes && !change.nodes.is_empty() {
+        let node_changes: Vec<_> = change
+            .nodes
+            .iter()
+            .map(|node| {
+                json!({
+                    "pos": node.pos.to_array(),
+                    "old": [node.old.name, node.old.param1, node.old.param2],
+                    "new": [node.new.name, node.new.param1, node.new.param2],
+                })
+            })
+            .collect();
+
+        line["nodes"] = json!(node_changes);
+    }
+
+    println!("{line}");
+}
+
+fn print_summary(summary: &DiffSummary) {
+    println!(
+        "{} blocks added, {} removed, {} modified, {} unchanged",
+        summary.added_blocks,
+        summary.removed_blocks,
+        summary.modified_blocks,
+        summary.unchanged_blocks
+    );
+
+    for (name, count) in &summary.nodes {
+        if count.added > 0 {
+            println!("{} {name} added", count.added);
+        }
+
+        if count.removed > 0 {
+            println!("{} {name} removed", count.removed);
+        }
+    }
+}
+
+fn print_summary_json(summary: &DiffSummary) {
+    for (name, count) in &summary.nodes {
+        let line = json!({
+            "type": "node",
+            "name": name,
+            "added": count.added,
+            "removed": count.removed,
+        });
+        println!("{line}");
+    }
+
+    let line = json!({
+        "type": "summary",
+        "added_blocks": summary.added_blocks,
+        "removed_blocks": summary.removed_blocks,
+        "modified_blocks": summary.modified_blocks,
+        "unchanged_blocks": summary.unchanged_blocks,
+    });
+    println!("{line}");
+}
@@ -0,0 +1,13 @@
+use std::{error::Error, path::Path, process::ExitCode};
+
+use glam::IVec3;
+use world::World;
+
+pub fn run(world_path: &Path, pos: IVec3) -> Result<ExitCode, Box<dyn Error>> {
+    let world = World::open(world_path)?;
+    let block = world.map.get_block(pos)?;
+
+    println!("{}", serde_json::to_string_pretty(&block)?);
+
+    Ok(ExitCode::SUCCESS)
+}
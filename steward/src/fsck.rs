@@ -0,0 +1,37 @@
+use std::{error::Error, path::Path, process::ExitCode};
+
+use serde_json::json;
+use world::{CheckOptions, Problem, World};
+
+pub fn run(world_path: &Path, repair: bool) -> Result<ExitCode, Box<dyn Error>> {
+    let world = World::open(world_path)?;
+
+    let options = CheckOptions { repair };
+    let summary = world::check_map(&world.map, &options, print_problem)?;
+
+    let line = json!({
+        "type": "summary",
+        "blocks": summary.blocks,
+        "problems": summary.problems,
+        "repaired": summary.repaired,
+    });
+    println!("{line}");
+
+    if summary.problems > summary.repaired {
+        Ok(ExitCode::FAILURE)
+    } else {
+        Ok(ExitCode::SUCCESS)
+    }
+}
+
+fn print_problem(problem: &Problem) {
+    let line = json!({
+        "type": "problem",
+        "pos": problem.pos.to_array(),
+        "kind": problem.kind.as_str(),
+        "message": problem.message,
+        "repair": problem.repair.as_str(),
+        "repaired": problem.repaired,
+    });
+    println!("{line}");
+}
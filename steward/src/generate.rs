@@ -0,0 +1,90 @@
+use std::{
+    error::Error,
+    path::Path,
+    process::ExitCode,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use world::{
+    GenerateProgress, Generator, GeneratorOptions, NodeArea, Terrain, TerrainNodes, World,
+    WorldOptions,
+};
+
+pub struct Args<'a> {
+    pub world: &'a Path,
+    pub terrain: Terrain,
+    pub seed: Option<u32>,
+    pub area: NodeArea,
+    pub nodes: TerrainNodes,
+    pub ground_level: i32,
+    pub water_level: i32,
+    pub height_range: i32,
+    pub no_caves: bool,
+    pub overwrite: bool,
+    pub gameid: &'a str,
+    pub backend: &'a str,
+}
+
+pub fn run(args: Args) -> Result<ExitCode, Box<dyn Error>> {
+    let world = if args.world.join("world.mt").exists() {
+        World::open(args.world)?
+    } else {
+        let options = WorldOptions {
+            gameid: args.gameid.to_string(),
+            backend: args.backend.to_string(),
+        };
+        let world = World::create(args.world, options)?;
+        eprintln!("created world {}", world.name);
+        world
+    };
+
+    let seed = args.seed.unwrap_or_else(random_seed);
+    eprintln!("seed {seed}");
+
+    let generator = Generator::new(GeneratorOptions {
+        seed,
+        terrain: args.terrain,
+        nodes: args.nodes,
+        ground_level: args.ground_level,
+        water_level: args.water_level,
+        height_range: args.height_range,
+        caves: !args.no_caves,
+    });
+
+    let report = world::generate_map(
+        &world.map,
+        &generator,
+        args.area,
+        args.overwrite,
+        print_progress,
+    )?;
+
+    eprintln!(
+        "generated {} blocks, skipped {} existing blocks",
+        report.generated, report.skipped
+    );
+
+    Ok(ExitCode::SUCCESS)
+}
+
+pub fn parse_terrain(s: &str) -> Result<Terrain, String> {
+    match s {
+        "flat" => Ok(Terrain::Flat),
+        "hills" => Ok(Terrain::Hills),
+        "islands" => Ok(Terrain::Islands),
+        _ => Err(format!(
+            "unknown terrain `{s}`, expected flat, hills or islands"
+        )),
+    }
+}
+
+fn random_seed() -> u32 {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    now.as_nanos() as u32
+}
+
+fn print_progress(progress: GenerateProgress) {
+    eprintln!("generated {}/{} blocks", progress.done, progress.total);
+}
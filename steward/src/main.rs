@@ -0,0 +1,576 @@
+mod anvil;
+mod diff;
+mod dump;
+mod fsck;
+mod generate;
+mod migrate;
+mod prune;
+mod recompress;
+mod rename;
+mod schematic;
+
+use std::{error::Error, path::PathBuf, process::ExitCode};
+
+use clap::{Parser, Subcommand};
+use glam::IVec3;
+use world::{BlockMapping, NodeArea, NodeColors, Terrain, TerrainNodes};
+
+/// Maintenance tools for Minetest worlds.
+#[derive(Parser)]
+#[command(version)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Check every block of a world for problems.
+    ///
+    /// Findings are printed to stdout as one JSON object per line, followed by
+    /// a summary line. Exits with status 1 if any problem was left unrepaired.
+    Fsck {
+        /// Path to the world directory.
+        world: PathBuf,
+
+        /// Delete or patch broken blocks instead of only reporting them.
+        #[arg(long)]
+        repair: bool,
+    },
+
+    /// Compare two snapshots of a world.
+    ///
+    /// Either argument may be a world directory or a bare map.sqlite file,
+    /// such as a backup.
+    Diff {
+        old: PathBuf,
+        new: PathBuf,
+
+        /// List every changed node of modified blocks.
+        #[arg(long)]
+        nodes: bool,
+
+        /// Also report changes in param1, which mostly holds light.
+        #[arg(long)]
+        param1: bool,
+
+        /// Skip blocks with equal timestamps instead of comparing their
+        /// nodes. Faster, but misses edits that kept the timestamp.
+        #[arg(long)]
+        trust_timestamps: bool,
+
+        /// Print one JSON object per line instead of text.
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Print a block as JSON.
+    ///
+    /// The output holds the block's flags, its mappings from ids to node
+    /// names, all 4096 nodes in the order z * 256 + y * 16 + x, and node
+    /// metadata, static objects and node timers.
+    DumpBlock {
+        /// Path to the world directory.
+        world: PathBuf,
+
+        /// Block position written as x,y,z, which is a node position divided
+        /// by 16 and rounded down.
+        #[arg(value_parser = prune::parse_pos, allow_hyphen_values = true)]
+        pos: IVec3,
+    },
+
+    /// Save an area of a world as a schematic.
+    ///
+    /// The format is picked by the file extension: .mts for the format of
+    /// `minetest.place_schematic`, .we for WorldEdit's `//save`, .schem
+    /// for Sponge schematics of Minecraft tools, or .vox for MagicaVoxel.
+    /// Nodes are turned into Minecraft blocks by the same table as for
+    /// import-anvil, and nodes it has no block for become air. For
+    /// MagicaVoxel, nodes are colored by a table of lines like
+    /// `default:stone 8a8a8a`, and nodes without a color are left out.
+    Export {
+        /// Path to the world directory.
+        world: PathBuf,
+
+        /// Area to save, written as x1,y1,z1:x2,y2,z2 in node positions.
+        #[arg(long, value_parser = prune::parse_area, allow_hyphen_values = true)]
+        area: NodeArea,
+
+        /// Schematic file to write.
+        output: PathBuf,
+
+        /// Block mapping table for .schem files to use instead of the
+        /// built-in one.
+        #[arg(long)]
+        mapping: Option<PathBuf>,
+
+        /// Color table for .vox files to use instead of the built-in one.
+        /// See `vox-colors` for a starting point.
+        #[arg(long)]
+        colors: Option<PathBuf>,
+
+        /// Version of .schem files to write, 2 or 3.
+        #[arg(long, default_value_t = 3)]
+        sponge_version: u32,
+
+        /// Node definition dump of the game, to tell which nodes are air.
+        /// Without it, only air and ignore are.
+        #[arg(long)]
+        node_defs: Option<PathBuf>,
+    },
+
+    /// Fill a world with terrain, creating the world if needed.
+    ///
+    /// The area is written as x1,y1,z1:x2,y2,z2 in node positions and is
+    /// rounded out to whole blocks. Air above the terrain is written too, so
+    /// the engine doesn't generate anything inside the area.
+    Generate {
+        /// Path to the world directory.
+        world: PathBuf,
+
+        /// Terrain type: flat, hills or islands.
+        #[arg(long, default_value = "hills", value_parser = generate::parse_terrain)]
+        terrain: Terrain,
+
+        /// Seed for the terrain. A random one is picked and printed if not
+        /// given.
+        #[arg(long)]
+        seed: Option<u32>,
+
+        /// Area to fill.
+        #[arg(
+            long,
+            default_value = "-128,-64,-128:127,63,127",
+            value_parser = prune::parse_area,
+            allow_hyphen_values = true
+        )]
+        area: NodeArea,
+
+        #[arg(long, default_value = "default:stone")]
+        stone: String,
+
+        #[arg(long, default_value = "default:dirt")]
+        dirt: String,
+
+        /// Topmost node of the ground above water.
+        #[arg(long, default_value = "default:dirt_with_grass")]
+        grass: String,
+
+        #[arg(long, default_value = "default:water_source")]
+        water: String,
+
+        /// Height of flat ground, and the average height of hills.
+        #[arg(long, default_value_t = 8, allow_hyphen_values = true)]
+        ground_level: i32,
+
+        /// Height of the topmost water node.
+        #[arg(long, default_value_t = 1, allow_hyphen_values = true)]
+        water_level: i32,
+
+        /// How far hills and the sea floor reach from their average height.
+        #[arg(long, default_value_t = 32)]
+        height_range: i32,
+
+        /// Don't carve caves.
+        #[arg(long)]
+        no_caves: bool,
+
+        /// Replace blocks that already exist.
+        #[arg(long)]
+        overwrite: bool,
+
+        /// Game of a newly created world.
+        #[arg(long, default_value = "minetest")]
+        gameid: String,
+
+        /// Map backend of a newly created world.
+        #[arg(long, default_value = "sqlite3")]
+        backend: String,
+    },
+
+    /// Place a schematic into a world.
+    ///
+    /// The format is picked by the file extension, as for export. MTS
+    /// schematics only replace air, apart from nodes they force into place.
+    /// WorldEdit and Sponge schematics replace everything in the way, apart
+    /// from Minecraft blocks the mapping table has no node for. Voxels of
+    /// MagicaVoxel models become the node of the nearest color in the color
+    /// table.
+    Import {
+        /// Path to the world directory.
+        world: PathBuf,
+
+        /// Schematic file to read.
+        input: PathBuf,
+
+        /// Node position of the schematic's minimum corner.
+        #[arg(long, value_parser = prune::parse_pos, allow_hyphen_values = true)]
+        pos: IVec3,
+
+        /// Let MTS schematics replace every node in the way, not only air.
+        #[arg(long)]
+        force: bool,
+
+        /// Block mapping table for .schem files to use instead of the
+        /// built-in one.
+        #[arg(long)]
+        mapping: Option<PathBuf>,
+
+        /// Color table for .vox files to use instead of the built-in one.
+        #[arg(long)]
+        colors: Option<PathBuf>,
+
+        /// Node definition dump of the game, to tell which nodes are air.
+        /// Without it, only air and ignore are.
+        #[arg(long)]
+        node_defs: Option<PathBuf>,
+    },
+
+    /// Copy an area of a Minecraft world into a Minetest world, creating
+    /// the Minetest world if needed.
+    ///
+    /// Region files of Minecraft 1.13 and later are supported. Block states
+    /// are translated by a table of lines like
+    /// `minecraft:oak_log[axis=x] default:tree 12`, and states the table has
+    /// no node for are listed when done. Minecraft's z axis is flipped, so
+    /// north stays north.
+    ImportAnvil {
+        /// Path to the Minetest world directory.
+        world: PathBuf,
+
+        /// Path to the Minecraft world directory or its region directory.
+        minecraft_world: PathBuf,
+
+        /// Area to copy, written as x1,y1,z1:x2,y2,z2 in Minecraft
+        /// coordinates.
+        #[arg(long, value_parser = prune::parse_area, allow_hyphen_values = true)]
+        area: NodeArea,
+
+        /// Added to the position of every node in the Minetest world.
+        #[arg(
+            long,
+            default_value = "0,0,0",
+            value_parser = prune::parse_pos,
+            allow_hyphen_values = true
+        )]
+        offset: IVec3,
+
+        /// Block mapping table to use instead of the built-in one for
+        /// Minetest Game. See `minecraft-mapping` for a starting point.
+        #[arg(long)]
+        mapping: Option<PathBuf>,
+    },
+
+    /// Print the built-in table of Minecraft block states used by
+    /// import-anvil.
+    MinecraftMapping,
+
+    /// Print the built-in table of node colors used for MagicaVoxel models.
+    VoxColors,
+
+    /// Move the map of a world to another backend.
+    ///
+    /// All blocks are copied, compared against the originals, and only then
+    /// is `backend` in world.mt switched over. The old database is kept.
+    Migrate {
+        /// Path to the world directory.
+        world: PathBuf,
+
+        /// Target backend: sqlite3, leveldb or postgresql.
+        backend: String,
+    },
+
+    /// Delete blocks that match all of the given rules.
+    ///
+    /// Positions are node positions written as x,y,z, and areas are written
+    /// as x1,y1,z1:x2,y2,z2. Deleted blocks are generated anew when a player
+    /// comes near them.
+    Prune {
+        /// Path to the world directory.
+        world: PathBuf,
+
+        /// Prune blocks farther than this many nodes from the center.
+        #[arg(long)]
+        radius: Option<u32>,
+
+        /// Center for --radius.
+        #[arg(
+            long,
+            default_value = "0,0,0",
+            value_parser = prune::parse_pos,
+            allow_hyphen_values = true
+        )]
+        center: IVec3,
+
+        /// Prune blocks outside this area.
+        #[arg(long, value_parser = prune::parse_area, allow_hyphen_values = true)]
+        area: Option<NodeArea>,
+
+        /// Prune blocks that contain only air.
+        #[arg(long)]
+        air_only: bool,
+
+        /// Prune blocks not modified since this game time, in seconds.
+        #[arg(long)]
+        before: Option<u32>,
+
+        /// Never prune blocks touching this area. May be given several times.
+        #[arg(long, value_parser = prune::parse_area, allow_hyphen_values = true)]
+        protect: Vec<NodeArea>,
+
+        /// Only list the blocks that would be pruned.
+        #[arg(long)]
+        dry_run: bool,
+
+        /// Node definition dump of the game, to tell which nodes are air.
+        /// Without it, only air and ignore are.
+        #[arg(long)]
+        node_defs: Option<PathBuf>,
+    },
+
+    /// Compress all blocks of a world anew and reclaim free space.
+    ///
+    /// Blocks are only rewritten if they get smaller. Sizes before and after
+    /// are printed when done. With --archive, the world is left as it is and
+    /// all blocks are written to a new archive instead.
+    Recompress {
+        /// Path to the world directory.
+        world: PathBuf,
+
+        /// zstd compression level, from 1 to 22.
+        #[arg(long, default_value_t = 19)]
+        level: i32,
+
+        /// Write the blocks compressed with --dictionary to this new sqlite
+        /// file. Minetest can't read them; use restore-archive to get a
+        /// playable map back.
+        #[arg(long, requires = "dictionary")]
+        archive: Option<PathBuf>,
+
+        /// Compress the --archive with this zstd dictionary.
+        #[arg(long, requires = "archive")]
+        dictionary: Option<PathBuf>,
+
+        /// Train a dictionary of at most this many bytes on the world and
+        /// write it to the --dictionary path first.
+        #[arg(long)]
+        train_dictionary: Option<usize>,
+
+        /// Don't vacuum the database afterwards.
+        #[arg(long)]
+        no_vacuum: bool,
+    },
+
+    /// Write the blocks of an archive made by recompress --archive back into
+    /// a world.
+    RestoreArchive {
+        /// Path to the archive.
+        archive: PathBuf,
+
+        /// Path to the world directory to write the blocks to.
+        world: PathBuf,
+
+        /// The dictionary the archive was compressed with.
+        #[arg(long)]
+        dictionary: PathBuf,
+
+        /// zstd compression level, from 1 to 22.
+        #[arg(long, default_value_t = 19)]
+        level: i32,
+    },
+
+    /// Replace node names in all blocks of a world.
+    ///
+    /// Only the name tables of blocks are rewritten, so this is fast even on
+    /// large worlds. Prints the number of blocks each alias was applied to.
+    Rename {
+        /// Path to the world directory.
+        world: PathBuf,
+
+        /// An alias written as old=new. May be given several times.
+        #[arg(long, value_parser = rename::parse_alias)]
+        alias: Vec<(String, String)>,
+
+        /// Lua file to read `register_alias` calls from, such as a mod's
+        /// init.lua. May be given several times.
+        #[arg(long)]
+        lua: Vec<PathBuf>,
+
+        /// Only report what would be renamed.
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
+fn main() -> Result<ExitCode, Box<dyn Error>> {
+    let cli = Cli::parse();
+
+    match cli.command {
+        Command::Diff {
+            old,
+            new,
+            nodes,
+            param1,
+            trust_timestamps,
+            json,
+        } => diff::run(diff::Args {
+            old: &old,
+            new: &new,
+            nodes,
+            param1,
+            trust_timestamps,
+            json,
+        }),
+        Command::DumpBlock { world, pos } => dump::run(&world, pos),
+        Command::Export {
+            world,
+            area,
+            output,
+            mapping,
+            colors,
+            sponge_version,
+            node_defs,
+        } => schematic::export(schematic::ExportArgs {
+            world: &world,
+            area,
+            output: &output,
+            mapping: mapping.as_deref(),
+            colors: colors.as_deref(),
+            sponge_version,
+            node_defs: node_defs.as_deref(),
+        }),
+        Command::Fsck { world, repair } => fsck::run(&world, repair),
+        Command::Generate {
+            world,
+            terrain,
+            seed,
+            area,
+            stone,
+            dirt,
+            grass,
+            water,
+            ground_level,
+            water_level,
+            height_range,
+            no_caves,
+            overwrite,
+            gameid,
+            backend,
+        } => generate::run(generate::Args {
+            world: &world,
+            terrain,
+            seed,
+            area,
+            nodes: TerrainNodes {
+                stone,
+                dirt,
+                grass,
+                water,
+            },
+            ground_level,
+            water_level,
+            height_range,
+            no_caves,
+            overwrite,
+            gameid: &gameid,
+            backend: &backend,
+        }),
+        Command::Import {
+            world,
+            input,
+            pos,
+            force,
+            mapping,
+            colors,
+            node_defs,
+        } => schematic::import(schematic::ImportArgs {
+            world: &world,
+            input: &input,
+            pos,
+            force,
+            mapping: mapping.as_deref(),
+            colors: colors.as_deref(),
+            node_defs: node_defs.as_deref(),
+        }),
+        Command::ImportAnvil {
+            world,
+            minecraft_world,
+            area,
+            offset,
+            mapping,
+        } => anvil::run(anvil::Args {
+            world: &world,
+            minecraft_world: &minecraft_world,
+            area,
+            offset,
+            mapping: mapping.as_deref(),
+        }),
+        Command::MinecraftMapping => {
+            print!("{}", BlockMapping::minetest_game_text());
+            Ok(ExitCode::SUCCESS)
+        }
+        Command::VoxColors => {
+            print!("{}", NodeColors::minetest_game_text());
+            Ok(ExitCode::SUCCESS)
+        }
+        Command::Migrate { world, backend } => migrate::run(&world, &backend),
+        Command::Prune {
+            world,
+            radius,
+            center,
+            area,
+            air_only,
+            before,
+            protect,
+            dry_run,
+            node_defs,
+        } => prune::run(prune::Args {
+            world: &world,
+            radius,
+            center,
+            area,
+            air_only,
+            before,
+            protect,
+            dry_run,
+            node_defs: node_defs.as_deref(),
+        }),
+        Command::Recompress {
+            world,
+            level,
+            archive,
+            dictionary,
+            train_dictionary,
+            no_vacuum,
+        } => recompress::run(recompress::Args {
+            world: &world,
+            level,
+            archive: archive.as_deref(),
+            dictionary: dictionary.as_deref(),
+            train_dictionary,
+            no_vacuum,
+        }),
+        Command::RestoreArchive {
+            archive,
+            world,
+            dictionary,
+            level,
+        } => recompress::restore(recompress::RestoreArgs {
+            archive: &archive,
+            world: &world,
+            level,
+            dictionary: &dictionary,
+        }),
+        Command::Rename {
+            world,
+            alias,
+            lua,
+            dry_run,
+        } => rename::run(rename::Args {
+            world: &world,
+            alias: &alias,
+            lua: &lua,
+            dry_run,
+        }),
+    }
+}
@@ -0,0 +1,20 @@
+use std::{error::Error, path::Path, process::ExitCode};
+
+use world::{MigrateProgress, MigrateStage};
+
+pub fn run(world_path: &Path, backend: &str) -> Result<ExitCode, Box<dyn Error>> {
+    let report = world::migrate_world(world_path, backend, print_progress)?;
+
+    eprintln!("migrated {} blocks to {backend}", report.blocks);
+
+    Ok(ExitCode::SUCCESS)
+}
+
+fn print_progress(progress: MigrateProgress) {
+    let stage = match progress.stage {
+        MigrateStage::Copy => "copied",
+        MigrateStage::Verify => "verified",
+    };
+
+    eprintln!("{stage} {}/{} blocks", progress.done, progress.total);
+}
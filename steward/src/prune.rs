@@ -0,0 +1,92 @@
+use std::{error::Error, path::Path, process::ExitCode};
+
+use glam::IVec3;
+use world::{NodeArea, PruneOptions, PruneRule, World};
+
+pub struct Args<'a> {
+    pub world: &'a Path,
+    pub radius: Option<u32>,
+    pub center: IVec3,
+    pub area: Option<NodeArea>,
+    pub air_only: bool,
+    pub before: Option<u32>,
+    pub protect: Vec<NodeArea>,
+    pub dry_run: bool,
+    pub node_defs: Option<&'a Path>,
+}
+
+pub fn run(args: Args) -> Result<ExitCode, Box<dyn Error>> {
+    let mut rules = Vec::new();
+
+    if let Some(radius) = args.radius {
+        rules.push(PruneRule::OutsideRadius {
+            center: args.center,
+            radius,
+        });
+    }
+
+    if let Some(area) = args.area {
+        rules.push(PruneRule::OutsideArea(area));
+    }
+
+    if args.air_only {
+        rules.push(PruneRule::AirOnly);
+    }
+
+    if let Some(time) = args.before {
+        rules.push(PruneRule::NotModifiedSince(time));
+    }
+
+    if rules.is_empty() {
+        return Err("no rules given, refusing to prune".into());
+    }
+
+    let node_defs = crate::schematic::read_node_defs(args.node_defs)?;
+    let world = World::open(args.world)?;
+
+    let options = PruneOptions {
+        rules,
+        protected: args.protect,
+        dry_run: args.dry_run,
+    };
+
+    let action = if args.dry_run {
+        "would prune"
+    } else {
+        "pruned"
+    };
+
+    let report = world::prune_map(&world.map, &options, node_defs.as_ref(), |pos| {
+        println!("{action} block ({}, {}, {})", pos.x, pos.y, pos.z);
+    })?;
+
+    eprintln!(
+        "{action} {} of {} blocks, {} protected, skipped {} unreadable",
+        report.pruned, report.blocks, report.protected, report.skipped
+    );
+
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Parses a node position written as `x,y,z`.
+pub fn parse_pos(s: &str) -> Result<IVec3, String> {
+    let coords: Vec<i32> = s
+        .split(',')
+        .map(|c| c.trim().parse())
+        .collect::<Result<_, _>>()
+        .map_err(|_| format!("invalid position `{s}`, expected x,y,z"))?;
+
+    match coords[..] {
+        [x, y, z] => Ok(IVec3::new(x, y, z)),
+        _ => Err(format!("invalid position `{s}`, expected x,y,z")),
+    }
+}
+
+/// Parses an area written as `x1,y1,z1:x2,y2,z2`.
+pub fn parse_area(s: &str) -> Result<NodeArea, String> {
+    let (a, b) = s
+        .split_once(':')
+        .ok_or_else(|| format!("invalid area `{s}`, expected x1,y1,z1:x2,y2,z2"))?;
+
+    Ok(NodeArea::new(parse_pos(a)?, parse_pos(b)?))
+}
@@ -0,0 +1,132 @@
+use std::{error::Error, fs, path::Path, process::ExitCode};
+
+use world::{Map, RecompressOptions, RecompressProgress, RecompressReport, SqliteBackend, World};
+
+pub struct Args<'a> {
+    pub world: &'a Path,
+    pub level: i32,
+    pub archive: Option<&'a Path>,
+    pub dictionary: Option<&'a Path>,
+    pub train_dictionary: Option<usize>,
+    pub no_vacuum: bool,
+}
+
+pub struct RestoreArgs<'a> {
+    pub archive: &'a Path,
+    pub world: &'a Path,
+    pub level: i32,
+    pub dictionary: &'a Path,
+}
+
+pub fn run(args: Args) -> Result<ExitCode, Box<dyn Error>> {
+    let world = World::open(args.world)?;
+
+    let dictionary = match (args.dictionary, args.train_dictionary) {
+        (Some(path), Some(max_size)) => {
+            let dictionary = world::train_dictionary(&world.map, max_size)?;
+            fs::write(path, &dictionary)?;
+            eprintln!(
+                "wrote {} byte dictionary to {}",
+                dictionary.len(),
+                path.display()
+            );
+            Some(dictionary)
+        }
+        (Some(path), None) => Some(fs::read(path)?),
+        (None, Some(_)) => return Err("--train-dictionary needs --dictionary".into()),
+        (None, None) => None,
+    };
+
+    let backend = world.meta.get_str("backend").unwrap_or("sqlite3");
+
+    // Minetest can't read blocks compressed with a dictionary, so they only
+    // ever go to a separate archive.
+    let report = match (args.archive, dictionary) {
+        (Some(path), Some(dictionary)) => {
+            if path.exists() {
+                return Err(format!("{} already exists", path.display()).into());
+            }
+
+            let archive = Map::new(SqliteBackend::create(path)?);
+            world::archive_map(
+                &world.map,
+                &archive,
+                args.level,
+                &dictionary,
+                print_progress,
+            )?
+        }
+        (None, None) => {
+            let options = RecompressOptions {
+                level: args.level,
+                vacuum: !args.no_vacuum,
+            };
+            world::recompress_map(&world.map, &options, print_progress)?
+        }
+        _ => return Err("--dictionary and --archive must be given together".into()),
+    };
+
+    print_report(&report, backend);
+
+    Ok(ExitCode::SUCCESS)
+}
+
+pub fn restore(args: RestoreArgs) -> Result<ExitCode, Box<dyn Error>> {
+    let archive = Map::new(SqliteBackend::new(args.archive)?);
+    let world = World::open(args.world)?;
+    let dictionary = fs::read(args.dictionary)?;
+
+    let report = world::restore_archive(
+        &archive,
+        &world.map,
+        args.level,
+        &dictionary,
+        print_progress,
+    )?;
+
+    print_report(&report, "archive");
+
+    Ok(ExitCode::SUCCESS)
+}
+
+fn print_report(report: &RecompressReport, backend: &str) {
+    eprintln!(
+        "recompressed {} of {} blocks, skipped {} in older formats",
+        report.recompressed, report.blocks, report.skipped
+    );
+    eprintln!(
+        "{backend}: block data {} -> {}",
+        format_size(report.data_before),
+        format_size(report.data_after)
+    );
+
+    if let (Some(before), Some(after)) = (report.disk_before, report.disk_after) {
+        eprintln!(
+            "{backend}: on disk {} -> {}",
+            format_size(before),
+            format_size(after)
+        );
+    }
+}
+
+fn print_progress(progress: RecompressProgress) {
+    eprintln!("recompressed {}/{} blocks", progress.done, progress.total);
+}
+
+fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+
+    let mut size = bytes as f64;
+    let mut unit = 0;
+
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+
+    if unit == 0 {
+        format!("{bytes} B")
+    } else {
+        format!("{size:.1} {}", UNITS[unit])
+    }
+}
@@ -0,0 +1,63 @@
+use std::{
+    collections::BTreeMap,
+    error::Error,
+    fs,
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
+
+use world::World;
+
+pub struct Args<'a> {
+    pub world: &'a Path,
+    pub alias: &'a [(String, String)],
+    pub lua: &'a [PathBuf],
+    pub dry_run: bool,
+}
+
+pub fn run(args: Args) -> Result<ExitCode, Box<dyn Error>> {
+    let mut aliases = BTreeMap::new();
+
+    for path in args.lua {
+        let source = fs::read_to_string(path)?;
+        aliases.extend(world::parse_register_aliases(&source));
+    }
+
+    // Aliases given on the command line take precedence over those in files.
+    aliases.extend(args.alias.iter().cloned());
+
+    if aliases.is_empty() {
+        return Err("no aliases given".into());
+    }
+
+    let world = World::open(args.world)?;
+    let report = world::rename_nodes(&world.map, &aliases, args.dry_run)?;
+
+    for (old, new) in &aliases {
+        let blocks = report.aliases.get(old).copied().unwrap_or(0);
+        println!("{old} -> {new}: {blocks} blocks");
+    }
+
+    let action = if args.dry_run {
+        "would rename"
+    } else {
+        "renamed"
+    };
+
+    eprintln!(
+        "{action} nodes in {} of {} blocks, skipped {} in older formats",
+        report.renamed_blocks, report.blocks, report.skipped
+    );
+
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Parses an alias written as `old=new`.
+pub fn parse_alias(s: &str) -> Result<(String, String), String> {
+    match s.split_once('=') {
+        Some((old, new)) if !old.is_empty() && !new.is_empty() => {
+            Ok((old.to_string(), new.to_string()))
+        }
+        _ => Err(format!("invalid alias `{s}`, expected old=new")),
+    }
+}
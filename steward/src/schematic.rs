@@ -0,0 +1,160 @@
+use std::{collections::BTreeMap, error::Error, fs, path::Path, process::ExitCode};
+
+use glam::IVec3;
+use world::{
+    BlockMapping, NodeArea, NodeColors, NodeDefRegistry, Schematic, SpongeSchematic, VoxSchematic,
+    World, WorldEditSchematic,
+};
+
+pub struct ExportArgs<'a> {
+    pub world: &'a Path,
+    pub area: NodeArea,
+    pub output: &'a Path,
+    pub mapping: Option<&'a Path>,
+    pub colors: Option<&'a Path>,
+    pub sponge_version: u32,
+    pub node_defs: Option<&'a Path>,
+}
+
+pub struct ImportArgs<'a> {
+    pub world: &'a Path,
+    pub input: &'a Path,
+    pub pos: IVec3,
+    pub force: bool,
+    pub mapping: Option<&'a Path>,
+    pub colors: Option<&'a Path>,
+    pub node_defs: Option<&'a Path>,
+}
+
+/// File formats, told apart by their extension.
+enum Format {
+    Mts,
+    WorldEdit,
+    Sponge,
+    Vox,
+}
+
+impl Format {
+    fn from_path(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let extension = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .unwrap_or_default();
+
+        match extension.to_ascii_lowercase().as_str() {
+            "mts" => Ok(Self::Mts),
+            "we" => Ok(Self::WorldEdit),
+            "schem" => Ok(Self::Sponge),
+            "vox" => Ok(Self::Vox),
+            _ => Err(format!("unknown schematic format `{}`", path.display()).into()),
+        }
+    }
+}
+
+pub fn export(args: ExportArgs) -> Result<ExitCode, Box<dyn Error>> {
+    let format = Format::from_path(args.output)?;
+    let node_defs = read_node_defs(args.node_defs)?;
+    let world = World::open(args.world)?;
+
+    let (data, nodes) = match format {
+        Format::Mts => {
+            let schematic = Schematic::from_map(&world.map, args.area)?;
+            (schematic.write_mts()?, schematic.nodes().count())
+        }
+        Format::WorldEdit => {
+            let schematic =
+                WorldEditSchematic::from_map(&world.map, args.area, node_defs.as_ref())?;
+            (schematic.write(), schematic.nodes.len())
+        }
+        Format::Sponge => {
+            let mapping = read_mapping(args.mapping)?;
+            let (schematic, report) =
+                SpongeSchematic::from_map(&world.map, args.area, &mapping, node_defs.as_ref())?;
+            print_unmapped("nodes", &report.unmapped);
+            (schematic.write(args.sponge_version)?, report.nodes)
+        }
+        Format::Vox => {
+            let colors = read_colors(args.colors)?;
+            let (schematic, report) =
+                VoxSchematic::from_map(&world.map, args.area, &colors, node_defs.as_ref())?;
+            print_unmapped("nodes", &report.unmapped);
+            (schematic.write()?, report.nodes)
+        }
+    };
+    fs::write(args.output, data)?;
+
+    eprintln!("exported {nodes} nodes to {}", args.output.display());
+
+    Ok(ExitCode::SUCCESS)
+}
+
+pub fn import(args: ImportArgs) -> Result<ExitCode, Box<dyn Error>> {
+    let format = Format::from_path(args.input)?;
+    let data = fs::read(args.input)?;
+    let node_defs = read_node_defs(args.node_defs)?;
+
+    let world = World::open(args.world)?;
+
+    let placed = match format {
+        Format::Mts => Schematic::read_mts(&data)?.place(
+            &world.map,
+            args.pos,
+            args.force,
+            node_defs.as_ref(),
+        )?,
+        Format::WorldEdit => WorldEditSchematic::read(&data)?.place(&world.map, args.pos)?,
+        Format::Sponge => {
+            let mapping = read_mapping(args.mapping)?;
+            let report = SpongeSchematic::read(&data)?.place(&world.map, args.pos, &mapping)?;
+            print_unmapped("block states", &report.unmapped);
+            report.nodes
+        }
+        Format::Vox => {
+            let colors = read_colors(args.colors)?;
+            let report = VoxSchematic::read(&data)?.place(&world.map, args.pos, &colors)?;
+            print_unmapped("colors, placed as the nearest color", &report.unmapped);
+            report.nodes
+        }
+    };
+
+    let pos = args.pos;
+    eprintln!("placed {placed} nodes at {},{},{}", pos.x, pos.y, pos.z);
+
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Reads the table of Minecraft block states, or takes the built-in one.
+pub fn read_mapping(path: Option<&Path>) -> Result<BlockMapping, Box<dyn Error>> {
+    match path {
+        Some(path) => Ok(BlockMapping::parse(&fs::read_to_string(path)?)?),
+        None => Ok(BlockMapping::minetest_game()),
+    }
+}
+
+/// Reads a node definition dump, if one is given.
+pub fn read_node_defs(path: Option<&Path>) -> Result<Option<NodeDefRegistry>, Box<dyn Error>> {
+    Ok(path.map(NodeDefRegistry::open).transpose()?)
+}
+
+/// Reads the table of node colors, or takes the built-in one.
+fn read_colors(path: Option<&Path>) -> Result<NodeColors, Box<dyn Error>> {
+    match path {
+        Some(path) => Ok(NodeColors::parse(&fs::read_to_string(path)?)?),
+        None => Ok(NodeColors::minetest_game()),
+    }
+}
+
+/// Lists what the block mapping had no translation for, most common first.
+pub fn print_unmapped(what: &str, unmapped: &BTreeMap<String, usize>) {
+    if unmapped.is_empty() {
+        return;
+    }
+
+    let mut unmapped: Vec<_> = unmapped.iter().collect();
+    unmapped.sort_by(|a, b| b.1.cmp(a.1));
+
+    println!("unmapped {what}:");
+    for (name, count) in unmapped {
+        println!("{count:>10} {name}");
+    }
+}